@@ -0,0 +1,367 @@
+//! Command line client to inspect and control PowerStation over DBus
+use std::error::Error;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use powerstation::client::{self, CardProxy, TdpProxy};
+use serde::Serialize;
+use serde_json::json;
+use tabled::Tabled;
+use zbus::Connection;
+
+use crate::output::{display_list, print_list, print_properties, Format};
+
+mod output;
+
+#[derive(Parser)]
+#[command(version, about = "Inspect and control PowerStation", long_about = None)]
+struct Cli {
+    /// Print output as JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// GPU card to use (e.g. "card1"). Defaults to the integrated GPU.
+    #[arg(long, global = true)]
+    card: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List all GPU cards
+    Cards,
+    /// List all CPU cores
+    Cores,
+    /// List the display connectors of all GPU cards, or of the given card
+    Connectors,
+    /// Get or set the TDP in watts
+    Tdp { value: Option<f64> },
+    /// Get or set the TDP boost in watts
+    Boost { value: Option<f64> },
+    /// Get or set the thermal throttle limit in degrees Celsius
+    ThermalLimit { value: Option<f64> },
+    /// Get or set the power profile
+    PowerProfile { value: Option<String> },
+    /// Get or set the GPU clock limits in MHz
+    GpuClock {
+        /// Minimum GPU clock in MHz
+        #[arg(long)]
+        min: Option<f64>,
+        /// Maximum GPU clock in MHz
+        #[arg(long)]
+        max: Option<f64>,
+        /// Enable or disable manual GPU clock control
+        #[arg(long)]
+        manual: Option<Toggle>,
+    },
+    /// Get or set simultaneous multithreading (SMT)
+    Smt { value: Option<Toggle> },
+    /// Get or set CPU boost
+    CpuBoost { value: Option<Toggle> },
+    /// Get or set the number of enabled CPU cores
+    CoresEnabled { value: Option<u32> },
+    /// List all saved profiles
+    Profiles,
+    /// Get the active profile, or apply the given profile
+    Profile { name: Option<String> },
+}
+
+/// Value of an on/off setting
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Toggle {
+    On,
+    Off,
+}
+
+impl From<Toggle> for bool {
+    fn from(value: Toggle) -> Self {
+        matches!(value, Toggle::On)
+    }
+}
+
+#[derive(Serialize, Tabled)]
+struct CardInfo {
+    #[tabled(rename = "NAME")]
+    name: String,
+    #[tabled(rename = "CLASS")]
+    class: String,
+    #[tabled(rename = "VENDOR")]
+    vendor: String,
+    #[tabled(rename = "DEVICE")]
+    device: String,
+    #[tabled(rename = "PATH")]
+    path: String,
+}
+
+#[derive(Serialize, Tabled)]
+struct CoreInfo {
+    #[tabled(rename = "NUMBER")]
+    number: u32,
+    #[tabled(rename = "CORE ID")]
+    core_id: u32,
+    #[tabled(rename = "ONLINE")]
+    online: bool,
+}
+
+#[derive(Serialize, Tabled)]
+struct ConnectorInfo {
+    #[tabled(rename = "CARD")]
+    card: String,
+    #[tabled(rename = "NAME")]
+    name: String,
+    #[tabled(rename = "ID")]
+    id: u32,
+    #[tabled(rename = "STATUS")]
+    status: String,
+    #[tabled(rename = "ENABLED")]
+    enabled: bool,
+    #[tabled(rename = "DPMS")]
+    dpms: bool,
+    #[tabled(rename = "MODES", display = "display_list")]
+    modes: Vec<String>,
+}
+
+#[derive(Serialize, Tabled)]
+struct ProfileInfo {
+    #[tabled(rename = "NAME")]
+    name: String,
+    #[tabled(rename = "ACTIVE")]
+    active: bool,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let format = if cli.json {
+        Format::Json
+    } else {
+        Format::Table
+    };
+    let conn = Connection::system().await?;
+    let card = cli.card.as_deref();
+
+    match cli.command {
+        Command::Cards => list_cards(&conn, format).await,
+        Command::Cores => list_cores(&conn, format).await,
+        Command::Connectors => list_connectors(&conn, card, format).await,
+        Command::Tdp { value } => {
+            let tdp = tdp_proxy(&conn, card).await?;
+            if let Some(value) = value {
+                tdp.set_tdp(value).await?;
+            }
+            let properties = vec![
+                ("tdp", json!(tdp.tdp().await?)),
+                ("min_tdp", json!(tdp.min_tdp().await.ok())),
+                ("max_tdp", json!(tdp.max_tdp().await.ok())),
+            ];
+            Ok(print_properties(format, properties)?)
+        }
+        Command::Boost { value } => {
+            let tdp = tdp_proxy(&conn, card).await?;
+            if let Some(value) = value {
+                tdp.set_boost(value).await?;
+            }
+            let properties = vec![
+                ("boost", json!(tdp.boost().await?)),
+                ("max_boost", json!(tdp.max_boost().await.ok())),
+            ];
+            Ok(print_properties(format, properties)?)
+        }
+        Command::ThermalLimit { value } => {
+            let tdp = tdp_proxy(&conn, card).await?;
+            if let Some(value) = value {
+                tdp.set_thermal_throttle_limit_c(value).await?;
+            }
+            let limit = tdp.thermal_throttle_limit_c().await?;
+            Ok(print_properties(
+                format,
+                vec![("thermal_limit", json!(limit))],
+            )?)
+        }
+        Command::PowerProfile { value } => {
+            let tdp = tdp_proxy(&conn, card).await?;
+            if let Some(value) = value {
+                tdp.set_power_profile(&value).await?;
+            }
+            let properties = vec![
+                ("power_profile", json!(tdp.power_profile().await?)),
+                (
+                    "power_profiles_available",
+                    json!(tdp.power_profiles_available().await.ok()),
+                ),
+            ];
+            Ok(print_properties(format, properties)?)
+        }
+        Command::GpuClock { min, max, manual } => {
+            let card = card_proxy(&conn, card).await?;
+            if let Some(manual) = manual {
+                card.set_manual_clock(manual.into()).await?;
+            }
+            if let Some(min) = min {
+                card.set_clock_value_mhz_min(min).await?;
+            }
+            if let Some(max) = max {
+                card.set_clock_value_mhz_max(max).await?;
+            }
+            let properties = vec![
+                ("manual_clock", json!(card.manual_clock().await?)),
+                ("clock_min", json!(card.clock_value_mhz_min().await?)),
+                ("clock_max", json!(card.clock_value_mhz_max().await?)),
+                (
+                    "clock_limit_min",
+                    json!(card.clock_limit_mhz_min().await.ok()),
+                ),
+                (
+                    "clock_limit_max",
+                    json!(card.clock_limit_mhz_max().await.ok()),
+                ),
+            ];
+            Ok(print_properties(format, properties)?)
+        }
+        Command::Smt { value } => {
+            let cpu = client::cpu(&conn).await?;
+            if let Some(value) = value {
+                cpu.set_smt_enabled(value.into()).await?;
+            }
+            let enabled = cpu.smt_enabled().await?;
+            Ok(print_properties(
+                format,
+                vec![("smt_enabled", json!(enabled))],
+            )?)
+        }
+        Command::CpuBoost { value } => {
+            let cpu = client::cpu(&conn).await?;
+            if let Some(value) = value {
+                cpu.set_boost_enabled(value.into()).await?;
+            }
+            let enabled = cpu.boost_enabled().await?;
+            Ok(print_properties(
+                format,
+                vec![("boost_enabled", json!(enabled))],
+            )?)
+        }
+        Command::CoresEnabled { value } => {
+            let cpu = client::cpu(&conn).await?;
+            if let Some(value) = value {
+                cpu.set_cores_enabled(value).await?;
+            }
+            let properties = vec![
+                ("cores_enabled", json!(cpu.cores_enabled().await?)),
+                ("cores_count", json!(cpu.cores_count().await?)),
+            ];
+            Ok(print_properties(format, properties)?)
+        }
+        Command::Profiles => list_profiles(&conn, format).await,
+        Command::Profile { name } => {
+            let profiles = client::profiles(&conn).await?;
+            if let Some(name) = name {
+                profiles.apply_profile(&name).await?;
+            }
+            let active = profiles.active_profile().await?;
+            Ok(print_properties(
+                format,
+                vec![("active_profile", json!(active))],
+            )?)
+        }
+    }
+}
+
+/// Print all GPU cards
+async fn list_cards(conn: &Connection, format: Format) -> Result<(), Box<dyn Error>> {
+    let mut cards = Vec::new();
+    for card in client::cards(conn).await? {
+        cards.push(CardInfo {
+            name: card.name().await?,
+            class: card.class().await?,
+            vendor: card.vendor().await?,
+            device: card.device().await?,
+            path: card.inner().path().to_string(),
+        });
+    }
+    Ok(print_list(format, &cards)?)
+}
+
+/// Print all CPU cores
+async fn list_cores(conn: &Connection, format: Format) -> Result<(), Box<dyn Error>> {
+    let mut cores = Vec::new();
+    for core in client::cores(conn).await? {
+        cores.push(CoreInfo {
+            number: core.number().await?,
+            core_id: core.core_id().await?,
+            online: core.online().await?,
+        });
+    }
+    Ok(print_list(format, &cores)?)
+}
+
+/// Print the connectors of the given card, or of all cards if no card is given
+async fn list_connectors(
+    conn: &Connection,
+    card: Option<&str>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let cards = match card {
+        Some(card) => vec![client::card(conn, card).await?],
+        None => client::cards(conn).await?,
+    };
+
+    let mut connectors = Vec::new();
+    for card in cards {
+        let card_name = card.name().await?;
+        for connector in client::connectors(conn, &card).await? {
+            connectors.push(ConnectorInfo {
+                card: card_name.clone(),
+                name: connector.name().await?,
+                id: connector.id().await?,
+                status: connector.status().await?,
+                enabled: connector.enabled().await?,
+                dpms: connector.dpms().await?,
+                modes: connector.modes().await?,
+            });
+        }
+    }
+    connectors.sort_by(|a, b| (&a.card, a.id).cmp(&(&b.card, b.id)));
+    Ok(print_list(format, &connectors)?)
+}
+
+/// Print all saved profiles
+async fn list_profiles(conn: &Connection, format: Format) -> Result<(), Box<dyn Error>> {
+    let proxy = client::profiles(conn).await?;
+    let active = proxy.active_profile().await?;
+    let profiles: Vec<ProfileInfo> = proxy
+        .profiles()
+        .await?
+        .into_iter()
+        .map(|name| ProfileInfo {
+            active: name == active,
+            name,
+        })
+        .collect();
+    Ok(print_list(format, &profiles)?)
+}
+
+/// Returns a proxy to the given card, or to the integrated GPU if no card is
+/// given
+async fn card_proxy(conn: &Connection, card: Option<&str>) -> client::Result<CardProxy<'static>> {
+    match card {
+        Some(card) => client::card(conn, card).await,
+        None => client::integrated_gpu(conn).await,
+    }
+}
+
+/// Returns a proxy to the TDP interface of the given card, or of the
+/// integrated GPU if no card is given
+async fn tdp_proxy(conn: &Connection, card: Option<&str>) -> client::Result<TdpProxy<'static>> {
+    match card {
+        Some(card) => client::tdp(conn, card).await,
+        None => client::integrated_gpu_tdp(conn).await,
+    }
+}
@@ -0,0 +1,79 @@
+//! Formatting of command output as human-readable tables or JSON
+use serde::Serialize;
+use serde_json::Value;
+use tabled::{settings::Style, Table, Tabled};
+
+/// Output format of all commands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+/// A named value shown by commands that get or set properties
+#[derive(Tabled)]
+struct Property {
+    #[tabled(rename = "PROPERTY")]
+    name: String,
+    #[tabled(rename = "VALUE")]
+    value: String,
+}
+
+/// Print the given list of objects, e.g. cards or cores
+pub fn print_list<T: Serialize + Tabled>(format: Format, rows: &[T]) -> serde_json::Result<()> {
+    match format {
+        Format::Table => {
+            let mut table = Table::new(rows);
+            table.with(Style::sharp());
+            println!("{table}");
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(rows)?),
+    }
+    Ok(())
+}
+
+/// Print the given property names and values. JSON output is an object with
+/// the property names as keys.
+pub fn print_properties(format: Format, properties: Vec<(&str, Value)>) -> serde_json::Result<()> {
+    match format {
+        Format::Table => {
+            let rows: Vec<Property> = properties
+                .into_iter()
+                .map(|(name, value)| Property {
+                    name: name.to_string(),
+                    value: display_value(&value),
+                })
+                .collect();
+            let mut table = Table::new(rows);
+            table.with(Style::sharp());
+            println!("{table}");
+        }
+        Format::Json => {
+            let object: serde_json::Map<String, Value> = properties
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&object)?);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the given value as table cell text, without quotes around strings
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(display_value)
+            .collect::<Vec<String>>()
+            .join(", "),
+        Value::Null => "-".to_string(),
+        value => value.to_string(),
+    }
+}
+
+/// Display helper for list columns, e.g. the modes of a connector
+pub fn display_list(values: &[String]) -> String {
+    values.join(", ")
+}
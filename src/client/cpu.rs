@@ -0,0 +1,162 @@
+//! Proxy for the `org.shadowblip.CPU` interface, as described in
+//! `bindings/dbus-xml/org-shadowblip-cpu.xml`.
+use zbus::{proxy, zvariant::OwnedObjectPath};
+
+#[proxy(
+    interface = "org.shadowblip.CPU",
+    default_service = "org.shadowblip.PowerStation",
+    default_path = "/org/shadowblip/Performance/CPU"
+)]
+pub trait Cpu {
+    /// Returns a list of DBus paths to all CPU cores
+    fn enumerate_cores(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// Returns a list of DBus paths to all L3 caches. On AMD CPUs, each L3
+    /// cache is a CCD or CCX.
+    fn enumerate_l3_caches(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// Returns a list of DBus paths to all CPU packages, the roots of the CPU
+    /// topology
+    fn enumerate_packages(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// Returns true if the CPU has the given feature flag
+    fn has_feature(&self, flag: &str) -> zbus::Result<bool>;
+
+    /// Whether CPU boost is enabled
+    #[zbus(property)]
+    fn boost_enabled(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_boost_enabled(&self, value: bool) -> zbus::Result<()>;
+
+    /// Total number of CPU cores detected
+    #[zbus(property)]
+    fn cores_count(&self) -> zbus::Result<u32>;
+
+    /// Number of CPU cores that are online
+    #[zbus(property)]
+    fn cores_enabled(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_cores_enabled(&self, value: u32) -> zbus::Result<()>;
+
+    /// Which cores are kept online when only some cores are enabled:
+    /// "performance", "efficiency" or "ccd"
+    #[zbus(property)]
+    fn cores_enabled_policy(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn set_cores_enabled_policy(&self, value: &str) -> zbus::Result<()>;
+
+    /// CPU feature flags from /proc/cpuinfo
+    #[zbus(property)]
+    fn features(&self) -> zbus::Result<Vec<String>>;
+
+    /// Energy performance preferences that can be set
+    #[zbus(property)]
+    fn available_energy_performance_preferences(&self) -> zbus::Result<Vec<String>>;
+
+    /// Scaling governors that can be set
+    #[zbus(property)]
+    fn available_governors(&self) -> zbus::Result<Vec<String>>;
+
+    /// Highest frequency supported by any core in kHz
+    #[zbus(property)]
+    fn cpuinfo_max_freq(&self) -> zbus::Result<u32>;
+
+    /// Lowest frequency supported by any core in kHz
+    #[zbus(property)]
+    fn cpuinfo_min_freq(&self) -> zbus::Result<u32>;
+
+    /// Average current frequency of all online cores in kHz
+    #[zbus(property)]
+    fn current_freq(&self) -> zbus::Result<u32>;
+
+    /// Energy performance preference (EPP) of the first core. Setting it
+    /// changes every core.
+    #[zbus(property)]
+    fn energy_performance_preference(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn set_energy_performance_preference(&self, value: &str) -> zbus::Result<()>;
+
+    /// Maximum intel_pstate performance limit in percent
+    #[zbus(property)]
+    fn max_perf_pct(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_max_perf_pct(&self, value: u32) -> zbus::Result<()>;
+
+    /// Minimum intel_pstate performance limit in percent
+    #[zbus(property)]
+    fn min_perf_pct(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_min_perf_pct(&self, value: u32) -> zbus::Result<()>;
+
+    /// Whether intel_pstate is not allowed to use turbo frequencies
+    #[zbus(property)]
+    fn no_turbo(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_no_turbo(&self, value: bool) -> zbus::Result<()>;
+
+    /// Name of the P-state driver, e.g. "amd-pstate", or an empty string if
+    /// none is loaded
+    #[zbus(property)]
+    fn pstate_driver(&self) -> zbus::Result<String>;
+
+    /// Operation mode of the P-state driver, e.g. "active" or "passive"
+    #[zbus(property)]
+    fn pstate_status(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn set_pstate_status(&self, value: &str) -> zbus::Result<()>;
+
+    /// Scaling governor of the first core. Setting it changes every core.
+    #[zbus(property)]
+    fn scaling_governor(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn set_scaling_governor(&self, value: &str) -> zbus::Result<()>;
+
+    /// Maximum scaling frequency of the first core in kHz. Setting it changes
+    /// every core.
+    #[zbus(property)]
+    fn scaling_max_freq(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_scaling_max_freq(&self, value: u32) -> zbus::Result<()>;
+
+    /// Minimum scaling frequency of the first core in kHz. Setting it changes
+    /// every core.
+    #[zbus(property)]
+    fn scaling_min_freq(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_scaling_min_freq(&self, value: u32) -> zbus::Result<()>;
+
+    /// Whether simultaneous multithreading (SMT) is enabled
+    #[zbus(property)]
+    fn smt_enabled(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_smt_enabled(&self, value: bool) -> zbus::Result<()>;
+
+    /// Interval of the telemetry signal in milliseconds, or 0 if the signal is
+    /// disabled
+    #[zbus(property)]
+    fn telemetry_interval(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_telemetry_interval(&self, value: u32) -> zbus::Result<()>;
+
+    /// Temperature of the CPU package in °C
+    #[zbus(property)]
+    fn temperature(&self) -> zbus::Result<f64>;
+
+    /// Utilization of all cores in percent since the previous read
+    #[zbus(property)]
+    fn utilization(&self) -> zbus::Result<f64>;
+
+    /// Emitted at the telemetry interval with the average current frequency
+    /// (kHz), utilization (%) and temperature (°C) of the CPU, followed by the
+    /// current frequency and utilization of every core, in the order of
+    /// `enumerate_cores`. Values that cannot be read are 0.
+    #[zbus(signal)]
+    fn telemetry(
+        &self,
+        current_freq: u32,
+        utilization: f64,
+        temperature: f64,
+        cores_current_freq: Vec<u32>,
+        cores_utilization: Vec<f64>,
+    ) -> zbus::Result<()>;
+}
@@ -0,0 +1,75 @@
+//! Proxy for the `org.shadowblip.CPU.Core` interface, as described in
+//! `bindings/dbus-xml/org-shadowblip-cpu-core.xml`.
+use zbus::proxy;
+
+#[proxy(
+    interface = "org.shadowblip.CPU.Core",
+    default_service = "org.shadowblip.PowerStation"
+)]
+pub trait Core {
+    /// Scaling governors that can be set for the CPU core
+    #[zbus(property)]
+    fn available_governors(&self) -> zbus::Result<Vec<String>>;
+
+    /// Performance of the CPU core relative to the fastest core, from 1 to
+    /// 1024, or 0 if unknown
+    #[zbus(property)]
+    fn capacity(&self) -> zbus::Result<u32>;
+
+    /// Core ID of the CPU core. This ID is identical for SMT siblings.
+    #[zbus(property)]
+    fn core_id(&self) -> zbus::Result<u32>;
+
+    /// Type of the CPU core, "performance" or "efficiency", or an empty string
+    /// if the CPU does not have different kinds of cores
+    #[zbus(property)]
+    fn core_type(&self) -> zbus::Result<String>;
+
+    /// Highest frequency supported by the CPU core in kHz
+    #[zbus(property)]
+    fn cpuinfo_max_freq(&self) -> zbus::Result<u32>;
+
+    /// Lowest frequency supported by the CPU core in kHz
+    #[zbus(property)]
+    fn cpuinfo_min_freq(&self) -> zbus::Result<u32>;
+
+    /// Current frequency of the CPU core in kHz
+    #[zbus(property)]
+    fn current_freq(&self) -> zbus::Result<u32>;
+
+    /// Logical number of the CPU core
+    #[zbus(property)]
+    fn number(&self) -> zbus::Result<u32>;
+
+    /// Whether the CPU core is online
+    #[zbus(property)]
+    fn online(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_online(&self, value: bool) -> zbus::Result<()>;
+
+    /// Scaling governor of the CPU core
+    #[zbus(property)]
+    fn scaling_governor(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn set_scaling_governor(&self, value: &str) -> zbus::Result<()>;
+
+    /// Maximum frequency the governor may select in kHz
+    #[zbus(property)]
+    fn scaling_max_freq(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_scaling_max_freq(&self, value: u32) -> zbus::Result<()>;
+
+    /// Minimum frequency the governor may select in kHz
+    #[zbus(property)]
+    fn scaling_min_freq(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_scaling_min_freq(&self, value: u32) -> zbus::Result<()>;
+
+    /// Temperature of the CPU core in °C, only available on Intel CPUs
+    #[zbus(property)]
+    fn temperature(&self) -> zbus::Result<f64>;
+
+    /// Utilization of the CPU core in percent since the previous read
+    #[zbus(property)]
+    fn utilization(&self) -> zbus::Result<f64>;
+}
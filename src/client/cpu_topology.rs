@@ -0,0 +1,33 @@
+//! Proxy for the `org.shadowblip.CPU.Topology` interface, as described in
+//! `bindings/dbus-xml/org-shadowblip-cpu-topology.xml`.
+use zbus::{proxy, zvariant::OwnedObjectPath};
+
+#[proxy(
+    interface = "org.shadowblip.CPU.Topology",
+    default_service = "org.shadowblip.PowerStation"
+)]
+pub trait Topology {
+    /// DBus paths of the nodes below this one. The children of an L3 cache are
+    /// the cores sharing it.
+    #[zbus(property)]
+    fn children(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// Whether any CPU core of the node is online. Setting this brings all
+    /// cores of the node online or offline.
+    #[zbus(property)]
+    fn enabled(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_enabled(&self, value: bool) -> zbus::Result<()>;
+
+    /// ID of the node, as reported by the kernel
+    #[zbus(property)]
+    fn id(&self) -> zbus::Result<u32>;
+
+    /// Level of the node: "package", "die", "cluster", "core" or "l3cache"
+    #[zbus(property)]
+    fn level(&self) -> zbus::Result<String>;
+
+    /// DBus paths of all CPU cores of the node, including SMT siblings
+    #[zbus(property)]
+    fn threads(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+use zbus::fdo;
+
+use crate::error::Error as ServiceError;
+
+/// Result type returned by the client helpers
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned when talking to PowerStation
+#[derive(Debug)]
+pub enum Error {
+    /// PowerStation is not running
+    NotRunning,
+    /// The requested object, interface or property does not exist, e.g. when
+    /// using the TDP interface of a card without TDP support.
+    NotFound(String),
+    /// The operation is not supported by the hardware
+    NotSupported(String),
+    /// The given value was rejected, e.g. an unknown power profile
+    InvalidArgs(String),
+    /// The given value is outside of the hardware limits
+    OutOfRange(String),
+    /// The caller is not authorized, or the card is leased by another client
+    PermissionDenied(String),
+    /// Reading from or writing to the hardware failed
+    Io(String),
+    /// Any other error reported by PowerStation
+    Failed(String),
+    /// Error connecting to or communicating over DBus
+    DBus(zbus::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotRunning => write!(f, "PowerStation is not running"),
+            Error::NotFound(msg) => write!(f, "Not found: {msg}"),
+            Error::NotSupported(msg) => write!(f, "Not supported: {msg}"),
+            Error::InvalidArgs(msg) => write!(f, "Invalid argument: {msg}"),
+            Error::OutOfRange(msg) => write!(f, "Out of range: {msg}"),
+            Error::PermissionDenied(msg) => write!(f, "Permission denied: {msg}"),
+            Error::Io(msg) => write!(f, "I/O error: {msg}"),
+            Error::Failed(msg) => write!(f, "{msg}"),
+            Error::DBus(err) => write!(f, "DBus error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::DBus(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<zbus::Error> for Error {
+    fn from(err: zbus::Error) -> Self {
+        // Methods return PowerStation errors, while properties can only return
+        // the standard DBus errors
+        let err = match ServiceError::from(err) {
+            ServiceError::ZBus(err) => err,
+            ServiceError::NotSupported(msg) => return Error::NotSupported(msg),
+            ServiceError::InvalidArgument(msg) => return Error::InvalidArgs(msg),
+            ServiceError::OutOfRange(msg) => return Error::OutOfRange(msg),
+            ServiceError::PermissionDenied(msg) => return Error::PermissionDenied(msg),
+            ServiceError::BackendFailed(msg) => return Error::Io(msg),
+        };
+
+        // Property errors are boxed, while method errors are only named
+        let err = match err {
+            zbus::Error::FDO(err) => *err,
+            err => fdo::Error::from(err),
+        };
+        match err {
+            fdo::Error::ZBus(err) => Error::DBus(err),
+            fdo::Error::ServiceUnknown(_) | fdo::Error::NameHasNoOwner(_) => Error::NotRunning,
+            fdo::Error::UnknownObject(msg)
+            | fdo::Error::UnknownInterface(msg)
+            | fdo::Error::UnknownMethod(msg)
+            | fdo::Error::UnknownProperty(msg) => Error::NotFound(msg),
+            fdo::Error::NotSupported(msg) | fdo::Error::PropertyReadOnly(msg) => {
+                Error::NotSupported(msg)
+            }
+            fdo::Error::InvalidArgs(msg) => Error::InvalidArgs(msg),
+            fdo::Error::LimitsExceeded(msg) => Error::OutOfRange(msg),
+            fdo::Error::AccessDenied(msg) => Error::PermissionDenied(msg),
+            fdo::Error::IOError(msg) => Error::Io(msg),
+            fdo::Error::Failed(msg) => Error::Failed(msg),
+            err => Error::Failed(err.to_string()),
+        }
+    }
+}
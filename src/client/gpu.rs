@@ -0,0 +1,13 @@
+//! Proxy for the `org.shadowblip.GPU` interface, as described in
+//! `bindings/dbus-xml/org-shadowblip-gpu.xml`.
+use zbus::{proxy, zvariant::OwnedObjectPath};
+
+#[proxy(
+    interface = "org.shadowblip.GPU",
+    default_service = "org.shadowblip.PowerStation",
+    default_path = "/org/shadowblip/Performance/GPU"
+)]
+pub trait Gpu {
+    /// Returns a list of DBus paths to all GPU cards
+    fn enumerate_cards(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+}
@@ -0,0 +1,101 @@
+//! Proxy for the `org.shadowblip.GPU.Card` interface, as described in
+//! `bindings/dbus-xml/org-shadowblip-gpu-card.xml`.
+use zbus::{proxy, zvariant::OwnedObjectPath};
+
+#[proxy(
+    interface = "org.shadowblip.GPU.Card",
+    default_service = "org.shadowblip.PowerStation"
+)]
+pub trait Card {
+    /// Returns a list of DBus paths to all connectors of the card
+    fn enumerate_connectors(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    /// Take exclusive control of the TDP and clock settings of the card
+    fn acquire_lease(&self) -> zbus::Result<()>;
+
+    /// Give up control of the card and restore its previous settings
+    fn release_lease(&self) -> zbus::Result<()>;
+
+    /// Class of the card, either "integrated", "dedicated" or "unknown"
+    #[zbus(property)]
+    fn class(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn class_id(&self) -> zbus::Result<String>;
+
+    /// Maximum GPU clock supported by the hardware in MHz
+    #[zbus(property)]
+    fn clock_limit_mhz_max(&self) -> zbus::Result<f64>;
+
+    /// Minimum GPU clock supported by the hardware in MHz
+    #[zbus(property)]
+    fn clock_limit_mhz_min(&self) -> zbus::Result<f64>;
+
+    /// Current maximum GPU clock in MHz
+    #[zbus(property)]
+    fn clock_value_mhz_max(&self) -> zbus::Result<f64>;
+    #[zbus(property)]
+    fn set_clock_value_mhz_max(&self, value: f64) -> zbus::Result<()>;
+
+    /// Current minimum GPU clock in MHz
+    #[zbus(property)]
+    fn clock_value_mhz_min(&self) -> zbus::Result<f64>;
+    #[zbus(property)]
+    fn set_clock_value_mhz_min(&self, value: f64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn device(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn device_id(&self) -> zbus::Result<String>;
+
+    /// GPU utilization in percent
+    #[zbus(property)]
+    fn gpu_busy_percent(&self) -> zbus::Result<u8>;
+
+    /// Unique bus name of the client controlling the card, or an empty string
+    #[zbus(property)]
+    fn lease_owner(&self) -> zbus::Result<String>;
+
+    /// Whether the GPU clock is manually controlled
+    #[zbus(property)]
+    fn manual_clock(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_manual_clock(&self, value: bool) -> zbus::Result<()>;
+
+    /// Name of the card, e.g. "card0"
+    #[zbus(property)]
+    fn name(&self) -> zbus::Result<String>;
+
+    /// Sysfs path of the card
+    #[zbus(property)]
+    fn path(&self) -> zbus::Result<String>;
+
+    /// GPU workload profile, e.g. "3D_FULL_SCREEN" on AMD GPUs
+    #[zbus(property)]
+    fn power_profile_mode(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn set_power_profile_mode(&self, value: &str) -> zbus::Result<()>;
+
+    /// Workload profiles supported by the card
+    #[zbus(property)]
+    fn power_profile_modes_available(&self) -> zbus::Result<Vec<String>>;
+
+    #[zbus(property)]
+    fn revision_id(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn subdevice(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn subdevice_id(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn subvendor_id(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn vendor(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn vendor_id(&self) -> zbus::Result<String>;
+}
@@ -0,0 +1,36 @@
+//! Proxy for the `org.shadowblip.GPU.Card.Connector` interface, as described
+//! in `bindings/dbus-xml/org-shadowblip-gpu-card-connector.xml`.
+use zbus::proxy;
+
+#[proxy(
+    interface = "org.shadowblip.GPU.Card.Connector",
+    default_service = "org.shadowblip.PowerStation"
+)]
+pub trait Connector {
+    /// Whether the display is powered on
+    #[zbus(property, name = "DPMS")]
+    fn dpms(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn enabled(&self) -> zbus::Result<bool>;
+
+    /// DRM connector ID
+    #[zbus(property)]
+    fn id(&self) -> zbus::Result<u32>;
+
+    /// Display modes supported by the connected display, e.g. "1920x1080"
+    #[zbus(property)]
+    fn modes(&self) -> zbus::Result<Vec<String>>;
+
+    /// Name of the connector, e.g. "eDP-1"
+    #[zbus(property)]
+    fn name(&self) -> zbus::Result<String>;
+
+    /// Sysfs path of the connector
+    #[zbus(property)]
+    fn path(&self) -> zbus::Result<String>;
+
+    /// Connection status, e.g. "connected" or "disconnected"
+    #[zbus(property)]
+    fn status(&self) -> zbus::Result<String>;
+}
@@ -0,0 +1,65 @@
+//! Proxy for the `org.shadowblip.GPU.Card.TDP` interface, as described in
+//! `bindings/dbus-xml/org-shadowblip-gpu-card.xml`.
+use std::collections::HashMap;
+
+use zbus::proxy;
+use zbus::zvariant::Value;
+
+#[proxy(
+    interface = "org.shadowblip.GPU.Card.TDP",
+    default_service = "org.shadowblip.PowerStation"
+)]
+pub trait Tdp {
+    /// Set the TDP, boost, thermal throttle limit and power profile in one
+    /// call, keyed by their property names. Nothing is changed if one of
+    /// them is invalid or fails to apply.
+    fn set_limits(&self, limits: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+
+    /// Sustained TDP in watts
+    #[zbus(property, name = "TDP")]
+    fn tdp(&self) -> zbus::Result<f64>;
+    #[zbus(property, name = "TDP")]
+    fn set_tdp(&self, value: f64) -> zbus::Result<()>;
+
+    /// Minimum supported TDP in watts
+    #[zbus(property)]
+    fn min_tdp(&self) -> zbus::Result<f64>;
+
+    /// Maximum supported TDP in watts
+    #[zbus(property)]
+    fn max_tdp(&self) -> zbus::Result<f64>;
+
+    /// Additional short-term TDP in watts on top of the sustained TDP
+    #[zbus(property)]
+    fn boost(&self) -> zbus::Result<f64>;
+    #[zbus(property)]
+    fn set_boost(&self, value: f64) -> zbus::Result<()>;
+
+    /// Maximum supported boost in watts
+    #[zbus(property)]
+    fn max_boost(&self) -> zbus::Result<f64>;
+
+    /// Temperature in degrees Celsius at which the APU is throttled
+    #[zbus(property)]
+    fn thermal_throttle_limit_c(&self) -> zbus::Result<f64>;
+    #[zbus(property)]
+    fn set_thermal_throttle_limit_c(&self, value: f64) -> zbus::Result<()>;
+
+    /// Current power profile
+    #[zbus(property)]
+    fn power_profile(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn set_power_profile(&self, value: &str) -> zbus::Result<()>;
+
+    /// Power profiles supported by the hardware
+    #[zbus(property)]
+    fn power_profiles_available(&self) -> zbus::Result<Vec<String>>;
+
+    /// TDP backends in the order they are tried, e.g. "hwmon" or "ryzenadj"
+    #[zbus(property)]
+    fn tdp_backends(&self) -> zbus::Result<Vec<String>>;
+
+    /// TDP backend that served the last read or write, or an empty string
+    #[zbus(property)]
+    fn active_tdp_backend(&self) -> zbus::Result<String>;
+}
@@ -0,0 +1,164 @@
+//! Typed DBus proxies for all PowerStation interfaces
+//!
+//! The proxies are kept in sync with the XML specifications in
+//! `bindings/dbus-xml`. The helpers in this module return proxies that do not
+//! cache properties, so values are always read from PowerStation.
+use zbus::{proxy::CacheProperties, zvariant::OwnedObjectPath, Connection};
+
+use crate::constants::GPU_PATH;
+
+pub mod cpu;
+pub mod cpu_core;
+pub mod cpu_topology;
+pub mod error;
+pub mod gpu;
+pub mod gpu_card;
+pub mod gpu_card_connector;
+pub mod gpu_card_tdp;
+pub mod profiles;
+
+pub use cpu::CpuProxy;
+pub use cpu_core::CoreProxy;
+pub use cpu_topology::TopologyProxy;
+pub use error::{Error, Result};
+pub use gpu::GpuProxy;
+pub use gpu_card::CardProxy;
+pub use gpu_card_connector::ConnectorProxy;
+pub use gpu_card_tdp::TdpProxy;
+pub use profiles::ProfilesProxy;
+
+/// Returns a proxy to the CPU
+pub async fn cpu(conn: &Connection) -> Result<CpuProxy<'static>> {
+    let proxy = CpuProxy::builder(conn)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    Ok(proxy)
+}
+
+/// Returns proxies to all CPU cores, ordered by core number
+pub async fn cores(conn: &Connection) -> Result<Vec<CoreProxy<'static>>> {
+    let mut cores = Vec::new();
+    for path in cpu(conn).await?.enumerate_cores().await? {
+        let core = CoreProxy::builder(conn)
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        cores.push((core.number().await?, core));
+    }
+    cores.sort_by_key(|(number, _)| *number);
+
+    Ok(cores.into_iter().map(|(_, core)| core).collect())
+}
+
+/// Returns proxies to all L3 caches of the CPU. On AMD CPUs, each L3 cache is
+/// a CCD or CCX.
+pub async fn l3_caches(conn: &Connection) -> Result<Vec<TopologyProxy<'static>>> {
+    let mut caches = Vec::new();
+    for path in cpu(conn).await?.enumerate_l3_caches().await? {
+        let cache = TopologyProxy::builder(conn)
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        caches.push(cache);
+    }
+
+    Ok(caches)
+}
+
+/// Returns a proxy to the profile manager
+pub async fn profiles(conn: &Connection) -> Result<ProfilesProxy<'static>> {
+    let proxy = ProfilesProxy::builder(conn)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    Ok(proxy)
+}
+
+/// Returns the DBus path of the given card name (e.g. "card1"). Full DBus
+/// paths are returned as is.
+pub fn card_path(name: &str) -> Result<OwnedObjectPath> {
+    let path = if name.starts_with('/') {
+        name.to_string()
+    } else {
+        format!("{GPU_PATH}/{name}")
+    };
+    let path = OwnedObjectPath::try_from(path).map_err(zbus::Error::from)?;
+    Ok(path)
+}
+
+/// Returns a proxy to the given card (e.g. "card1")
+pub async fn card(conn: &Connection, name: &str) -> Result<CardProxy<'static>> {
+    let proxy = CardProxy::builder(conn)
+        .path(card_path(name)?)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    Ok(proxy)
+}
+
+/// Returns proxies to all GPU cards
+pub async fn cards(conn: &Connection) -> Result<Vec<CardProxy<'static>>> {
+    let gpu = GpuProxy::builder(conn)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    let mut cards = Vec::new();
+    for path in gpu.enumerate_cards().await? {
+        let card = CardProxy::builder(conn)
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        cards.push(card);
+    }
+
+    Ok(cards)
+}
+
+/// Returns a proxy to the integrated GPU
+pub async fn integrated_gpu(conn: &Connection) -> Result<CardProxy<'static>> {
+    for card in cards(conn).await? {
+        if card.class().await? == "integrated" {
+            return Ok(card);
+        }
+    }
+    Err(Error::NotFound("No integrated GPU found".to_string()))
+}
+
+/// Returns a proxy to the TDP interface of the given card (e.g. "card1")
+pub async fn tdp(conn: &Connection, name: &str) -> Result<TdpProxy<'static>> {
+    let proxy = TdpProxy::builder(conn)
+        .path(card_path(name)?)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    Ok(proxy)
+}
+
+/// Returns a proxy to the TDP interface of the integrated GPU
+pub async fn integrated_gpu_tdp(conn: &Connection) -> Result<TdpProxy<'static>> {
+    let card = integrated_gpu(conn).await?;
+    tdp(conn, card.inner().path().as_str()).await
+}
+
+/// Returns proxies to all connectors of the given card
+pub async fn connectors(
+    conn: &Connection,
+    card: &CardProxy<'_>,
+) -> Result<Vec<ConnectorProxy<'static>>> {
+    let mut connectors = Vec::new();
+    for path in card.enumerate_connectors().await? {
+        let connector = ConnectorProxy::builder(conn)
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        connectors.push(connector);
+    }
+
+    Ok(connectors)
+}
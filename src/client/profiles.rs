@@ -0,0 +1,35 @@
+//! Proxy for the `org.shadowblip.Profiles` interface, as described in
+//! `bindings/dbus-xml/org-shadowblip-profiles.xml`.
+use std::collections::HashMap;
+
+use zbus::proxy;
+use zbus::zvariant::{OwnedValue, Value};
+
+#[proxy(
+    interface = "org.shadowblip.Profiles",
+    default_service = "org.shadowblip.PowerStation",
+    default_path = "/org/shadowblip/Performance/Profiles"
+)]
+pub trait Profiles {
+    /// Returns the settings of the given profile, keyed by the name of the
+    /// DBus property they set
+    fn get_profile(&self, name: &str) -> zbus::Result<HashMap<String, OwnedValue>>;
+
+    /// Create or replace the given profile
+    fn save_profile(&self, name: &str, settings: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+
+    /// Delete the given profile
+    fn delete_profile(&self, name: &str) -> zbus::Result<()>;
+
+    /// Apply all settings of the given profile
+    fn apply_profile(&self, name: &str) -> zbus::Result<()>;
+
+    /// Name of the last applied profile, or an empty string if no profile is
+    /// active
+    #[zbus(property)]
+    fn active_profile(&self) -> zbus::Result<String>;
+
+    /// Names of all saved profiles
+    #[zbus(property)]
+    fn profiles(&self) -> zbus::Result<Vec<String>>;
+}
@@ -0,0 +1,515 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use log::LevelFilter;
+use serde::Deserialize;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::Mutex;
+
+use crate::performance::gpu::tdp::TdpBackend;
+
+/// Path to the PowerStation configuration file
+pub const CONFIG_PATH: &str = "/etc/powerstation/config.toml";
+
+/// Directory with drop-in configuration files, which are applied on top of
+/// [CONFIG_PATH]
+pub const CONFIG_DIR: &str = "/etc/powerstation/config.d";
+
+/// PowerStation configuration. Every section is optional.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub daemon: DaemonConfig,
+    pub platform: PlatformConfig,
+    pub cpu: CpuConfig,
+    pub tdp: TdpConfig,
+    pub power: PowerConfig,
+    pub power_profiles: PowerProfilesConfig,
+    pub gamemode: GameModeConfig,
+    pub apps: Vec<AppProfile>,
+}
+
+impl Config {
+    /// Load the configuration from the given file and the "*.toml" drop-in
+    /// files in the given directory. Drop-in files are applied in lexical
+    /// order and replace the values they set. Missing files are skipped, and
+    /// invalid files are ignored as a whole.
+    pub fn load<P: AsRef<Path>, D: AsRef<Path>>(path: P, dir: D) -> Config {
+        let mut files = vec![path.as_ref().to_path_buf()];
+        files.extend(drop_in_files(dir.as_ref()));
+
+        let mut table = toml::Table::new();
+        for file in files {
+            if let Some(content) = read_file(&file) {
+                merge(&mut table, content);
+            }
+        }
+        match toml::Value::Table(table).try_into::<Config>() {
+            Ok(config) => config,
+            Err(e) => {
+                log::error!("Ignoring invalid configuration: {e}");
+                Config::default()
+            }
+        }
+    }
+
+    /// Returns the names of the changed settings that are only read at
+    /// startup
+    fn restart_required(&self, other: &Config) -> Vec<&'static str> {
+        let changes = [
+            (
+                "daemon.worker_threads",
+                self.daemon.worker_threads != other.daemon.worker_threads,
+            ),
+            ("platform", self.platform != other.platform),
+            ("tdp.backends", self.tdp.backends != other.tdp.backends),
+            ("tdp.ryzenadj", self.tdp.ryzenadj != other.tdp.ryzenadj),
+            ("power", self.power.is_empty() && !other.power.is_empty()),
+            (
+                "power_profiles.enabled",
+                self.power_profiles.enabled != other.power_profiles.enabled,
+            ),
+            (
+                "gamemode.enabled",
+                self.gamemode.enabled != other.gamemode.enabled,
+            ),
+            (
+                "gamemode.address",
+                self.gamemode.address != other.gamemode.address,
+            ),
+        ];
+        changes
+            .into_iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(name, _)| name)
+            .collect()
+    }
+}
+
+/// Read a single configuration file. The file is checked on its own so
+/// errors, e.g. unknown keys, are reported with the file and line.
+fn read_file(path: &Path) -> Option<toml::Table> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            log::info!("No configuration found at {:?}", path);
+            return None;
+        }
+        Err(e) => {
+            log::warn!("Failed to read configuration file {:?}: {e}", path);
+            return None;
+        }
+    };
+    let table =
+        toml::from_str::<Config>(&content).and_then(|_| toml::from_str::<toml::Table>(&content));
+    match table {
+        Ok(table) => {
+            log::info!("Loaded configuration from {:?}", path);
+            Some(table)
+        }
+        Err(e) => {
+            log::error!("Ignoring invalid configuration file {:?}: {e}", path);
+            None
+        }
+    }
+}
+
+/// Returns the "*.toml" files in the given directory in lexical order
+fn drop_in_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Merge the given table into the base table. Tables are merged recursively
+/// and all other values, including arrays, are replaced.
+fn merge(base: &mut toml::Table, table: toml::Table) {
+    for (key, value) in table {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(table)) => merge(base, table),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Shared handle to the current [Config]. Settings are read whenever they are
+/// applied, so most of them take effect without a restart when the
+/// configuration is reloaded.
+#[derive(Clone)]
+pub struct ConfigStore {
+    path: PathBuf,
+    dir: PathBuf,
+    config: Arc<Mutex<Config>>,
+}
+
+impl ConfigStore {
+    /// Create a store with the given configuration, which was loaded from the
+    /// given file and drop-in directory
+    pub fn new<P: Into<PathBuf>, D: Into<PathBuf>>(path: P, dir: D, config: Config) -> Self {
+        ConfigStore {
+            path: path.into(),
+            dir: dir.into(),
+            config: Arc::new(Mutex::new(config)),
+        }
+    }
+
+    /// Returns a copy of the current configuration
+    pub async fn get(&self) -> Config {
+        self.config.lock().await.clone()
+    }
+
+    /// Load the configuration files again and return the new configuration
+    pub async fn reload(&self) -> Config {
+        let config = Config::load(&self.path, &self.dir);
+        let mut current = self.config.lock().await;
+        for name in current.restart_required(&config) {
+            log::warn!("Changes to {name} take effect after a restart");
+        }
+        *current = config.clone();
+        config
+    }
+}
+
+/// Reload the configuration whenever SIGHUP is received
+pub fn watch_reload(config: ConfigStore) -> std::io::Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::task::spawn(async move {
+        while hangup.recv().await.is_some() {
+            let reloaded = config.reload().await;
+            log::set_max_level(reloaded.daemon.level_filter());
+            log::info!("Reloaded configuration");
+        }
+    });
+    Ok(())
+}
+
+/// Settings of the daemon itself. These are read at startup, except for the
+/// log level.
+///
+/// ```toml
+/// [daemon]
+/// worker_threads = 2
+/// log_level = "info"
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Number of threads used to serve DBus requests
+    pub worker_threads: usize,
+    /// Most verbose level of messages to log. Everything is logged if unset.
+    pub log_level: Option<LogLevel>,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            worker_threads: 2,
+            log_level: None,
+        }
+    }
+}
+
+impl DaemonConfig {
+    /// Returns the filter for logged messages
+    pub fn level_filter(&self) -> LevelFilter {
+        match self.log_level {
+            Some(LogLevel::Error) => LevelFilter::Error,
+            Some(LogLevel::Warn) => LevelFilter::Warn,
+            Some(LogLevel::Info) => LevelFilter::Info,
+            Some(LogLevel::Debug) => LevelFilter::Debug,
+            Some(LogLevel::Trace) | None => LevelFilter::Trace,
+        }
+    }
+}
+
+/// Level of logged messages
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// Location of the platform database and the limits of devices that are not
+/// in it. These are read at startup.
+///
+/// ```toml
+/// [platform]
+/// database_dir = "/usr/local/share/powerstation/platform"
+/// min_tdp = 5.0
+/// max_tdp = 25.0
+/// max_boost = 2.0
+/// ```
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlatformConfig {
+    /// Directory with the platform database. Defaults to the
+    /// "powerstation/platform" directory in the XDG data directories.
+    pub database_dir: Option<PathBuf>,
+    /// Limits used if the device is not in the platform database
+    pub min_tdp: Option<f64>,
+    pub max_tdp: Option<f64>,
+    pub max_boost: Option<f64>,
+}
+
+/// CPU settings applied on startup and resume when none were saved
+///
+/// ```toml
+/// [cpu]
+/// enable_smt = false
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CpuConfig {
+    /// Enable SMT if no SMT setting was saved
+    pub enable_smt: bool,
+    /// Bring all cores online if no number of enabled cores was saved
+    pub enable_all_cores: bool,
+}
+
+impl Default for CpuConfig {
+    fn default() -> Self {
+        CpuConfig {
+            enable_smt: true,
+            enable_all_cores: true,
+        }
+    }
+}
+
+/// TDP control settings. The backends and RyzenAdj settings are read when a
+/// card is added.
+///
+/// ```toml
+/// [tdp]
+/// limit_policy = "clamp"
+/// backends = ["hwmon", "asus_wmi"]
+///
+/// [tdp.ryzenadj]
+/// stapm_limit = 12.0
+/// ```
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TdpConfig {
+    /// Handling of TDP and boost values outside of the hardware limits, which
+    /// are read from the platform database or from the hwmon interface of the
+    /// card.
+    pub limit_policy: LimitPolicy,
+    /// Order in which the AMD TDP backends are tried. Backends that are not
+    /// listed are not used. Overrides the order from the platform database.
+    pub backends: Option<Vec<TdpBackend>>,
+    pub ryzenadj: RyzenAdjConfig,
+}
+
+/// Limits reported by RyzenAdj for GPUs it cannot read the limits of, until
+/// they are set
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RyzenAdjConfig {
+    /// PCI device IDs of the GPUs, e.g. "163f" for Van Gogh
+    pub unreadable_devices: Vec<String>,
+    pub stapm_limit: f64,
+    pub fast_limit: f64,
+    pub thermal_limit: f64,
+}
+
+impl Default for RyzenAdjConfig {
+    fn default() -> Self {
+        RyzenAdjConfig {
+            // Van Gogh and Sephiroth, used by the Steam Deck
+            unreadable_devices: vec![String::from("163f"), String::from("1435")],
+            stapm_limit: 12.0,
+            fast_limit: 15.0,
+            thermal_limit: 95.0,
+        }
+    }
+}
+
+/// What to do with a TDP or boost value outside of the hardware limits
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitPolicy {
+    /// Refuse the value
+    #[default]
+    Reject,
+    /// Set the closest value within the limits instead
+    Clamp,
+    /// Set the value anyway. This can damage the hardware.
+    Unsafe,
+}
+
+impl LimitPolicy {
+    /// Returns the value to set for the given value and limits, or None if
+    /// the value is refused. Limits that are unknown or 0 are not enforced.
+    pub fn enforce(&self, value: f64, min: Option<f64>, max: Option<f64>) -> Option<f64> {
+        let min = min.filter(|min| *min > 0.0);
+        let max = max.filter(|max| *max > 0.0);
+        let below = min.is_some_and(|min| value < min);
+        let above = max.is_some_and(|max| value > max);
+        match self {
+            LimitPolicy::Reject if below || above => None,
+            LimitPolicy::Clamp if below => min,
+            LimitPolicy::Clamp if above => max,
+            _ => Some(value),
+        }
+    }
+}
+
+/// Profiles and limits to switch to automatically when the power source or
+/// battery level changes, as reported by UPower.
+///
+/// ```toml
+/// [power]
+/// ac_profile = "turbo"
+/// battery_profile = "balanced"
+///
+/// [[power.battery_thresholds]]
+/// below_percent = 20
+/// max_tdp = "min"
+/// ```
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerConfig {
+    /// Profile to apply when switching to AC power
+    pub ac_profile: Option<String>,
+    /// Profile to apply when switching to battery power
+    pub battery_profile: Option<String>,
+    /// Additional limits while on battery below a given charge level. If
+    /// several thresholds match, the one with the lowest percentage is used.
+    pub battery_thresholds: Vec<BatteryThreshold>,
+}
+
+impl PowerConfig {
+    /// Returns true if no automatic switching is configured
+    pub fn is_empty(&self) -> bool {
+        self.ac_profile.is_none()
+            && self.battery_profile.is_none()
+            && self.battery_thresholds.is_empty()
+    }
+}
+
+/// Settings of the power-profiles-daemon compatible interface, used by desktop
+/// shells to switch between the "power-saver", "balanced" and "performance"
+/// profiles. Profiles that are not configured use built-in defaults based on
+/// the detected hardware.
+///
+/// ```toml
+/// [power_profiles]
+/// enabled = true
+///
+/// [power_profiles.performance]
+/// tdp = 25
+/// boost = 5
+/// power_profile = "performance"
+/// ```
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerProfilesConfig {
+    /// Serve the interface and claim the power-profiles-daemon bus names
+    pub enabled: bool,
+    #[serde(rename = "power-saver")]
+    pub power_saver: Option<PowerProfileSettings>,
+    pub balanced: Option<PowerProfileSettings>,
+    pub performance: Option<PowerProfileSettings>,
+}
+
+/// Settings applied to every card with a TDP interface when switching to a
+/// power-profiles-daemon profile. Unset values are left unchanged.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerProfileSettings {
+    pub tdp: Option<TdpLimit>,
+    pub boost: Option<f64>,
+    /// Platform power profile, e.g. "low-power" or "performance"
+    pub power_profile: Option<String>,
+}
+
+/// Settings applied while any game is registered with Feral GameMode. The
+/// previous values are restored once the last game unregisters.
+///
+/// ```toml
+/// [gamemode]
+/// enabled = true
+/// tdp = "max"
+/// boost = 5
+/// power_profile_mode = "3D_FULL_SCREEN"
+/// gpu_clock_mhz = 1600
+/// ```
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameModeConfig {
+    /// Watch GameMode for registered games
+    pub enabled: bool,
+    /// Address of the bus GameMode is running on. Defaults to the session bus
+    /// of the first regular user that is logged in.
+    pub address: Option<String>,
+    pub tdp: Option<TdpLimit>,
+    pub boost: Option<f64>,
+    /// Platform power profile, e.g. "performance"
+    pub power_profile: Option<String>,
+    /// GPU power profile mode, e.g. "3D_FULL_SCREEN"
+    pub power_profile_mode: Option<String>,
+    /// Lock the GPU clock of every card to the given value in MHz
+    pub gpu_clock_mhz: Option<f64>,
+}
+
+/// Profile to apply while a process with the given name is running. The
+/// replaced settings are restored once the process exits. If several bound
+/// processes are running, the first binding is used.
+///
+/// ```toml
+/// [[apps]]
+/// process = "eldenring.exe"
+/// profile = "turbo"
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AppProfile {
+    /// Name of the executable, e.g. "steam" or "eldenring.exe"
+    pub process: String,
+    /// Name of the saved profile to apply
+    pub profile: String,
+}
+
+/// Settings applied while on battery below the given charge level
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BatteryThreshold {
+    /// Battery charge in percent below which this threshold applies
+    pub below_percent: f64,
+    /// Profile to apply instead of the battery profile
+    pub profile: Option<String>,
+    /// Upper limit for the TDP
+    pub max_tdp: Option<TdpLimit>,
+}
+
+/// A TDP value given either in watts or as one of the hardware limits
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum TdpLimit {
+    Watts(f64),
+    Hardware(HardwareLimit),
+}
+
+/// TDP limits of the detected hardware
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HardwareLimit {
+    Min,
+    Max,
+}
@@ -1,4 +1,10 @@
+/// Well-known bus name of the PowerStation daemon
 pub const BUS_NAME: &str = "org.shadowblip.PowerStation";
+/// Root path of all objects served by PowerStation
 pub const PREFIX: &str = "/org/shadowblip/Performance";
+/// Path of the CPU object. CPU cores are served below it as `Core<N>`.
 pub const CPU_PATH: &str = "/org/shadowblip/Performance/CPU";
+/// Path of the GPU object. GPU cards are served below it as `card<N>`.
 pub const GPU_PATH: &str = "/org/shadowblip/Performance/GPU";
+/// Path of the profile manager, which stores and applies named profiles
+pub const PROFILES_PATH: &str = "/org/shadowblip/Performance/Profiles";
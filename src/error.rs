@@ -0,0 +1,56 @@
+//! Errors returned by PowerStation over DBus
+//!
+//! Methods of the CPU, GPU, TDP and connector interfaces return errors named
+//! `org.shadowblip.PowerStation.Error.*`. DBus properties can only return the
+//! standard `org.freedesktop.DBus.Error.*` errors, so property errors are
+//! mapped to the closest standard error instead.
+use zbus::{fdo, DBusError};
+
+/// Errors returned by the methods of PowerStation interfaces
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "org.shadowblip.PowerStation.Error")]
+pub enum Error {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    /// The operation is not supported by the hardware
+    NotSupported(String),
+    /// The given value is invalid, e.g. an unknown power profile
+    InvalidArgument(String),
+    /// The given value is outside of the hardware limits
+    OutOfRange(String),
+    /// The caller is not authorized, or the device is leased by another
+    /// client
+    PermissionDenied(String),
+    /// Reading from or writing to the hardware failed
+    BackendFailed(String),
+}
+
+impl From<Error> for fdo::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::ZBus(err) => fdo::Error::ZBus(err),
+            Error::NotSupported(msg) => fdo::Error::NotSupported(msg),
+            Error::InvalidArgument(msg) => fdo::Error::InvalidArgs(msg),
+            Error::OutOfRange(msg) => fdo::Error::LimitsExceeded(msg),
+            Error::PermissionDenied(msg) => fdo::Error::AccessDenied(msg),
+            Error::BackendFailed(msg) => fdo::Error::IOError(msg),
+        }
+    }
+}
+
+impl From<fdo::Error> for Error {
+    fn from(err: fdo::Error) -> Self {
+        match err {
+            fdo::Error::ZBus(err) => Error::ZBus(err),
+            fdo::Error::NotSupported(msg) => Error::NotSupported(msg),
+            fdo::Error::InvalidArgs(msg) => Error::InvalidArgument(msg),
+            fdo::Error::LimitsExceeded(msg) => Error::OutOfRange(msg),
+            fdo::Error::AccessDenied(msg) => Error::PermissionDenied(msg),
+            fdo::Error::IOError(msg) | fdo::Error::Failed(msg) => Error::BackendFailed(msg),
+            err => Error::BackendFailed(err.to_string()),
+        }
+    }
+}
+
+/// Result type returned by the methods of PowerStation interfaces
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,19 @@
+//! Client library for PowerStation
+//!
+//! Provides typed DBus proxies for every interface served by the
+//! `powerstation` daemon, along with helpers to look up commonly used objects
+//! such as the TDP interface of the integrated GPU.
+//!
+//! ```no_run
+//! use powerstation::client;
+//!
+//! # async fn example() -> powerstation::client::Result<()> {
+//! let conn = zbus::Connection::system().await?;
+//! let tdp = client::integrated_gpu_tdp(&conn).await?;
+//! tdp.set_tdp(15.0).await?;
+//! # Ok(())
+//! # }
+//! ```
+pub mod client;
+pub mod constants;
+pub mod error;
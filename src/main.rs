@@ -1,25 +1,69 @@
-use constants::PREFIX;
 use simple_logger::SimpleLogger;
 use std::{error::Error, future::pending};
 use zbus::fdo::ObjectManager;
 use zbus::Connection;
 
-use crate::constants::{BUS_NAME, CPU_PATH, GPU_PATH};
-use crate::dbus::gpu::{get_connectors, get_gpus, GPUBus};
-use crate::performance::{cpu::cpu_features, gpu::dbus};
-
-mod constants;
+use crate::config::{self as configuration, Config, ConfigStore, CONFIG_DIR, CONFIG_PATH};
+use crate::dbus::gpu::{get_gpus, GPUBus};
+use crate::performance::cpu::{cpu_features, telemetry, topology};
+use crate::performance::gpu::platform::hardware::Hardware;
+use crate::performance::overrides::OverrideStack;
+use crate::performance::profile::{ProfileManager, PROFILES_FILE};
+use crate::performance::state::{self, StateStore, STATE_PATH};
+use crate::performance::{apps, gamemode, monitor, power, power_profiles, sleep};
+use crate::performance::{gpu::dbus, gpu::dbus::lease, gpu::hotplug};
+use powerstation::constants::{BUS_NAME, CPU_PATH, GPU_PATH, PREFIX, PROFILES_PATH};
+
+mod config;
 mod performance;
+mod polkit;
+mod root;
 
-#[tokio::main(flavor = "multi_thread", worker_threads = 2)]
-async fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> Result<(), Box<dyn Error>> {
     SimpleLogger::new().init().unwrap();
     const VERSION: &str = env!("CARGO_PKG_VERSION");
     log::info!("Starting PowerStation v{}", VERSION);
 
+    // Parse command line arguments
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--root" => {
+                let Some(path) = args.next() else {
+                    return Err("Missing path for --root".into());
+                };
+                root::set_root(path);
+            }
+            _ => return Err(format!("Unknown argument: {arg}").into()),
+        }
+    }
+    if root::is_overridden() {
+        log::info!("Using alternate filesystem root: {:?}", root::root());
+    }
+
+    // Load the configuration, which sets up the runtime
+    let config_path = root::path(CONFIG_PATH);
+    let config_dir = root::path(CONFIG_DIR);
+    let config = Config::load(&config_path, &config_dir);
+    log::set_max_level(config.daemon.level_filter());
+    Hardware::configure(config.platform.clone());
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(config.daemon.worker_threads.max(1))
+        .enable_all()
+        .build()?;
+
+    runtime.block_on(run(ConfigStore::new(config_path, config_dir, config)))
+}
+
+/// Serve all PowerStation interfaces until the process is stopped
+async fn run(config: ConfigStore) -> Result<(), Box<dyn Error>> {
+    // Load the last applied settings
+    let state = StateStore::load(root::path(STATE_PATH));
+
     // Discover all CPUs
-    let cpu = cpu_features::Cpu::new();
-    let cores = cpu_features::get_cores();
+    let cpu = cpu_features::Cpu::new(state.clone(), config.clone());
+    let telemetry_interval = cpu.subscribe_telemetry_interval();
+    let cores: Vec<_> = cpu.cores().into_iter().cloned().collect();
 
     // Configure the connection
     let connection = Connection::system().await?;
@@ -34,53 +78,83 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     // Generate CPU objects to serve
     connection.object_server().at(CPU_PATH, cpu).await?;
+    for (node_path, node) in topology::build(&cores, &state) {
+        connection.object_server().at(node_path, node).await?;
+    }
     for core in cores {
         let core_path = format!("{0}/Core{1}", CPU_PATH, core.number());
         connection.object_server().at(core_path, core).await?;
     }
 
+    // Create a GPU Bus instance which allows card enumeration
+    let gpu_bus = GPUBus::new(vec![]);
+    connection.object_server().at(GPU_PATH, gpu_bus).await?;
+
     // Discover all GPUs and Generate GPU objects to serve
-    let mut gpu_obj_paths: Vec<String> = Vec::new();
-    for mut card in get_gpus().await {
-        // Build the DBus object path for this card
-        let gpu_name = card.name().await;
-        let card_name = gpu_name.as_str().title();
-        let gpu_path = card.gpu_path().await;
-        gpu_obj_paths.push(gpu_path.clone());
-
-        // Get the TDP interface from the card and serve it on DBus
-        match card.get_tdp_interface().await {
-            Some(tdp) => {
-                log::debug!("Discovered TDP interface on card: {}", card_name);
-                connection.object_server().at(gpu_path.clone(), tdp).await?;
-            }
-            None => {
-                log::warn!("Card {} does not have a TDP interface", card_name);
-            }
-        }
+    for card in get_gpus(state.clone()).await {
+        hotplug::serve_gpu(&connection, card, &config).await?;
+    }
 
-        // Get GPU connectors from the card and serve them on DBus
-        let mut connector_paths: Vec<String> = Vec::new();
-        let connectors = get_connectors(gpu_name);
-        for connector in connectors {
-            let name = connector.name.clone().replace('-', "/");
-            let port_path = format!("{0}/{1}", gpu_path, name);
-            connector_paths.push(port_path.clone());
-            log::debug!("Discovered connector on {}: {}", card_name, port_path);
-            connection.object_server().at(port_path, connector).await?;
-        }
-        card.set_connector_paths(connector_paths).await;
+    // Add and remove GPU objects as cards and connectors are hotplugged
+    if let Err(e) = hotplug::watch_drm(connection.clone(), state.clone(), config.clone()) {
+        log::warn!("Unable to watch for GPU hotplug events: {e}");
+    }
+
+    // Serve the profile manager to store and apply named profiles
+    let profiles =
+        ProfileManager::new(connection.clone(), state.clone(), root::path(PROFILES_FILE));
+    connection
+        .object_server()
+        .at(PROFILES_PATH, profiles)
+        .await?;
 
-        // Serve the GPU interface on DBus
-        connection
-            .object_server()
-            .at(gpu_path.clone(), card)
-            .await?;
+    // Re-apply the last applied settings
+    state::restore(&connection, &state).await?;
+
+    // Release the leases of cards when their clients disconnect
+    if let Err(e) = lease::watch_owners(connection.clone()).await {
+        log::warn!("Unable to watch for disconnected lease owners: {e}");
     }
 
-    // Create a GPU Bus instance which allows card enumeration
-    let gpu_bus = GPUBus::new(gpu_obj_paths);
-    connection.object_server().at(GPU_PATH, gpu_bus).await?;
+    // Notify clients about changes made outside of PowerStation
+    monitor::watch_properties(connection.clone());
+
+    // Emit the CPU telemetry signal once a client sets an interval
+    telemetry::watch_telemetry(connection.clone(), telemetry_interval);
+
+    // Settings applied temporarily by GameMode and application profiles
+    let overrides = OverrideStack::new(connection.clone());
+
+    // Re-apply the last applied settings whenever the system resumes
+    if let Err(e) = sleep::watch_resume(connection.clone(), state.clone(), overrides.clone()).await
+    {
+        log::warn!("Unable to watch for system resume: {e}");
+    }
+
+    // Switch profiles whenever the power source or battery level changes
+    if let Err(e) =
+        power::watch_power_source(connection.clone(), config.clone(), overrides.clone()).await
+    {
+        log::warn!("Unable to watch for power source changes: {e}");
+    }
+
+    // Apply the GameMode settings while games are running
+    if let Err(e) = gamemode::watch_games(config.clone(), overrides.clone()).await {
+        log::warn!("Unable to watch for GameMode games: {e}");
+    }
+
+    // Apply the profiles bound to applications while they are running
+    apps::watch_apps(connection.clone(), config.clone(), overrides);
+
+    // Serve the power-profiles-daemon compatible interface for desktop shells
+    if let Err(e) = power_profiles::serve(&connection, config.clone(), state.clone()).await {
+        log::warn!("Unable to serve the power profiles interface: {e}");
+    }
+
+    // Reload the configuration on SIGHUP
+    if let Err(e) = configuration::watch_reload(config) {
+        log::warn!("Unable to watch for configuration reloads: {e}");
+    }
 
     // Request a name
     connection.request_name(BUS_NAME).await?;
@@ -90,17 +164,3 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
-
-trait TitleCase {
-    fn title(&self) -> String;
-}
-
-impl TitleCase for &str {
-    fn title(&self) -> String {
-        if !self.is_ascii() || self.is_empty() {
-            return String::from(*self);
-        }
-        let (head, tail) = self.split_at(1);
-        head.to_uppercase() + tail
-    }
-}
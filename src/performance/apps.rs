@@ -0,0 +1,115 @@
+use std::{collections::HashSet, fs, time::Duration};
+
+use zbus::Connection;
+
+use crate::config::{AppProfile, ConfigStore};
+use crate::performance::overrides::{Override, OverrideStack};
+use crate::performance::profile::{Profile, ProfileManager};
+use crate::root;
+use powerstation::constants::PROFILES_PATH;
+
+/// Directory with an entry for every running process
+const PROC_PATH: &str = "/proc";
+
+/// How often to check for started and exited processes
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Name of the application profile on the override stack
+const OVERRIDE_NAME: &str = "app";
+
+/// Periodically check the running processes and apply the profile bound to the
+/// first configured process that is running. The replaced values are restored
+/// once the process exits.
+pub fn watch_apps(connection: Connection, config: ConfigStore, overrides: OverrideStack) {
+    tokio::task::spawn(async move {
+        // Binding that is currently applied
+        let mut active: Option<AppProfile> = None;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let apps = config.get().await.apps;
+            if apps.is_empty() && active.is_none() {
+                continue;
+            }
+            let running = running_processes();
+            let app = apps.iter().find(|app| running.contains(&app.process));
+            if active.as_ref() == app {
+                continue;
+            }
+
+            if let Some(app) = active.take() {
+                log::info!("Process {} exited. Restoring settings.", app.process);
+                overrides.remove(OVERRIDE_NAME).await;
+            }
+            let Some(app) = app else {
+                continue;
+            };
+            match profile(&connection, &app.profile).await {
+                Some(profile) => {
+                    log::info!(
+                        "Process {} started. Applying profile {}.",
+                        app.process,
+                        app.profile
+                    );
+                    let settings = Override {
+                        cpu: profile.cpu,
+                        card: profile.gpu,
+                        ..Default::default()
+                    };
+                    overrides.push(OVERRIDE_NAME, settings).await;
+                }
+                None => log::warn!(
+                    "Unknown profile {} for process {}",
+                    app.profile,
+                    app.process
+                ),
+            }
+            active = Some(app.clone());
+        }
+    });
+}
+
+/// Returns the saved profile with the given name
+async fn profile(connection: &Connection, name: &str) -> Option<Profile> {
+    let profiles = connection
+        .object_server()
+        .interface::<_, ProfileManager>(PROFILES_PATH)
+        .await
+        .ok()?;
+    let profile = profiles.get().await.profile(name);
+    profile
+}
+
+/// Returns the names of all running processes. Both the command name and the
+/// file name of the executable are included, since the command name is
+/// truncated to 15 characters and Wine reports the Windows executable only in
+/// the command line.
+fn running_processes() -> HashSet<String> {
+    let mut names = HashSet::new();
+    let Ok(entries) = fs::read_dir(root::path(PROC_PATH)) else {
+        return names;
+    };
+    for entry in entries.flatten() {
+        let is_process = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.parse::<u32>().is_ok());
+        if !is_process {
+            continue;
+        }
+
+        let path = entry.path();
+        if let Ok(comm) = fs::read_to_string(path.join("comm")) {
+            names.insert(comm.trim().to_string());
+        }
+        if let Ok(cmdline) = fs::read(path.join("cmdline")) {
+            let cmdline = String::from_utf8_lossy(&cmdline);
+            let executable = cmdline.split('\0').next().unwrap_or_default();
+            let name = executable.rsplit(['/', '\\']).next().unwrap_or_default();
+            if !name.is_empty() {
+                names.insert(name.to_string());
+            }
+        }
+    }
+    names
+}
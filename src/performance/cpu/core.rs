@@ -1,24 +1,128 @@
 use std::{
     fs::{self, OpenOptions},
-    io::Write,
+    io::{ErrorKind, Write},
+    path::Path,
 };
 use tokio::io::AsyncWriteExt;
 use zbus::fdo;
+use zbus::message::Header;
+use zbus::object_server::SignalEmitter;
+use zbus::Connection;
 use zbus_macros::interface;
 
+use crate::performance::cpu::cpu_features::{parse_cpu_list, Cpu};
+use crate::performance::cpu::telemetry;
+use crate::performance::monitor::PropertyMonitor;
+use crate::polkit::{self, Action};
+use powerstation::constants::CPU_PATH;
+
+/// Type of a CPU core on CPUs with different kinds of cores, e.g. Intel
+/// P-cores and E-cores or AMD Zen 5 and Zen 5c cores
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CoreType {
+    #[default]
+    Unknown,
+    Performance,
+    Efficiency,
+}
+
+impl CoreType {
+    /// Returns the name of the core type, or an empty string if it is unknown
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoreType::Unknown => "",
+            CoreType::Performance => "performance",
+            CoreType::Efficiency => "efficiency",
+        }
+    }
+}
+
+/// Position of a CPU core in the CPU topology. SMT siblings share the same
+/// position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct CoreTopology {
+    pub package_id: u32,
+    pub die_id: u32,
+    pub cluster_id: u32,
+    pub core_id: u32,
+}
+
 // Instance of a single CPU core
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CPUCore {
     // CPU core number
     pub number: u32,
     // sysfs path to the CPU core
     // E.g. /sys/bus/cpu/devices/cpu{}
     pub path: String,
+    // Type of the core, if the CPU has different kinds of cores
+    pub core_type: CoreType,
+    // Performance of the core relative to the fastest core, from 1 to 1024,
+    // or 0 if unknown
+    pub capacity: u32,
+    // Preferred core ranking from amd-pstate, or 0 if unknown. Higher ranked
+    // cores reach higher frequencies.
+    pub ranking: u32,
+    // Position of the core in the CPU topology, or None if unknown. The kernel
+    // removes the topology of offline cores.
+    pub topology: Option<CoreTopology>,
+    // ID of the L3 cache used by the core, or None if unknown. On AMD CPUs,
+    // cores sharing an L3 cache are on the same CCD or CCX.
+    pub l3_id: Option<u32>,
 }
 
 impl CPUCore {
     pub fn new(number: u32, path: String) -> CPUCore {
-        CPUCore { number, path }
+        CPUCore {
+            number,
+            path,
+            core_type: CoreType::default(),
+            capacity: 0,
+            ranking: 0,
+            topology: None,
+            l3_id: None,
+        }
+    }
+
+    /// Read the given numeric sysfs attribute of this core, relative to the
+    /// core path, e.g. "cpu_capacity". Returns None if it does not exist.
+    pub fn read_attribute(&self, name: &str) -> Option<u32> {
+        let path = format!("{0}/{1}", self.path, name);
+        fs::read_to_string(path).ok()?.trim().parse().ok()
+    }
+
+    /// Read the position of this core in the CPU topology. Returns None if the
+    /// core ID is not available, e.g. because the core is offline.
+    pub fn read_topology(&self) -> Option<CoreTopology> {
+        Some(CoreTopology {
+            package_id: self
+                .read_attribute("topology/physical_package_id")
+                .unwrap_or(0),
+            die_id: self.read_attribute("topology/die_id").unwrap_or(0),
+            cluster_id: self.read_attribute("topology/cluster_id").unwrap_or(0),
+            core_id: self.read_attribute("topology/core_id")?,
+        })
+    }
+
+    /// Read the given list of CPU numbers of this core, relative to the core
+    /// path, e.g. "topology/thread_siblings_list". Returns an empty list if it
+    /// does not exist.
+    pub fn read_cpu_list(&self, name: &str) -> Vec<u32> {
+        let path = format!("{0}/{1}", self.path, name);
+        fs::read_to_string(path)
+            .map(|list| parse_cpu_list(&list))
+            .unwrap_or_default()
+    }
+
+    /// Read the ID of the L3 cache of this core. Kernels that do not report
+    /// cache IDs identify the cache by the lowest core sharing it instead.
+    pub fn read_l3_id(&self) -> Option<u32> {
+        if let Some(id) = self.read_attribute("cache/index3/id") {
+            return Some(id);
+        }
+        self.read_cpu_list("cache/index3/shared_cpu_list")
+            .into_iter()
+            .min()
     }
 
     /// Asyncronously set the core to online
@@ -33,13 +137,153 @@ impl CPUCore {
         // Open the sysfs file to write to
         let path = format!("{0}/online", self.path);
         let mut options = tokio::fs::OpenOptions::new();
-        let file = options.write(true).open(path);
+        let file = options.write(true).truncate(true).open(path);
 
         // Write the value
         file.await?.write_all(status.as_bytes()).await?;
 
         Ok(())
     }
+
+    /// Returns true if frequency scaling is available for this core
+    pub fn has_cpufreq(&self) -> bool {
+        Path::new(&format!("{0}/cpufreq", self.path)).is_dir()
+    }
+
+    /// Read the given cpufreq attribute of this core. Attributes that the
+    /// cpufreq driver does not provide are reported as not supported.
+    fn read_cpufreq(&self, name: &str) -> fdo::Result<String> {
+        let path = format!("{0}/cpufreq/{1}", self.path, name);
+        let value = fs::read_to_string(path).map_err(|err| match err.kind() {
+            ErrorKind::NotFound => fdo::Error::NotSupported(format!(
+                "{name} is not available for core {}",
+                self.number
+            )),
+            // convert the std::io::Error to a zbus::fdo::Error
+            _ => fdo::Error::IOError(err.to_string()),
+        })?;
+        Ok(value.trim().to_string())
+    }
+
+    /// Read the given cpufreq frequency of this core in kHz
+    fn read_cpufreq_khz(&self, name: &str) -> fdo::Result<u32> {
+        self.read_cpufreq(name)?
+            .parse::<u32>()
+            // convert the ParseIntError to a zbus::fdo::Error
+            .map_err(|err| fdo::Error::Failed(err.to_string()))
+    }
+
+    /// Write the given cpufreq attribute of this core
+    fn write_cpufreq(&self, name: &str, value: &str) -> fdo::Result<()> {
+        let path = format!("{0}/cpufreq/{1}", self.path, name);
+        let file = OpenOptions::new().write(true).truncate(true).open(path);
+        file
+            // convert the std::io::Error to a zbus::fdo::Error
+            .map_err(|err| fdo::Error::Failed(err.to_string()))?
+            .write_all(value.as_bytes())
+            // convert the std::io::Error to a zbus::fdo::Error
+            .map_err(|err| fdo::Error::IOError(err.to_string()))
+    }
+
+    /// Set the scaling governor of this core. The governor must be one of the
+    /// available governors.
+    pub fn write_scaling_governor(&self, governor: &str) -> fdo::Result<()> {
+        let available = self.available_governors()?;
+        if !available.iter().any(|g| g == governor) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Unsupported governor {governor} for core {}. Available governors: {}",
+                self.number,
+                available.join(", ")
+            )));
+        }
+        log::info!("Setting governor of core {} to {governor}", self.number);
+        self.write_cpufreq("scaling_governor", governor)
+    }
+
+    /// Returns the energy performance preference (EPP) of this core
+    pub fn energy_performance_preference(&self) -> fdo::Result<String> {
+        self.read_cpufreq("energy_performance_preference")
+    }
+
+    /// Returns the energy performance preferences that can be set for this
+    /// core
+    pub fn available_energy_performance_preferences(&self) -> fdo::Result<Vec<String>> {
+        let preferences = self.read_cpufreq("energy_performance_available_preferences")?;
+        Ok(preferences.split_whitespace().map(String::from).collect())
+    }
+
+    /// Set the energy performance preference (EPP) of this core. The
+    /// preference must be one of the available preferences.
+    pub fn write_energy_performance_preference(&self, preference: &str) -> fdo::Result<()> {
+        let available = self.available_energy_performance_preferences()?;
+        if !available.iter().any(|p| p == preference) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Unsupported energy performance preference {preference} for core {}. Available preferences: {}",
+                self.number,
+                available.join(", ")
+            )));
+        }
+        log::info!(
+            "Setting energy performance preference of core {} to {preference}",
+            self.number
+        );
+        self.write_cpufreq("energy_performance_preference", preference)
+    }
+
+    /// Set the minimum and maximum scaling frequency of this core in kHz.
+    /// Unset values are left unchanged. The frequencies must be within the
+    /// hardware limits of the core, unless `clamp` is set, in which case they
+    /// are limited to them.
+    pub fn write_scaling_freq(
+        &self,
+        min: Option<u32>,
+        max: Option<u32>,
+        clamp: bool,
+    ) -> fdo::Result<()> {
+        let limit_min = self.cpuinfo_min_freq()?;
+        let limit_max = self.cpuinfo_max_freq()?;
+        let limit = |value: u32| -> fdo::Result<u32> {
+            if clamp {
+                return Ok(value.clamp(limit_min, limit_max));
+            }
+            if value < limit_min || value > limit_max {
+                return Err(fdo::Error::LimitsExceeded(format!(
+                    "Frequency {value} kHz is outside of the limits of core {} ({limit_min}-{limit_max} kHz)",
+                    self.number
+                )));
+            }
+            Ok(value)
+        };
+        let min = min.map(limit).transpose()?;
+        let max = max.map(limit).transpose()?;
+
+        let current_min = self.scaling_min_freq()?;
+        let current_max = self.scaling_max_freq()?;
+        let new_min = min.unwrap_or(current_min);
+        let new_max = max.unwrap_or(current_max);
+        if new_min > new_max {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Minimum frequency {new_min} kHz is above the maximum frequency {new_max} kHz"
+            )));
+        }
+
+        // The kernel refuses a minimum above the current maximum, so the
+        // maximum has to be raised first in that case.
+        log::info!(
+            "Setting scaling frequency of core {} to {new_min}-{new_max} kHz",
+            self.number
+        );
+        let mut writes = [("scaling_min_freq", min), ("scaling_max_freq", max)];
+        if new_min > current_max {
+            writes.reverse();
+        }
+        for (name, value) in writes {
+            if let Some(value) = value {
+                self.write_cpufreq(name, &value.to_string())?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[interface(name = "org.shadowblip.CPU.Core")]
@@ -71,6 +315,20 @@ impl CPUCore {
         Ok(id)
     }
 
+    /// Returns the type of the core, "performance" or "efficiency", or an
+    /// empty string if the CPU does not have different kinds of cores
+    #[zbus(property)]
+    pub fn core_type(&self) -> String {
+        self.core_type.as_str().to_string()
+    }
+
+    /// Returns the performance of the core relative to the fastest core, from
+    /// 1 to 1024, or 0 if unknown
+    #[zbus(property)]
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
     // Returns true if the given core is online
     #[zbus(property)]
     pub fn online(&self) -> fdo::Result<bool> {
@@ -90,7 +348,14 @@ impl CPUCore {
 
     // Sets the given core to online
     #[zbus(property)]
-    pub fn set_online(&mut self, enabled: bool) -> fdo::Result<()> {
+    pub async fn set_online(
+        &mut self,
+        enabled: bool,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuTopology).await?;
         let enabled_str = if enabled { "enabled" } else { "disabled" };
         log::info!("Setting core {} to {}", self.number, enabled_str);
         let status = if enabled { "1" } else { "0" };
@@ -100,7 +365,7 @@ impl CPUCore {
 
         // Open the sysfs file to write to
         let path = format!("{0}/online", self.path);
-        let file = OpenOptions::new().write(true).open(path);
+        let file = OpenOptions::new().write(true).truncate(true).open(path);
 
         // Write the value
         file
@@ -110,6 +375,169 @@ impl CPUCore {
             // convert the std::io::Error to a zbus::fdo::Error
             .map_err(|err| fdo::Error::IOError(err.to_string()))?;
 
+        // The CPU core count changed as well. This is emitted from a separate
+        // task since the CPU object may be locked while it updates this core.
+        let connection = emitter.connection().clone();
+        tokio::task::spawn(async move {
+            let object_server = connection.object_server();
+            let Ok(cpu) = object_server.interface::<_, Cpu>(CPU_PATH).await else {
+                return;
+            };
+            let result = cpu
+                .get()
+                .await
+                .cores_enabled_changed(cpu.signal_emitter())
+                .await;
+            if let Err(e) = result {
+                log::warn!("Failed to emit CPU property changes: {e}");
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Returns the cpufreq scaling governor of the core
+    #[zbus(property)]
+    pub fn scaling_governor(&self) -> fdo::Result<String> {
+        self.read_cpufreq("scaling_governor")
+    }
+
+    /// Set the cpufreq scaling governor of the core
+    #[zbus(property)]
+    pub async fn set_scaling_governor(
+        &mut self,
+        governor: String,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        self.write_scaling_governor(&governor)
+    }
+
+    /// Returns the scaling governors that can be set for the core
+    #[zbus(property)]
+    pub fn available_governors(&self) -> fdo::Result<Vec<String>> {
+        let governors = self.read_cpufreq("scaling_available_governors")?;
+        Ok(governors.split_whitespace().map(String::from).collect())
+    }
+
+    /// Returns the minimum frequency the governor may select in kHz
+    #[zbus(property)]
+    pub fn scaling_min_freq(&self) -> fdo::Result<u32> {
+        self.read_cpufreq_khz("scaling_min_freq")
+    }
+
+    /// Set the minimum frequency the governor may select in kHz
+    #[zbus(property)]
+    pub async fn set_scaling_min_freq(
+        &mut self,
+        freq: u32,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        self.write_scaling_freq(Some(freq), None, false)
+    }
+
+    /// Returns the maximum frequency the governor may select in kHz
+    #[zbus(property)]
+    pub fn scaling_max_freq(&self) -> fdo::Result<u32> {
+        self.read_cpufreq_khz("scaling_max_freq")
+    }
+
+    /// Set the maximum frequency the governor may select in kHz
+    #[zbus(property)]
+    pub async fn set_scaling_max_freq(
+        &mut self,
+        freq: u32,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        self.write_scaling_freq(None, Some(freq), false)
+    }
+
+    /// Returns the minimum frequency supported by the core in kHz
+    #[zbus(property)]
+    pub fn cpuinfo_min_freq(&self) -> fdo::Result<u32> {
+        self.read_cpufreq_khz("cpuinfo_min_freq")
+    }
+
+    /// Returns the maximum frequency supported by the core in kHz
+    #[zbus(property)]
+    pub fn cpuinfo_max_freq(&self) -> fdo::Result<u32> {
+        self.read_cpufreq_khz("cpuinfo_max_freq")
+    }
+
+    /// Returns the current frequency of the core in kHz
+    #[zbus(property(emits_changed_signal = "false"))]
+    pub fn current_freq(&self) -> fdo::Result<u32> {
+        self.read_cpufreq_khz("scaling_cur_freq")
+    }
+
+    /// Returns the utilization of the core in percent over the last sampling
+    /// interval
+    #[zbus(property(emits_changed_signal = "false"))]
+    pub fn utilization(&self) -> fdo::Result<f64> {
+        telemetry::utilization(&format!("cpu{}", self.number))
+    }
+
+    /// Returns the temperature of the core in °C. This is only available on
+    /// Intel CPUs.
+    #[zbus(property(emits_changed_signal = "false"))]
+    pub fn temperature(&self) -> fdo::Result<f64> {
+        let topology = self.topology.ok_or_else(|| {
+            fdo::Error::NotSupported(format!("Topology of core {} is unknown", self.number))
+        })?;
+        telemetry::core_temperature(topology.package_id, topology.core_id)
+    }
+}
+
+/// Monitored property values of a CPU core
+#[derive(Debug, PartialEq)]
+pub struct CoreSnapshot {
+    online: Option<bool>,
+    scaling_governor: Option<String>,
+    scaling_min_freq: Option<u32>,
+    scaling_max_freq: Option<u32>,
+}
+
+impl PropertyMonitor for CPUCore {
+    type Snapshot = CoreSnapshot;
+
+    async fn snapshot(&self) -> CoreSnapshot {
+        CoreSnapshot {
+            online: self.online().ok(),
+            scaling_governor: self.scaling_governor().ok(),
+            scaling_min_freq: self.scaling_min_freq().ok(),
+            scaling_max_freq: self.scaling_max_freq().ok(),
+        }
+    }
+
+    async fn emit_changed(
+        &self,
+        previous: &CoreSnapshot,
+        current: &CoreSnapshot,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()> {
+        if current.online.is_some() && previous.online != current.online {
+            self.online_changed(emitter).await?;
+        }
+        if current.scaling_governor.is_some()
+            && previous.scaling_governor != current.scaling_governor
+        {
+            self.scaling_governor_changed(emitter).await?;
+        }
+        if current.scaling_min_freq.is_some()
+            && previous.scaling_min_freq != current.scaling_min_freq
+        {
+            self.scaling_min_freq_changed(emitter).await?;
+        }
+        if current.scaling_max_freq.is_some()
+            && previous.scaling_max_freq != current.scaling_max_freq
+        {
+            self.scaling_max_freq_changed(emitter).await?;
+        }
         Ok(())
     }
 }
@@ -1,166 +1,607 @@
 use std::collections::HashMap;
-use std::{fs::OpenOptions, io::Write};
+use std::str::FromStr;
+use std::{fs::OpenOptions, io::Write, path::Path};
 use tokio::fs;
+use tokio::sync::watch;
 use zbus::fdo;
+use zbus::message::Header;
+use zbus::object_server::SignalEmitter;
 use zbus::zvariant::ObjectPath;
+use zbus::Connection;
 use zbus_macros::interface;
 
-use crate::constants::CPU_PATH;
-use crate::performance::cpu::core::CPUCore;
+use crate::config::ConfigStore;
+use crate::performance::cpu::core::{CPUCore, CoreTopology, CoreType};
+use crate::performance::cpu::pstate::{self, PstateDriver};
+use crate::performance::cpu::telemetry::{self, MIN_TELEMETRY_INTERVAL};
+use crate::performance::cpu::topology::{self, TopologyLevel, TopologyNode};
+use crate::performance::monitor::PropertyMonitor;
+use crate::performance::state::{CpuState, StateStore};
+use crate::polkit::{self, Action};
+use crate::root;
+use powerstation::constants::CPU_PATH;
+use powerstation::error;
 
 // Path to discover the number of CPUs the system has
 const CPUID_PATH: &str = "/sys/bus/cpu/devices";
 const SMT_PATH: &str = "/sys/devices/system/cpu/smt/control";
 const BOOST_PATH: &str = "/sys/devices/system/cpu/cpufreq/boost";
+// Lists of the performance and efficiency cores of Intel hybrid CPUs
+const INTEL_CORE_CPUS_PATH: &str = "/sys/devices/cpu_core/cpus";
+const INTEL_ATOM_CPUS_PATH: &str = "/sys/devices/cpu_atom/cpus";
+
+/// Cores performing below this share of the fastest core, in percent, are
+/// efficiency cores. Preferred cores of the same kind differ by much less.
+const EFFICIENCY_CORE_THRESHOLD: u32 = 80;
+
+/// Which cores to keep online when only some cores are enabled. SMT siblings
+/// are always kept together, and core 0 cannot be disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CorePolicy {
+    /// Keep the cores with the highest performance online
+    #[default]
+    Performance,
+    /// Keep efficiency cores online
+    Efficiency,
+    /// Keep the cores sharing an L3 cache (a CCD on AMD) with core 0 online,
+    /// before those of other L3 caches
+    Ccd,
+}
+
+impl CorePolicy {
+    /// Returns the name of the policy
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CorePolicy::Performance => "performance",
+            CorePolicy::Efficiency => "efficiency",
+            CorePolicy::Ccd => "ccd",
+        }
+    }
+}
+
+impl FromStr for CorePolicy {
+    type Err = fdo::Error;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "performance" => Ok(CorePolicy::Performance),
+            "efficiency" => Ok(CorePolicy::Efficiency),
+            "ccd" => Ok(CorePolicy::Ccd),
+            _ => Err(fdo::Error::InvalidArgs(format!(
+                "Unknown core policy {policy}. Supported policies: performance, efficiency, ccd"
+            ))),
+        }
+    }
+}
 
 // Instance of the CPU on the host machine
 pub struct Cpu {
-    core_map: HashMap<u32, Vec<CPUCore>>,
+    core_map: HashMap<CoreTopology, Vec<CPUCore>>,
     core_count: u32,
+    // DBus paths and levels of the CPU topology nodes
+    topology: Vec<(String, TopologyLevel)>,
+    cores_policy: CorePolicy,
+    // Interval of the telemetry signal in milliseconds, or 0 if disabled
+    telemetry_interval: watch::Sender<u32>,
+    state: StateStore,
+    config: ConfigStore,
 }
 
 impl Cpu {
     // Returns a new CPU instance
-    pub fn new() -> Cpu {
-        // Create a hashmap to organize the cores by their core ID
-        let mut core_map: HashMap<u32, Vec<CPUCore>> = HashMap::new();
-        let mut cores = get_cores();
-
-        // Ensure SMT is enabled
-        let file = OpenOptions::new().write(true).open(SMT_PATH);
-        let _ = file.unwrap().write_all("on".as_bytes());
-
-        // Ensure all cores are online
-        for core in cores.iter_mut() {
-            let _ = core.set_online(true);
-        }
+    pub fn new(state: StateStore, config: ConfigStore) -> Cpu {
+        // Create a hashmap to organize the cores by their position in the
+        // topology
+        let mut core_map: HashMap<CoreTopology, Vec<CPUCore>> = HashMap::new();
+        let cores = get_cores();
+        let topology = topology::build(&cores, &state)
+            .into_iter()
+            .map(|(path, node)| (path, node.level))
+            .collect();
 
-        // Organize cores by core id
+        // Organize cores by package, die, cluster and core id. Cores with an
+        // unknown topology are kept on their own.
         let mut core_count = 0;
         for core in cores {
             core_count += 1;
-            let core_id = core.core_id().unwrap();
-            core_map.entry(core_id).or_insert_with(|| {
-                let list: Vec<CPUCore> = Vec::new();
-                list
+            let key = core.topology.unwrap_or(CoreTopology {
+                package_id: u32::MAX,
+                core_id: core.number,
+                ..Default::default()
             });
-
-            let list = core_map.get_mut(&core_id).unwrap();
-            list.push(core);
+            core_map.entry(key).or_default().push(core);
         }
         log::info!("Core Map: {:?}", core_map);
 
         Cpu {
             core_map,
             core_count,
+            topology,
+            cores_policy: CorePolicy::default(),
+            telemetry_interval: watch::Sender::new(0),
+            state,
+            config,
         }
     }
-}
 
-#[interface(name = "org.shadowblip.CPU")]
-impl Cpu {
-    // Returns whether or not boost is enabled
-    #[zbus(property)]
-    pub async fn boost_enabled(&self) -> fdo::Result<bool> {
-        if !has_feature("cpb".to_string()).await? {
-            return Ok(false);
+    /// Returns a receiver for changes of the telemetry signal interval in
+    /// milliseconds
+    pub fn subscribe_telemetry_interval(&self) -> watch::Receiver<u32> {
+        self.telemetry_interval.subscribe()
+    }
+
+    /// Set which cores are kept online when only some cores are enabled,
+    /// without rearranging the enabled cores
+    pub fn set_cores_policy(&mut self, policy: CorePolicy) {
+        self.cores_policy = policy;
+    }
+
+    /// Returns all cores ordered by their number
+    pub fn cores(&self) -> Vec<&CPUCore> {
+        let mut cores: Vec<&CPUCore> = self.core_map.values().flatten().collect();
+        cores.sort_by_key(|core| core.number);
+        cores
+    }
+
+    /// Emit the telemetry signal with the current frequency and utilization of
+    /// the CPU and every core, and the CPU temperature. Values that cannot be
+    /// read are sent as 0.
+    pub async fn emit_telemetry(&self, emitter: &SignalEmitter<'_>) -> zbus::Result<()> {
+        let mut cores_current_freq = Vec::new();
+        let mut cores_utilization = Vec::new();
+        for core in self.cores() {
+            let online = core.online().unwrap_or(false);
+            let freq = core.current_freq().ok().filter(|_| online);
+            cores_current_freq.push(freq.unwrap_or(0));
+            cores_utilization.push(core.utilization().unwrap_or(0.0));
         }
-        let result = fs::read_to_string(BOOST_PATH);
-        let status = result
-            .await
-            // convert the std::io::Error to a zbus::fdo::Error
-            .map_err(|err| fdo::Error::IOError(err.to_string()))?
-            .trim()
-            .to_lowercase();
+        Cpu::telemetry(
+            emitter,
+            self.current_freq().await.unwrap_or(0),
+            self.utilization().await.unwrap_or(0.0),
+            self.temperature().await.unwrap_or(0.0),
+            cores_current_freq,
+            cores_utilization,
+        )
+        .await
+    }
 
-        Ok(status == "1" || status == "on")
+    /// Re-apply the saved CPU settings. If no SMT or core count was saved,
+    /// SMT is enabled and all cores are brought online, unless this is
+    /// disabled in the configuration.
+    pub async fn restore(&mut self, state: &CpuState, emitter: &SignalEmitter<'_>) {
+        log::info!("Restoring saved CPU settings: {state:?}");
+        let config = self.config.get().await.cpu;
+        let previous = self.snapshot().await;
+        let smt_enabled = state.smt_enabled.or(config.enable_smt.then_some(true));
+        if let Some(enabled) = smt_enabled {
+            if let Err(e) = write_smt_enabled(enabled) {
+                log::warn!("Failed to restore SMT: {e}");
+            }
+        }
+
+        if let Some(policy) = &state.cores_enabled_policy {
+            match policy.parse() {
+                Ok(policy) => self.cores_policy = policy,
+                Err(e) => log::warn!("Failed to restore core policy: {e}"),
+            }
+        }
+
+        let cores_enabled = state
+            .cores_enabled
+            .or(config.enable_all_cores.then_some(self.core_count));
+        if let Some(count) = cores_enabled {
+            if let Err(e) = self.update_cores_enabled(count).await {
+                log::warn!("Failed to restore enabled cores: {e}");
+            }
+        }
+
+        if let Some(enabled) = state.boost_enabled {
+            if let Err(e) = write_boost_enabled(enabled) {
+                log::warn!("Failed to restore boost: {e}");
+            }
+        }
+
+        // Switching the P-state driver mode resets the cpufreq policies, so it
+        // needs to be set before the governor and frequencies.
+        if let Some(status) = &state.pstate_status {
+            if let Err(e) = pstate::write_status(status) {
+                log::warn!("Failed to restore P-state driver mode: {e}");
+            }
+        }
+
+        if let Some(governor) = &state.scaling_governor {
+            if let Err(e) = self.write_scaling_governor(governor) {
+                log::warn!("Failed to restore scaling governor: {e}");
+            }
+        }
+
+        if state.scaling_min_freq.is_some() || state.scaling_max_freq.is_some() {
+            let result = self.write_scaling_freq(state.scaling_min_freq, state.scaling_max_freq);
+            if let Err(e) = result {
+                log::warn!("Failed to restore scaling frequencies: {e}");
+            }
+        }
+
+        if let Some(preference) = &state.energy_performance_preference {
+            if let Err(e) = self.write_energy_performance_preference(preference) {
+                log::warn!("Failed to restore energy performance preference: {e}");
+            }
+        }
+
+        if state.min_perf_pct.is_some() || state.max_perf_pct.is_some() {
+            if let Err(e) = pstate::write_perf_pct(state.min_perf_pct, state.max_perf_pct) {
+                log::warn!("Failed to restore performance limits: {e}");
+            }
+        }
+
+        if let Some(no_turbo) = state.no_turbo {
+            if let Err(e) = pstate::write_no_turbo(no_turbo) {
+                log::warn!("Failed to restore no_turbo: {e}");
+            }
+        }
+
+        // Notify clients about the restored values
+        let current = self.snapshot().await;
+        if let Err(e) = self.emit_changed(&previous, &current, emitter).await {
+            log::warn!("Failed to emit restored CPU properties: {e}");
+        }
+        if let Err(e) = self.emit_cores_online_changed(emitter).await {
+            log::warn!("Failed to emit restored core properties: {e}");
+        }
+        if let Err(e) = self.emit_cores_cpufreq_changed(emitter).await {
+            log::warn!("Failed to emit restored core properties: {e}");
+        }
     }
 
-    // Set whether or not boost is enabled
-    #[zbus(property)]
-    pub async fn set_boost_enabled(&mut self, enabled: bool) -> fdo::Result<()> {
-        log::info!("Setting boost enabled to {}", enabled);
-        let status = if enabled { "1" } else { "0" };
+    /// Apply the given CPU settings and save them. Unlike [Cpu::restore], this
+    /// stops at the first setting that fails and returns its error.
+    pub async fn apply(
+        &mut self,
+        settings: &CpuState,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.apply_with(settings, true, emitter).await
+    }
 
-        // Open the sysfs file to write to
-        let file = OpenOptions::new().write(true).open(BOOST_PATH);
+    /// Apply the given CPU settings without saving them, e.g. while a game is
+    /// running. Like [Cpu::apply], this stops at the first setting that fails.
+    pub async fn apply_temporarily(
+        &mut self,
+        settings: &CpuState,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.apply_with(settings, false, emitter).await
+    }
 
-        // Write the value
-        file
-            // convert the std::io::Error to a zbus::fdo::Error
-            .map_err(|err| fdo::Error::Failed(err.to_string()))?
-            .write_all(status.as_bytes())
-            // convert the std::io::Error to a zbus::fdo::Error
-            .map_err(|err| fdo::Error::IOError(err.to_string()))?;
+    /// Apply the given CPU settings, saving the applied values if `save` is set
+    async fn apply_with(
+        &mut self,
+        settings: &CpuState,
+        save: bool,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let previous = self.snapshot().await;
+        let mut applied = CpuState::default();
+        let result = self.apply_settings(settings, &mut applied).await;
+        if save {
+            self.state.update_cpu(|s| s.merge(&applied)).await;
+        }
 
+        let current = self.snapshot().await;
+        if let Err(e) = self.emit_changed(&previous, &current, emitter).await {
+            log::warn!("Failed to emit applied CPU properties: {e}");
+        }
+        if let Err(e) = self.emit_cores_online_changed(emitter).await {
+            log::warn!("Failed to emit applied core properties: {e}");
+        }
+        if let Err(e) = self.emit_cores_cpufreq_changed(emitter).await {
+            log::warn!("Failed to emit applied core properties: {e}");
+        }
+        result
+    }
+
+    /// Apply the given CPU settings, recording every value that was set
+    /// successfully in `applied`.
+    async fn apply_settings(
+        &mut self,
+        settings: &CpuState,
+        applied: &mut CpuState,
+    ) -> fdo::Result<()> {
+        // SMT needs to be set first, since it limits how many cores can be
+        // enabled.
+        if let Some(enabled) = settings.smt_enabled {
+            write_smt_enabled(enabled)?;
+            applied.smt_enabled = Some(enabled);
+        }
+        if let Some(policy) = &settings.cores_enabled_policy {
+            self.cores_policy = policy.parse()?;
+            applied.cores_enabled_policy = Some(policy.clone());
+        }
+        if let Some(num) = settings.cores_enabled {
+            self.update_cores_enabled(num).await?;
+            applied.cores_enabled = Some(num);
+        } else if settings.cores_enabled_policy.is_some() {
+            // Rearrange the enabled cores for the new policy
+            let num = self.cores_enabled().await?;
+            self.update_cores_enabled(num).await?;
+        }
+        if let Some(enabled) = settings.boost_enabled {
+            write_boost_enabled(enabled)?;
+            applied.boost_enabled = Some(enabled);
+        }
+        if let Some(status) = &settings.pstate_status {
+            pstate::write_status(status)?;
+            applied.pstate_status = Some(status.clone());
+        }
+        if let Some(governor) = &settings.scaling_governor {
+            self.write_scaling_governor(governor)?;
+            applied.scaling_governor = Some(governor.clone());
+        }
+        if settings.scaling_min_freq.is_some() || settings.scaling_max_freq.is_some() {
+            self.write_scaling_freq(settings.scaling_min_freq, settings.scaling_max_freq)?;
+            applied.scaling_min_freq = settings.scaling_min_freq;
+            applied.scaling_max_freq = settings.scaling_max_freq;
+        }
+        if let Some(preference) = &settings.energy_performance_preference {
+            self.write_energy_performance_preference(preference)?;
+            applied.energy_performance_preference = Some(preference.clone());
+        }
+        if settings.min_perf_pct.is_some() || settings.max_perf_pct.is_some() {
+            pstate::write_perf_pct(settings.min_perf_pct, settings.max_perf_pct)?;
+            applied.min_perf_pct = settings.min_perf_pct;
+            applied.max_perf_pct = settings.max_perf_pct;
+        }
+        if let Some(no_turbo) = settings.no_turbo {
+            pstate::write_no_turbo(no_turbo)?;
+            applied.no_turbo = Some(no_turbo);
+        }
         Ok(())
     }
 
-    // Returns whether or not SMT is currently enabled
-    #[zbus(property)]
-    pub async fn smt_enabled(&self) -> fdo::Result<bool> {
-        if !has_feature("ht".to_string()).await? {
-            return Ok(false);
+    /// Check the given CPU settings that do not depend on each other before
+    /// any of them is applied, e.g. before applying a profile.
+    pub fn validate(&self, settings: &CpuState) -> fdo::Result<()> {
+        if let Some(policy) = &settings.cores_enabled_policy {
+            policy.parse::<CorePolicy>()?;
         }
-        let result = fs::read_to_string(SMT_PATH);
-        let status = result
-            .await
-            // convert the std::io::Error to a zbus::fdo::Error
-            .map_err(|err| fdo::Error::IOError(err.to_string()))?
-            .trim()
-            .to_lowercase();
+        if settings.cores_enabled == Some(0) {
+            return Err(fdo::Error::InvalidArgs(String::from(
+                "Cowardly refusing to set core count to 0",
+            )));
+        }
+        if settings.scaling_min_freq.is_some() || settings.scaling_max_freq.is_some() {
+            self.check_scaling_freq(settings.scaling_min_freq, settings.scaling_max_freq)?;
+        }
+        for value in [settings.min_perf_pct, settings.max_perf_pct]
+            .into_iter()
+            .flatten()
+        {
+            if value > 100 {
+                return Err(fdo::Error::LimitsExceeded(format!(
+                    "Performance limit {value}% is above 100%"
+                )));
+            }
+        }
+        Ok(())
+    }
 
-        Ok(status == "1" || status == "on")
+    /// Returns the current CPU settings. Values that cannot be read are unset.
+    pub async fn current(&self) -> CpuState {
+        CpuState {
+            boost_enabled: self.boost_enabled().await.ok(),
+            smt_enabled: self.smt_enabled().await.ok(),
+            cores_enabled: self.cores_enabled().await.ok(),
+            cores_enabled_policy: Some(self.cores_policy.as_str().to_string()),
+            scaling_governor: self.scaling_governor().await.ok(),
+            scaling_min_freq: self.scaling_min_freq().await.ok(),
+            scaling_max_freq: self.scaling_max_freq().await.ok(),
+            pstate_status: self.pstate_status().await.ok(),
+            energy_performance_preference: self.energy_performance_preference().await.ok(),
+            min_perf_pct: self.min_perf_pct().await.ok(),
+            max_perf_pct: self.max_perf_pct().await.ok(),
+            no_turbo: self.no_turbo().await.ok(),
+        }
     }
 
-    // Set whether or not SMT is enabled
-    #[zbus(property)]
-    pub async fn set_smt_enabled(&mut self, enabled: bool) -> fdo::Result<()> {
-        log::info!("Setting smt enabled to {}", enabled);
-        let status = if enabled { "on" } else { "off" };
+    /// Returns the DBus paths of all CPU cores, ordered by their number
+    pub fn core_paths(&self) -> Vec<String> {
+        self.cores()
+            .into_iter()
+            .map(|core| format!("{}/Core{}", CPU_PATH, core.number))
+            .collect()
+    }
 
-        // Open the sysfs file to write to
-        let file = OpenOptions::new().write(true).open(SMT_PATH);
+    /// Returns the DBus paths of the CPU topology nodes of the given level
+    fn topology_paths(&self, level: TopologyLevel) -> Vec<String> {
+        self.topology
+            .iter()
+            .filter(|(_, l)| *l == level)
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
 
-        // Write the value
-        file
-            // convert the std::io::Error to a zbus::fdo::Error
-            .map_err(|err| fdo::Error::Failed(err.to_string()))?
-            .write_all(status.as_bytes())
-            // convert the std::io::Error to a zbus::fdo::Error
-            .map_err(|err| fdo::Error::IOError(err.to_string()))?;
+    /// Emit the "PropertiesChanged" signal for the online state of every core
+    /// and topology node. Enabling or disabling SMT and cores changes this for
+    /// many cores at once.
+    pub async fn emit_cores_online_changed(&self, emitter: &SignalEmitter<'_>) -> zbus::Result<()> {
+        let object_server = emitter.connection().object_server();
+        for path in self.core_paths() {
+            let Ok(core) = object_server.interface::<_, CPUCore>(path).await else {
+                continue;
+            };
+            core.get()
+                .await
+                .online_changed(core.signal_emitter())
+                .await?;
+        }
+        for (path, _) in self.topology.iter() {
+            let Ok(node) = object_server
+                .interface::<_, TopologyNode>(path.as_str())
+                .await
+            else {
+                continue;
+            };
+            node.get()
+                .await
+                .enabled_changed(node.signal_emitter())
+                .await?;
+        }
+        Ok(())
+    }
 
+    /// Emit the "PropertiesChanged" signal for the scaling governor and
+    /// frequencies of every core, which are set for all cores at once.
+    async fn emit_cores_cpufreq_changed(&self, emitter: &SignalEmitter<'_>) -> zbus::Result<()> {
+        let object_server = emitter.connection().object_server();
+        for path in self.core_paths() {
+            let Ok(core) = object_server.interface::<_, CPUCore>(path).await else {
+                continue;
+            };
+            let core_ref = core.get().await;
+            core_ref
+                .scaling_governor_changed(core.signal_emitter())
+                .await?;
+            core_ref
+                .scaling_min_freq_changed(core.signal_emitter())
+                .await?;
+            core_ref
+                .scaling_max_freq_changed(core.signal_emitter())
+                .await?;
+        }
         Ok(())
     }
 
-    // Returns a list of features that the CPU supports
-    #[zbus(property)]
-    pub async fn features(&self) -> fdo::Result<Vec<String>> {
-        get_features().await
+    /// Returns all cores with frequency scaling, ordered by their number
+    fn cpufreq_cores(&self) -> Vec<&CPUCore> {
+        let mut cores: Vec<&CPUCore> = self
+            .core_map
+            .values()
+            .flatten()
+            .filter(|core| core.has_cpufreq())
+            .collect();
+        cores.sort_by_key(|core| core.number);
+        cores
     }
 
-    /// Returns the total number of CPU cores detected
-    #[zbus(property)]
-    pub async fn cores_count(&self) -> fdo::Result<u32> {
-        Ok(self.core_count)
+    /// Returns the first core with frequency scaling. Its governor and
+    /// scaling frequencies are reported for the whole CPU.
+    fn cpufreq_core(&self) -> fdo::Result<&CPUCore> {
+        self.cpufreq_cores().into_iter().next().ok_or_else(|| {
+            fdo::Error::NotSupported("CPU frequency scaling is not available".into())
+        })
     }
 
-    #[zbus(property)]
-    pub async fn cores_enabled(&self) -> fdo::Result<u32> {
-        let mut count = 0;
-        for core_list in self.core_map.values() {
-            for core in core_list {
-                let is_online = core.online()?;
-                if is_online {
-                    count += 1;
-                }
+    /// Returns the lowest and highest frequency supported by any core in kHz
+    fn cpuinfo_freq_limits(&self) -> fdo::Result<(u32, u32)> {
+        let mut limits: Option<(u32, u32)> = None;
+        for core in self.cpufreq_cores() {
+            let min = core.cpuinfo_min_freq()?;
+            let max = core.cpuinfo_max_freq()?;
+            limits = match limits {
+                Some((lowest, highest)) => Some((lowest.min(min), highest.max(max))),
+                None => Some((min, max)),
+            };
+        }
+        limits.ok_or_else(|| {
+            fdo::Error::NotSupported("CPU frequency scaling is not available".into())
+        })
+    }
+
+    /// Set the scaling governor of every core
+    fn write_scaling_governor(&self, governor: &str) -> fdo::Result<()> {
+        self.cpufreq_core()?;
+        for core in self.cpufreq_cores() {
+            core.write_scaling_governor(governor)?;
+        }
+        Ok(())
+    }
+
+    /// Set the energy performance preference (EPP) of every core
+    fn write_energy_performance_preference(&self, preference: &str) -> fdo::Result<()> {
+        self.cpufreq_core()?;
+        for core in self.cpufreq_cores() {
+            core.write_energy_performance_preference(preference)?;
+        }
+        Ok(())
+    }
+
+    /// Set the minimum and maximum scaling frequency of every core in kHz.
+    /// The frequencies must be supported by at least one core, and are
+    /// limited to the range each core supports.
+    fn write_scaling_freq(&self, min: Option<u32>, max: Option<u32>) -> fdo::Result<()> {
+        self.check_scaling_freq(min, max)?;
+        for core in self.cpufreq_cores() {
+            core.write_scaling_freq(min, max, true)?;
+        }
+        Ok(())
+    }
+
+    /// Check that the given scaling frequencies in kHz are supported by at
+    /// least one core
+    fn check_scaling_freq(&self, min: Option<u32>, max: Option<u32>) -> fdo::Result<()> {
+        let (limit_min, limit_max) = self.cpuinfo_freq_limits()?;
+        for value in [min, max].into_iter().flatten() {
+            if value < limit_min || value > limit_max {
+                return Err(fdo::Error::LimitsExceeded(format!(
+                    "Frequency {value} kHz is outside of the CPU limits ({limit_min}-{limit_max} kHz)"
+                )));
             }
         }
-        Ok(count)
+        Ok(())
     }
 
-    #[zbus(property)]
-    pub async fn set_cores_enabled(&mut self, num: u32) -> fdo::Result<()> {
+    /// Returns the cores grouped with their SMT siblings, in the order they
+    /// are kept online with the current core policy. The group of core 0 always
+    /// comes first, since it cannot be disabled.
+    fn core_groups(&self) -> Vec<&Vec<CPUCore>> {
+        let l3_domain = |core: &CPUCore| core.l3_id.unwrap_or(0);
+        let first_domain = self
+            .core_map
+            .values()
+            .flatten()
+            .find(|core| core.number == 0)
+            .map(l3_domain)
+            .unwrap_or(0);
+
+        let mut groups: Vec<(CoreTopology, &Vec<CPUCore>)> = self
+            .core_map
+            .iter()
+            .map(|(topology, list)| (*topology, list))
+            .collect();
+        groups.sort_by_key(|(topology, list)| {
+            let core = &list[0];
+            let has_core0 = list.iter().any(|core| core.number == 0);
+            let key = match self.cores_policy {
+                CorePolicy::Performance => {
+                    let type_rank = match core.core_type {
+                        CoreType::Performance => 0,
+                        CoreType::Unknown => 1,
+                        CoreType::Efficiency => 2,
+                    };
+                    (type_rank, u32::MAX - core.capacity, u32::MAX - core.ranking)
+                }
+                CorePolicy::Efficiency => {
+                    let type_rank = match core.core_type {
+                        CoreType::Efficiency => 0,
+                        CoreType::Unknown => 1,
+                        CoreType::Performance => 2,
+                    };
+                    (type_rank, core.capacity, 0)
+                }
+                CorePolicy::Ccd => {
+                    let domain = l3_domain(core);
+                    ((domain != first_domain) as u32, domain, 0)
+                }
+            };
+            (!has_core0, key, *topology)
+        });
+        groups.into_iter().map(|(_, list)| list).collect()
+    }
+
+    /// Enable the given number of cores, keeping hyper-threaded siblings
+    /// together.
+    async fn update_cores_enabled(&mut self, num: u32) -> fdo::Result<()> {
         log::info!("Setting core count to {}", num);
         if num < 1 {
             return Err(fdo::Error::InvalidArgs(String::from(
@@ -190,16 +631,12 @@ impl Cpu {
             num
         };
 
-        // Collect all core IDs from the core map
-        let mut core_ids = self.core_map.keys().cloned().collect::<Vec<u32>>();
-        core_ids.sort();
-
-        // Enable/disable cores based on their hyper-threaded sibling
+        // Enable/disable cores based on their hyper-threaded sibling, in the
+        // order of the core policy
         let mut enabled_count = 1;
-        for core_id in core_ids {
-            let core_list = self.core_map.get_mut(&core_id).unwrap();
+        for core_list in self.core_groups() {
             let mut is_physical = true;
-            for core in core_list.iter_mut() {
+            for core in core_list.iter() {
                 if core.number == 0 {
                     is_physical = false;
                     continue;
@@ -221,13 +658,449 @@ impl Cpu {
 
         Ok(())
     }
+}
+
+#[interface(name = "org.shadowblip.CPU")]
+impl Cpu {
+    // Returns whether or not boost is enabled
+    #[zbus(property)]
+    pub async fn boost_enabled(&self) -> fdo::Result<bool> {
+        if !has_feature("cpb".to_string()).await? {
+            return Ok(false);
+        }
+        let result = fs::read_to_string(root::path(BOOST_PATH));
+        let status = result
+            .await
+            // convert the std::io::Error to a zbus::fdo::Error
+            .map_err(|err| fdo::Error::IOError(err.to_string()))?
+            .trim()
+            .to_lowercase();
+
+        Ok(status == "1" || status == "on")
+    }
+
+    // Set whether or not boost is enabled
+    #[zbus(property)]
+    pub async fn set_boost_enabled(
+        &mut self,
+        enabled: bool,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuTopology).await?;
+        write_boost_enabled(enabled)?;
+        self.state
+            .update_cpu(|s| s.boost_enabled = Some(enabled))
+            .await;
+
+        Ok(())
+    }
+
+    /// Returns the name of the P-state driver, e.g. "amd-pstate", or an empty
+    /// string if none is loaded
+    #[zbus(property)]
+    pub async fn pstate_driver(&self) -> fdo::Result<String> {
+        let driver = PstateDriver::detect();
+        Ok(driver.map(|d| d.name().to_string()).unwrap_or_default())
+    }
+
+    /// Returns the operation mode of the P-state driver, e.g. "active", or
+    /// "off" if another cpufreq driver is used instead
+    #[zbus(property)]
+    pub async fn pstate_status(&self) -> fdo::Result<String> {
+        pstate::read_status()
+    }
+
+    /// Switch the P-state driver to the given operation mode
+    #[zbus(property)]
+    pub async fn set_pstate_status(
+        &mut self,
+        status: String,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        pstate::write_status(&status)?;
+        self.state
+            .update_cpu(|s| s.pstate_status = Some(status))
+            .await;
+
+        // The driver mode changes the governors and preferences of all cores
+        self.available_governors_changed(&emitter).await?;
+        self.scaling_governor_changed(&emitter).await?;
+        self.available_energy_performance_preferences_changed(&emitter)
+            .await?;
+        self.energy_performance_preference_changed(&emitter).await?;
+        self.emit_cores_cpufreq_changed(&emitter).await?;
+
+        Ok(())
+    }
+
+    /// Returns the energy performance preference (EPP) of the first core
+    #[zbus(property)]
+    pub async fn energy_performance_preference(&self) -> fdo::Result<String> {
+        self.cpufreq_core()?.energy_performance_preference()
+    }
+
+    /// Set the energy performance preference (EPP) of every core
+    #[zbus(property)]
+    pub async fn set_energy_performance_preference(
+        &mut self,
+        preference: String,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        self.write_energy_performance_preference(&preference)?;
+        self.state
+            .update_cpu(|s| s.energy_performance_preference = Some(preference))
+            .await;
+
+        Ok(())
+    }
+
+    /// Returns the energy performance preferences that can be set
+    #[zbus(property)]
+    pub async fn available_energy_performance_preferences(&self) -> fdo::Result<Vec<String>> {
+        self.cpufreq_core()?
+            .available_energy_performance_preferences()
+    }
+
+    /// Returns the minimum intel_pstate performance limit in percent
+    #[zbus(property)]
+    pub async fn min_perf_pct(&self) -> fdo::Result<u32> {
+        pstate::read_min_perf_pct()
+    }
+
+    /// Set the minimum intel_pstate performance limit in percent
+    #[zbus(property)]
+    pub async fn set_min_perf_pct(
+        &mut self,
+        pct: u32,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        pstate::write_perf_pct(Some(pct), None)?;
+        self.state.update_cpu(|s| s.min_perf_pct = Some(pct)).await;
+
+        Ok(())
+    }
+
+    /// Returns the maximum intel_pstate performance limit in percent
+    #[zbus(property)]
+    pub async fn max_perf_pct(&self) -> fdo::Result<u32> {
+        pstate::read_max_perf_pct()
+    }
+
+    /// Set the maximum intel_pstate performance limit in percent
+    #[zbus(property)]
+    pub async fn set_max_perf_pct(
+        &mut self,
+        pct: u32,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        pstate::write_perf_pct(None, Some(pct))?;
+        self.state.update_cpu(|s| s.max_perf_pct = Some(pct)).await;
+
+        Ok(())
+    }
+
+    /// Returns true if intel_pstate is not allowed to use turbo frequencies
+    #[zbus(property)]
+    pub async fn no_turbo(&self) -> fdo::Result<bool> {
+        pstate::read_no_turbo()
+    }
+
+    /// Set whether intel_pstate is not allowed to use turbo frequencies
+    #[zbus(property)]
+    pub async fn set_no_turbo(
+        &mut self,
+        no_turbo: bool,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        pstate::write_no_turbo(no_turbo)?;
+        self.state.update_cpu(|s| s.no_turbo = Some(no_turbo)).await;
+
+        Ok(())
+    }
+
+    // Returns whether or not SMT is currently enabled
+    #[zbus(property)]
+    pub async fn smt_enabled(&self) -> fdo::Result<bool> {
+        read_smt_enabled().await
+    }
+
+    // Set whether or not SMT is enabled
+    #[zbus(property)]
+    pub async fn set_smt_enabled(
+        &mut self,
+        enabled: bool,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuTopology).await?;
+        write_smt_enabled(enabled)?;
+        self.state
+            .update_cpu(|s| s.smt_enabled = Some(enabled))
+            .await;
+
+        // Toggling SMT brings sibling cores on or offline
+        self.cores_enabled_changed(&emitter).await?;
+        self.emit_cores_online_changed(&emitter).await?;
+
+        Ok(())
+    }
+
+    // Returns a list of features that the CPU supports
+    #[zbus(property)]
+    pub async fn features(&self) -> fdo::Result<Vec<String>> {
+        get_features().await
+    }
+
+    /// Returns the total number of CPU cores detected
+    #[zbus(property)]
+    pub async fn cores_count(&self) -> fdo::Result<u32> {
+        Ok(self.core_count)
+    }
+
+    #[zbus(property)]
+    pub async fn cores_enabled(&self) -> fdo::Result<u32> {
+        let mut count = 0;
+        for core_list in self.core_map.values() {
+            for core in core_list {
+                let is_online = core.online()?;
+                if is_online {
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    #[zbus(property)]
+    pub async fn set_cores_enabled(
+        &mut self,
+        num: u32,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuTopology).await?;
+        self.update_cores_enabled(num).await?;
+        self.state.update_cpu(|s| s.cores_enabled = Some(num)).await;
+        self.emit_cores_online_changed(&emitter).await?;
+
+        Ok(())
+    }
+
+    /// Returns which cores are kept online when only some cores are enabled:
+    /// "performance", "efficiency" or "ccd"
+    #[zbus(property)]
+    pub async fn cores_enabled_policy(&self) -> fdo::Result<String> {
+        Ok(self.cores_policy.as_str().to_string())
+    }
+
+    /// Set which cores are kept online when only some cores are enabled. The
+    /// currently enabled number of cores is rearranged for the new policy.
+    #[zbus(property)]
+    pub async fn set_cores_enabled_policy(
+        &mut self,
+        policy: String,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuTopology).await?;
+        self.cores_policy = policy.parse()?;
+        self.state
+            .update_cpu(|s| s.cores_enabled_policy = Some(policy))
+            .await;
+        let num = self.cores_enabled().await?;
+        self.update_cores_enabled(num).await?;
+        self.emit_cores_online_changed(&emitter).await?;
+
+        Ok(())
+    }
+
+    /// Returns the cpufreq scaling governor of the first core
+    #[zbus(property)]
+    pub async fn scaling_governor(&self) -> fdo::Result<String> {
+        self.cpufreq_core()?.scaling_governor()
+    }
+
+    /// Set the cpufreq scaling governor of every core
+    #[zbus(property)]
+    pub async fn set_scaling_governor(
+        &mut self,
+        governor: String,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        self.write_scaling_governor(&governor)?;
+        self.state
+            .update_cpu(|s| s.scaling_governor = Some(governor))
+            .await;
+        self.emit_cores_cpufreq_changed(&emitter).await?;
+
+        Ok(())
+    }
+
+    /// Returns the scaling governors that can be set
+    #[zbus(property)]
+    pub async fn available_governors(&self) -> fdo::Result<Vec<String>> {
+        self.cpufreq_core()?.available_governors()
+    }
+
+    /// Returns the minimum scaling frequency of the first core in kHz
+    #[zbus(property)]
+    pub async fn scaling_min_freq(&self) -> fdo::Result<u32> {
+        self.cpufreq_core()?.scaling_min_freq()
+    }
+
+    /// Set the minimum scaling frequency of every core in kHz
+    #[zbus(property)]
+    pub async fn set_scaling_min_freq(
+        &mut self,
+        freq: u32,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        self.write_scaling_freq(Some(freq), None)?;
+        self.state
+            .update_cpu(|s| s.scaling_min_freq = Some(freq))
+            .await;
+        self.emit_cores_cpufreq_changed(&emitter).await?;
+
+        Ok(())
+    }
+
+    /// Returns the maximum scaling frequency of the first core in kHz
+    #[zbus(property)]
+    pub async fn scaling_max_freq(&self) -> fdo::Result<u32> {
+        self.cpufreq_core()?.scaling_max_freq()
+    }
+
+    /// Set the maximum scaling frequency of every core in kHz
+    #[zbus(property)]
+    pub async fn set_scaling_max_freq(
+        &mut self,
+        freq: u32,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        self.write_scaling_freq(None, Some(freq))?;
+        self.state
+            .update_cpu(|s| s.scaling_max_freq = Some(freq))
+            .await;
+        self.emit_cores_cpufreq_changed(&emitter).await?;
+
+        Ok(())
+    }
+
+    /// Returns the lowest frequency supported by any core in kHz
+    #[zbus(property)]
+    pub async fn cpuinfo_min_freq(&self) -> fdo::Result<u32> {
+        Ok(self.cpuinfo_freq_limits()?.0)
+    }
+
+    /// Returns the highest frequency supported by any core in kHz
+    #[zbus(property)]
+    pub async fn cpuinfo_max_freq(&self) -> fdo::Result<u32> {
+        Ok(self.cpuinfo_freq_limits()?.1)
+    }
+
+    /// Returns the average current frequency of all online cores in kHz
+    #[zbus(property(emits_changed_signal = "false"))]
+    pub async fn current_freq(&self) -> fdo::Result<u32> {
+        self.cpufreq_core()?;
+        let mut freqs = Vec::new();
+        for core in self.cpufreq_cores() {
+            if core.online()? {
+                freqs.push(core.current_freq()? as u64);
+            }
+        }
+        let average = freqs.iter().sum::<u64>() / freqs.len().max(1) as u64;
+        Ok(average as u32)
+    }
+
+    /// Returns the utilization of all cores in percent over the last sampling
+    /// interval
+    #[zbus(property(emits_changed_signal = "false"))]
+    pub async fn utilization(&self) -> fdo::Result<f64> {
+        telemetry::utilization("cpu")
+    }
+
+    /// Returns the temperature of the CPU package in °C
+    #[zbus(property(emits_changed_signal = "false"))]
+    pub async fn temperature(&self) -> fdo::Result<f64> {
+        telemetry::package_temperature()
+    }
+
+    /// Interval of the telemetry signal in milliseconds, or 0 if the signal is
+    /// disabled. The signal is received by every client, so changing the
+    /// interval needs the same authorization as the CPU frequency settings.
+    #[zbus(property)]
+    pub async fn telemetry_interval(&self) -> fdo::Result<u32> {
+        Ok(*self.telemetry_interval.borrow())
+    }
+
+    // Set the interval of the telemetry signal. The setter is documented with
+    // the getter, so the introspection data has a single comment.
+    #[zbus(property)]
+    pub async fn set_telemetry_interval(
+        &mut self,
+        interval: u32,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuFrequency).await?;
+        if interval != 0 && interval < MIN_TELEMETRY_INTERVAL {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Telemetry interval {interval} ms is below the minimum of {MIN_TELEMETRY_INTERVAL} ms"
+            )));
+        }
+        log::info!("Setting telemetry interval to {interval} ms");
+        self.telemetry_interval.send_replace(interval);
+        Ok(())
+    }
+
+    /// Emitted at the telemetry interval with the average current frequency
+    /// (kHz), utilization (%) and temperature (°C) of the CPU, followed by
+    /// the current frequency and utilization of every core, in the order of
+    /// EnumerateCores. Values that cannot be read are 0.
+    #[zbus(signal)]
+    async fn telemetry(
+        emitter: &SignalEmitter<'_>,
+        current_freq: u32,
+        utilization: f64,
+        temperature: f64,
+        cores_current_freq: Vec<u32>,
+        cores_utilization: Vec<f64>,
+    ) -> zbus::Result<()>;
 
     /// Returns a list of DBus paths to all CPU cores
-    pub async fn enumerate_cores(&mut self) -> fdo::Result<Vec<ObjectPath>> {
+    pub async fn enumerate_cores(
+        &mut self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> error::Result<Vec<ObjectPath>> {
+        polkit::check(connection, Some(&header), Action::Read).await?;
         let mut paths: Vec<ObjectPath> = Vec::new();
 
-        for i in 0..self.core_count {
-            let path = format!("{}/Core{1}", CPU_PATH, i);
+        for path in self.core_paths() {
             let path = ObjectPath::from_string_unchecked(path);
             paths.push(path);
         }
@@ -235,10 +1108,235 @@ impl Cpu {
         Ok(paths)
     }
 
+    /// Returns a list of DBus paths to all CPU packages, the roots of the CPU
+    /// topology
+    pub async fn enumerate_packages(
+        &mut self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> error::Result<Vec<ObjectPath<'_>>> {
+        polkit::check(connection, Some(&header), Action::Read).await?;
+        let paths = self.topology_paths(TopologyLevel::Package);
+        Ok(paths
+            .into_iter()
+            .map(ObjectPath::from_string_unchecked)
+            .collect())
+    }
+
+    /// Returns a list of DBus paths to all L3 caches. On AMD CPUs, each L3
+    /// cache is a CCD or CCX.
+    pub async fn enumerate_l3_caches(
+        &mut self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> error::Result<Vec<ObjectPath<'_>>> {
+        polkit::check(connection, Some(&header), Action::Read).await?;
+        let paths = self.topology_paths(TopologyLevel::L3Cache);
+        Ok(paths
+            .into_iter()
+            .map(ObjectPath::from_string_unchecked)
+            .collect())
+    }
+
     /// Returns true if the CPU has the given feature flag.
-    pub async fn has_feature(&mut self, flag: String) -> fdo::Result<bool> {
-        has_feature(flag).await
+    pub async fn has_feature(
+        &mut self,
+        flag: String,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> error::Result<bool> {
+        polkit::check(connection, Some(&header), Action::Read).await?;
+        Ok(has_feature(flag).await?)
+    }
+}
+
+/// Monitored property values of the CPU
+#[derive(Debug, PartialEq)]
+pub struct CpuSnapshot {
+    boost_enabled: Option<bool>,
+    smt_enabled: Option<bool>,
+    cores_enabled: Option<u32>,
+    scaling_governor: Option<String>,
+    scaling_min_freq: Option<u32>,
+    scaling_max_freq: Option<u32>,
+    pstate_status: Option<String>,
+    energy_performance_preference: Option<String>,
+    available_energy_performance_preferences: Option<Vec<String>>,
+    available_governors: Option<Vec<String>>,
+    min_perf_pct: Option<u32>,
+    max_perf_pct: Option<u32>,
+    no_turbo: Option<bool>,
+}
+
+impl PropertyMonitor for Cpu {
+    type Snapshot = CpuSnapshot;
+
+    async fn snapshot(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            boost_enabled: self.boost_enabled().await.ok(),
+            smt_enabled: self.smt_enabled().await.ok(),
+            cores_enabled: self.cores_enabled().await.ok(),
+            scaling_governor: self.scaling_governor().await.ok(),
+            scaling_min_freq: self.scaling_min_freq().await.ok(),
+            scaling_max_freq: self.scaling_max_freq().await.ok(),
+            pstate_status: self.pstate_status().await.ok(),
+            energy_performance_preference: self.energy_performance_preference().await.ok(),
+            available_energy_performance_preferences: self
+                .available_energy_performance_preferences()
+                .await
+                .ok(),
+            available_governors: self.available_governors().await.ok(),
+            min_perf_pct: self.min_perf_pct().await.ok(),
+            max_perf_pct: self.max_perf_pct().await.ok(),
+            no_turbo: self.no_turbo().await.ok(),
+        }
+    }
+
+    async fn emit_changed(
+        &self,
+        previous: &CpuSnapshot,
+        current: &CpuSnapshot,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()> {
+        if current.boost_enabled.is_some() && previous.boost_enabled != current.boost_enabled {
+            self.boost_enabled_changed(emitter).await?;
+        }
+        if current.smt_enabled.is_some() && previous.smt_enabled != current.smt_enabled {
+            self.smt_enabled_changed(emitter).await?;
+        }
+        if current.cores_enabled.is_some() && previous.cores_enabled != current.cores_enabled {
+            self.cores_enabled_changed(emitter).await?;
+        }
+        if current.scaling_governor.is_some()
+            && previous.scaling_governor != current.scaling_governor
+        {
+            self.scaling_governor_changed(emitter).await?;
+        }
+        if current.scaling_min_freq.is_some()
+            && previous.scaling_min_freq != current.scaling_min_freq
+        {
+            self.scaling_min_freq_changed(emitter).await?;
+        }
+        if current.scaling_max_freq.is_some()
+            && previous.scaling_max_freq != current.scaling_max_freq
+        {
+            self.scaling_max_freq_changed(emitter).await?;
+        }
+        if current.pstate_status.is_some() && previous.pstate_status != current.pstate_status {
+            self.pstate_status_changed(emitter).await?;
+        }
+        if current.energy_performance_preference.is_some()
+            && previous.energy_performance_preference != current.energy_performance_preference
+        {
+            self.energy_performance_preference_changed(emitter).await?;
+        }
+        if current.available_energy_performance_preferences.is_some()
+            && previous.available_energy_performance_preferences
+                != current.available_energy_performance_preferences
+        {
+            self.available_energy_performance_preferences_changed(emitter)
+                .await?;
+        }
+        if current.available_governors.is_some()
+            && previous.available_governors != current.available_governors
+        {
+            self.available_governors_changed(emitter).await?;
+        }
+        if current.min_perf_pct.is_some() && previous.min_perf_pct != current.min_perf_pct {
+            self.min_perf_pct_changed(emitter).await?;
+        }
+        if current.max_perf_pct.is_some() && previous.max_perf_pct != current.max_perf_pct {
+            self.max_perf_pct_changed(emitter).await?;
+        }
+        if current.no_turbo.is_some() && previous.no_turbo != current.no_turbo {
+            self.no_turbo_changed(emitter).await?;
+        }
+        Ok(())
+    }
+}
+
+// Write whether or not boost is enabled to sysfs
+fn write_boost_enabled(enabled: bool) -> fdo::Result<()> {
+    log::info!("Setting boost enabled to {}", enabled);
+    let status = if enabled { "1" } else { "0" };
+
+    // Open the sysfs file to write to
+    let file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(root::path(BOOST_PATH));
+
+    // Write the value
+    file
+        // convert the std::io::Error to a zbus::fdo::Error
+        .map_err(|err| fdo::Error::Failed(err.to_string()))?
+        .write_all(status.as_bytes())
+        // convert the std::io::Error to a zbus::fdo::Error
+        .map_err(|err| fdo::Error::IOError(err.to_string()))?;
+
+    Ok(())
+}
+
+/// Returns whether or not SMT is currently enabled
+pub async fn read_smt_enabled() -> fdo::Result<bool> {
+    if !has_feature("ht".to_string()).await? {
+        return Ok(false);
+    }
+    let result = fs::read_to_string(root::path(SMT_PATH));
+    let status = result
+        .await
+        // convert the std::io::Error to a zbus::fdo::Error
+        .map_err(|err| fdo::Error::IOError(err.to_string()))?
+        .trim()
+        .to_lowercase();
+
+    Ok(status == "1" || status == "on")
+}
+
+/// Returns the number of online cores. Core 0 is always online.
+pub fn read_cores_enabled() -> fdo::Result<u32> {
+    let entries = std::fs::read_dir(root::path(CPUID_PATH))
+        .map_err(|err| fdo::Error::IOError(err.to_string()))?;
+    let mut count = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(number) = name.to_str().and_then(|name| name.strip_prefix("cpu")) else {
+            continue;
+        };
+        if number.parse::<u32>().is_err() {
+            continue;
+        }
+        let online = match std::fs::read_to_string(entry.path().join("online")) {
+            Ok(status) => status.trim() == "1",
+            Err(_) => number == "0",
+        };
+        if online {
+            count += 1;
+        }
     }
+    Ok(count)
+}
+
+// Write whether or not SMT is enabled to sysfs
+fn write_smt_enabled(enabled: bool) -> fdo::Result<()> {
+    log::info!("Setting smt enabled to {}", enabled);
+    let status = if enabled { "on" } else { "off" };
+
+    // Open the sysfs file to write to
+    let file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(root::path(SMT_PATH));
+
+    // Write the value
+    file
+        // convert the std::io::Error to a zbus::fdo::Error
+        .map_err(|err| fdo::Error::Failed(err.to_string()))?
+        .write_all(status.as_bytes())
+        // convert the std::io::Error to a zbus::fdo::Error
+        .map_err(|err| fdo::Error::IOError(err.to_string()))?;
+
+    Ok(())
 }
 
 // Returns true if the CPU has the given feature flag.
@@ -252,7 +1350,7 @@ async fn get_features() -> fdo::Result<Vec<String>> {
     let mut features: Vec<String> = Vec::new();
 
     // Read the data from cpuinfo
-    let path = "/proc/cpuinfo";
+    let path = root::path("/proc/cpuinfo");
     let result = fs::read_to_string(path);
     let content = result
         .await
@@ -282,16 +1380,164 @@ async fn get_features() -> fdo::Result<Vec<String>> {
     Ok(features)
 }
 
-// Returns a list of all detected cores
+// Returns a list of all detected cores ordered by their number, along with
+// their topology and types. CPU numbers can have gaps, so they are taken from
+// the sysfs directory names.
 pub fn get_cores() -> Vec<CPUCore> {
     let mut cores: Vec<CPUCore> = Vec::new();
-    let paths = std::fs::read_dir(CPUID_PATH).unwrap();
-    for (i, path) in paths.enumerate() {
-        log::info!("Discovered core: {}", path.unwrap().path().display());
-        let core_path = format!("/sys/bus/cpu/devices/cpu{0}", i);
-        let core = CPUCore::new(i as u32, core_path);
-        cores.push(core);
+    let cpuid_path = root::path(CPUID_PATH);
+    let paths = std::fs::read_dir(&cpuid_path).unwrap();
+    for entry in paths.flatten() {
+        let name = entry.file_name();
+        let number = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("cpu"))
+            .and_then(|number| number.parse::<u32>().ok());
+        let Some(number) = number else {
+            continue;
+        };
+        log::info!("Discovered core: {}", entry.path().display());
+        let core_path = format!("{0}/cpu{1}", cpuid_path, number);
+        cores.push(CPUCore::new(number, core_path));
+    }
+    cores.sort_by_key(|core| core.number);
+
+    // The kernel removes the topology and cache information of offline cores,
+    // so it is derived from the online cores listing them instead. Cores are
+    // not brought online for this, since discovery must not change the
+    // enabled cores.
+    for core in cores.iter_mut() {
+        core.topology = core.read_topology();
+        core.l3_id = core.read_l3_id();
     }
+    detect_core_types(&mut cores);
+    derive_from_siblings(&mut cores);
 
     cores
 }
+
+// Derive the topology, L3 cache and type of the cores whose topology could not
+// be read, e.g. since they are offline, from the cores that list them as SMT
+// siblings or as sharing their L3 cache. Offline cores whose siblings are
+// offline as well keep an unknown topology.
+fn derive_from_siblings(cores: &mut [CPUCore]) {
+    let known: Vec<CPUCore> = cores
+        .iter()
+        .filter(|core| core.topology.is_some())
+        .cloned()
+        .collect();
+    for core in cores.iter_mut().filter(|core| core.topology.is_none()) {
+        let sibling = known.iter().find(|sibling| {
+            sibling
+                .read_cpu_list("topology/thread_siblings_list")
+                .contains(&core.number)
+        });
+        if let Some(sibling) = sibling {
+            log::info!(
+                "Using the topology of core {} for its SMT sibling {}",
+                sibling.number,
+                core.number
+            );
+            core.topology = sibling.topology;
+            core.l3_id = core.l3_id.or(sibling.l3_id);
+            if core.core_type == CoreType::Unknown {
+                core.core_type = sibling.core_type;
+            }
+            if core.capacity == 0 {
+                core.capacity = sibling.capacity;
+            }
+            if core.ranking == 0 {
+                core.ranking = sibling.ranking;
+            }
+        }
+        if core.l3_id.is_none() {
+            core.l3_id = known
+                .iter()
+                .find(|other| {
+                    other
+                        .read_cpu_list("cache/index3/shared_cpu_list")
+                        .contains(&core.number)
+                })
+                .and_then(|other| other.l3_id);
+        }
+    }
+}
+
+/// Parse a list of CPU numbers from sysfs, e.g. "0-3,8"
+pub fn parse_cpu_list(list: &str) -> Vec<u32> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let (Ok(start), Ok(end)) = (start.parse::<u32>(), end.parse::<u32>()) else {
+            log::warn!("Invalid CPU list: {list}");
+            continue;
+        };
+        cpus.extend(start..=end);
+    }
+    cpus
+}
+
+// Detect the type, capacity and preferred core ranking of the given cores. The
+// type is read from the core lists of Intel hybrid CPUs, or derived from the
+// capacity, which is taken from "cpu_capacity" or the highest performance
+// reported by amd-pstate.
+fn detect_core_types(cores: &mut [CPUCore]) {
+    let perf: Vec<Option<u32>> = cores
+        .iter()
+        .map(|core| {
+            core.read_attribute("cpu_capacity")
+                .or_else(|| core.read_attribute("cpufreq/amd_pstate_highest_perf"))
+        })
+        .collect();
+    let max_perf = perf.iter().flatten().max().copied().unwrap_or(0);
+    for (core, perf) in cores.iter_mut().zip(perf) {
+        core.ranking = core
+            .read_attribute("cpufreq/amd_pstate_prefcore_ranking")
+            .unwrap_or(0);
+        let Some(perf) = perf.filter(|_| max_perf > 0) else {
+            continue;
+        };
+        core.capacity = (perf * 1024 / max_perf).max(1);
+        core.core_type = if perf * 100 < max_perf * EFFICIENCY_CORE_THRESHOLD {
+            CoreType::Efficiency
+        } else {
+            CoreType::Performance
+        };
+    }
+
+    // Intel hybrid CPUs list their core types explicitly
+    let core_cpus = root::path(INTEL_CORE_CPUS_PATH);
+    let atom_cpus = root::path(INTEL_ATOM_CPUS_PATH);
+    if Path::new(&core_cpus).exists() && Path::new(&atom_cpus).exists() {
+        let read_list =
+            |path: &str| parse_cpu_list(&std::fs::read_to_string(path).unwrap_or_default());
+        let performance = read_list(&core_cpus);
+        let efficiency = read_list(&atom_cpus);
+        for core in cores.iter_mut() {
+            if performance.contains(&core.number) {
+                core.core_type = CoreType::Performance;
+            } else if efficiency.contains(&core.number) {
+                core.core_type = CoreType::Efficiency;
+            }
+        }
+    }
+
+    // Cores of the same kind are not worth reporting
+    if cores
+        .iter()
+        .all(|core| core.core_type == CoreType::Performance)
+    {
+        for core in cores.iter_mut() {
+            core.core_type = CoreType::Unknown;
+        }
+    }
+    for core in cores.iter() {
+        log::debug!(
+            "Core {} type: {:?}, capacity: {}, ranking: {}",
+            core.number,
+            core.core_type,
+            core.capacity,
+            core.ranking
+        );
+    }
+}
@@ -1,2 +1,5 @@
 pub mod core;
 pub mod cpu_features;
+pub mod pstate;
+pub mod telemetry;
+pub mod topology;
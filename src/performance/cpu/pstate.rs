@@ -0,0 +1,190 @@
+use std::{fs, fs::OpenOptions, io::Write, path::Path};
+use zbus::fdo;
+
+use crate::root;
+
+// Paths to the global settings of the P-state drivers
+const AMD_PSTATE_PATH: &str = "/sys/devices/system/cpu/amd_pstate";
+const INTEL_PSTATE_PATH: &str = "/sys/devices/system/cpu/intel_pstate";
+
+/// P-state driver that controls the CPU frequency
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PstateDriver {
+    Amd,
+    Intel,
+}
+
+impl PstateDriver {
+    /// Returns the P-state driver that controls the CPU frequency, if any. The
+    /// settings of a driver are present even if it was switched off, e.g. in
+    /// favor of acpi-cpufreq.
+    pub fn detect() -> Option<PstateDriver> {
+        PstateDriver::installed().filter(|driver| {
+            let status = read(&format!("{0}/status", driver.path()));
+            status.is_ok_and(|status| status != "off")
+        })
+    }
+
+    /// Returns the P-state driver whose settings are present, even if it is
+    /// switched off
+    fn installed() -> Option<PstateDriver> {
+        [PstateDriver::Amd, PstateDriver::Intel]
+            .into_iter()
+            .find(|driver| Path::new(&driver.path()).is_dir())
+    }
+
+    /// Returns the name of the driver, as used by the kernel
+    pub fn name(&self) -> &'static str {
+        match self {
+            PstateDriver::Amd => "amd-pstate",
+            PstateDriver::Intel => "intel_pstate",
+        }
+    }
+
+    /// Returns the operation modes the driver can be switched to
+    pub fn modes(&self) -> &'static [&'static str] {
+        match self {
+            PstateDriver::Amd => &["active", "passive", "guided"],
+            PstateDriver::Intel => &["active", "passive"],
+        }
+    }
+
+    /// Returns the sysfs path to the global settings of the driver
+    fn path(&self) -> String {
+        match self {
+            PstateDriver::Amd => root::path(AMD_PSTATE_PATH),
+            PstateDriver::Intel => root::path(INTEL_PSTATE_PATH),
+        }
+    }
+}
+
+/// Returns the loaded P-state driver, or an error if there is none
+fn driver() -> fdo::Result<PstateDriver> {
+    PstateDriver::detect().ok_or_else(|| {
+        fdo::Error::NotSupported("No amd-pstate or intel_pstate driver is loaded".into())
+    })
+}
+
+/// Returns the path to the given intel_pstate attribute, or an error if the
+/// intel_pstate driver is not loaded
+fn intel_path(name: &str) -> fdo::Result<String> {
+    if driver()? != PstateDriver::Intel {
+        return Err(fdo::Error::NotSupported(format!(
+            "{name} is only supported by intel_pstate"
+        )));
+    }
+    Ok(format!("{0}/{1}", PstateDriver::Intel.path(), name))
+}
+
+// Read the given sysfs file
+fn read(path: &str) -> fdo::Result<String> {
+    let value = fs::read_to_string(path)
+        // convert the std::io::Error to a zbus::fdo::Error
+        .map_err(|err| fdo::Error::IOError(err.to_string()))?;
+    Ok(value.trim().to_string())
+}
+
+// Write the given value to the given sysfs file
+fn write(path: &str, value: &str) -> fdo::Result<()> {
+    let file = OpenOptions::new().write(true).truncate(true).open(path);
+    file
+        // convert the std::io::Error to a zbus::fdo::Error
+        .map_err(|err| fdo::Error::Failed(err.to_string()))?
+        .write_all(value.as_bytes())
+        // convert the std::io::Error to a zbus::fdo::Error
+        .map_err(|err| fdo::Error::IOError(err.to_string()))
+}
+
+// Returns the P-state driver whose settings are present, or an error if there
+// is none. Unlike [driver], this includes a driver that is switched off, so
+// that it can be switched on again.
+fn installed_driver() -> fdo::Result<PstateDriver> {
+    PstateDriver::installed().ok_or_else(|| {
+        fdo::Error::NotSupported("No amd-pstate or intel_pstate driver is available".into())
+    })
+}
+
+/// Returns the operation mode of the P-state driver, e.g. "active" or "off"
+pub fn read_status() -> fdo::Result<String> {
+    read(&format!("{0}/status", installed_driver()?.path()))
+}
+
+/// Switch the P-state driver to the given operation mode
+pub fn write_status(status: &str) -> fdo::Result<()> {
+    let driver = installed_driver()?;
+    if !driver.modes().contains(&status) {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "Unsupported {} mode {status}. Supported modes: {}",
+            driver.name(),
+            driver.modes().join(", ")
+        )));
+    }
+    log::info!("Setting {} mode to {status}", driver.name());
+    write(&format!("{0}/status", driver.path()), status)
+}
+
+/// Returns the given intel_pstate performance limit in percent
+fn read_perf_pct(name: &str) -> fdo::Result<u32> {
+    read(&intel_path(name)?)?
+        .parse::<u32>()
+        // convert the ParseIntError to a zbus::fdo::Error
+        .map_err(|err| fdo::Error::Failed(err.to_string()))
+}
+
+/// Returns the minimum intel_pstate performance limit in percent
+pub fn read_min_perf_pct() -> fdo::Result<u32> {
+    read_perf_pct("min_perf_pct")
+}
+
+/// Returns the maximum intel_pstate performance limit in percent
+pub fn read_max_perf_pct() -> fdo::Result<u32> {
+    read_perf_pct("max_perf_pct")
+}
+
+/// Set the minimum and maximum intel_pstate performance limits in percent.
+/// Unset values are left unchanged.
+pub fn write_perf_pct(min: Option<u32>, max: Option<u32>) -> fdo::Result<()> {
+    for value in [min, max].into_iter().flatten() {
+        if value > 100 {
+            return Err(fdo::Error::LimitsExceeded(format!(
+                "Performance limit {value}% is above 100%"
+            )));
+        }
+    }
+    let current_min = read_min_perf_pct()?;
+    let current_max = read_max_perf_pct()?;
+    let new_min = min.unwrap_or(current_min);
+    let new_max = max.unwrap_or(current_max);
+    if new_min > new_max {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "Minimum performance limit {new_min}% is above the maximum of {new_max}%"
+        )));
+    }
+
+    // The driver limits the minimum to the current maximum, so the maximum
+    // has to be raised first in that case.
+    log::info!("Setting intel_pstate performance limits to {new_min}-{new_max}%");
+    let mut writes = [("min_perf_pct", min), ("max_perf_pct", max)];
+    if new_min > current_max {
+        writes.reverse();
+    }
+    for (name, value) in writes {
+        if let Some(value) = value {
+            write(&intel_path(name)?, &value.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if intel_pstate is not allowed to use turbo frequencies
+pub fn read_no_turbo() -> fdo::Result<bool> {
+    let status = read(&intel_path("no_turbo")?)?;
+    Ok(status == "1")
+}
+
+/// Set whether intel_pstate is not allowed to use turbo frequencies
+pub fn write_no_turbo(no_turbo: bool) -> fdo::Result<()> {
+    log::info!("Setting intel_pstate no_turbo to {no_turbo}");
+    let status = if no_turbo { "1" } else { "0" };
+    write(&intel_path("no_turbo")?, status)
+}
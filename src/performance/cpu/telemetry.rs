@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+use zbus::fdo;
+use zbus::Connection;
+
+use crate::performance::cpu::cpu_features::Cpu;
+use crate::root;
+use powerstation::constants::CPU_PATH;
+
+// Path to the time each CPU spent in each state since boot
+const PROC_STAT_PATH: &str = "/proc/stat";
+// Path to discover temperature sensors
+const HWMON_PATH: &str = "/sys/class/hwmon";
+
+/// Shortest time the utilization read from a property is computed over. Reads
+/// within this time of the previous sample return that sample.
+const MIN_SAMPLE_WINDOW: Duration = Duration::from_millis(500);
+
+/// Shortest interval at which the telemetry signal can be emitted
+pub const MIN_TELEMETRY_INTERVAL: u32 = 100;
+
+/// Names of the hwmon drivers reporting CPU temperatures
+const TEMPERATURE_DRIVERS: &[&str] = &["zenpower", "k10temp", "coretemp"];
+
+/// Labels of the package temperature of AMD CPUs, in order of preference.
+/// "Tctl" may include an offset used for fan control.
+const AMD_PACKAGE_LABELS: &[&str] = &["Tdie", "Tctl"];
+
+/// Time a CPU spent busy and in total, in USER_HZ
+#[derive(Debug, Clone, Copy, Default)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+impl CpuTimes {
+    /// Returns the share of time the CPU was busy since the given times, in
+    /// percent
+    fn utilization_since(&self, previous: &CpuTimes) -> f64 {
+        let total = self.total.saturating_sub(previous.total);
+        if total == 0 {
+            return 0.0;
+        }
+        let busy = self.busy.saturating_sub(previous.busy);
+        (busy as f64 * 100.0 / total as f64).min(100.0)
+    }
+}
+
+/// Samples the CPU times from /proc/stat and computes the utilization since
+/// the previous sample. It is shared by property reads and the telemetry task.
+#[derive(Default)]
+struct Sampler {
+    // CPU times of the previous sample, keyed by the name of the CPU in
+    // /proc/stat, e.g. "cpu" for all CPUs or "cpu3"
+    times: HashMap<String, CpuTimes>,
+    utilization: HashMap<String, f64>,
+    // Time of the previous sample, if any was taken
+    sampled_at: Option<Instant>,
+}
+
+impl Sampler {
+    /// Take a sample and compute the utilization since the previous one. The
+    /// first sample covers the time since boot. CPUs that did not report any
+    /// time since the previous sample keep their utilization.
+    fn sample(&mut self) -> fdo::Result<()> {
+        let times = read_proc_stat()?;
+        let mut utilization = HashMap::new();
+        for (name, current) in times.iter() {
+            let previous = self.times.get(name).copied().unwrap_or_default();
+            let value = match self.utilization.get(name) {
+                Some(value) if current.total == previous.total => *value,
+                _ => current.utilization_since(&previous),
+            };
+            utilization.insert(name.clone(), value);
+        }
+        self.times = times;
+        self.utilization = utilization;
+        self.sampled_at = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Take a sample unless the previous one was taken within the given time
+    fn sample_if_older(&mut self, window: Duration) -> fdo::Result<()> {
+        match self.sampled_at {
+            Some(sampled_at) if sampled_at.elapsed() < window => Ok(()),
+            _ => self.sample(),
+        }
+    }
+}
+
+/// Samples of the CPU times, shared by all reads of the utilization
+static SAMPLER: LazyLock<Mutex<Sampler>> = LazyLock::new(Default::default);
+
+// Read the CPU times of all online CPUs from /proc/stat
+fn read_proc_stat() -> fdo::Result<HashMap<String, CpuTimes>> {
+    let stat = fs::read_to_string(root::path(PROC_STAT_PATH))
+        // convert the std::io::Error to a zbus::fdo::Error
+        .map_err(|err| fdo::Error::IOError(err.to_string()))?;
+
+    let mut times = HashMap::new();
+    for line in stat.lines().filter(|line| line.starts_with("cpu")) {
+        let mut fields = line.split_whitespace();
+        let Some(name) = fields.next() else {
+            continue;
+        };
+        // user, nice, system, idle, iowait, irq, softirq and steal. Guest time
+        // is already included in user time.
+        let values: Vec<u64> = fields.take(8).filter_map(|v| v.parse().ok()).collect();
+        if values.len() < 4 {
+            log::warn!("Invalid CPU times in {PROC_STAT_PATH}: {line}");
+            continue;
+        }
+        let total: u64 = values.iter().sum();
+        let idle = values[3] + values.get(4).unwrap_or(&0);
+        let busy = total.saturating_sub(idle);
+        times.insert(name.to_string(), CpuTimes { busy, total });
+    }
+    Ok(times)
+}
+
+/// Returns the utilization of the given CPU in percent since the previous
+/// read, where "cpu" is all CPUs and "cpu3" is core 3. The first read covers
+/// the time since boot, and reads within [MIN_SAMPLE_WINDOW] of the previous
+/// sample return that sample. Offline cores are idle.
+pub fn utilization(name: &str) -> fdo::Result<f64> {
+    let mut sampler = SAMPLER.lock().unwrap();
+    sampler.sample_if_older(MIN_SAMPLE_WINDOW)?;
+    Ok(sampler.utilization.get(name).copied().unwrap_or(0.0))
+}
+
+/// A hwmon device reporting CPU temperatures
+struct Sensor {
+    // Name of the hwmon driver, e.g. "k10temp"
+    name: String,
+    // Temperatures in °C, keyed by their label
+    temperatures: HashMap<String, f64>,
+}
+
+// Returns all hwmon devices reporting CPU temperatures, in the order of
+// TEMPERATURE_DRIVERS
+fn sensors() -> Vec<Sensor> {
+    let Ok(entries) = fs::read_dir(root::path(HWMON_PATH)) else {
+        return vec![];
+    };
+    let mut sensors = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = fs::read_to_string(path.join("name")).unwrap_or_default();
+        let name = name.trim();
+        if !TEMPERATURE_DRIVERS.contains(&name) {
+            continue;
+        }
+
+        // Read every temperature input, e.g. "temp1_input" labeled by
+        // "temp1_label". Unlabeled inputs are named after the input.
+        let mut temperatures = HashMap::new();
+        for file in fs::read_dir(&path).into_iter().flatten().flatten() {
+            let file_name = file.file_name().to_string_lossy().to_string();
+            let Some(input) = file_name
+                .strip_suffix("_input")
+                .filter(|input| input.starts_with("temp"))
+            else {
+                continue;
+            };
+            let Ok(value) = fs::read_to_string(file.path()) else {
+                continue;
+            };
+            let Ok(millidegrees) = value.trim().parse::<i64>() else {
+                continue;
+            };
+            let label = fs::read_to_string(path.join(format!("{input}_label")))
+                .map(|label| label.trim().to_string())
+                .unwrap_or_else(|_| input.to_string());
+            temperatures.insert(label, millidegrees as f64 / 1000.0);
+        }
+        sensors.push(Sensor {
+            name: name.to_string(),
+            temperatures,
+        });
+    }
+    sensors.sort_by_key(|sensor| {
+        TEMPERATURE_DRIVERS
+            .iter()
+            .position(|driver| *driver == sensor.name)
+    });
+    sensors
+}
+
+/// Returns the temperature of the CPU package in °C from the k10temp,
+/// zenpower or coretemp driver. With multiple packages, the hottest one is
+/// reported.
+pub fn package_temperature() -> fdo::Result<f64> {
+    for sensor in sensors() {
+        let temperature = if sensor.name == "coretemp" {
+            sensor
+                .temperatures
+                .iter()
+                .filter(|(label, _)| label.starts_with("Package id"))
+                .map(|(_, temperature)| *temperature)
+                .reduce(f64::max)
+        } else {
+            AMD_PACKAGE_LABELS
+                .iter()
+                .find_map(|label| sensor.temperatures.get(*label).copied())
+        };
+        if let Some(temperature) = temperature {
+            return Ok(temperature);
+        }
+    }
+    Err(fdo::Error::NotSupported(
+        "No CPU temperature sensor was found".into(),
+    ))
+}
+
+/// Returns the temperature of the given physical core in °C. Only coretemp
+/// reports the temperature of single cores.
+pub fn core_temperature(package_id: u32, core_id: u32) -> fdo::Result<f64> {
+    let package = format!("Package id {package_id}");
+    let core = format!("Core {core_id}");
+    let sensors: Vec<Sensor> = sensors()
+        .into_iter()
+        .filter(|sensor| sensor.name == "coretemp")
+        .collect();
+
+    // Each package has its own coretemp device. Without a package
+    // temperature, the only device is used.
+    let sensor = match sensors.len() {
+        1 => sensors.first(),
+        _ => sensors
+            .iter()
+            .find(|sensor| sensor.temperatures.contains_key(&package)),
+    };
+    sensor
+        .and_then(|sensor| sensor.temperatures.get(&core).copied())
+        .ok_or_else(|| {
+            fdo::Error::NotSupported(format!(
+                "No temperature sensor was found for core {core_id} of package {package_id}"
+            ))
+        })
+}
+
+/// Emit the telemetry signal of the CPU at the interval set on the given
+/// channel, in milliseconds. The utilization is sampled at every signal, and
+/// nothing runs while the interval is 0.
+pub fn watch_telemetry(connection: Connection, mut interval_ms: watch::Receiver<u32>) {
+    tokio::task::spawn(async move {
+        loop {
+            let ms = *interval_ms.borrow_and_update();
+            if ms == 0 {
+                if interval_ms.changed().await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            let period = Duration::from_millis(ms as u64);
+            let start = tokio::time::Instant::now() + period;
+            let mut interval = tokio::time::interval_at(start, period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        if let Err(e) = SAMPLER.lock().unwrap().sample() {
+                            log::warn!("Failed to sample CPU utilization: {e}");
+                        }
+                        emit_telemetry(&connection).await;
+                    }
+                    changed = interval_ms.changed() => {
+                        if changed.is_err() {
+                            return;
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+// Emit the telemetry signal of the CPU once
+async fn emit_telemetry(connection: &Connection) {
+    let object_server = connection.object_server();
+    let Ok(cpu) = object_server.interface::<_, Cpu>(CPU_PATH).await else {
+        return;
+    };
+    let result = cpu.get().await.emit_telemetry(cpu.signal_emitter()).await;
+    if let Err(e) = result {
+        log::warn!("Failed to emit CPU telemetry: {e}");
+    }
+}
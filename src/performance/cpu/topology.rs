@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+use zbus::fdo;
+use zbus::message::Header;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath};
+use zbus::Connection;
+use zbus_macros::interface;
+
+use crate::performance::cpu::core::{CPUCore, CoreTopology};
+use crate::performance::cpu::cpu_features::{self, CorePolicy, Cpu};
+use crate::performance::state::StateStore;
+use crate::polkit::{self, Action};
+use powerstation::constants::CPU_PATH;
+
+/// Level of a node in the CPU topology
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TopologyLevel {
+    Package,
+    Die,
+    Cluster,
+    Core,
+    /// Cores sharing an L3 cache, e.g. a CCD on AMD
+    L3Cache,
+}
+
+impl TopologyLevel {
+    /// Returns the name of the level
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TopologyLevel::Package => "package",
+            TopologyLevel::Die => "die",
+            TopologyLevel::Cluster => "cluster",
+            TopologyLevel::Core => "core",
+            TopologyLevel::L3Cache => "l3cache",
+        }
+    }
+}
+
+/// Returns the DBus path of the given CPU package
+pub fn package_path(id: u32) -> String {
+    format!("{CPU_PATH}/Package{id}")
+}
+
+/// Returns the DBus path of the given L3 cache
+pub fn l3_cache_path(id: u32) -> String {
+    format!("{CPU_PATH}/L3Cache{id}")
+}
+
+/// Node of the CPU topology, e.g. a package or the cores sharing an L3 cache
+pub struct TopologyNode {
+    pub level: TopologyLevel,
+    id: u32,
+    // DBus paths of the nodes below this one
+    children: Vec<String>,
+    // Cores of the node, grouped with their SMT siblings
+    cores: Vec<Vec<CPUCore>>,
+    state: StateStore,
+}
+
+impl TopologyNode {
+    fn new(level: TopologyLevel, id: u32, state: StateStore) -> TopologyNode {
+        TopologyNode {
+            level,
+            id,
+            children: Vec::new(),
+            cores: Vec::new(),
+            state,
+        }
+    }
+}
+
+/// Build the topology tree (package, die, cluster, core) and the L3 cache
+/// nodes of the given cores, keyed by their DBus path. Cores with an unknown
+/// topology are left out.
+pub fn build(cores: &[CPUCore], state: &StateStore) -> BTreeMap<String, TopologyNode> {
+    // Group the cores with their SMT siblings
+    let mut groups: BTreeMap<CoreTopology, Vec<CPUCore>> = BTreeMap::new();
+    for core in cores {
+        let Some(topology) = core.topology else {
+            log::warn!("Topology of core {} is unknown", core.number);
+            continue;
+        };
+        groups.entry(topology).or_default().push(core.clone());
+    }
+
+    let mut nodes: BTreeMap<String, TopologyNode> = BTreeMap::new();
+    for (topology, mut group) in groups {
+        group.sort_by_key(|core| core.number);
+        let package = package_path(topology.package_id);
+        let die = format!("{package}/Die{}", topology.die_id);
+        let cluster = format!("{die}/Cluster{}", topology.cluster_id);
+        let core = format!("{cluster}/Core{}", topology.core_id);
+        let levels = [
+            (package, TopologyLevel::Package, topology.package_id),
+            (die, TopologyLevel::Die, topology.die_id),
+            (cluster, TopologyLevel::Cluster, topology.cluster_id),
+            (core.clone(), TopologyLevel::Core, topology.core_id),
+        ];
+
+        let mut parent: Option<String> = None;
+        for (path, level, id) in levels {
+            let parent_path = parent.as_deref();
+            add_cores(&mut nodes, &path, level, id, &group, parent_path, state);
+            parent = Some(path);
+        }
+
+        // L3 caches are not part of the tree, since they can be shared by
+        // cores of different clusters or dies.
+        if let Some(l3_id) = group[0].l3_id {
+            let path = l3_cache_path(l3_id);
+            add_cores(
+                &mut nodes,
+                &path,
+                TopologyLevel::L3Cache,
+                l3_id,
+                &group,
+                None,
+                state,
+            );
+            add_child(&mut nodes, &path, &core);
+        }
+    }
+
+    nodes
+}
+
+// Add the given SMT siblings to the node at the given path, creating it if it
+// does not exist yet
+fn add_cores(
+    nodes: &mut BTreeMap<String, TopologyNode>,
+    path: &str,
+    level: TopologyLevel,
+    id: u32,
+    group: &[CPUCore],
+    parent: Option<&str>,
+    state: &StateStore,
+) {
+    nodes
+        .entry(path.to_string())
+        .or_insert_with(|| TopologyNode::new(level, id, state.clone()))
+        .cores
+        .push(group.to_vec());
+    if let Some(parent) = parent {
+        add_child(nodes, parent, path);
+    }
+}
+
+// Add the given child path to the node at the given path
+fn add_child(nodes: &mut BTreeMap<String, TopologyNode>, path: &str, child: &str) {
+    let Some(node) = nodes.get_mut(path) else {
+        return;
+    };
+    if !node.children.iter().any(|c| c == child) {
+        node.children.push(child.to_string());
+    }
+}
+
+#[interface(name = "org.shadowblip.CPU.Topology")]
+impl TopologyNode {
+    /// Returns the level of the node: "package", "die", "cluster", "core" or
+    /// "l3cache"
+    #[zbus(property)]
+    pub fn level(&self) -> String {
+        self.level.as_str().to_string()
+    }
+
+    /// Returns the ID of the node, as reported by the kernel
+    #[zbus(property)]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the DBus paths of the nodes below this one. The children of an
+    /// L3 cache are the cores sharing it.
+    #[zbus(property)]
+    pub fn children(&self) -> Vec<OwnedObjectPath> {
+        self.children
+            .iter()
+            .map(|path| ObjectPath::from_string_unchecked(path.clone()).into())
+            .collect()
+    }
+
+    /// Returns the DBus paths of all CPU cores of the node, including SMT
+    /// siblings
+    #[zbus(property)]
+    pub fn threads(&self) -> Vec<OwnedObjectPath> {
+        let mut numbers: Vec<u32> = self.cores.iter().flatten().map(|c| c.number).collect();
+        numbers.sort();
+        numbers
+            .into_iter()
+            .map(|number| {
+                let path = format!("{CPU_PATH}/Core{number}");
+                ObjectPath::from_string_unchecked(path).into()
+            })
+            .collect()
+    }
+
+    /// Returns true if any core of the node is online
+    #[zbus(property)]
+    pub fn enabled(&self) -> fdo::Result<bool> {
+        for core in self.cores.iter().flatten() {
+            if core.online()? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Bring all cores of the node online or offline. SMT siblings stay offline
+    /// while SMT is disabled. A node with core 0 cannot be disabled. The number
+    /// of enabled cores is saved with the "ccd" core policy.
+    #[zbus(property)]
+    pub async fn set_enabled(
+        &mut self,
+        enabled: bool,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetCpuTopology).await?;
+        let level = self.level.as_str();
+        if !enabled && self.cores.iter().flatten().any(|core| core.number == 0) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Cowardly refusing to disable {level} {}, which contains core 0",
+                self.id
+            )));
+        }
+        let enabled_str = if enabled { "enabled" } else { "disabled" };
+        log::info!("Setting {level} {} to {enabled_str}", self.id);
+
+        let smt_enabled = cpu_features::read_smt_enabled().await?;
+        for group in self.cores.iter() {
+            for (i, core) in group.iter().enumerate() {
+                if enabled && !smt_enabled && i > 0 {
+                    log::info!("Ignoring core {} while SMT is disabled.", core.number);
+                    continue;
+                }
+                core.set_online_async(enabled)
+                    .await
+                    .map_err(|err| fdo::Error::IOError(err.to_string()))?;
+            }
+        }
+
+        // The enabled cores are saved as their number with the CCD policy,
+        // which brings up the cores sharing an L3 cache with core 0 first.
+        let count = cpu_features::read_cores_enabled()?;
+        self.state
+            .update_cpu(|s| {
+                s.cores_enabled = Some(count);
+                s.cores_enabled_policy = Some(CorePolicy::Ccd.as_str().to_string());
+            })
+            .await;
+
+        // The online state of the cores and of other nodes changed as well,
+        // along with the core policy of the CPU. This is done from a separate
+        // task since this node is locked until the property is set.
+        let connection = connection.clone();
+        tokio::task::spawn(async move {
+            let object_server = connection.object_server();
+            let Ok(cpu) = object_server.interface::<_, Cpu>(CPU_PATH).await else {
+                return;
+            };
+            let mut cpu_ref = cpu.get_mut().await;
+            cpu_ref.set_cores_policy(CorePolicy::Ccd);
+            let result = cpu_ref.cores_enabled_changed(cpu.signal_emitter()).await;
+            if let Err(e) = result {
+                log::warn!("Failed to emit CPU property changes: {e}");
+            }
+            let result = cpu_ref
+                .cores_enabled_policy_changed(cpu.signal_emitter())
+                .await;
+            if let Err(e) = result {
+                log::warn!("Failed to emit CPU property changes: {e}");
+            }
+            let result = cpu_ref
+                .emit_cores_online_changed(cpu.signal_emitter())
+                .await;
+            if let Err(e) = result {
+                log::warn!("Failed to emit core property changes: {e}");
+            }
+        });
+
+        Ok(())
+    }
+}
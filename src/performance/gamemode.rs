@@ -0,0 +1,176 @@
+use std::{fs, path::Path, str::FromStr, time::Duration};
+
+use futures_lite::StreamExt;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::Connection;
+use zbus_macros::proxy;
+
+use crate::config::{ConfigStore, GameModeConfig};
+use crate::performance::overrides::{Override, OverrideStack};
+use crate::performance::state::CardState;
+use crate::root;
+
+/// Name of the GameMode settings on the override stack
+const OVERRIDE_NAME: &str = "gamemode";
+
+/// Directory with the runtime directory of every logged in user, which
+/// contains the socket of their session bus
+const RUN_USER_PATH: &str = "/run/user";
+
+/// Lowest UID of regular users
+const MIN_USER_UID: u32 = 1000;
+
+/// How long to wait before connecting to GameMode again
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Proxy for the Feral GameMode daemon, which games register with while they
+/// are running
+#[proxy(
+    interface = "com.feralinteractive.GameMode",
+    default_service = "com.feralinteractive.GameMode",
+    default_path = "/com/feralinteractive/GameMode"
+)]
+trait GameMode {
+    /// Number of registered games
+    #[zbus(property)]
+    fn client_count(&self) -> zbus::Result<i32>;
+
+    #[zbus(signal)]
+    fn game_registered(&self, pid: i32, object_path: OwnedObjectPath) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn game_unregistered(&self, pid: i32, object_path: OwnedObjectPath) -> zbus::Result<()>;
+}
+
+/// Watch GameMode and apply the configured settings while any game is
+/// registered. The replaced values are restored once the last game exits.
+/// GameMode runs on the session bus of the user, which is connected to once it
+/// is available, and again whenever it goes away, e.g. after logging out.
+pub async fn watch_games(store: ConfigStore, overrides: OverrideStack) -> zbus::Result<()> {
+    let config = store.get().await.gamemode;
+    if !config.enabled {
+        log::debug!("GameMode integration is disabled");
+        return Ok(());
+    }
+    // Check a configured address right away instead of on every retry
+    if let Some(address) = config.address.as_deref() {
+        zbus::Address::from_str(address)?;
+    }
+
+    tokio::task::spawn(async move {
+        loop {
+            let Some(bus) = connect(config.address.as_deref()).await else {
+                tokio::time::sleep(RECONNECT_INTERVAL).await;
+                continue;
+            };
+            if let Err(e) = watch(&bus, &store, &overrides).await {
+                log::warn!("Failed to watch for GameMode games: {e}");
+            }
+            log::info!("Lost the connection to GameMode. Restoring settings.");
+            overrides.remove(OVERRIDE_NAME).await;
+            tokio::time::sleep(RECONNECT_INTERVAL).await;
+        }
+    });
+
+    Ok(())
+}
+
+/// Connect to the bus at the given address, or to the session bus of the first
+/// regular user that is logged in
+async fn connect(address: Option<&str>) -> Option<Connection> {
+    let address = match address {
+        Some(address) => address.to_string(),
+        None => session_bus_address()?,
+    };
+    let builder = zbus::connection::Builder::address(address.as_str()).ok()?;
+    match builder.build().await {
+        Ok(bus) => {
+            log::info!("Watching for GameMode games on {address}");
+            Some(bus)
+        }
+        Err(e) => {
+            log::debug!("Unable to connect to {address}: {e}");
+            None
+        }
+    }
+}
+
+/// Returns the address of the session bus of the regular user (UID 1000 or
+/// above) with the lowest UID that is logged in. The session buses of system
+/// users, e.g. of the display manager, are skipped.
+fn session_bus_address() -> Option<String> {
+    let entries = fs::read_dir(root::path(RUN_USER_PATH)).ok()?;
+    let mut uids: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        .filter(|uid| *uid >= MIN_USER_UID)
+        .collect();
+    uids.sort();
+    uids.into_iter().find_map(|uid| {
+        let socket = root::path(&format!("{RUN_USER_PATH}/{uid}/bus"));
+        Path::new(&socket)
+            .exists()
+            .then(|| format!("unix:path={socket}"))
+    })
+}
+
+/// Apply the GameMode settings while any game is registered with GameMode on
+/// the given bus. Returns once the bus is disconnected.
+async fn watch(
+    bus: &Connection,
+    store: &ConfigStore,
+    overrides: &OverrideStack,
+) -> zbus::Result<()> {
+    let gamemode = GameModeProxy::builder(bus)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    let mut changes = gamemode
+        .receive_game_registered()
+        .await?
+        .map(|_| ())
+        .or(gamemode.receive_game_unregistered().await?.map(|_| ()));
+
+    // Whether the GameMode settings are applied
+    let mut applied = false;
+    loop {
+        // GameMode may not be running yet
+        let count = gamemode.client_count().await.unwrap_or(0);
+        if count > 0 && !applied {
+            log::info!("Game registered with GameMode. Applying GameMode settings.");
+            let config = store.get().await.gamemode;
+            let settings = Override {
+                card: settings(&config),
+                tdp_limit: config.tdp,
+                ..Default::default()
+            };
+            overrides.push(OVERRIDE_NAME, settings).await;
+            applied = true;
+        } else if count <= 0 && applied {
+            log::info!("All games unregistered from GameMode. Restoring settings.");
+            overrides.remove(OVERRIDE_NAME).await;
+            applied = false;
+        }
+
+        if changes.next().await.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+/// Returns the card settings to apply while games are running
+fn settings(config: &GameModeConfig) -> CardState {
+    let mut settings = CardState {
+        boost: config.boost,
+        power_profile: config.power_profile.clone(),
+        power_profile_mode: config.power_profile_mode.clone(),
+        ..Default::default()
+    };
+    if let Some(clock) = config.gpu_clock_mhz {
+        settings.manual_clock = Some(true);
+        settings.clock_value_mhz_min = Some(clock);
+        settings.clock_value_mhz_max = Some(clock);
+    }
+    settings
+}
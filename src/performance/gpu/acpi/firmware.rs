@@ -2,6 +2,8 @@ use crate::performance::gpu::tdp::{TDPError, TDPResult};
 
 use std::fs;
 
+use crate::root;
+
 const PLATFORM_PROFILE_PATH: &str = "/sys/firmware/acpi/platform_profile";
 const PLATFORM_PROFILES_AVAIAL_PATH: &str = "/sys/firmware/acpi/platform_profile_choices";
 
@@ -11,8 +13,8 @@ pub struct Acpi {}
 impl Acpi {
     /// Check if ACPI supports platform profiles on this device
     pub async fn new() -> Option<Self> {
-        if fs::metadata(PLATFORM_PROFILE_PATH).is_err()
-            || fs::metadata(PLATFORM_PROFILES_AVAIAL_PATH).is_err()
+        if fs::metadata(root::path(PLATFORM_PROFILE_PATH)).is_err()
+            || fs::metadata(root::path(PLATFORM_PROFILES_AVAIAL_PATH)).is_err()
         {
             return None;
         }
@@ -21,7 +23,7 @@ impl Acpi {
 
     /// Reads the currently set power profile
     pub async fn power_profile(&self) -> TDPResult<String> {
-        match fs::read(PLATFORM_PROFILE_PATH) {
+        match fs::read(root::path(PLATFORM_PROFILE_PATH)) {
             Ok(data) => {
                 let profile = match String::from_utf8(data) {
                     Ok(profile) => profile.split_whitespace().collect(),
@@ -32,7 +34,7 @@ impl Acpi {
                     }
                 };
 
-                log::info!("Platform profile is currently set to {profile}");
+                log::debug!("Platform profile is currently set to {profile}");
                 Ok(profile)
             }
             Err(e) => Err(TDPError::IOError(format!(
@@ -43,7 +45,7 @@ impl Acpi {
 
     /// Returns a list of valid power profiles for this interface
     pub async fn power_profiles_available(&self) -> TDPResult<Vec<String>> {
-        match fs::read(PLATFORM_PROFILES_AVAIAL_PATH) {
+        match fs::read(root::path(PLATFORM_PROFILES_AVAIAL_PATH)) {
             Ok(data) => {
                 let profiles_raw = match String::from_utf8(data) {
                     Ok(profile) => profile,
@@ -82,18 +84,20 @@ impl Acpi {
         if !valid_profiles.contains(&profile) {
             log::warn!("Incompatible profile requested: {profile}. Attempting to translate to valid profile.");
             match profile.as_str() {
-                "max-performance" => match fs::write(PLATFORM_PROFILE_PATH, "performance") {
-                    Ok(_) => {
-                        log::info!("Set platform perfomance profile to performance");
-                        return Ok(());
-                    }
-                    Err(e) => {
-                        return Err(TDPError::IOError(format!(
-                            "Failed to set power profile: {e:?}"
-                        )))
+                "max-performance" => {
+                    match fs::write(root::path(PLATFORM_PROFILE_PATH), "performance") {
+                        Ok(_) => {
+                            log::info!("Set platform perfomance profile to performance");
+                            return Ok(());
+                        }
+                        Err(e) => {
+                            return Err(TDPError::IOError(format!(
+                                "Failed to set power profile: {e:?}"
+                            )))
+                        }
                     }
-                },
-                "power-saving" => match fs::write(PLATFORM_PROFILE_PATH, "balanced") {
+                }
+                "power-saving" => match fs::write(root::path(PLATFORM_PROFILE_PATH), "balanced") {
                     Ok(_) => {
                         log::info!("Set platform perfomance profile to balanced");
                         return Ok(());
@@ -112,7 +116,7 @@ impl Acpi {
             };
         };
 
-        match fs::write(PLATFORM_PROFILE_PATH, profile.clone()) {
+        match fs::write(root::path(PLATFORM_PROFILE_PATH), profile.clone()) {
             Ok(_) => {
                 log::info!("Set platform perfomance profile to {profile}");
                 Ok(())
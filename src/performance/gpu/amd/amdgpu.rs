@@ -6,11 +6,12 @@ use std::{
 
 use tokio::sync::Mutex;
 
-use crate::constants::GPU_PATH;
+use crate::config::TdpConfig;
 use crate::performance::gpu::{
     dbus::devices::TDPDevices,
     interface::{GPUDevice, GPUError, GPUResult},
 };
+use powerstation::constants::GPU_PATH;
 
 use super::tdp::Tdp;
 
@@ -36,11 +37,12 @@ impl GPUDevice for AmdGpu {
         format!("{0}/{1}", GPU_PATH, self.name().await)
     }
 
-    /// Returns the TDP DBus interface for this GPU
-    async fn get_tdp_interface(&self) -> Option<Arc<Mutex<TDPDevices>>> {
+    /// Returns the TDP DBus interface for this GPU with the given TDP
+    /// configuration
+    async fn get_tdp_interface(&self, config: &TdpConfig) -> Option<Arc<Mutex<TDPDevices>>> {
         match self.class.as_str() {
             "integrated" => Some(Arc::new(Mutex::new(TDPDevices::Amd(
-                Tdp::new(self.path.as_str(), self.device_id.as_str()).await,
+                Tdp::new(self.path.as_str(), self.device_id.as_str(), config).await,
             )))),
             _ => None,
         }
@@ -208,11 +210,91 @@ impl GPUDevice for AmdGpu {
         // Write the value
         OpenOptions::new()
             .write(true)
+            .truncate(true)
             .open(path)
             .map_err(|err| GPUError::FailedOperation(err.to_string()))?
             .write_all(status.as_bytes())
             .map_err(|err| GPUError::IOError(err.to_string()))
     }
+
+    async fn power_profile_mode(&self) -> GPUResult<String> {
+        let modes = get_power_profile_modes(self.path().await)
+            .map_err(|err| GPUError::IOError(err.to_string()))?;
+
+        modes
+            .into_iter()
+            .find(|mode| mode.active)
+            .map(|mode| mode.name)
+            .ok_or_else(|| GPUError::FailedOperation("No active power profile mode".to_owned()))
+    }
+
+    async fn power_profile_modes_available(&self) -> GPUResult<Vec<String>> {
+        let modes = get_power_profile_modes(self.path().await)
+            .map_err(|err| GPUError::IOError(err.to_string()))?;
+
+        Ok(modes.into_iter().map(|mode| mode.name).collect())
+    }
+
+    async fn set_power_profile_mode(&mut self, mode: String) -> GPUResult<()> {
+        // Modes are selected by writing their index
+        // https://www.kernel.org/doc/html/latest/gpu/amdgpu/thermal.html#pp-power-profile-mode
+        let modes = get_power_profile_modes(self.path().await)
+            .map_err(|err| GPUError::IOError(err.to_string()))?;
+        let Some(index) = modes.iter().find(|m| m.name == mode).map(|m| m.index) else {
+            return Err(GPUError::InvalidArgument(format!(
+                "{mode} is not a valid power profile mode"
+            )));
+        };
+
+        let path = format!("{0}/{1}", self.path().await, "device/pp_power_profile_mode");
+        log::debug!("Writing value '{}' to: {}", index, path);
+        OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|err| GPUError::FailedOperation(err.to_string()))?
+            .write_all(index.to_string().as_bytes())
+            .map_err(|err| GPUError::IOError(err.to_string()))
+    }
+}
+
+/// A power profile mode from pp_power_profile_mode
+struct PowerProfileMode {
+    index: u32,
+    name: String,
+    active: bool,
+}
+
+/// Reads the pp_power_profile_mode from sysfs and returns all modes. Every mode
+/// starts with its index and name, with the active mode marked by "*", e.g.
+/// " 1 3D_FULL_SCREEN*:". Headers and per-clock rows are skipped.
+fn get_power_profile_modes(gpu_path: String) -> Result<Vec<PowerProfileMode>, std::io::Error> {
+    let path = format!("{0}/{1}", gpu_path, "device/pp_power_profile_mode");
+    let result = fs::read_to_string(path)?;
+
+    let mut modes = Vec::new();
+    for line in result.lines() {
+        let mut parts = line.split_whitespace();
+        let Some(Ok(index)) = parts.next().map(|part| part.parse::<u32>()) else {
+            continue;
+        };
+        let Some(name) = parts.next() else {
+            continue;
+        };
+        // Older GPUs separate the marker from the name, e.g. "3D_FULL_SCREEN *:"
+        let active = name.contains('*') || parts.next().is_some_and(|part| part.starts_with('*'));
+        let name = name.trim_end_matches(':').trim_end_matches('*');
+        if name.is_empty() {
+            continue;
+        }
+        modes.push(PowerProfileMode {
+            index,
+            name: name.to_string(),
+            active,
+        });
+    }
+
+    Ok(modes)
 }
 
 /// Reads the pp_od_clk_voltage from sysfs and returns the OD_RANGE values.
@@ -1,10 +1,14 @@
-use std::{collections::HashMap, fs, io, ops::Add, path::PathBuf, str::FromStr};
-
-use udev::Device;
+use std::{
+    collections::HashMap,
+    fs, io,
+    ops::Add,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use crate::performance::gpu::{
     platform::hardware::Hardware,
-    tdp::{HardwareAccess, TDPDevice, TDPError, TDPResult},
+    tdp::{HardwareAccess, TDPDevice, TDPError, TDPResult, TdpBackend},
 };
 
 /// Amount to scale the TDP values by. E.g. 15 == 15000000
@@ -14,8 +18,8 @@ const TDP_SCALE: f64 = 1000000.0;
 pub struct Hwmon {
     /// Detected hardware TDP limits
     hardware: Option<Hardware>,
-    /// Udev device used to set/get sysfs properties
-    device: Device,
+    /// Path to the hwmon device used to set/get sysfs attributes
+    path: PathBuf,
     /// Mapping of attribute labels to their attribute path. In the hwmon
     /// interface there are typically "*_label" attributes which name a particular
     /// set of attributes that denotes its function. For example, an interface
@@ -49,36 +53,28 @@ impl Hwmon {
         };
         log::debug!("Found hwmon interface: {hwmon_path:?}");
 
-        // Use udev to read/write attributes
-        let device = Device::from_syspath(hwmon_path.as_path())?;
-
         // Create a mapping of attribute labels to their corresponding attribute.
         let mut label_map = HashMap::new();
-        for attrib in device.attributes() {
-            log::debug!(
-                "Found device attribute: {:?}: {:?}",
-                attrib.name(),
-                attrib.value()
-            );
-            let name = attrib.name().to_string_lossy();
-            if !name.ends_with("_label") {
-                continue;
-            }
-
-            let key = attrib.value().to_string_lossy().to_string();
+        for entry in fs::read_dir(hwmon_path.as_path())? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
             let Some(value) = name.strip_suffix("_label").map(String::from) else {
                 continue;
             };
+            let Some(key) = read_attribute(&entry.path()) else {
+                continue;
+            };
+            log::debug!("Found device attribute: {name:?}: {key:?}");
 
             label_map.insert(key, value);
         }
 
         // Get the hardware limits
-        let hardware = Self::get_limits(&device, &label_map);
+        let hardware = Self::get_limits(hwmon_path.as_path(), &label_map);
 
         let hwmon = Self {
             hardware,
-            device,
+            path: hwmon_path,
             label_map,
         };
 
@@ -86,20 +82,21 @@ impl Hwmon {
     }
 
     /// Returns the detected TDP limits
-    fn get_limits(device: &Device, label_map: &HashMap<String, String>) -> Option<Hardware> {
+    fn get_limits(path: &Path, label_map: &HashMap<String, String>) -> Option<Hardware> {
         let prefix = label_map.get("fastPPT")?;
 
         let cap_max = format!("{prefix}_cap_max");
-        let max_value = device.attribute_value(cap_max)?.to_str()?;
+        let max_value = read_attribute(&path.join(cap_max))?;
         let max_value: f64 = max_value.parse().ok()?;
         let cap_min = format!("{prefix}_cap_min");
-        let min_value = device.attribute_value(cap_min)?.to_str()?;
+        let min_value = read_attribute(&path.join(cap_min))?;
         let min_value: f64 = min_value.parse().ok()?;
 
         let hardware = Hardware {
             min_tdp: (min_value / TDP_SCALE),
             max_tdp: (max_value / TDP_SCALE),
             max_boost: 0.0,
+            tdp_backends: None,
         };
 
         Some(hardware)
@@ -137,6 +134,22 @@ impl Hwmon {
         self.set_label_value("fastPPT", "cap", value)
     }
 
+    /// Check that the given value is within the `*_cap_min` and `*_cap_max`
+    /// limits of the attribute with the given label
+    fn check_cap(&self, label: &str, value: u64) -> TDPResult<()> {
+        let min = self.get_label_value::<u64>(label, "cap_min");
+        let max = self.get_label_value::<u64>(label, "cap_max");
+        if min.is_some_and(|min| value < min) || max.is_some_and(|max| value > max) {
+            return Err(TDPError::OutOfRange(format!(
+                "{label} of {}W is outside of the limits of {}-{}W",
+                value as f64 / TDP_SCALE,
+                min.unwrap_or_default() as f64 / TDP_SCALE,
+                max.unwrap_or_default() as f64 / TDP_SCALE
+            )));
+        }
+        Ok(())
+    }
+
     /// Returns the value of the attribute with the given label name found
     /// in the `*_label` attribute. For example, if `power1_label` is "slowPPT",
     /// and you want to get the value of `power1_cap`, you can use this method
@@ -148,7 +161,7 @@ impl Hwmon {
     {
         let prefix = self.label_map.get(label)?;
         let attribute = format!("{prefix}_{attribute}");
-        let value = self.device.attribute_value(attribute)?.to_str()?;
+        let value = read_attribute(&self.path.join(attribute))?;
         value.parse().ok()
     }
 
@@ -160,11 +173,16 @@ impl Hwmon {
     {
         let prefix = self.label_map.get(label).unwrap();
         let attribute = format!("{prefix}_{attribute}");
-        self.device
-            .set_attribute_value(attribute, value.to_string().as_str())
+        fs::write(self.path.join(attribute), value.to_string())
     }
 }
 
+/// Returns the trimmed value of the given sysfs attribute
+fn read_attribute(path: &Path) -> Option<String> {
+    let value = fs::read_to_string(path).ok()?;
+    Some(value.trim().to_string())
+}
+
 impl HardwareAccess for Hwmon {
     fn hardware(&self) -> Option<&Hardware> {
         self.hardware.as_ref()
@@ -192,9 +210,11 @@ impl TDPDevice for Hwmon {
 
         // Get the current boost value before updating. We will
         // use this value to also adjust the Fast PPT Limit.
-        let boost = self.boost().await? as u64;
+        let boost = self.boost().await?;
         let slow_ppt = (value * TDP_SCALE) as u64; // 15 == 15000000
-        let fast_ppt = (value * TDP_SCALE) as u64 + boost;
+        let fast_ppt = ((value + boost) * TDP_SCALE) as u64;
+        self.check_cap("slowPPT", slow_ppt)?;
+        self.check_cap("fastPPT", fast_ppt)?;
 
         self.set_slow_ppt_cap(slow_ppt)?;
         self.set_fast_ppt_cap(fast_ppt)?;
@@ -232,12 +252,40 @@ impl TDPDevice for Hwmon {
         let slow_ppt_scaled = slow_ppt_raw / TDP_SCALE;
         let fast_ppt_scaled = slow_ppt_scaled + value;
         let fast_ppt_raw = (fast_ppt_scaled * TDP_SCALE) as u64;
+        self.check_cap("fastPPT", fast_ppt_raw)?;
 
         self.set_fast_ppt_cap(fast_ppt_raw)?;
 
         Ok(())
     }
 
+    async fn set_tdp_and_boost(&mut self, tdp: f64, boost: f64) -> TDPResult<()> {
+        log::debug!("Setting TDP to {tdp} with boost {boost}");
+        if tdp < 1.0 || boost < 0.0 {
+            return Err(TDPError::InvalidArgument(format!(
+                "Cowardly refusing to set TDP {tdp}W with boost {boost}W",
+            )));
+        }
+        let slow_ppt = (tdp * TDP_SCALE) as u64;
+        let fast_ppt = ((tdp + boost) * TDP_SCALE) as u64;
+        self.check_cap("slowPPT", slow_ppt)?;
+        self.check_cap("fastPPT", fast_ppt)?;
+
+        // Keep the fastPPT above the slowPPT while the limits are written
+        let lowering = self
+            .get_slow_ppt_cap::<u64>()
+            .is_some_and(|current| slow_ppt < current);
+        if lowering {
+            self.set_slow_ppt_cap(slow_ppt)?;
+            self.set_fast_ppt_cap(fast_ppt)?;
+        } else {
+            self.set_fast_ppt_cap(fast_ppt)?;
+            self.set_slow_ppt_cap(slow_ppt)?;
+        }
+
+        Ok(())
+    }
+
     async fn thermal_throttle_limit_c(&self) -> TDPResult<f64> {
         Err(TDPError::FeatureUnsupported)
     }
@@ -257,4 +305,12 @@ impl TDPDevice for Hwmon {
     async fn set_power_profile(&mut self, _profile: String) -> TDPResult<()> {
         Err(TDPError::FeatureUnsupported)
     }
+
+    fn backends(&self) -> Vec<TdpBackend> {
+        vec![TdpBackend::Hwmon]
+    }
+
+    fn active_backend(&self) -> Option<TdpBackend> {
+        Some(TdpBackend::Hwmon)
+    }
 }
@@ -3,24 +3,22 @@ use std::error::Error;
 
 use libryzenadj::RyzenAdj;
 
+use crate::config::RyzenAdjConfig;
 use crate::performance::gpu::{
     platform::hardware::Hardware,
-    tdp::{HardwareAccess, TDPDevice, TDPError, TDPResult},
+    tdp::{HardwareAccess, TDPDevice, TDPError, TDPResult, TdpBackend},
 };
 
-/// Steam Deck GPU ID
-const DEV_ID_VANGOGH: &str = "163f";
-const DEV_ID_SEPHIROTH: &str = "1435";
-
 /// Implementation of TDP control for AMD GPUs
 pub struct RyzenAdjTdp {
     //pub path: String,
-    pub device_id: String,
     pub profile: String,
     ryzenadj: RyzenAdj,
     pub unsupported_stapm_limit: f32,
     pub unsupported_ppt_limit_fast: f32,
     pub unsupported_thm_limit: f32,
+    /// Whether ryzenadj cannot read the limits of this GPU
+    unreadable_limits: bool,
     // We need Hardware for the TDPDevice trait's default methods
     hardware: Option<Hardware>,
 }
@@ -36,26 +34,19 @@ unsafe impl Send for RyzenAdjTdp {} // implementor (RyzenAdj) may be unsafe
 
 impl RyzenAdjTdp {
     /// Create a new TDP instance
-    pub fn new(_path: String, device_id: String) -> Result<RyzenAdjTdp, Box<dyn Error>> {
+    pub fn new(
+        _path: String,
+        device_id: String,
+        config: &RyzenAdjConfig,
+    ) -> Result<RyzenAdjTdp, Box<dyn Error>> {
         // Currently there is no known way to read this value
         let profile = String::from("power-saving");
 
         // Set fake TDP limits for GPUs that don't support ryzenadj monitoring (e.g. Steam Deck)
-        let unsupported_stapm_limit: f32 = match device_id.as_str() {
-            DEV_ID_VANGOGH => 12.0,
-            DEV_ID_SEPHIROTH => 12.0,
-            _ => 10.0,
-        };
-        let unsupported_ppt_limit_fast: f32 = match device_id.as_str() {
-            DEV_ID_VANGOGH => 15.0,
-            DEV_ID_SEPHIROTH => 15.0,
-            _ => 10.0,
-        };
-        let unsupported_thm_limit: f32 = match device_id.as_str() {
-            DEV_ID_VANGOGH => 95.0,
-            DEV_ID_SEPHIROTH => 95.0,
-            _ => 95.0,
-        };
+        let unreadable_limits = config.unreadable_devices.contains(&device_id);
+        let unsupported_stapm_limit = config.stapm_limit as f32;
+        let unsupported_ppt_limit_fast = config.fast_limit as f32;
+        let unsupported_thm_limit = config.thermal_limit as f32;
         let ryzenadj = RyzenAdj::new().map_err(|err| err.to_string())?;
 
         // Get hardware instance for min/max TDP values
@@ -69,19 +60,19 @@ impl RyzenAdjTdp {
 
         Ok(RyzenAdjTdp {
             //path,
-            device_id,
             profile,
             ryzenadj,
             unsupported_stapm_limit,
             unsupported_ppt_limit_fast,
             unsupported_thm_limit,
+            unreadable_limits,
             hardware,
         })
     }
 
     /// Returns true if ryzenadj cannot read values from the given GPU
     fn is_unsupported_gpu(&self) -> bool {
-        matches!(self.device_id.as_str(), DEV_ID_VANGOGH | DEV_ID_SEPHIROTH)
+        self.unreadable_limits
     }
 
     /// Set the current Slow PPT limit using ryzenadj
@@ -351,6 +342,36 @@ impl TDPDevice for RyzenAdjTdp {
         Ok(())
     }
 
+    async fn set_tdp_and_boost(&mut self, tdp: f64, boost: f64) -> TDPResult<()> {
+        log::debug!("Setting TDP to {tdp} with boost {boost}");
+        if tdp < 1.0 || boost < 0.0 {
+            return Err(TDPError::InvalidArgument(format!(
+                "Cowardly refusing to set TDP {tdp}W with boost {boost}W",
+            )));
+        }
+        let stapm_limit = (tdp * 1000.0) as u32;
+        let slow_ppt_limit = ((tdp + boost) * 1000.0) as u32;
+        let fast_ppt_limit = ((tdp + boost) * 1250.0) as u32;
+
+        // Keep STAPM <= slow PPT <= fast PPT while the limits are written
+        let lowering = RyzenAdjTdp::get_stapm_limit(self).is_ok_and(|current| tdp < current as f64);
+        if lowering {
+            RyzenAdjTdp::set_stapm_limit(self, stapm_limit).map_err(TDPError::FailedOperation)?;
+            RyzenAdjTdp::set_ppt_limit_slow(self, slow_ppt_limit)
+                .map_err(TDPError::FailedOperation)?;
+            RyzenAdjTdp::set_ppt_limit_fast(self, fast_ppt_limit)
+                .map_err(TDPError::FailedOperation)?;
+        } else {
+            RyzenAdjTdp::set_ppt_limit_fast(self, fast_ppt_limit)
+                .map_err(TDPError::FailedOperation)?;
+            RyzenAdjTdp::set_ppt_limit_slow(self, slow_ppt_limit)
+                .map_err(TDPError::FailedOperation)?;
+            RyzenAdjTdp::set_stapm_limit(self, stapm_limit).map_err(TDPError::FailedOperation)?;
+        }
+
+        Ok(())
+    }
+
     async fn thermal_throttle_limit_c(&self) -> TDPResult<f64> {
         let limit = RyzenAdjTdp::get_thm_limit(self)
             .map_err(|err| TDPError::FailedOperation(err.to_string()))?;
@@ -382,4 +403,12 @@ impl TDPDevice for RyzenAdjTdp {
             "power-saving".to_string(),
         ])
     }
+
+    fn backends(&self) -> Vec<TdpBackend> {
+        vec![TdpBackend::RyzenAdj]
+    }
+
+    fn active_backend(&self) -> Option<TdpBackend> {
+        Some(TdpBackend::RyzenAdj)
+    }
 }
@@ -1,22 +1,66 @@
+use std::sync::Mutex;
+
+use crate::config::TdpConfig;
 use crate::performance::gpu::{
     acpi::firmware::Acpi,
     asus::asus_wmi::AsusWmi,
     platform::hardware::Hardware,
-    tdp::{HardwareAccess, TDPDevice, TDPError, TDPResult},
+    tdp::{HardwareAccess, TDPDevice, TDPError, TDPResult, TdpBackend},
 };
+#[cfg(target_arch = "x86_64")]
+use crate::root;
 
 use super::hwmon::Hwmon;
 #[cfg(target_arch = "x86_64")]
 use super::ryzenadj::RyzenAdjTdp;
 
+/// Interface used to control the TDP of an AMD GPU
+enum Backend {
+    AsusWmi(AsusWmi),
+    Hwmon(Hwmon),
+    #[cfg(target_arch = "x86_64")]
+    RyzenAdj(RyzenAdjTdp),
+}
+
+impl Backend {
+    fn kind(&self) -> TdpBackend {
+        match self {
+            Backend::AsusWmi(_) => TdpBackend::AsusWmi,
+            Backend::Hwmon(_) => TdpBackend::Hwmon,
+            #[cfg(target_arch = "x86_64")]
+            Backend::RyzenAdj(_) => TdpBackend::RyzenAdj,
+        }
+    }
+}
+
+/// Create the RyzenAdj backend of the given GPU. RyzenAdj always accesses the
+/// real hardware, so it is not used when running against an alternate
+/// filesystem root.
+#[cfg(target_arch = "x86_64")]
+fn ryzenadj(path: &str, device_id: &str, config: &TdpConfig) -> Option<RyzenAdjTdp> {
+    if root::is_overridden() {
+        return None;
+    }
+    match RyzenAdjTdp::new(path.to_string(), device_id.to_string(), &config.ryzenadj) {
+        Ok(ryzenadj) => {
+            log::info!("Found RyzenAdj interface for TDP control");
+            Some(ryzenadj)
+        }
+        Err(e) => {
+            log::warn!("Failed to create Ryzenadj Instance: {e:?}");
+            None
+        }
+    }
+}
+
 /// Implementation of TDP control for AMD GPUs
 pub struct Tdp {
-    asus_wmi: Option<AsusWmi>,
+    /// Available TDP backends in the order they are tried
+    backends: Vec<Backend>,
     acpi: Option<Acpi>,
-    #[cfg(target_arch = "x86_64")]
-    ryzenadj: Option<RyzenAdjTdp>,
-    hwmon: Option<Hwmon>,
     hardware: Option<Hardware>,
+    /// Backend that served the last successful read or write
+    active: Mutex<Option<TdpBackend>>,
 }
 
 // Implement HardwareAccess for Tdp
@@ -27,15 +71,10 @@ impl HardwareAccess for Tdp {
 }
 
 impl Tdp {
-    pub async fn new(path: &str, device_id: &str) -> Tdp {
-        let asus_wmi = match AsusWmi::new().await {
-            Some(asus_wmi) => {
-                log::info!("Found Asus WMI interface for TDP control");
-                Some(asus_wmi)
-            }
-            None => None,
-        };
-
+    /// Detect the TDP backends of the given GPU. Backends are tried in the
+    /// configured order, or else in the order from the platform database, and
+    /// backends that are not listed are not created.
+    pub async fn new(path: &str, device_id: &str, config: &TdpConfig) -> Tdp {
         let acpi = match Acpi::new().await {
             Some(acpi) => {
                 log::info!("Found ACPI interface for platform profile control");
@@ -44,7 +83,9 @@ impl Tdp {
             None => None,
         };
 
-        let hwmon = match Hwmon::new(path) {
+        // Hwmon is detected before the order is known, since the hardware
+        // limits may come from it.
+        let mut hwmon = match Hwmon::new(path) {
             Ok(hwmon) => {
                 log::info!("Found hwmon interface for TDP control");
                 Some(hwmon)
@@ -55,18 +96,6 @@ impl Tdp {
             }
         };
 
-        #[cfg(target_arch = "x86_64")]
-        let ryzenadj = match RyzenAdjTdp::new(path.to_string(), device_id.to_string()) {
-            Ok(ryzenadj) => {
-                log::info!("Found RyzenAdj interface for TDP control");
-                Some(ryzenadj)
-            }
-            Err(e) => {
-                log::warn!("Failed to create Ryzenadj Instance: {e:?}");
-                None
-            }
-        };
-
         let hardware = match Hardware::new() {
             Some(hardware) => {
                 log::info!("Found Hardware interface for TDP control");
@@ -82,63 +111,105 @@ impl Tdp {
             }
         };
 
+        let order = match config.backends.as_deref() {
+            Some(order) => order.to_vec(),
+            None => match hardware
+                .as_ref()
+                .and_then(|hardware| hardware.tdp_backends())
+            {
+                Some(order) => order.to_vec(),
+                None => TdpBackend::AMD_DEFAULT_ORDER.to_vec(),
+            },
+        };
+
+        // Only the listed backends are created
+        let mut backends: Vec<Backend> = Vec::new();
+        for kind in order {
+            if backends.iter().any(|backend| backend.kind() == kind) {
+                continue;
+            }
+            let backend = match kind {
+                TdpBackend::AsusWmi => match AsusWmi::new().await {
+                    Some(asus_wmi) => {
+                        log::info!("Found Asus WMI interface for TDP control");
+                        Some(Backend::AsusWmi(asus_wmi))
+                    }
+                    None => None,
+                },
+                TdpBackend::Hwmon => hwmon.take().map(Backend::Hwmon),
+                #[cfg(target_arch = "x86_64")]
+                TdpBackend::RyzenAdj => ryzenadj(path, device_id, config).map(Backend::RyzenAdj),
+                _ => None,
+            };
+            if let Some(backend) = backend {
+                backends.push(backend);
+            }
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = device_id;
+        let names: Vec<String> = backends.iter().map(|b| b.kind().to_string()).collect();
+        log::info!("Using TDP backends in order: {names:?}");
+
         Tdp {
-            asus_wmi,
+            backends,
             acpi,
-            #[cfg(target_arch = "x86_64")]
-            ryzenadj,
-            hwmon,
             hardware,
+            active: Mutex::new(None),
+        }
+    }
+
+    /// Remember the backend that served a read or write
+    fn set_active(&self, backend: TdpBackend) {
+        let Ok(mut active) = self.active.lock() else {
+            return;
+        };
+        if *active != Some(backend) {
+            log::info!("Using {backend} for TDP control");
+            *active = Some(backend);
         }
     }
+
+    #[cfg(target_arch = "x86_64")]
+    fn ryzenadj(&self) -> Option<&RyzenAdjTdp> {
+        self.backends.iter().find_map(|backend| match backend {
+            Backend::RyzenAdj(ryzenadj) => Some(ryzenadj),
+            _ => None,
+        })
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn ryzenadj_mut(&mut self) -> Option<&mut RyzenAdjTdp> {
+        self.backends.iter_mut().find_map(|backend| match backend {
+            Backend::RyzenAdj(ryzenadj) => Some(ryzenadj),
+            _ => None,
+        })
+    }
 }
 
 impl TDPDevice for Tdp {
     async fn tdp(&self) -> TDPResult<f64> {
-        log::info!("Get TDP");
+        log::debug!("Get TDP");
 
         // TODO: set platform profile based on % of max TDP.
-        if self.asus_wmi.is_some() {
-            let asus_wmi = self.asus_wmi.as_ref().unwrap();
-            match asus_wmi.tdp().await {
-                Ok(tdp) => {
-                    log::info!("TDP is currently {tdp}");
-                    return Ok(tdp);
-                }
-                Err(e) => {
-                    log::warn!("Failed to read current TDP using Asus WMI: {e:?}");
-                }
+        for backend in self.backends.iter() {
+            let result = match backend {
+                Backend::AsusWmi(asus_wmi) => asus_wmi.tdp().await,
+                Backend::Hwmon(hwmon) => hwmon.tdp().await,
+                #[cfg(target_arch = "x86_64")]
+                Backend::RyzenAdj(ryzenadj) => ryzenadj.tdp().await,
             };
-        };
-
-        // TODO: set platform profile based on % of max TDP.
-        if let Some(hwmon) = self.hwmon.as_ref() {
-            match hwmon.tdp().await {
+            match result {
                 Ok(tdp) => {
-                    log::info!("TDP is currently {tdp}");
+                    log::debug!("TDP is currently {tdp}");
+                    self.set_active(backend.kind());
                     return Ok(tdp);
                 }
                 Err(e) => {
-                    log::warn!("Failed to read current TDP using hwmon: {e:?}");
+                    log::warn!("Failed to read current TDP using {}: {e:?}", backend.kind());
                 }
             };
         }
 
-        // TODO: set platform profile based on % of max TDP.
-        #[cfg(target_arch = "x86_64")]
-        if self.ryzenadj.is_some() {
-            let ryzenadj = self.ryzenadj.as_ref().unwrap();
-            match ryzenadj.tdp().await {
-                Ok(tdp) => {
-                    log::info!("TDP is currently {tdp}");
-                    return Ok(tdp);
-                }
-                Err(e) => {
-                    log::warn!("Failed to read current TDP using RyzenAdj: {e:?}");
-                }
-            };
-        };
-
         Err(TDPError::FailedOperation(
             "No TDP Interface available to read TDP.".into(),
         ))
@@ -146,44 +217,27 @@ impl TDPDevice for Tdp {
 
     async fn set_tdp(&mut self, value: f64) -> TDPResult<()> {
         log::info!("Set TDP");
-        if self.asus_wmi.is_some() {
-            let asus_wmi = self.asus_wmi.as_mut().unwrap();
-            match asus_wmi.set_tdp(value).await {
-                Ok(_) => {
-                    log::info!("TDP set to {value}");
-                    return Ok(());
-                }
-                Err(e) => {
-                    log::warn!("Failed to set TDP using Asus WMI: {e:?}");
-                }
-            };
-        };
-
-        if let Some(hwmon) = self.hwmon.as_mut() {
-            match hwmon.set_tdp(value).await {
-                Ok(tdp) => {
-                    log::info!("TDP set to {value}");
-                    return Ok(tdp);
-                }
-                Err(e) => {
-                    log::warn!("Failed to set TDP using hwmon: {e:?}");
-                }
+        for backend in self.backends.iter_mut() {
+            let result = match backend {
+                Backend::AsusWmi(asus_wmi) => asus_wmi.set_tdp(value).await,
+                Backend::Hwmon(hwmon) => hwmon.set_tdp(value).await,
+                #[cfg(target_arch = "x86_64")]
+                Backend::RyzenAdj(ryzenadj) => ryzenadj.set_tdp(value).await,
             };
-        }
-
-        #[cfg(target_arch = "x86_64")]
-        if self.ryzenadj.is_some() {
-            let ryzenadj = self.ryzenadj.as_mut().unwrap();
-            match ryzenadj.set_tdp(value).await {
+            let kind = backend.kind();
+            match result {
                 Ok(_) => {
                     log::info!("TDP set to {value}");
+                    self.set_active(kind);
                     return Ok(());
                 }
+                // Other backends are not tried with values the hardware refuses
+                Err(TDPError::OutOfRange(e)) => return Err(TDPError::OutOfRange(e)),
                 Err(e) => {
-                    log::warn!("Failed to set TDP using RyzenAdj: {e:?}");
+                    log::warn!("Failed to set TDP using {kind}: {e:?}");
                 }
             };
-        };
+        }
 
         Err(TDPError::FailedOperation(
             "No TDP Interface available to set TDP.".into(),
@@ -191,45 +245,28 @@ impl TDPDevice for Tdp {
     }
 
     async fn boost(&self) -> TDPResult<f64> {
-        log::info!("Get TDP Boost");
-        if self.asus_wmi.is_some() {
-            let asus_wmi = self.asus_wmi.as_ref().unwrap();
-            match asus_wmi.boost().await {
-                Ok(boost) => {
-                    log::info!("Boost is currently {boost}");
-                    return Ok(boost);
-                }
-                Err(e) => {
-                    log::warn!("Failed to read current boost using Asus WMI: {e:?}");
-                }
-            };
-        };
-
-        if let Some(hwmon) = self.hwmon.as_ref() {
-            match hwmon.boost().await {
-                Ok(boost) => {
-                    log::info!("Boost is currently {boost}");
-                    return Ok(boost);
-                }
-                Err(e) => {
-                    log::warn!("Failed to read current boost using hwmon: {e:?}");
-                }
+        log::debug!("Get TDP Boost");
+        for backend in self.backends.iter() {
+            let result = match backend {
+                Backend::AsusWmi(asus_wmi) => asus_wmi.boost().await,
+                Backend::Hwmon(hwmon) => hwmon.boost().await,
+                #[cfg(target_arch = "x86_64")]
+                Backend::RyzenAdj(ryzenadj) => ryzenadj.boost().await,
             };
-        };
-
-        #[cfg(target_arch = "x86_64")]
-        if self.ryzenadj.is_some() {
-            let ryzenadj = self.ryzenadj.as_ref().unwrap();
-            match ryzenadj.boost().await {
+            match result {
                 Ok(boost) => {
-                    log::info!("Boost is currently {boost}");
+                    log::debug!("Boost is currently {boost}");
+                    self.set_active(backend.kind());
                     return Ok(boost);
                 }
                 Err(e) => {
-                    log::warn!("Failed to read current boost using RyzenAdj: {e:?}");
+                    log::warn!(
+                        "Failed to read current boost using {}: {e:?}",
+                        backend.kind()
+                    );
                 }
             };
-        };
+        }
 
         Err(TDPError::FailedOperation(
             "No TDP Interface available to read boost.".into(),
@@ -238,58 +275,73 @@ impl TDPDevice for Tdp {
 
     async fn set_boost(&mut self, value: f64) -> TDPResult<()> {
         log::info!("Set TDP Boost");
-        if self.asus_wmi.is_some() {
-            let asus_wmi = self.asus_wmi.as_mut().unwrap();
-            match asus_wmi.set_boost(value).await {
-                Ok(_) => {
-                    log::info!("Boost set to {value}");
-                    return Ok(());
-                }
-                Err(e) => {
-                    log::warn!("Failed to set boost using Asus WMI: {e:?}");
-                }
+        for backend in self.backends.iter_mut() {
+            let result = match backend {
+                Backend::AsusWmi(asus_wmi) => asus_wmi.set_boost(value).await,
+                Backend::Hwmon(hwmon) => hwmon.set_boost(value).await,
+                #[cfg(target_arch = "x86_64")]
+                Backend::RyzenAdj(ryzenadj) => ryzenadj.set_boost(value).await,
             };
-        };
-
-        if let Some(hwmon) = self.hwmon.as_mut() {
-            match hwmon.set_boost(value).await {
+            let kind = backend.kind();
+            match result {
                 Ok(_) => {
                     log::info!("Boost set to {value}");
+                    self.set_active(kind);
                     return Ok(());
                 }
+                // Other backends are not tried with values the hardware refuses
+                Err(TDPError::OutOfRange(e)) => return Err(TDPError::OutOfRange(e)),
                 Err(e) => {
-                    log::warn!("Failed to set boost using hwmon: {e:?}");
+                    log::warn!("Failed to set boost using {kind}: {e:?}");
                 }
             };
-        };
+        }
 
-        #[cfg(target_arch = "x86_64")]
-        if self.ryzenadj.is_some() {
-            let ryzenadj = self.ryzenadj.as_mut().unwrap();
-            match ryzenadj.set_boost(value).await {
+        Err(TDPError::FailedOperation(
+            "No TDP Interface available to set boost.".into(),
+        ))
+    }
+
+    async fn set_tdp_and_boost(&mut self, tdp: f64, boost: f64) -> TDPResult<()> {
+        log::info!("Set TDP and Boost");
+        for backend in self.backends.iter_mut() {
+            let result = match backend {
+                Backend::AsusWmi(asus_wmi) => match asus_wmi.set_tdp(tdp).await {
+                    Ok(_) => asus_wmi.set_boost(boost).await,
+                    Err(e) => Err(e),
+                },
+                Backend::Hwmon(hwmon) => hwmon.set_tdp_and_boost(tdp, boost).await,
+                #[cfg(target_arch = "x86_64")]
+                Backend::RyzenAdj(ryzenadj) => ryzenadj.set_tdp_and_boost(tdp, boost).await,
+            };
+            let kind = backend.kind();
+            match result {
                 Ok(_) => {
-                    log::info!("Boost set to {value}");
+                    log::info!("TDP set to {tdp} with boost {boost}");
+                    self.set_active(kind);
                     return Ok(());
                 }
+                // Other backends are not tried with values the hardware refuses
+                Err(TDPError::OutOfRange(e)) => return Err(TDPError::OutOfRange(e)),
                 Err(e) => {
-                    log::warn!("Failed to set boost using RyzenAdj: {e:?}");
+                    log::warn!("Failed to set TDP and boost using {kind}: {e:?}");
                 }
             };
-        };
+        }
 
         Err(TDPError::FailedOperation(
-            "No TDP Interface available to set boost.".into(),
+            "No TDP Interface available to set TDP and boost.".into(),
         ))
     }
 
     async fn thermal_throttle_limit_c(&self) -> TDPResult<f64> {
-        log::info!("Get tctl limit");
+        log::debug!("Get tctl limit");
         #[cfg(target_arch = "x86_64")]
-        if self.ryzenadj.is_some() {
-            let ryzenadj = self.ryzenadj.as_ref().unwrap();
+        if let Some(ryzenadj) = self.ryzenadj() {
             match ryzenadj.thermal_throttle_limit_c().await {
                 Ok(limit) => {
-                    log::info!("Thermal throttle limit is currently {limit}");
+                    log::debug!("Thermal throttle limit is currently {limit}");
+                    self.set_active(TdpBackend::RyzenAdj);
                     return Ok(limit);
                 }
                 Err(e) => {
@@ -306,11 +358,11 @@ impl TDPDevice for Tdp {
     async fn set_thermal_throttle_limit_c(&mut self, limit: f64) -> TDPResult<()> {
         log::info!("Set tctl limit");
         #[cfg(target_arch = "x86_64")]
-        if self.ryzenadj.is_some() {
-            let ryzenadj = self.ryzenadj.as_mut().unwrap();
+        if let Some(ryzenadj) = self.ryzenadj_mut() {
             match ryzenadj.set_thermal_throttle_limit_c(limit).await {
                 Ok(_) => {
                     log::info!("Thermal throttle limit was set to {:e}", limit as i32);
+                    self.set_active(TdpBackend::RyzenAdj);
                     return Ok(());
                 }
                 Err(e) => {
@@ -325,12 +377,11 @@ impl TDPDevice for Tdp {
     }
 
     async fn power_profile(&self) -> TDPResult<String> {
-        log::info!("Get power_profile");
-        if self.acpi.is_some() {
-            let acpi = self.acpi.as_ref().unwrap();
+        log::debug!("Get power_profile");
+        if let Some(acpi) = self.acpi.as_ref() {
             match acpi.power_profile().await {
                 Ok(profile) => {
-                    log::info!("Power profile is currently {profile}");
+                    log::debug!("Power profile is currently {profile}");
                     return Ok(profile);
                 }
                 Err(e) => {
@@ -340,11 +391,10 @@ impl TDPDevice for Tdp {
         };
 
         #[cfg(target_arch = "x86_64")]
-        if self.ryzenadj.is_some() {
-            let ryzenadj = self.ryzenadj.as_ref().unwrap();
+        if let Some(ryzenadj) = self.ryzenadj() {
             match ryzenadj.power_profile().await {
                 Ok(profile) => {
-                    log::info!("Power profile is currently {profile}");
+                    log::debug!("Power profile is currently {profile}");
                     return Ok(profile);
                 }
                 Err(e) => {
@@ -360,8 +410,7 @@ impl TDPDevice for Tdp {
 
     async fn set_power_profile(&mut self, profile: String) -> TDPResult<()> {
         log::info!("Set power_profile");
-        if self.acpi.is_some() {
-            let acpi = self.acpi.as_mut().unwrap();
+        if let Some(acpi) = self.acpi.as_mut() {
             match acpi.set_power_profile(profile.clone()).await {
                 Ok(_) => {
                     log::info!("Power profile was set to {profile}");
@@ -374,9 +423,8 @@ impl TDPDevice for Tdp {
         };
 
         #[cfg(target_arch = "x86_64")]
-        if self.ryzenadj.is_some() {
-            let ryzenadj = self.ryzenadj.as_mut().unwrap();
-            match ryzenadj.set_power_profile(profile.clone()).await {
+        if let Some(ryzenadj) = self.ryzenadj_mut() {
+            match TDPDevice::set_power_profile(ryzenadj, profile.clone()).await {
                 Ok(_) => {
                     log::info!("Power profile was set to {profile}");
                     return Ok(());
@@ -393,8 +441,7 @@ impl TDPDevice for Tdp {
     }
 
     async fn power_profiles_available(&self) -> TDPResult<Vec<String>> {
-        if self.acpi.is_some() {
-            let acpi = self.acpi.as_ref().unwrap();
+        if let Some(acpi) = self.acpi.as_ref() {
             match acpi.power_profiles_available().await {
                 Ok(profiles) => {
                     log::info!("Available power profiles are {profiles:?}");
@@ -407,8 +454,7 @@ impl TDPDevice for Tdp {
         };
 
         #[cfg(target_arch = "x86_64")]
-        if self.ryzenadj.is_some() {
-            let ryzenadj = self.ryzenadj.as_ref().unwrap();
+        if let Some(ryzenadj) = self.ryzenadj() {
             match ryzenadj.power_profiles_available().await {
                 Ok(profiles) => {
                     log::info!("Available power profiles are {profiles:?}");
@@ -424,4 +470,12 @@ impl TDPDevice for Tdp {
             "No TDP Interface available to list available power profiles.".into(),
         ))
     }
+
+    fn backends(&self) -> Vec<TdpBackend> {
+        self.backends.iter().map(Backend::kind).collect()
+    }
+
+    fn active_backend(&self) -> Option<TdpBackend> {
+        self.active.lock().ok().and_then(|active| *active)
+    }
 }
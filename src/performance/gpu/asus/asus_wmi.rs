@@ -1,64 +1,117 @@
-use crate::performance::gpu::tdp::{TDPError, TDPResult};
-
-use rog_platform::{
-    error::PlatformError,
-    firmware_attributes::{AttrValue, FirmwareAttributes},
-    platform::RogPlatform,
-};
+use std::{fs, path::PathBuf};
 
-impl From<PlatformError> for TDPError {
-    fn from(value: PlatformError) -> Self {
-        Self::FailedOperation(value.to_string())
-    }
+use crate::performance::gpu::tdp::{TDPError, TDPResult};
+use crate::root;
+
+/// Firmware attributes of the asus-armoury driver
+const ATTRIBUTES_PATH: &str = "/sys/class/firmware-attributes/asus-armoury/attributes";
+/// Device attributes of the asus-wmi platform driver, used by kernels without
+/// the asus-armoury driver
+const PLATFORM_PATH: &str = "/sys/devices/platform/asus-nb-wmi";
+
+/// Sustained power limit (STAPM)
+const SPL: &str = "ppt_pl1_spl";
+/// Slow package power tracking limit
+const SPPT: &str = "ppt_pl2_sppt";
+/// Fast package power tracking limit
+const FPPT: &str = "ppt_pl3_fppt";
+/// Sets the SPPT to the given value and the FPPT to the value + 25%
+const PLATFORM_SPPT: &str = "ppt_platform_sppt";
+
+/// Layout of the sysfs attributes of the driver
+enum Attributes {
+    /// A directory per attribute with its "current_value", "min_value" and
+    /// "max_value"
+    Firmware(PathBuf),
+    /// A file per attribute
+    Platform(PathBuf),
 }
 
 /// Implementation of asus-wmi sysfs
 /// See https://www.kernel.org/doc/html/v6.8-rc4/admin-guide/abi-testing.html#abi-sys-devices-platform-platform-ppt-apu-sppt
 pub struct AsusWmi {
-    attributes: FirmwareAttributes,
-    platform: RogPlatform,
+    attributes: Attributes,
 }
 
 impl AsusWmi {
     /// test if we are in an asus system with asus-wmi loaded
     pub async fn new() -> Option<Self> {
-        match RogPlatform::new() {
-            Ok(platform) => {
-                log::info!("Module asus-wmi found");
-                let attributes = FirmwareAttributes::new();
-                Some(Self {
-                    attributes,
-                    platform,
-                })
-            }
-            Err(err) => {
-                log::info!("Module asus-wmi not found: {err:?}");
-                None
-            }
+        let firmware = PathBuf::from(root::path(ATTRIBUTES_PATH));
+        let platform = PathBuf::from(root::path(PLATFORM_PATH));
+        let attributes = if firmware.join(SPL).is_dir() {
+            log::info!("Module asus-armoury found");
+            Attributes::Firmware(firmware)
+        } else if platform.join(SPL).is_file() {
+            log::info!("Module asus-wmi found");
+            Attributes::Platform(platform)
+        } else {
+            log::info!("Module asus-wmi not found");
+            return None;
+        };
+        Some(Self { attributes })
+    }
+
+    /// Returns true if the attribute with the given name exists
+    fn has(&self, name: &str) -> bool {
+        match &self.attributes {
+            Attributes::Firmware(path) => path.join(name).is_dir(),
+            Attributes::Platform(path) => path.join(name).is_file(),
         }
     }
 
-    /// Returns the currently set STAPM value
-    pub async fn tdp(&self) -> TDPResult<f64> {
-        let attr = self
-            .attributes
-            .attributes()
-            .iter()
-            .find(|a| a.name() == "ppt_pl1_spl");
-        let Some(attr) = attr else {
-            return Ok(self.platform.get_ppt_pl1_spl()? as f64);
+    /// Returns the path of the value of the attribute with the given name
+    fn value_path(&self, name: &str) -> PathBuf {
+        match &self.attributes {
+            Attributes::Firmware(path) => path.join(name).join("current_value"),
+            Attributes::Platform(path) => path.join(name),
+        }
+    }
+
+    /// Returns the current value of the attribute with the given name
+    fn read(&self, name: &str) -> TDPResult<f64> {
+        let path = self.value_path(name);
+        let value = fs::read_to_string(&path)
+            .map_err(|e| TDPError::IOError(format!("Failed to read {path:?}: {e}")))?;
+        value
+            .trim()
+            .parse()
+            .map_err(|e| TDPError::FailedOperation(format!("Invalid value in {path:?}: {e}")))
+    }
+
+    /// Returns the limit of the attribute with the given name, e.g.
+    /// "min_value", if the driver reports one
+    fn limit(&self, name: &str, limit: &str) -> Option<f64> {
+        let Attributes::Firmware(path) = &self.attributes else {
+            return None;
         };
+        let value = fs::read_to_string(path.join(name).join(limit)).ok()?;
+        value.trim().parse().ok()
+    }
 
-        match attr.current_value() {
-            Ok(attr_value) => match attr_value {
-                AttrValue::Integer(value) => Ok(value as f64),
-                _ => Err(TDPError::FailedOperation("Failed to read SPL.".to_string())),
-            },
-            Err(e) => {
-                let err = format!("Failed to read SPL: {e:?}");
-                Err(TDPError::FailedOperation(err))
-            }
+    /// Set the attribute with the given name to the given value. Values
+    /// outside of the limits reported by the driver are refused.
+    fn write(&self, name: &str, value: f64) -> TDPResult<()> {
+        let value = value.round();
+        let min = self.limit(name, "min_value");
+        let max = self.limit(name, "max_value");
+        if min.is_some_and(|min| value < min) || max.is_some_and(|max| value > max) {
+            return Err(TDPError::OutOfRange(format!(
+                "{name} must be between {} and {}",
+                min.unwrap_or_default(),
+                max.unwrap_or(u8::MAX as f64)
+            )));
         }
+
+        let path = self.value_path(name);
+        fs::write(&path, (value as i32).to_string())
+            .map_err(|e| TDPError::IOError(format!("Failed to write {path:?}: {e}")))?;
+        log::info!("Set {name} to {value}");
+        Ok(())
+    }
+
+    /// Returns the currently set STAPM value
+    pub async fn tdp(&self) -> TDPResult<f64> {
+        self.read(SPL)
     }
 
     /// Sets STAPM to the given value and adjusts the SPPT/FPPT to maintaing the current boost
@@ -74,27 +127,7 @@ impl AsusWmi {
         let boost = self.boost().await?;
 
         // Set the STAPM value to the given TDP value
-        let attr = self
-            .attributes
-            .attributes()
-            .iter()
-            .find(|a| a.name() == "ppt_pl1_spl");
-
-        if let Some(attr) = attr {
-            let val = AttrValue::Integer(value as i32);
-            match attr.set_current_value(val) {
-                Ok(_) => {
-                    log::info!("Set SPL to {value}");
-                }
-                Err(e) => {
-                    return Err(TDPError::FailedOperation(format!(
-                        "Failed to set SPL: {e:?}"
-                    )));
-                }
-            }
-        } else {
-            self.platform.set_ppt_pl1_spl(value as u8)?;
-        }
+        self.write(SPL, value)?;
 
         // Set the boost back to the expected value with the new TDP
         self.set_boost(boost).await?;
@@ -104,82 +137,39 @@ impl AsusWmi {
 
     /// Returns the current difference between STAPM and SPPT
     pub async fn boost(&self) -> TDPResult<f64> {
-        let stapm = match self.tdp().await {
-            Ok(val) => val,
-            Err(e) => {
-                return Err(e);
-            }
-        };
-
-        let attr = self
-            .attributes
-            .attributes()
-            .iter()
-            .find(|a| a.name() == "ppt_platform_sppt");
-
-        let slow_ppt = {
-            if let Some(attr) = attr {
-                match attr.current_value() {
-                    Ok(attr_value) => match attr_value {
-                        AttrValue::Integer(value) => value as f64,
-                        _ => {
-                            return Err(TDPError::FailedOperation(
-                                "Failed to read SPPT.".to_string(),
-                            ))
-                        }
-                    },
-                    Err(_) => {
-                        return Err(TDPError::FailedOperation(
-                            "Failed to read SPPT.".to_string(),
-                        ))
-                    }
-                }
-                //
-            } else {
-                self.platform.get_ppt_platform_sppt()? as f64
-            }
+        let stapm = self.tdp().await?;
+        let slow_ppt = if self.has(PLATFORM_SPPT) {
+            self.read(PLATFORM_SPPT)?
+        } else {
+            self.read(SPPT)?
         };
 
         let boost = slow_ppt - stapm;
-        log::info!("Found current boost: {boost}");
+        log::debug!("Found current boost: {boost}");
         Ok(boost)
     }
 
     /// Sets SPPT and FPPT to the current STAPM plus the given value
     pub async fn set_boost(&mut self, value: f64) -> TDPResult<()> {
-        let stapm = match self.tdp().await {
-            Ok(val) => val,
-            Err(e) => {
-                return Err(e);
-            }
-        };
+        let stapm = self.tdp().await?;
         if (stapm + value) < 1.0 || (stapm + value) > u8::MAX as f64 {
             return Err(TDPError::InvalidArgument(
                 "Combined TDP + Boost value must be between 1 and 255".to_string(),
             ));
         }
-        let sppt_val = (stapm + value) as i32;
+        let sppt_val = stapm + value;
 
         // ppt_platform_sppt will set sppt to value and fppt to value + 25%
-        let attr = self
-            .attributes
-            .attributes()
-            .iter()
-            .find(|a| a.name() == "ppt_platform_sppt");
-        let Some(attr) = attr else {
-            return Ok(self.platform.set_ppt_platform_sppt(sppt_val as u8)?);
-        };
-
-        let boost = AttrValue::Integer(sppt_val);
-        match attr.set_current_value(boost) {
-            Ok(_) => {
-                log::info!("Set SPPT to {value}");
-            }
-            Err(e) => {
-                return Err(TDPError::FailedOperation(format!(
-                    "Failed to set SPPT: {e:?}"
-                )));
-            }
+        if self.has(PLATFORM_SPPT) {
+            return self.write(PLATFORM_SPPT, sppt_val);
+        }
+        self.write(SPPT, sppt_val)?;
+        if self.has(FPPT) {
+            let fppt_val = match self.limit(FPPT, "max_value") {
+                Some(max) => (sppt_val * 1.25).min(max),
+                None => sppt_val * 1.25,
+            };
+            self.write(FPPT, fppt_val)?;
         }
 
         Ok(())
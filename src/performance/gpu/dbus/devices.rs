@@ -2,10 +2,11 @@ use std::sync::Arc;
 
 use tokio::sync::Mutex;
 
+use crate::config::TdpConfig;
 use crate::performance::gpu::{
     amd, intel,
     interface::{GPUDevice, GPUResult},
-    tdp::{TDPDevice, TDPResult},
+    tdp::{TDPDevice, TDPResult, TdpBackend},
 };
 
 #[allow(clippy::large_enum_variant)]
@@ -64,6 +65,13 @@ impl TDPDevices {
         }
     }
 
+    pub async fn set_tdp_and_boost(&mut self, tdp: f64, boost: f64) -> TDPResult<()> {
+        match self {
+            Self::Amd(dev) => dev.set_tdp_and_boost(tdp, boost).await,
+            Self::Intel(dev) => dev.set_tdp_and_boost(tdp, boost).await,
+        }
+    }
+
     pub async fn thermal_throttle_limit_c(&self) -> TDPResult<f64> {
         match self {
             Self::Amd(dev) => dev.thermal_throttle_limit_c().await,
@@ -99,6 +107,20 @@ impl TDPDevices {
             Self::Intel(dev) => dev.power_profiles_available().await,
         }
     }
+
+    pub fn backends(&self) -> Vec<TdpBackend> {
+        match self {
+            Self::Amd(dev) => dev.backends(),
+            Self::Intel(dev) => dev.backends(),
+        }
+    }
+
+    pub fn active_backend(&self) -> Option<TdpBackend> {
+        match self {
+            Self::Amd(dev) => dev.active_backend(),
+            Self::Intel(dev) => dev.active_backend(),
+        }
+    }
 }
 
 pub enum GPUDevices {
@@ -107,10 +129,10 @@ pub enum GPUDevices {
 }
 
 impl GPUDevices {
-    pub async fn get_tdp_interface(&self) -> Option<Arc<Mutex<TDPDevices>>> {
+    pub async fn get_tdp_interface(&self, config: &TdpConfig) -> Option<Arc<Mutex<TDPDevices>>> {
         match self {
-            Self::AmdGpu(dev) => dev.get_tdp_interface().await,
-            Self::IntelGpu(dev) => dev.get_tdp_interface().await,
+            Self::AmdGpu(dev) => dev.get_tdp_interface(config).await,
+            Self::IntelGpu(dev) => dev.get_tdp_interface(config).await,
         }
     }
 
@@ -261,6 +283,27 @@ impl GPUDevices {
         }
     }
 
+    pub async fn power_profile_mode(&self) -> GPUResult<String> {
+        match self {
+            Self::AmdGpu(dev) => dev.power_profile_mode().await,
+            Self::IntelGpu(dev) => dev.power_profile_mode().await,
+        }
+    }
+
+    pub async fn power_profile_modes_available(&self) -> GPUResult<Vec<String>> {
+        match self {
+            Self::AmdGpu(dev) => dev.power_profile_modes_available().await,
+            Self::IntelGpu(dev) => dev.power_profile_modes_available().await,
+        }
+    }
+
+    pub async fn set_power_profile_mode(&mut self, mode: String) -> GPUResult<()> {
+        match self {
+            Self::AmdGpu(dev) => dev.set_power_profile_mode(mode).await,
+            Self::IntelGpu(dev) => dev.set_power_profile_mode(mode).await,
+        }
+    }
+
     pub async fn get_gpu_busy_percent(&self) -> GPUResult<u8> {
         match self {
             Self::AmdGpu(dev) => dev.get_gpu_busy_percent().await,
@@ -3,44 +3,63 @@ use std::io::{prelude::*, BufReader};
 use std::path::PathBuf;
 use std::sync::Arc;
 use zbus::fdo;
+use zbus::message::Header;
+use zbus::object_server::SignalEmitter;
 use zbus::zvariant::ObjectPath;
+use zbus::Connection;
 use zbus_macros::interface;
 
 use tokio::sync::Mutex;
 
+use crate::config::ConfigStore;
 use crate::performance::gpu::amd::amdgpu::AmdGpu;
 use crate::performance::gpu::connector::Connector;
 use crate::performance::gpu::dbus::devices::GPUDevices;
+use crate::performance::gpu::dbus::lease::{controlled_by, CardLease};
 use crate::performance::gpu::dbus::tdp::GPUTDPDBusIface;
 use crate::performance::gpu::intel::intelgpu::IntelGPU;
 use crate::performance::gpu::interface::GPUError;
+use crate::performance::monitor::PropertyMonitor;
+use crate::performance::state::{within_limits, CardState, StateStore};
+use crate::polkit::{self, Action};
+use crate::root;
+use powerstation::error::{self, Error};
 
-const DRM_PATH: &str = "/sys/class/drm";
+pub const DRM_PATH: &str = "/sys/class/drm";
 const PCI_IDS_PATH: &str = "/usr/share/hwdata/pci.ids";
 
-impl From<GPUError> for fdo::Error {
+impl From<GPUError> for Error {
     fn from(val: GPUError) -> Self {
-        match &val {
-            GPUError::FailedOperation(err) => fdo::Error::Failed(err.to_string()),
-            //Self::FeatureUnsupported => fdo::Error::Failed(String::from("Unsupported feature")),
-            GPUError::InvalidArgument(err) => fdo::Error::Failed(err.to_string()),
-            GPUError::IOError(err) => fdo::Error::IOError(err.to_string()),
+        match val {
+            GPUError::FailedOperation(err) => Error::BackendFailed(err),
+            GPUError::InvalidArgument(err) => Error::InvalidArgument(err),
+            GPUError::IOError(err) => Error::BackendFailed(err),
         }
     }
 }
 
+impl From<GPUError> for fdo::Error {
+    fn from(val: GPUError) -> Self {
+        Error::from(val).into()
+    }
+}
+
 /// Represents the DBus for GPUs in the system
 #[derive(Clone)]
 pub struct GPUDBusInterface {
     connector_paths: Vec<String>,
     gpu_obj: Arc<Mutex<GPUDevices>>,
+    state: StateStore,
+    lease: CardLease,
 }
 
 impl GPUDBusInterface {
-    pub async fn new(gpu: Arc<Mutex<GPUDevices>>) -> Self {
+    pub async fn new(gpu: Arc<Mutex<GPUDevices>>, state: StateStore) -> Self {
         Self {
             gpu_obj: gpu,
             connector_paths: vec![],
+            state,
+            lease: CardLease::default(),
         }
     }
 
@@ -52,20 +71,247 @@ impl GPUDBusInterface {
         self.connector_paths = connector_paths
     }
 
-    pub async fn get_tdp_interface(&self) -> Option<GPUTDPDBusIface> {
-        self.gpu_obj
-            .lock()
+    /// Returns the DBus paths of all connectors on this card
+    pub fn connector_paths(&self) -> Vec<String> {
+        self.connector_paths.clone()
+    }
+
+    /// Add the given connector path if it is not already known
+    pub fn add_connector_path(&mut self, path: String) {
+        if !self.connector_paths.contains(&path) {
+            self.connector_paths.push(path);
+        }
+    }
+
+    /// Remove the given connector path
+    pub fn remove_connector_path(&mut self, path: &str) {
+        self.connector_paths.retain(|p| p != path);
+    }
+
+    /// Returns the TDP interface of this card with the current TDP
+    /// configuration
+    pub async fn get_tdp_interface(&self, config: &ConfigStore) -> Option<GPUTDPDBusIface> {
+        let gpu = self.gpu_obj.lock().await;
+        let name = gpu.name().await;
+        let tdp_config = config.get().await.tdp;
+        gpu.get_tdp_interface(&tdp_config).await.map(|dev| {
+            GPUTDPDBusIface::new(
+                name,
+                dev,
+                self.state.clone(),
+                self.lease.clone(),
+                config.clone(),
+            )
+        })
+    }
+
+    /// Re-apply the saved clock settings. Clock values outside of the
+    /// detected clock limits are skipped.
+    pub async fn restore(&mut self, state: &CardState, emitter: &SignalEmitter<'_>) {
+        self.apply_state(state).await;
+
+        // Notify clients about the restored values
+        let current = self.snapshot().await;
+        if let Err(e) = self
+            .emit_changed(&CardSnapshot::default(), &current, emitter)
+            .await
+        {
+            log::warn!("Failed to emit restored clock properties: {e}");
+        }
+    }
+
+    /// Apply the given clock settings and save them. Unlike [Self::restore],
+    /// this stops at the first setting that fails and returns its error.
+    pub async fn apply(
+        &mut self,
+        settings: &CardState,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.apply_with(settings, true, emitter).await
+    }
+
+    /// Apply the given clock settings without saving them, e.g. while a game
+    /// is running. Like [Self::apply], this stops at the first setting that
+    /// fails.
+    pub async fn apply_temporarily(
+        &mut self,
+        settings: &CardState,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.apply_with(settings, false, emitter).await
+    }
+
+    /// Apply the given clock settings, saving the applied values if `save` is
+    /// set
+    async fn apply_with(
+        &mut self,
+        settings: &CardState,
+        save: bool,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let previous = self.snapshot().await;
+        let mut applied = CardState::default();
+        let result = self.apply_settings(settings, &mut applied).await;
+        if save {
+            let name = self.name().await;
+            self.state.update_card(&name, |s| s.merge(&applied)).await;
+        }
+
+        let current = self.snapshot().await;
+        if let Err(e) = self.emit_changed(&previous, &current, emitter).await {
+            log::warn!("Failed to emit applied clock properties: {e}");
+        }
+        result
+    }
+
+    /// Apply the given clock settings to the card, recording every value that
+    /// was set successfully in `applied`.
+    async fn apply_settings(
+        &self,
+        settings: &CardState,
+        applied: &mut CardState,
+    ) -> fdo::Result<()> {
+        let mut gpu = self.gpu_obj.lock().await;
+        if let Some(enabled) = settings.manual_clock {
+            gpu.set_manual_clock(enabled).await?;
+            applied.manual_clock = Some(enabled);
+        }
+        if let Some(value) = settings.clock_value_mhz_min {
+            gpu.set_clock_value_mhz_min(value).await?;
+            applied.clock_value_mhz_min = Some(value);
+        }
+        if let Some(value) = settings.clock_value_mhz_max {
+            gpu.set_clock_value_mhz_max(value).await?;
+            applied.clock_value_mhz_max = Some(value);
+        }
+        if let Some(mode) = settings.power_profile_mode.clone() {
+            gpu.set_power_profile_mode(mode.clone()).await?;
+            applied.power_profile_mode = Some(mode);
+        }
+        Ok(())
+    }
+
+    /// Apply the given saved clock settings to the card
+    async fn apply_state(&self, state: &CardState) {
+        let mut gpu = self.gpu_obj.lock().await;
+
+        // Manual clock mode needs to be set first, since the clock limits
+        // may only be available in manual mode.
+        if let Some(enabled) = state.manual_clock {
+            if let Err(e) = gpu.set_manual_clock(enabled).await {
+                log::warn!("Failed to restore manual clock: {e:?}");
+            }
+        }
+        let min = gpu.clock_limit_mhz_min().await.ok();
+        let max = gpu.clock_limit_mhz_max().await.ok();
+
+        if let Some(value) = state.clock_value_mhz_min {
+            if !within_limits(value, min, max) {
+                log::warn!("Saved minimum clock {value} is outside of the clock limits. Skipping.");
+            } else if let Err(e) = gpu.set_clock_value_mhz_min(value).await {
+                log::warn!("Failed to restore minimum clock: {e:?}");
+            }
+        }
+        if let Some(value) = state.clock_value_mhz_max {
+            if !within_limits(value, min, max) {
+                log::warn!("Saved maximum clock {value} is outside of the clock limits. Skipping.");
+            } else if let Err(e) = gpu.set_clock_value_mhz_max(value).await {
+                log::warn!("Failed to restore maximum clock: {e:?}");
+            }
+        }
+        if let Some(mode) = state.power_profile_mode.clone() {
+            if let Err(e) = gpu.set_power_profile_mode(mode).await {
+                log::warn!("Failed to restore power profile mode: {e:?}");
+            }
+        }
+    }
+
+    /// Returns the current clock settings of the card. Values that cannot be
+    /// read are unset.
+    pub async fn current(&self) -> CardState {
+        let gpu = self.gpu_obj.lock().await;
+        CardState {
+            clock_value_mhz_min: gpu.clock_value_mhz_min().await.ok(),
+            clock_value_mhz_max: gpu.clock_value_mhz_max().await.ok(),
+            manual_clock: gpu.manual_clock().await.ok(),
+            power_profile_mode: gpu.power_profile_mode().await.ok(),
+            ..Default::default()
+        }
+    }
+
+    /// Re-apply clock settings that were replaced temporarily, saving them if
+    /// `save` is set. The clock values are set before the clock mode, since
+    /// they can only be set while the clock is still manual. The clock mode is
+    /// restored even if setting the clock values fails.
+    pub async fn reapply(
+        &mut self,
+        previous: &CardState,
+        save: bool,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let clocks = CardState {
+            manual_clock: None,
+            ..previous.clone()
+        };
+        let manual_clock = CardState {
+            manual_clock: previous.manual_clock,
+            ..Default::default()
+        };
+        let result = self.apply_with(&clocks, save, emitter).await;
+        self.apply_with(&manual_clock, save, emitter)
             .await
-            .get_tdp_interface()
+            .and(result)
+    }
+
+    /// Release the lease of this card if it is held by the given client, and
+    /// restore the TDP and clock settings it replaced.
+    pub async fn release_lease_of(
+        &mut self,
+        owner: &str,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let Some(replaced) = self.lease.release(owner).await else {
+            return Ok(());
+        };
+        self.lease_owner_changed(emitter).await?;
+
+        // Only restore the values that were changed during the lease
+        let tdp = emitter
+            .connection()
+            .object_server()
+            .interface::<_, GPUTDPDBusIface>(emitter.path().as_str())
             .await
-            .map(GPUTDPDBusIface::new)
+            .ok();
+        let mut current = self.current().await;
+        if let Some(tdp) = tdp.as_ref() {
+            current.merge(&tdp.get().await.current().await);
+        }
+        let changed = replaced.changed(&current);
+        log::info!("Released lease of {owner}. Restoring {changed:?}");
+
+        if let Some(tdp) = tdp {
+            let result = tdp
+                .get_mut()
+                .await
+                .apply(&changed, tdp.signal_emitter())
+                .await;
+            if let Err(e) = result {
+                log::error!("Failed to restore TDP settings: {e}");
+            }
+        }
+        self.reapply(&changed, true, emitter).await
     }
 }
 
 #[interface(name = "org.shadowblip.GPU.Card")]
 impl GPUDBusInterface {
     /// Returns a list of DBus paths to all connectors
-    pub fn enumerate_connectors(&self) -> fdo::Result<Vec<ObjectPath>> {
+    pub async fn enumerate_connectors(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> error::Result<Vec<ObjectPath>> {
+        polkit::check(connection, Some(&header), Action::Read).await?;
         Ok(self
             .connector_paths
             .iter()
@@ -164,13 +410,24 @@ impl GPUDBusInterface {
     }
 
     #[zbus(property)]
-    async fn set_clock_value_mhz_min(&mut self, value: f64) -> fdo::Result<()> {
+    async fn set_clock_value_mhz_min(
+        &mut self,
+        value: f64,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetGpuClock).await?;
+        self.lease.check(header.as_ref()).await?;
         self.gpu_obj
             .lock()
             .await
             .set_clock_value_mhz_min(value)
-            .await
-            .map_err(|err| err.into())
+            .await?;
+        let name = self.name().await;
+        self.state
+            .update_card(&name, |s| s.clock_value_mhz_min = Some(value))
+            .await;
+        Ok(())
     }
 
     #[zbus(property)]
@@ -184,13 +441,24 @@ impl GPUDBusInterface {
     }
 
     #[zbus(property)]
-    async fn set_clock_value_mhz_max(&mut self, value: f64) -> fdo::Result<()> {
+    async fn set_clock_value_mhz_max(
+        &mut self,
+        value: f64,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetGpuClock).await?;
+        self.lease.check(header.as_ref()).await?;
         self.gpu_obj
             .lock()
             .await
             .set_clock_value_mhz_max(value)
-            .await
-            .map_err(|err| err.into())
+            .await?;
+        let name = self.name().await;
+        self.state
+            .update_card(&name, |s| s.clock_value_mhz_max = Some(value))
+            .await;
+        Ok(())
     }
 
     #[zbus(property)]
@@ -204,11 +472,68 @@ impl GPUDBusInterface {
     }
 
     #[zbus(property)]
-    async fn set_manual_clock(&mut self, enabled: bool) -> fdo::Result<()> {
+    async fn set_manual_clock(
+        &mut self,
+        enabled: bool,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetGpuClock).await?;
+        self.lease.check(header.as_ref()).await?;
+        let previous = self.snapshot().await;
+        self.gpu_obj.lock().await.set_manual_clock(enabled).await?;
+        let name = self.name().await;
+        self.state
+            .update_card(&name, |s| s.manual_clock = Some(enabled))
+            .await;
+
+        // The clock limits and values may only be available in manual mode
+        let current = self.snapshot().await;
+        if let Err(e) = self.emit_changed(&previous, &current, &emitter).await {
+            log::debug!("Failed to emit clock changes: {e}");
+        }
+        Ok(())
+    }
+
+    /// The GPU workload profile, e.g. "3D_FULL_SCREEN" on AMD GPUs
+    #[zbus(property)]
+    async fn power_profile_mode(&self) -> fdo::Result<String> {
+        self.gpu_obj
+            .lock()
+            .await
+            .power_profile_mode()
+            .await
+            .map_err(|err| err.into())
+    }
+
+    #[zbus(property)]
+    async fn set_power_profile_mode(
+        &mut self,
+        mode: String,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetGpuClock).await?;
+        self.lease.check(header.as_ref()).await?;
+        self.gpu_obj
+            .lock()
+            .await
+            .set_power_profile_mode(mode.clone())
+            .await?;
+        let name = self.name().await;
+        self.state
+            .update_card(&name, |s| s.power_profile_mode = Some(mode))
+            .await;
+        Ok(())
+    }
+
+    #[zbus(property)]
+    async fn power_profile_modes_available(&self) -> fdo::Result<Vec<String>> {
         self.gpu_obj
             .lock()
             .await
-            .set_manual_clock(enabled)
+            .power_profile_modes_available()
             .await
             .map_err(|err| err.into())
     }
@@ -222,6 +547,126 @@ impl GPUDBusInterface {
             .await
             .map_err(|err| err.into())
     }
+
+    /// Unique bus name of the client controlling the TDP and clock settings
+    /// of this card, or an empty string if the card is not leased.
+    #[zbus(property)]
+    async fn lease_owner(&self) -> String {
+        self.lease.owner().await.unwrap_or_default()
+    }
+
+    /// Take exclusive control of the TDP and clock settings of this card.
+    /// Other clients cannot change them until the lease is released or the
+    /// caller disconnects, after which the current settings are restored.
+    async fn acquire_lease(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> error::Result<()> {
+        polkit::check(connection, Some(&header), Action::SetTdp).await?;
+        polkit::check(connection, Some(&header), Action::SetGpuClock).await?;
+        let Some(sender) = header.sender() else {
+            return Err(Error::PermissionDenied(String::from(
+                "Unable to determine the caller",
+            )));
+        };
+
+        let mut replaced = self.current().await;
+        if let Ok(tdp) = connection
+            .object_server()
+            .interface::<_, GPUTDPDBusIface>(emitter.path().as_str())
+            .await
+        {
+            replaced.merge(&tdp.get().await.current().await);
+        }
+        if self.lease.acquire(sender.as_str(), replaced).await? {
+            log::info!("{sender} acquired the lease of {}", emitter.path());
+            self.lease_owner_changed(&emitter).await?;
+        }
+        Ok(())
+    }
+
+    /// Give up control of this card and restore the TDP and clock settings
+    /// from before the lease was acquired.
+    async fn release_lease(
+        &mut self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> error::Result<()> {
+        let sender = header.sender().map(|sender| sender.to_string());
+        match self.lease.owner().await {
+            Some(owner) if Some(&owner) == sender.as_ref() => {
+                Ok(self.release_lease_of(&owner, &emitter).await?)
+            }
+            Some(owner) => Err(controlled_by(&owner)),
+            None => Err(Error::PermissionDenied(String::from("Card is not leased"))),
+        }
+    }
+}
+
+/// Monitored property values of the [GPUDBusInterface]
+#[derive(Debug, Default, PartialEq)]
+pub struct CardSnapshot {
+    clock_limit_mhz_min: Option<f64>,
+    clock_limit_mhz_max: Option<f64>,
+    clock_value_mhz_min: Option<f64>,
+    clock_value_mhz_max: Option<f64>,
+    manual_clock: Option<bool>,
+    power_profile_mode: Option<String>,
+}
+
+impl PropertyMonitor for GPUDBusInterface {
+    type Snapshot = CardSnapshot;
+
+    async fn snapshot(&self) -> CardSnapshot {
+        let gpu = self.gpu_obj.lock().await;
+        CardSnapshot {
+            clock_limit_mhz_min: gpu.clock_limit_mhz_min().await.ok(),
+            clock_limit_mhz_max: gpu.clock_limit_mhz_max().await.ok(),
+            clock_value_mhz_min: gpu.clock_value_mhz_min().await.ok(),
+            clock_value_mhz_max: gpu.clock_value_mhz_max().await.ok(),
+            manual_clock: gpu.manual_clock().await.ok(),
+            power_profile_mode: gpu.power_profile_mode().await.ok(),
+        }
+    }
+
+    async fn emit_changed(
+        &self,
+        previous: &CardSnapshot,
+        current: &CardSnapshot,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()> {
+        if current.clock_limit_mhz_min.is_some()
+            && previous.clock_limit_mhz_min != current.clock_limit_mhz_min
+        {
+            self.clock_limit_mhz_min_changed(emitter).await?;
+        }
+        if current.clock_limit_mhz_max.is_some()
+            && previous.clock_limit_mhz_max != current.clock_limit_mhz_max
+        {
+            self.clock_limit_mhz_max_changed(emitter).await?;
+        }
+        if current.clock_value_mhz_min.is_some()
+            && previous.clock_value_mhz_min != current.clock_value_mhz_min
+        {
+            self.clock_value_mhz_min_changed(emitter).await?;
+        }
+        if current.clock_value_mhz_max.is_some()
+            && previous.clock_value_mhz_max != current.clock_value_mhz_max
+        {
+            self.clock_value_mhz_max_changed(emitter).await?;
+        }
+        if current.manual_clock.is_some() && previous.manual_clock != current.manual_clock {
+            self.manual_clock_changed(emitter).await?;
+        }
+        if current.power_profile_mode.is_some()
+            && previous.power_profile_mode != current.power_profile_mode
+        {
+            self.power_profile_mode_changed(emitter).await?;
+        }
+        Ok(())
+    }
 }
 
 /// Used to enumerate all GPU cards over DBus
@@ -236,12 +681,29 @@ impl GPUBus {
             gpu_object_paths: gpu_paths,
         }
     }
+
+    /// Returns the DBus paths of all GPU cards
+    pub fn card_paths(&self) -> Vec<String> {
+        self.gpu_object_paths.clone()
+    }
+
+    /// Add the given card path if it is not already known
+    pub fn add_card_path(&mut self, path: String) {
+        if !self.gpu_object_paths.contains(&path) {
+            self.gpu_object_paths.push(path);
+        }
+    }
+
+    /// Remove the given card path
+    pub fn remove_card_path(&mut self, path: &str) {
+        self.gpu_object_paths.retain(|p| p != path);
+    }
 }
 
 #[interface(name = "org.shadowblip.GPU")]
 impl GPUBus {
     /// Returns a list of DBus paths to all GPU cards
-    pub async fn enumerate_cards(&self) -> fdo::Result<Vec<ObjectPath>> {
+    pub async fn enumerate_cards(&self) -> error::Result<Vec<ObjectPath>> {
         let mut paths: Vec<ObjectPath> = Vec::new();
 
         for item in &self.gpu_object_paths {
@@ -254,9 +716,9 @@ impl GPUBus {
 }
 
 /// Returns a list of all detected gpu devices
-pub async fn get_gpus() -> Vec<GPUDBusInterface> {
+pub async fn get_gpus(state: StateStore) -> Vec<GPUDBusInterface> {
     let mut gpus = vec![];
-    let paths = fs::read_dir(DRM_PATH).unwrap();
+    let paths = fs::read_dir(root::path(DRM_PATH)).unwrap();
     for path in paths {
         let path = path.unwrap();
         let filename = path.file_name().to_str().unwrap().to_string();
@@ -270,7 +732,7 @@ pub async fn get_gpus() -> Vec<GPUDBusInterface> {
         }
 
         log::info!("Discovered gpu: {}", file_path);
-        match get_gpu(file_path).await {
+        match get_gpu(file_path, state.clone()).await {
             Ok(gpu) => gpus.push(gpu),
             Err(err) => {
                 log::error!("Error in get_gpu: {}", err);
@@ -283,7 +745,7 @@ pub async fn get_gpus() -> Vec<GPUDBusInterface> {
 }
 
 /// Returns the GPU instance for the given path in /sys/class/drm
-pub async fn get_gpu(path: String) -> Result<GPUDBusInterface, std::io::Error> {
+pub async fn get_gpu(path: String, state: StateStore) -> Result<GPUDBusInterface, std::io::Error> {
     let filename = path.split("/").last().unwrap();
     let file_prefix = format!("{0}/{1}", path, "device");
     let class_id = fs::read_to_string(format!("{0}/{1}", file_prefix, "class"))?
@@ -365,6 +827,7 @@ pub async fn get_gpu(path: String) -> Result<GPUDBusInterface, std::io::Error> {
         if line_clean.starts_with(&device_id) {
             device = Some(line_clean.trim_start_matches(&device_id).trim().to_string());
             log::debug!("Found device name: {}", device.clone().unwrap());
+            continue;
         }
 
         if device.is_some() && !line.starts_with("\t\t") {
@@ -394,8 +857,8 @@ pub async fn get_gpu(path: String) -> Result<GPUDBusInterface, std::io::Error> {
         "AMD"
         | "AuthenticAMD"
         | "AuthenticAMD Advanced Micro Devices, Inc."
-        | "Advanced Micro Devices, Inc. [AMD/ATI]" => Ok(GPUDBusInterface::new(Arc::new(
-            Mutex::new(GPUDevices::AmdGpu(AmdGpu {
+        | "Advanced Micro Devices, Inc. [AMD/ATI]" => Ok(GPUDBusInterface::new(
+            Arc::new(Mutex::new(GPUDevices::AmdGpu(AmdGpu {
                 name: filename.to_string(),
                 path: path.clone(),
                 class: class.to_string(),
@@ -409,12 +872,13 @@ pub async fn get_gpu(path: String) -> Result<GPUDBusInterface, std::io::Error> {
                 subdevice_id,
                 subvendor_id,
                 revision_id,
-            })),
-        ))
+            }))),
+            state,
+        )
         .await),
         // Intel Implementation
-        "Intel" | "GenuineIntel" | "Intel Corporation" => Ok(GPUDBusInterface::new(Arc::new(
-            Mutex::new(GPUDevices::IntelGpu(IntelGPU {
+        "Intel" | "GenuineIntel" | "Intel Corporation" => Ok(GPUDBusInterface::new(
+            Arc::new(Mutex::new(GPUDevices::IntelGpu(IntelGPU {
                 name: filename.to_string(),
                 path: path.clone(),
                 class: class.to_string(),
@@ -429,8 +893,9 @@ pub async fn get_gpu(path: String) -> Result<GPUDBusInterface, std::io::Error> {
                 subvendor_id,
                 revision_id,
                 manual_clock: true,
-            })),
-        ))
+            }))),
+            state,
+        )
         .await),
         _ => Err(std::io::Error::new(
             std::io::ErrorKind::Unsupported,
@@ -446,7 +911,7 @@ pub fn get_connector(gpu_name: String, path: String) -> Connector {
 
     Connector {
         name: name.to_string(),
-        path: format!("{0}/{1}", DRM_PATH, path),
+        path: format!("{0}/{1}", root::path(DRM_PATH), path),
     }
 }
 
@@ -455,7 +920,7 @@ pub fn get_connector(gpu_name: String, path: String) -> Connector {
 pub fn get_connectors(gpu_name: String) -> Vec<Connector> {
     log::debug!("Discovering connectors for GPU: {}", gpu_name);
     let mut connectors: Vec<Connector> = Vec::new();
-    let paths = fs::read_dir(DRM_PATH).unwrap();
+    let paths = fs::read_dir(root::path(DRM_PATH)).unwrap();
     for path in paths {
         let path = path.unwrap();
         let filename = path.file_name().to_str().unwrap().to_string();
@@ -483,6 +948,7 @@ fn get_pci_ids_path() -> PathBuf {
     // Get the data directories in preference order
     let data_dirs = base_dirs.get_data_dirs();
     for dir in data_dirs {
+        let dir = PathBuf::from(root::path(&dir.to_string_lossy()));
         if dir.exists() {
             let mut path = dir.into_os_string();
             path.push("/pci.ids");
@@ -491,5 +957,5 @@ fn get_pci_ids_path() -> PathBuf {
     }
 
     log::warn!("Config base path not found. Using fallback path.");
-    PathBuf::from(PCI_IDS_PATH)
+    PathBuf::from(root::path(PCI_IDS_PATH))
 }
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use futures_lite::StreamExt;
+use tokio::sync::Mutex;
+use zbus::fdo::DBusProxy;
+use zbus::message::Header;
+use zbus::Connection;
+
+use crate::performance::gpu::dbus::gpu::{GPUBus, GPUDBusInterface};
+use crate::performance::state::CardState;
+use powerstation::constants::GPU_PATH;
+use powerstation::error::{self, Error};
+
+/// Exclusive control of the TDP and clock settings of a card by one client
+#[derive(Debug, Clone)]
+struct Lease {
+    /// Unique bus name of the client holding the lease
+    owner: String,
+    /// Settings of the card when the lease was acquired
+    replaced: CardState,
+}
+
+/// Lease of a card, shared by its card and TDP interfaces. While a client
+/// holds the lease, the TDP and clock settings can only be changed by that
+/// client or by PowerStation itself.
+#[derive(Debug, Clone, Default)]
+pub struct CardLease(Arc<Mutex<Option<Lease>>>);
+
+impl CardLease {
+    /// Returns the unique bus name of the client holding the lease
+    pub async fn owner(&self) -> Option<String> {
+        self.0
+            .lock()
+            .await
+            .as_ref()
+            .map(|lease| lease.owner.clone())
+    }
+
+    /// Check that the sender of the message with the given header is allowed
+    /// to change settings. Calls without a header are made by PowerStation
+    /// itself, e.g. for GameMode or the power source policy, and are always
+    /// allowed. Settings applied on behalf of a client, like profiles, need to
+    /// be checked with the header of that client.
+    pub async fn check(&self, header: Option<&Header<'_>>) -> error::Result<()> {
+        let Some(header) = header else {
+            return Ok(());
+        };
+        let lease = self.0.lock().await;
+        let Some(lease) = lease.as_ref() else {
+            return Ok(());
+        };
+        match header.sender() {
+            Some(sender) if sender.as_str() == lease.owner => Ok(()),
+            _ => Err(controlled_by(&lease.owner)),
+        }
+    }
+
+    /// Give the lease to the given client, saving the settings to restore
+    /// once it is released. Returns false if the client already holds it.
+    pub async fn acquire(&self, owner: &str, replaced: CardState) -> error::Result<bool> {
+        let mut lease = self.0.lock().await;
+        match lease.as_ref() {
+            Some(lease) if lease.owner == owner => return Ok(false),
+            Some(lease) => return Err(controlled_by(&lease.owner)),
+            None => (),
+        }
+        *lease = Some(Lease {
+            owner: owner.to_string(),
+            replaced,
+        });
+        Ok(true)
+    }
+
+    /// Release the lease if it is held by the given client. Returns the
+    /// settings that were replaced while it was held.
+    pub async fn release(&self, owner: &str) -> Option<CardState> {
+        let mut lease = self.0.lock().await;
+        if lease.as_ref().is_none_or(|lease| lease.owner != owner) {
+            return None;
+        }
+        lease.take().map(|lease| lease.replaced)
+    }
+}
+
+/// Returns the error for a card that is leased by another client
+pub fn controlled_by(owner: &str) -> Error {
+    Error::PermissionDenied(format!("Card is controlled by {owner}"))
+}
+
+/// Release all leases of clients as soon as they disconnect from the bus
+pub async fn watch_owners(connection: Connection) -> zbus::Result<()> {
+    let dbus = DBusProxy::new(&connection).await?;
+    let mut owner_changes = dbus.receive_name_owner_changed().await?;
+    tokio::task::spawn(async move {
+        while let Some(signal) = owner_changes.next().await {
+            let Ok(args) = signal.args() else {
+                continue;
+            };
+            if args.new_owner().is_some() {
+                continue;
+            }
+            let owner = args.name().as_str();
+
+            let object_server = connection.object_server();
+            let card_paths = match object_server.interface::<_, GPUBus>(GPU_PATH).await {
+                Ok(gpu_bus) => gpu_bus.get().await.card_paths(),
+                Err(_) => vec![],
+            };
+            for path in card_paths {
+                let Ok(card) = object_server
+                    .interface::<_, GPUDBusInterface>(path.as_str())
+                    .await
+                else {
+                    continue;
+                };
+                let result = card
+                    .get_mut()
+                    .await
+                    .release_lease_of(owner, card.signal_emitter())
+                    .await;
+                if let Err(e) = result {
+                    log::error!("Failed to release lease of {path}: {e}");
+                }
+            }
+        }
+    });
+    Ok(())
+}
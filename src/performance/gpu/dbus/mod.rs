@@ -1,3 +1,4 @@
 pub mod devices;
 pub mod gpu;
+pub mod lease;
 pub mod tdp;
@@ -1,31 +1,363 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use zbus::fdo;
+use zbus::message::Header;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{self, OwnedValue};
+use zbus::Connection;
 use zbus_macros::interface;
 
 use tokio::sync::Mutex;
 
+use crate::config::{ConfigStore, HardwareLimit, TdpLimit};
 use crate::performance::gpu::dbus::devices::TDPDevices;
+use crate::performance::gpu::dbus::lease::CardLease;
 use crate::performance::gpu::tdp::TDPError;
 use crate::performance::gpu::tdp::TDPResult;
+use crate::performance::gpu::tdp::TdpBackend;
+use crate::performance::monitor::PropertyMonitor;
+use crate::performance::state::{CardState, StateStore};
+use crate::polkit::{self, Action};
+use powerstation::error::{self, Error};
+
+/// Power profiles from when only RyzenAdj was supported, which are translated
+/// to the available profiles of the device.
+const LEGACY_POWER_PROFILES: [&str; 2] = ["max-performance", "power-saving"];
 
 pub struct GPUTDPDBusIface {
+    card_name: String,
     dev: Arc<Mutex<TDPDevices>>,
+    state: StateStore,
+    lease: CardLease,
+    config: ConfigStore,
 }
 
-impl From<TDPError> for fdo::Error {
+impl From<TDPError> for Error {
     fn from(val: TDPError) -> Self {
-        match &val {
-            TDPError::FailedOperation(err) => fdo::Error::Failed(err.to_string()),
-            TDPError::FeatureUnsupported => fdo::Error::Failed(String::from("Unsupported feature")),
-            TDPError::InvalidArgument(err) => fdo::Error::Failed(err.to_string()),
-            TDPError::IOError(err) => fdo::Error::IOError(err.to_string()),
+        match val {
+            TDPError::FailedOperation(err) => Error::BackendFailed(err),
+            TDPError::FeatureUnsupported => {
+                Error::NotSupported(String::from("Unsupported feature"))
+            }
+            TDPError::InvalidArgument(err) => Error::InvalidArgument(err),
+            TDPError::OutOfRange(err) => Error::OutOfRange(err),
+            TDPError::IOError(err) => Error::BackendFailed(err),
         }
     }
 }
 
+impl From<TDPError> for fdo::Error {
+    fn from(val: TDPError) -> Self {
+        Error::from(val).into()
+    }
+}
+
 impl GPUTDPDBusIface {
-    pub fn new(dev: Arc<Mutex<TDPDevices>>) -> GPUTDPDBusIface {
-        GPUTDPDBusIface { dev }
+    pub fn new(
+        card_name: String,
+        dev: Arc<Mutex<TDPDevices>>,
+        state: StateStore,
+        lease: CardLease,
+        config: ConfigStore,
+    ) -> GPUTDPDBusIface {
+        GPUTDPDBusIface {
+            card_name,
+            dev,
+            state,
+            lease,
+            config,
+        }
+    }
+
+    /// Re-apply the saved TDP settings. Values outside of the detected
+    /// hardware limits are skipped.
+    pub async fn restore(&mut self, state: &CardState, emitter: &SignalEmitter<'_>) {
+        self.apply_state(state).await;
+
+        // Notify clients about the restored values
+        let current = self.snapshot().await;
+        if let Err(e) = self
+            .emit_changed(&TDPSnapshot::default(), &current, emitter)
+            .await
+        {
+            log::warn!("Failed to emit restored TDP properties: {e}");
+        }
+    }
+
+    /// Apply the given TDP settings and save them. Unlike [Self::restore],
+    /// this stops at the first setting that fails and returns its error.
+    pub async fn apply(
+        &mut self,
+        settings: &CardState,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.apply_with(settings, true, emitter).await
+    }
+
+    /// Apply the given TDP settings without saving them, e.g. while a game is
+    /// running. Like [Self::apply], this stops at the first setting that
+    /// fails.
+    pub async fn apply_temporarily(
+        &mut self,
+        settings: &CardState,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.apply_with(settings, false, emitter).await
+    }
+
+    /// Apply the given TDP settings, saving the applied values if `save` is
+    /// set
+    async fn apply_with(
+        &mut self,
+        settings: &CardState,
+        save: bool,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let previous = self.snapshot().await;
+        let mut applied = CardState::default();
+        let result = self.apply_settings(settings, &mut applied).await;
+        if save {
+            self.state
+                .update_card(&self.card_name, |s| s.merge(&applied))
+                .await;
+        }
+
+        let current = self.snapshot().await;
+        if let Err(e) = self.emit_changed(&previous, &current, emitter).await {
+            log::warn!("Failed to emit applied TDP properties: {e}");
+        }
+        result
+    }
+
+    /// Check that the sender of the message with the given header is allowed
+    /// to change the settings of this card while it is leased
+    pub async fn check_lease(&self, header: Option<&Header<'_>>) -> error::Result<()> {
+        self.lease.check(header).await
+    }
+
+    /// Returns the current TDP settings of the device. Values that cannot be
+    /// read are unset.
+    pub async fn current(&self) -> CardState {
+        let dev = self.dev.lock().await;
+        CardState {
+            tdp: dev.tdp().await.ok(),
+            boost: dev.boost().await.ok(),
+            thermal_throttle_limit_c: dev.thermal_throttle_limit_c().await.ok(),
+            power_profile: dev.power_profile().await.ok(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the TDP in watts for the given limit
+    pub async fn resolve_tdp(&self, limit: TdpLimit) -> fdo::Result<f64> {
+        let dev = self.dev.lock().await;
+        let tdp = match limit {
+            TdpLimit::Watts(watts) => watts,
+            TdpLimit::Hardware(HardwareLimit::Min) => dev.min_tdp().await?,
+            TdpLimit::Hardware(HardwareLimit::Max) => dev.max_tdp().await?,
+        };
+        Ok(tdp)
+    }
+
+    /// Apply the given TDP settings to the device, recording every value that
+    /// was set successfully in `applied`. The power profile is set first and
+    /// the TDP and boost last, since changing the platform profile of some
+    /// devices (e.g. asus-wmi) resets their power limits.
+    async fn apply_settings(
+        &self,
+        settings: &CardState,
+        applied: &mut CardState,
+    ) -> fdo::Result<()> {
+        let mut dev = self.dev.lock().await;
+        if let Some(profile) = settings.power_profile.clone() {
+            dev.set_power_profile(profile.clone()).await?;
+            applied.power_profile = Some(profile);
+        }
+        if let Some(limit) = settings.thermal_throttle_limit_c {
+            dev.set_thermal_throttle_limit_c(limit).await?;
+            applied.thermal_throttle_limit_c = Some(limit);
+        }
+        let tdp = match settings.tdp {
+            Some(tdp) => Some(self.limit_tdp(&dev, tdp).await?),
+            None => None,
+        };
+        let boost = match settings.boost {
+            Some(boost) => Some(self.limit_boost(&dev, boost).await?),
+            None => None,
+        };
+        match (tdp, boost) {
+            (Some(tdp), Some(boost)) => {
+                dev.set_tdp_and_boost(tdp, boost).await?;
+                applied.tdp = Some(tdp);
+                applied.boost = Some(boost);
+            }
+            (Some(tdp), None) => {
+                dev.set_tdp(tdp).await?;
+                applied.tdp = Some(tdp);
+            }
+            (None, Some(boost)) => {
+                dev.set_boost(boost).await?;
+                applied.boost = Some(boost);
+            }
+            (None, None) => (),
+        }
+        Ok(())
+    }
+
+    /// Write the given values to the device as they are, without applying the
+    /// limit policy, e.g. to roll back to values read from the device that
+    /// are outside of the current limits. Like [Self::apply_settings], the TDP
+    /// and boost are written last. All values are written even if one fails,
+    /// and the first error is returned.
+    async fn write_raw(&self, values: &CardState) -> fdo::Result<()> {
+        let mut dev = self.dev.lock().await;
+        let mut result = Ok(());
+        if let Some(profile) = values.power_profile.clone() {
+            let profile_result = dev.set_power_profile(profile).await;
+            result = result.and(profile_result.map_err(fdo::Error::from));
+        }
+        if let Some(limit) = values.thermal_throttle_limit_c {
+            let limit_result = dev.set_thermal_throttle_limit_c(limit).await;
+            result = result.and(limit_result.map_err(fdo::Error::from));
+        }
+        let tdp_result = match (values.tdp, values.boost) {
+            (Some(tdp), Some(boost)) => dev.set_tdp_and_boost(tdp, boost).await,
+            (Some(tdp), None) => dev.set_tdp(tdp).await,
+            (None, Some(boost)) => dev.set_boost(boost).await,
+            (None, None) => Ok(()),
+        };
+        result.and(tdp_result.map_err(fdo::Error::from))
+    }
+
+    /// Write back the given values that were replaced by [Self::apply] and
+    /// save them, e.g. when a profile fails to apply to another device. Like
+    /// rolling back "SetLimits", the values are written as they were read
+    /// from the device.
+    pub async fn roll_back(
+        &mut self,
+        replaced: &CardState,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let previous = self.snapshot().await;
+        let result = self.write_raw(replaced).await;
+        self.state
+            .update_card(&self.card_name, |s| s.merge(replaced))
+            .await;
+
+        let current = self.snapshot().await;
+        if let Err(e) = self.emit_changed(&previous, &current, emitter).await {
+            log::warn!("Failed to emit restored TDP properties: {e}");
+        }
+        result
+    }
+
+    /// Returns the TDP to set for the given value according to the limit
+    /// policy
+    async fn limit_tdp(&self, dev: &TDPDevices, tdp: f64) -> TDPResult<f64> {
+        let min_tdp = dev.min_tdp().await.ok();
+        let max_tdp = dev.max_tdp().await.ok();
+        let limit_policy = self.config.get().await.tdp.limit_policy;
+        let limited = limit_policy.enforce(tdp, min_tdp, max_tdp);
+        let Some(limited) = limited else {
+            return Err(TDPError::OutOfRange(format!(
+                "TDP {tdp} is outside of the hardware limits of {}-{}W",
+                min_tdp.unwrap_or_default(),
+                max_tdp.unwrap_or_default()
+            )));
+        };
+        if limited != tdp {
+            log::info!("Limiting TDP {tdp} to {limited}");
+        }
+        Ok(limited)
+    }
+
+    /// Returns the boost to set for the given value according to the limit
+    /// policy
+    async fn limit_boost(&self, dev: &TDPDevices, boost: f64) -> TDPResult<f64> {
+        let max_boost = dev.max_boost().await.ok();
+        let limit_policy = self.config.get().await.tdp.limit_policy;
+        let limited = limit_policy.enforce(boost, None, max_boost);
+        let Some(limited) = limited else {
+            return Err(TDPError::OutOfRange(format!(
+                "Boost {boost} is above the hardware limit of {}W",
+                max_boost.unwrap_or_default()
+            )));
+        };
+        if limited != boost {
+            log::info!("Limiting boost {boost} to {limited}");
+        }
+        Ok(limited)
+    }
+
+    /// Check the given TDP settings against the hardware limits and the
+    /// features supported by the device, before any of them is applied.
+    pub async fn validate(&self, settings: &CardState) -> fdo::Result<()> {
+        let dev = self.dev.lock().await;
+        if let Some(tdp) = settings.tdp {
+            if tdp < 1.0 {
+                return Err(fdo::Error::InvalidArgs(format!("Invalid TDP {tdp}")));
+            }
+            self.limit_tdp(&dev, tdp).await?;
+        }
+        if let Some(boost) = settings.boost {
+            if boost < 0.0 {
+                return Err(fdo::Error::InvalidArgs(format!("Invalid boost {boost}")));
+            }
+            self.limit_boost(&dev, boost).await?;
+        }
+        if let Some(limit) = settings.thermal_throttle_limit_c {
+            dev.thermal_throttle_limit_c().await?;
+            if limit <= 0.0 {
+                return Err(fdo::Error::InvalidArgs(format!(
+                    "Invalid thermal throttle limit {limit}"
+                )));
+            }
+        }
+        if let Some(profile) = settings.power_profile.as_ref() {
+            let available = dev.power_profiles_available().await?;
+            if !available.contains(profile) && !LEGACY_POWER_PROFILES.contains(&profile.as_str()) {
+                return Err(fdo::Error::InvalidArgs(format!(
+                    "Unknown power profile {profile}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the given saved TDP settings to the device, in the same order as
+    /// [Self::apply_settings]
+    async fn apply_state(&self, state: &CardState) {
+        let mut dev = self.dev.lock().await;
+        if let Some(profile) = state.power_profile.clone() {
+            if let Err(e) = dev.set_power_profile(profile).await {
+                log::warn!("Failed to restore power profile: {e:?}");
+            }
+        }
+        if let Some(limit) = state.thermal_throttle_limit_c {
+            if let Err(e) = dev.set_thermal_throttle_limit_c(limit).await {
+                log::warn!("Failed to restore thermal throttle limit: {e:?}");
+            }
+        }
+        if let Some(tdp) = state.tdp {
+            match self.limit_tdp(&dev, tdp).await {
+                Ok(tdp) => {
+                    if let Err(e) = dev.set_tdp(tdp).await {
+                        log::warn!("Failed to restore TDP: {e:?}");
+                    }
+                }
+                Err(e) => log::warn!("Skipping saved TDP: {e:?}"),
+            }
+        }
+        if let Some(boost) = state.boost {
+            match self.limit_boost(&dev, boost).await {
+                Ok(boost) => {
+                    if let Err(e) = dev.set_boost(boost).await {
+                        log::warn!("Failed to restore boost: {e:?}");
+                    }
+                }
+                Err(e) => log::warn!("Skipping saved boost: {e:?}"),
+            }
+        }
     }
 }
 
@@ -33,7 +365,7 @@ impl GPUTDPDBusIface {
 impl GPUTDPDBusIface {
     /// Get the currently set TDP value
     #[zbus(property, name = "TDP")]
-    async fn tdp(&self) -> fdo::Result<f64> {
+    pub async fn tdp(&self) -> fdo::Result<f64> {
         match self.dev.lock().await.tdp().await {
             TDPResult::Ok(result) => Ok(result),
             TDPResult::Err(err) => Err(err.into()),
@@ -42,13 +374,90 @@ impl GPUTDPDBusIface {
 
     /// Sets the given TDP value
     #[zbus(property, name = "TDP")]
-    async fn set_tdp(&mut self, value: f64) -> fdo::Result<()> {
-        match self.dev.lock().await.set_tdp(value).await {
-            TDPResult::Ok(result) => Ok(result),
+    async fn set_tdp(
+        &mut self,
+        value: f64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetTdp).await?;
+        self.lease.check(header.as_ref()).await?;
+        let mut dev = self.dev.lock().await;
+        let value = self.limit_tdp(&dev, value).await?;
+        let result = dev.set_tdp(value).await;
+        drop(dev);
+        match result {
+            TDPResult::Ok(result) => {
+                self.state
+                    .update_card(&self.card_name, |s| s.tdp = Some(value))
+                    .await;
+                // Some implementations adjust the boost along with the TDP
+                if let Err(e) = self.boost_changed(&emitter).await {
+                    log::debug!("Failed to emit boost change: {e}");
+                }
+                Ok(result)
+            }
             TDPResult::Err(err) => Err(err.into()),
         }
     }
 
+    /// Set the TDP, boost, thermal throttle limit and power profile in one
+    /// call. Each is optional and given by its property name, e.g.
+    /// {"TDP": 15.0, "Boost": 5.0}. All values are checked against the
+    /// hardware limits before any of them is applied. The power profile is
+    /// applied first, then the thermal throttle limit and then the TDP and
+    /// boost, since changing the power profile may reset the power limits. If
+    /// one fails to apply, the previous values are restored as they were read
+    /// from the device. The error also reports if restoring them fails.
+    async fn set_limits(
+        &mut self,
+        limits: HashMap<String, OwnedValue>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> error::Result<()> {
+        polkit::check(connection, Some(&header), Action::SetTdp).await?;
+        self.lease.check(Some(&header)).await?;
+        let settings = parse_limits(limits)?;
+        self.validate(&settings).await?;
+
+        let previous = self.snapshot().await;
+        let replaced = self.current().await.only(&settings);
+        let result = self
+            .apply_settings(&settings, &mut CardState::default())
+            .await;
+        let result = match result {
+            Ok(()) => {
+                self.state
+                    .update_card(&self.card_name, |s| s.merge(&settings))
+                    .await;
+                Ok(())
+            }
+            Err(e) => {
+                log::warn!("Failed to set limits of {}: {e}", self.card_name);
+                match self.write_raw(&replaced).await {
+                    Ok(()) => Err(e),
+                    Err(rollback) => {
+                        log::error!(
+                            "Failed to roll back limits of {}: {rollback}",
+                            self.card_name
+                        );
+                        Err(fdo::Error::IOError(format!(
+                            "{e}. Rolling back the previous limits failed as well: {rollback}"
+                        )))
+                    }
+                }
+            }
+        };
+
+        let current = self.snapshot().await;
+        if let Err(e) = self.emit_changed(&previous, &current, &emitter).await {
+            log::warn!("Failed to emit changed TDP properties: {e}");
+        }
+        result.map_err(Error::from)
+    }
+
     #[zbus(property)]
     async fn min_tdp(&self) -> fdo::Result<f64> {
         match self.dev.lock().await.min_tdp().await {
@@ -84,9 +493,25 @@ impl GPUTDPDBusIface {
     }
 
     #[zbus(property)]
-    async fn set_boost(&mut self, value: f64) -> fdo::Result<()> {
-        match self.dev.lock().await.set_boost(value).await {
-            TDPResult::Ok(result) => Ok(result),
+    async fn set_boost(
+        &mut self,
+        value: f64,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetTdp).await?;
+        self.lease.check(header.as_ref()).await?;
+        let mut dev = self.dev.lock().await;
+        let value = self.limit_boost(&dev, value).await?;
+        let result = dev.set_boost(value).await;
+        drop(dev);
+        match result {
+            TDPResult::Ok(result) => {
+                self.state
+                    .update_card(&self.card_name, |s| s.boost = Some(value))
+                    .await;
+                Ok(result)
+            }
             TDPResult::Err(err) => Err(err.into()),
         }
     }
@@ -100,7 +525,14 @@ impl GPUTDPDBusIface {
     }
 
     #[zbus(property)]
-    async fn set_thermal_throttle_limit_c(&mut self, limit: f64) -> fdo::Result<()> {
+    async fn set_thermal_throttle_limit_c(
+        &mut self,
+        limit: f64,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetTdp).await?;
+        self.lease.check(header.as_ref()).await?;
         match self
             .dev
             .lock()
@@ -108,7 +540,14 @@ impl GPUTDPDBusIface {
             .set_thermal_throttle_limit_c(limit)
             .await
         {
-            TDPResult::Ok(result) => Ok(result),
+            TDPResult::Ok(result) => {
+                self.state
+                    .update_card(&self.card_name, |s| {
+                        s.thermal_throttle_limit_c = Some(limit)
+                    })
+                    .await;
+                Ok(result)
+            }
             TDPResult::Err(err) => Err(err.into()),
         }
     }
@@ -122,18 +561,130 @@ impl GPUTDPDBusIface {
     }
 
     #[zbus(property)]
-    async fn set_power_profile(&mut self, profile: String) -> fdo::Result<()> {
-        match self.dev.lock().await.set_power_profile(profile).await {
-            TDPResult::Ok(result) => Ok(result),
+    async fn set_power_profile(
+        &mut self,
+        profile: String,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(connection, header.as_ref(), Action::SetTdp).await?;
+        self.lease.check(header.as_ref()).await?;
+        match self
+            .dev
+            .lock()
+            .await
+            .set_power_profile(profile.clone())
+            .await
+        {
+            TDPResult::Ok(result) => {
+                self.state
+                    .update_card(&self.card_name, |s| s.power_profile = Some(profile))
+                    .await;
+                Ok(result)
+            }
             TDPResult::Err(err) => Err(err.into()),
         }
     }
 
     #[zbus(property)]
-    async fn power_profiles_available(&self) -> fdo::Result<Vec<String>> {
+    pub async fn power_profiles_available(&self) -> fdo::Result<Vec<String>> {
         match self.dev.lock().await.power_profiles_available().await {
             TDPResult::Ok(result) => Ok(result),
             TDPResult::Err(err) => Err(err.into()),
         }
     }
+
+    /// Detected TDP backends in the order they are tried, e.g. ["hwmon",
+    /// "ryzenadj"]. Backends disabled in the configuration are not listed.
+    #[zbus(property)]
+    async fn tdp_backends(&self) -> Vec<String> {
+        let dev = self.dev.lock().await;
+        dev.backends().iter().map(ToString::to_string).collect()
+    }
+
+    /// TDP backend that served the last successful read or write, or an
+    /// empty string if none has yet
+    #[zbus(property)]
+    async fn active_tdp_backend(&self) -> String {
+        let dev = self.dev.lock().await;
+        dev.active_backend()
+            .map(|backend| backend.to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Returns the settings given by property name to [GPUTDPDBusIface::set_limits]
+fn parse_limits(limits: HashMap<String, OwnedValue>) -> fdo::Result<CardState> {
+    let mut settings = CardState::default();
+    for (name, value) in limits {
+        let invalid = |e: zvariant::Error| fdo::Error::InvalidArgs(format!("Invalid {name}: {e}"));
+        match name.as_str() {
+            "TDP" => settings.tdp = Some(f64::try_from(value).map_err(invalid)?),
+            "Boost" => settings.boost = Some(f64::try_from(value).map_err(invalid)?),
+            "ThermalThrottleLimitC" => {
+                settings.thermal_throttle_limit_c = Some(f64::try_from(value).map_err(invalid)?)
+            }
+            "PowerProfile" => {
+                settings.power_profile = Some(String::try_from(value).map_err(invalid)?)
+            }
+            _ => {
+                return Err(fdo::Error::InvalidArgs(format!(
+                    "Unknown TDP setting {name}"
+                )))
+            }
+        }
+    }
+    Ok(settings)
+}
+
+/// Monitored property values of the [GPUTDPDBusIface]
+#[derive(Debug, Default, PartialEq)]
+pub struct TDPSnapshot {
+    tdp: Option<f64>,
+    boost: Option<f64>,
+    thermal_throttle_limit_c: Option<f64>,
+    power_profile: Option<String>,
+    active_backend: Option<TdpBackend>,
+}
+
+impl PropertyMonitor for GPUTDPDBusIface {
+    type Snapshot = TDPSnapshot;
+
+    async fn snapshot(&self) -> TDPSnapshot {
+        let dev = self.dev.lock().await;
+        TDPSnapshot {
+            tdp: dev.tdp().await.ok(),
+            boost: dev.boost().await.ok(),
+            thermal_throttle_limit_c: dev.thermal_throttle_limit_c().await.ok(),
+            power_profile: dev.power_profile().await.ok(),
+            active_backend: dev.active_backend(),
+        }
+    }
+
+    async fn emit_changed(
+        &self,
+        previous: &TDPSnapshot,
+        current: &TDPSnapshot,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()> {
+        // The generated name follows the "TDP" DBus property name
+        if current.tdp.is_some() && previous.tdp != current.tdp {
+            self.t_d_p_changed(emitter).await?;
+        }
+        if current.boost.is_some() && previous.boost != current.boost {
+            self.boost_changed(emitter).await?;
+        }
+        if current.thermal_throttle_limit_c.is_some()
+            && previous.thermal_throttle_limit_c != current.thermal_throttle_limit_c
+        {
+            self.thermal_throttle_limit_c_changed(emitter).await?;
+        }
+        if current.power_profile.is_some() && previous.power_profile != current.power_profile {
+            self.power_profile_changed(emitter).await?;
+        }
+        if current.active_backend.is_some() && previous.active_backend != current.active_backend {
+            self.active_tdp_backend_changed(emitter).await?;
+        }
+        Ok(())
+    }
 }
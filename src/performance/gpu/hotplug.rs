@@ -0,0 +1,344 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tokio::io::unix::AsyncFd;
+use zbus::Connection;
+
+use crate::config::ConfigStore;
+use crate::performance::gpu::connector::Connector;
+use crate::performance::gpu::dbus::gpu::{
+    get_connector, get_connectors, get_gpu, GPUBus, GPUDBusInterface, DRM_PATH,
+};
+use crate::performance::gpu::dbus::tdp::GPUTDPDBusIface;
+use crate::performance::state::{self, StateStore};
+use crate::root;
+use powerstation::constants::GPU_PATH;
+
+/// How often the DRM directory is checked for added and removed devices when
+/// running against an alternate filesystem root
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A DRM device that was added or removed
+#[derive(Debug)]
+struct DrmEvent {
+    /// The udev action, e.g. "add" or "remove"
+    action: String,
+    syspath: PathBuf,
+}
+
+/// Serve the given GPU card, its TDP interface and all of its connectors on
+/// DBus and add it to the list of cards returned by [GPUBus]. The TDP
+/// interface is created with the current TDP configuration.
+pub async fn serve_gpu(
+    connection: &Connection,
+    mut card: GPUDBusInterface,
+    config: &ConfigStore,
+) -> zbus::Result<()> {
+    let object_server = connection.object_server();
+
+    // Build the DBus object path for this card
+    let gpu_name = card.name().await;
+    let gpu_path = card.gpu_path().await;
+
+    // Get the TDP interface from the card and serve it on DBus
+    match card.get_tdp_interface(config).await {
+        Some(tdp) => {
+            log::debug!("Discovered TDP interface on card: {}", gpu_name);
+            object_server.at(gpu_path.clone(), tdp).await?;
+        }
+        None => {
+            log::warn!("Card {} does not have a TDP interface", gpu_name);
+        }
+    }
+
+    // Get GPU connectors from the card and serve them on DBus
+    let mut connector_paths: Vec<String> = Vec::new();
+    let connectors = get_connectors(gpu_name.clone());
+    for connector in connectors {
+        let port_path = connector_path(&gpu_path, &connector);
+        connector_paths.push(port_path.clone());
+        log::debug!("Discovered connector on {}: {}", gpu_name, port_path);
+        object_server.at(port_path, connector).await?;
+    }
+    card.set_connector_paths(connector_paths).await;
+
+    // Serve the GPU interface on DBus
+    object_server.at(gpu_path.clone(), card).await?;
+
+    // Make the card discoverable through the GPU Bus
+    if let Ok(gpu_bus) = object_server.interface::<_, GPUBus>(GPU_PATH).await {
+        gpu_bus.get_mut().await.add_card_path(gpu_path);
+    }
+
+    Ok(())
+}
+
+/// Stop serving the GPU card at the given DBus path along with its TDP
+/// interface and connectors.
+pub async fn remove_gpu(connection: &Connection, gpu_path: &str) -> zbus::Result<()> {
+    let object_server = connection.object_server();
+
+    // Remove the card from the GPU Bus first so it is no longer enumerated
+    if let Ok(gpu_bus) = object_server.interface::<_, GPUBus>(GPU_PATH).await {
+        gpu_bus.get_mut().await.remove_card_path(gpu_path);
+    }
+
+    let Ok(card) = object_server
+        .interface::<_, GPUDBusInterface>(gpu_path)
+        .await
+    else {
+        // The card was never served, e.g. because of an unsupported vendor
+        return Ok(());
+    };
+    let connector_paths = card.get().await.connector_paths();
+    for path in connector_paths {
+        object_server.remove::<Connector, _>(path).await?;
+    }
+    if object_server
+        .interface::<_, GPUTDPDBusIface>(gpu_path)
+        .await
+        .is_ok()
+    {
+        object_server.remove::<GPUTDPDBusIface, _>(gpu_path).await?;
+    }
+    object_server
+        .remove::<GPUDBusInterface, _>(gpu_path)
+        .await?;
+
+    Ok(())
+}
+
+/// Watch the udev "drm" subsystem and add or remove GPU cards and connectors
+/// as they appear and disappear, e.g. when an eGPU or dock is connected or a
+/// driver is reloaded. Udev only reports real devices, so the DRM directory is
+/// polled instead when running against an alternate filesystem root.
+pub fn watch_drm(
+    connection: Connection,
+    state: StateStore,
+    config: ConfigStore,
+) -> std::io::Result<()> {
+    if root::is_overridden() {
+        poll_drm(connection, state, config);
+        return Ok(());
+    }
+
+    let socket = udev::MonitorBuilder::new()?
+        .match_subsystem("drm")?
+        .listen()?;
+    let socket = AsyncFd::new(socket)?;
+
+    tokio::task::spawn(async move {
+        loop {
+            let mut guard = match socket.readable().await {
+                Ok(guard) => guard,
+                Err(e) => {
+                    log::error!("Failed to read udev events: {e}");
+                    break;
+                }
+            };
+
+            // Collect the events first since the socket iterator cannot be
+            // held across await points.
+            let events: Vec<DrmEvent> = guard
+                .get_inner()
+                .iter()
+                .map(|event| DrmEvent {
+                    action: event
+                        .action()
+                        .map(|action| action.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    syspath: event.syspath().to_path_buf(),
+                })
+                .collect();
+            guard.clear_ready();
+
+            for event in events {
+                if let Err(e) =
+                    handle_event(&connection, &state, &config, &event.action, &event.syspath).await
+                {
+                    log::error!("Failed to handle udev event {event:?}: {e}");
+                }
+            }
+        }
+        log::warn!("Stopped watching for GPU hotplug events");
+    });
+
+    Ok(())
+}
+
+/// Periodically compare the entries of the DRM directory and handle every
+/// added or removed entry like a udev event
+fn poll_drm(connection: Connection, state: StateStore, config: ConfigStore) {
+    let drm_path = PathBuf::from(root::path(DRM_PATH));
+    tokio::task::spawn(async move {
+        let mut known = read_drm_entries(&drm_path);
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let current = read_drm_entries(&drm_path);
+
+            // Connectors are removed before their cards and cards are added
+            // before their connectors
+            let mut removed: Vec<&PathBuf> = known.difference(&current).collect();
+            removed.sort_by(|a, b| b.cmp(a));
+            let mut added: Vec<&PathBuf> = current.difference(&known).collect();
+            added.sort();
+            let events = removed
+                .into_iter()
+                .map(|syspath| ("remove", syspath))
+                .chain(added.into_iter().map(|syspath| ("add", syspath)))
+                .map(|(action, syspath)| DrmEvent {
+                    action: action.to_string(),
+                    syspath: syspath.clone(),
+                });
+
+            for event in events {
+                if let Err(e) =
+                    handle_event(&connection, &state, &config, &event.action, &event.syspath).await
+                {
+                    log::error!("Failed to handle DRM change {event:?}: {e}");
+                }
+            }
+            known = current;
+        }
+    });
+}
+
+/// Returns the paths of all entries in the given DRM directory
+fn read_drm_entries(path: &Path) -> HashSet<PathBuf> {
+    match fs::read_dir(path) {
+        Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+        Err(e) => {
+            log::warn!("Failed to read {path:?}: {e}");
+            HashSet::new()
+        }
+    }
+}
+
+/// Add or remove the DBus objects for the DRM device at the given sysfs path
+/// for the given udev action, e.g. "add" or "remove".
+async fn handle_event(
+    connection: &Connection,
+    state: &StateStore,
+    config: &ConfigStore,
+    action: &str,
+    syspath: &Path,
+) -> zbus::Result<()> {
+    // Only "cardX" and "cardX-YYYY" devices are served
+    let Some(sysname) = syspath.file_name().map(|name| name.to_string_lossy()) else {
+        return Ok(());
+    };
+    if !sysname.starts_with("card") {
+        return Ok(());
+    }
+    let (gpu_name, connector_name) = match sysname.split_once('-') {
+        Some((gpu_name, connector_name)) => (gpu_name, Some(connector_name)),
+        None => (sysname.as_ref(), None),
+    };
+    let gpu_path = format!("{0}/{1}", GPU_PATH, gpu_name);
+
+    match (action, connector_name) {
+        ("add", None) => add_gpu(connection, state, config, gpu_name, &gpu_path).await,
+        ("remove", None) => {
+            log::info!("GPU removed: {}", gpu_name);
+            remove_gpu(connection, &gpu_path).await
+        }
+        ("add", Some(_)) => add_connector(connection, gpu_name, &gpu_path, &sysname).await,
+        ("remove", Some(name)) => remove_connector(connection, &gpu_path, name).await,
+        _ => Ok(()),
+    }
+}
+
+/// Serve a newly added GPU card and re-apply its saved settings
+async fn add_gpu(
+    connection: &Connection,
+    state: &StateStore,
+    config: &ConfigStore,
+    gpu_name: &str,
+    gpu_path: &str,
+) -> zbus::Result<()> {
+    let object_server = connection.object_server();
+    if object_server
+        .interface::<_, GPUDBusInterface>(gpu_path)
+        .await
+        .is_ok()
+    {
+        log::debug!("GPU {} is already being served", gpu_name);
+        return Ok(());
+    }
+
+    let file_path = format!("{0}/{1}", root::path(DRM_PATH), gpu_name);
+    log::info!("GPU added: {}", file_path);
+    let card = match get_gpu(file_path, state.clone()).await {
+        Ok(card) => card,
+        Err(e) => {
+            log::warn!("Ignoring GPU {}: {}", gpu_name, e);
+            return Ok(());
+        }
+    };
+    serve_gpu(connection, card, config).await?;
+    state::restore_card(connection, state, gpu_path).await
+}
+
+/// Serve a newly added connector and add it to its card
+async fn add_connector(
+    connection: &Connection,
+    gpu_name: &str,
+    gpu_path: &str,
+    sysname: &str,
+) -> zbus::Result<()> {
+    let object_server = connection.object_server();
+    let Ok(card) = object_server
+        .interface::<_, GPUDBusInterface>(gpu_path)
+        .await
+    else {
+        // The connector will be discovered once the card is added
+        return Ok(());
+    };
+
+    let connector = get_connector(gpu_name.to_string(), sysname.to_string());
+    let port_path = connector_path(gpu_path, &connector);
+    log::info!("Connector added on {}: {}", gpu_name, port_path);
+    object_server.at(port_path.clone(), connector).await?;
+    card.get_mut().await.add_connector_path(port_path);
+
+    Ok(())
+}
+
+/// Stop serving a removed connector and remove it from its card
+async fn remove_connector(
+    connection: &Connection,
+    gpu_path: &str,
+    connector_name: &str,
+) -> zbus::Result<()> {
+    let object_server = connection.object_server();
+    let port_path = format!("{0}/{1}", gpu_path, connector_name.replace('-', "/"));
+    log::info!("Connector removed: {}", port_path);
+    let Ok(card) = object_server
+        .interface::<_, GPUDBusInterface>(gpu_path)
+        .await
+    else {
+        // The connector was removed along with its card
+        return Ok(());
+    };
+    card.get_mut().await.remove_connector_path(&port_path);
+    if object_server
+        .interface::<_, Connector>(port_path.as_str())
+        .await
+        .is_ok()
+    {
+        object_server.remove::<Connector, _>(port_path).await?;
+    }
+
+    Ok(())
+}
+
+/// Returns the DBus path of the given connector on the given card
+fn connector_path(gpu_path: &str, connector: &Connector) -> String {
+    let name = connector.name.clone().replace('-', "/");
+    format!("{0}/{1}", gpu_path, name)
+}
@@ -6,11 +6,12 @@ use std::{
 
 use tokio::sync::Mutex;
 
-use crate::constants::PREFIX;
+use crate::config::TdpConfig;
 use crate::performance::gpu::{
     dbus::devices::TDPDevices,
     interface::{GPUDevice, GPUError, GPUResult},
 };
+use powerstation::constants::PREFIX;
 
 use super::tdp::Tdp;
 
@@ -38,7 +39,7 @@ impl GPUDevice for IntelGPU {
     }
 
     /// Returns the TDP DBus interface for this GPU
-    async fn get_tdp_interface(&self) -> Option<Arc<Mutex<TDPDevices>>> {
+    async fn get_tdp_interface(&self, _config: &TdpConfig) -> Option<Arc<Mutex<TDPDevices>>> {
         match self.class.as_str() {
             "integrated" => Some(Arc::new(Mutex::new(TDPDevices::Intel(Tdp::new(
                 self.path.clone(),
@@ -139,7 +140,7 @@ impl GPUDevice for IntelGPU {
 
         // Open the sysfs file to write to
         let path = format!("{0}/{1}", self.path().await, "gt_min_freq_mhz");
-        let file = OpenOptions::new().write(true).open(path);
+        let file = OpenOptions::new().write(true).truncate(true).open(path);
 
         // Write the value
         file.map_err(|err| GPUError::FailedOperation(err.to_string()))?
@@ -170,7 +171,7 @@ impl GPUDevice for IntelGPU {
 
         // Open the sysfs file to write to
         let path = format!("{0}/{1}", self.path().await, "gt_max_freq_mhz");
-        let file = OpenOptions::new().write(true).open(path);
+        let file = OpenOptions::new().write(true).truncate(true).open(path);
 
         // Write the value
         file.map_err(|err| GPUError::FailedOperation(err.to_string()))?
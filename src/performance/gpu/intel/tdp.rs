@@ -3,8 +3,15 @@ use std::io::Write;
 
 use crate::performance::gpu::{
     platform::hardware::Hardware,
-    tdp::{HardwareAccess, TDPDevice, TDPError, TDPResult},
+    tdp::{HardwareAccess, TDPDevice, TDPError, TDPResult, TdpBackend},
 };
+use crate::root;
+
+// Paths to the long and short term RAPL power limits
+const LONG_TDP_PATH: &str =
+    "/sys/class/powercap/intel-rapl/intel-rapl:0/constraint_0_power_limit_uw";
+const SHORT_TDP_PATH: &str =
+    "/sys/class/powercap/intel-rapl/intel-rapl:0/constraint_1_power_limit_uw";
 
 /// Implementation of TDP control for Intel GPUs
 pub struct Tdp {
@@ -34,7 +41,7 @@ impl Tdp {
 
 impl TDPDevice for Tdp {
     async fn tdp(&self) -> TDPResult<f64> {
-        let path = "/sys/class/powercap/intel-rapl/intel-rapl:0/constraint_0_power_limit_uw";
+        let path = root::path(LONG_TDP_PATH);
         let result = fs::read_to_string(path);
         let content = result.map_err(|err| TDPError::IOError(err.to_string()))?;
         let content = content.trim();
@@ -67,8 +74,8 @@ impl TDPDevice for Tdp {
         }
 
         // Open the sysfs file to write to
-        let path = "/sys/class/powercap/intel-rapl/intel-rapl:0/constraint_0_power_limit_uw";
-        let file = OpenOptions::new().write(true).open(path);
+        let path = root::path(LONG_TDP_PATH);
+        let file = OpenOptions::new().write(true).truncate(true).open(path);
 
         // Convert the value to a writable string
         let value = format!("{}", value * 1000000.0);
@@ -83,7 +90,7 @@ impl TDPDevice for Tdp {
     }
 
     async fn boost(&self) -> TDPResult<f64> {
-        let path = "/sys/class/powercap/intel-rapl/intel-rapl:0/constraint_1_power_limit_uw";
+        let path = root::path(SHORT_TDP_PATH);
         let result = fs::read_to_string(path);
         let content = result.map_err(|err| TDPError::IOError(err.to_string()))?;
         let content = content.trim();
@@ -118,8 +125,8 @@ impl TDPDevice for Tdp {
         };
 
         // Write the short tdp
-        let path = "/sys/class/powercap/intel-rapl/intel-rapl:0/constraint_1_power_limit_uw";
-        let file = OpenOptions::new().write(true).open(path);
+        let path = root::path(SHORT_TDP_PATH);
+        let file = OpenOptions::new().write(true).truncate(true).open(path);
         let value = format!("{}", short_tdp);
         file.map_err(|err| TDPError::FailedOperation(err.to_string()))?
             .write_all(value.as_bytes())
@@ -150,4 +157,13 @@ impl TDPDevice for Tdp {
         log::error!("Power profiles not supported on intel gpu");
         Err(TDPError::FeatureUnsupported)
     }
+
+    fn backends(&self) -> Vec<TdpBackend> {
+        vec![TdpBackend::IntelRapl]
+    }
+
+    // RAPL is the only TDP interface used for Intel GPUs
+    fn active_backend(&self) -> Option<TdpBackend> {
+        Some(TdpBackend::IntelRapl)
+    }
 }
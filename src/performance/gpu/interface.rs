@@ -1,10 +1,12 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{fmt, path::PathBuf, sync::Arc};
 
 use tokio::fs;
 use tokio::sync::Mutex;
 
+use crate::config::TdpConfig;
 use crate::performance::gpu::dbus::devices::TDPDevices;
 
+#[derive(Debug)]
 pub enum GPUError {
     //FeatureUnsupported,
     FailedOperation(String),
@@ -12,9 +14,19 @@ pub enum GPUError {
     IOError(String),
 }
 
+impl fmt::Display for GPUError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GPUError::FailedOperation(err) => write!(f, "{err}"),
+            GPUError::InvalidArgument(err) => write!(f, "Invalid argument: {err}"),
+            GPUError::IOError(err) => write!(f, "I/O error: {err}"),
+        }
+    }
+}
+
 impl From<GPUError> for String {
-    fn from(_val: GPUError) -> Self {
-        todo!()
+    fn from(val: GPUError) -> Self {
+        val.to_string()
     }
 }
 
@@ -22,7 +34,7 @@ pub type GPUResult<T> = Result<T, GPUError>;
 
 /// Represents the data contained in /sys/class/drm/cardX
 pub trait GPUDevice: Send + Sync {
-    async fn get_tdp_interface(&self) -> Option<Arc<Mutex<TDPDevices>>>;
+    async fn get_tdp_interface(&self, config: &TdpConfig) -> Option<Arc<Mutex<TDPDevices>>>;
     async fn get_gpu_path(&self) -> String;
     async fn name(&self) -> String;
     async fn path(&self) -> String;
@@ -44,6 +56,21 @@ pub trait GPUDevice: Send + Sync {
     async fn set_clock_value_mhz_max(&mut self, value: f64) -> GPUResult<()>;
     async fn manual_clock(&self) -> GPUResult<bool>;
     async fn set_manual_clock(&mut self, enabled: bool) -> GPUResult<()>;
+    async fn power_profile_mode(&self) -> GPUResult<String> {
+        Err(GPUError::FailedOperation(
+            "Power profile modes not supported".to_owned(),
+        ))
+    }
+    async fn power_profile_modes_available(&self) -> GPUResult<Vec<String>> {
+        Err(GPUError::FailedOperation(
+            "Power profile modes not supported".to_owned(),
+        ))
+    }
+    async fn set_power_profile_mode(&mut self, _mode: String) -> GPUResult<()> {
+        Err(GPUError::FailedOperation(
+            "Power profile modes not supported".to_owned(),
+        ))
+    }
     async fn get_gpu_busy_percent(&self) -> GPUResult<u8> {
         let path: PathBuf = format!("{0}/{1}", self.path().await, "device/gpu_busy_percent").into();
         if !path.exists() {
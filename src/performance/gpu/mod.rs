@@ -3,6 +3,7 @@ pub mod amd;
 pub mod asus;
 pub mod connector;
 pub mod dbus;
+pub mod hotplug;
 pub mod intel;
 pub mod interface;
 pub mod platform;
@@ -1,14 +1,22 @@
+use crate::config::PlatformConfig;
 use crate::performance::gpu::platform::model_config::{Config, ModelConfig};
+use crate::performance::gpu::tdp::TdpBackend;
+use crate::root;
 use std::collections::HashMap;
 use std::fs;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Platform settings from the PowerStation configuration
+static PLATFORM_CONFIG: OnceLock<PlatformConfig> = OnceLock::new();
 
 #[derive(Default, Clone)]
 pub struct Hardware {
     pub min_tdp: f64,
     pub max_tdp: f64,
     pub max_boost: f64,
+    pub tdp_backends: Option<Vec<TdpBackend>>,
 }
 
 impl Hardware {
@@ -17,6 +25,18 @@ impl Hardware {
     pub const INTEL_APU_DATABASE: &str = "intel_apu_database.toml";
     pub const DMI_OVERRIDES_APU_DATABASE: &str = "dmi_overrides_apu_database.toml";
 
+    /// Use the given platform settings. This must be called before any
+    /// hardware is detected, otherwise the defaults remain in use.
+    pub fn configure(config: PlatformConfig) {
+        if PLATFORM_CONFIG.set(config).is_err() {
+            log::warn!("Platform settings are already set. Ignoring new settings.");
+        }
+    }
+
+    fn platform_config() -> &'static PlatformConfig {
+        PLATFORM_CONFIG.get_or_init(PlatformConfig::default)
+    }
+
     // Enhanced new method that loads and parses configurations
     pub fn new() -> Option<Self> {
         match Self::load_and_apply_configs() {
@@ -55,17 +75,20 @@ impl Hardware {
             min_tdp: 0.0,
             max_tdp: 0.0,
             max_boost: 0.0,
+            tdp_backends: None,
         };
 
         // Get current model with two-level matching strategy
         let current_model = Self::get_current_model()?;
 
         // Find matching model in merged configuration
+        let mut matched = false;
         for model in &merged_config.models {
             if model.model_name == current_model {
                 hardware.min_tdp = model.min_tdp;
                 hardware.max_tdp = model.max_tdp;
                 hardware.max_boost = model.max_boost;
+                hardware.tdp_backends = model.tdp_backends.clone();
                 log::info!(
                     "Applied configuration for model {}: min_tdp={}, max_tdp={}, max_boost={}",
                     model.model_name,
@@ -73,10 +96,19 @@ impl Hardware {
                     model.max_tdp,
                     model.max_boost
                 );
+                matched = true;
                 break;
             }
         }
 
+        // Use the configured limits for models that are not in the database
+        if !matched {
+            let config = Self::platform_config();
+            hardware.min_tdp = config.min_tdp.unwrap_or(hardware.min_tdp);
+            hardware.max_tdp = config.max_tdp.unwrap_or(hardware.max_tdp);
+            hardware.max_boost = config.max_boost.unwrap_or(hardware.max_boost);
+        }
+
         Ok(hardware)
     }
 
@@ -119,7 +151,8 @@ impl Hardware {
     // Get current device model with two-level matching strategy
     fn get_current_model() -> Result<String, Box<dyn std::error::Error>> {
         // First try: Match by product_name
-        let product_name_path = Path::new("/sys/class/dmi/id/product_name");
+        let product_name_path = root::path("/sys/class/dmi/id/product_name");
+        let product_name_path = Path::new(&product_name_path);
         if product_name_path.exists() {
             let model = fs::read_to_string(product_name_path)?.trim().to_string();
 
@@ -138,7 +171,8 @@ impl Hardware {
         }
 
         // Second try: Match by CPU model
-        let cpu_info_path = Path::new("/proc/cpuinfo");
+        let cpu_info_path = root::path("/proc/cpuinfo");
+        let cpu_info_path = Path::new(&cpu_info_path);
         if cpu_info_path.exists() {
             let cpu_info = fs::read_to_string(cpu_info_path)?;
 
@@ -206,11 +240,17 @@ impl Hardware {
     }
 
     /// Returns the platform directory with platform configs (e.g.
-    /// "/usr/share/powerstation/platform")
+    /// "/usr/share/powerstation/platform"), unless another directory is
+    /// configured
     fn get_platform_dir() -> PathBuf {
+        if let Some(dir) = Self::platform_config().database_dir.as_ref() {
+            return PathBuf::from(root::path(&dir.to_string_lossy()));
+        }
+
         let base_dirs = xdg::BaseDirectories::with_prefix("powerstation");
         let data_dirs = base_dirs.get_data_dirs();
         for dir in data_dirs {
+            let dir = PathBuf::from(root::path(&dir.to_string_lossy()));
             if !dir.exists() {
                 continue;
             }
@@ -222,7 +262,7 @@ impl Hardware {
             return platform_dir;
         }
 
-        PathBuf::from(Self::PLATFORM_DIR)
+        PathBuf::from(root::path(Self::PLATFORM_DIR))
     }
 
     pub fn min_tdp(&self) -> f64 {
@@ -236,4 +276,9 @@ impl Hardware {
     pub fn max_boost(&self) -> f64 {
         self.max_boost
     }
+
+    /// Returns the order of the TDP backends for this model, if set
+    pub fn tdp_backends(&self) -> Option<&[TdpBackend]> {
+        self.tdp_backends.as_deref()
+    }
 }
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::performance::gpu::tdp::TdpBackend;
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Config {
     pub models: Vec<ModelConfig>,
@@ -11,4 +13,8 @@ pub struct ModelConfig {
     pub min_tdp: f64,
     pub max_tdp: f64,
     pub max_boost: f64,
+    /// Order in which the AMD TDP backends are tried on this model. Backends
+    /// that are not listed are not used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tdp_backends: Option<Vec<TdpBackend>>,
 }
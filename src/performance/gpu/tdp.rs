@@ -1,16 +1,32 @@
-use std::io;
+use std::{fmt, io};
+
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
 pub enum TDPError {
     FeatureUnsupported,
     FailedOperation(String),
     InvalidArgument(String),
+    /// The value is outside of the limits supported by the hardware
+    OutOfRange(String),
     IOError(String),
 }
 
+impl fmt::Display for TDPError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TDPError::FeatureUnsupported => write!(f, "Unsupported feature"),
+            TDPError::FailedOperation(err) => write!(f, "{err}"),
+            TDPError::InvalidArgument(err) => write!(f, "Invalid argument: {err}"),
+            TDPError::OutOfRange(err) => write!(f, "Out of range: {err}"),
+            TDPError::IOError(err) => write!(f, "I/O error: {err}"),
+        }
+    }
+}
+
 impl From<TDPError> for String {
-    fn from(_val: TDPError) -> Self {
-        todo!()
+    fn from(val: TDPError) -> Self {
+        val.to_string()
     }
 }
 
@@ -22,6 +38,34 @@ impl From<io::Error> for TDPError {
 
 pub type TDPResult<T> = Result<T, TDPError>;
 
+/// Interface used to read and write the TDP limits of a device
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TdpBackend {
+    AsusWmi,
+    Hwmon,
+    #[serde(rename = "ryzenadj")]
+    RyzenAdj,
+    IntelRapl,
+}
+
+impl TdpBackend {
+    /// Order in which the AMD backends are tried by default
+    pub const AMD_DEFAULT_ORDER: [TdpBackend; 3] =
+        [TdpBackend::AsusWmi, TdpBackend::Hwmon, TdpBackend::RyzenAdj];
+}
+
+impl fmt::Display for TdpBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TdpBackend::AsusWmi => write!(f, "asus_wmi"),
+            TdpBackend::Hwmon => write!(f, "hwmon"),
+            TdpBackend::RyzenAdj => write!(f, "ryzenadj"),
+            TdpBackend::IntelRapl => write!(f, "intel_rapl"),
+        }
+    }
+}
+
 // Helper trait to simplify access to hardware information
 pub trait HardwareAccess {
     fn hardware(&self) -> Option<&crate::performance::gpu::platform::hardware::Hardware>;
@@ -38,6 +82,19 @@ pub trait TDPDevice: Sync + Send + HardwareAccess {
     async fn power_profiles_available(&self) -> TDPResult<Vec<String>>;
     async fn set_power_profile(&mut self, profile: String) -> TDPResult<()>;
 
+    /// Returns the available backends in the order they are tried
+    fn backends(&self) -> Vec<TdpBackend>;
+
+    /// Returns the backend that served the last successful read or write
+    fn active_backend(&self) -> Option<TdpBackend>;
+
+    /// Set the TDP and boost together. Implementations should override this
+    /// to write the limits in one pass without reading them back first.
+    async fn set_tdp_and_boost(&mut self, tdp: f64, boost: f64) -> TDPResult<()> {
+        self.set_tdp(tdp).await?;
+        self.set_boost(boost).await
+    }
+
     // Default implementations for hardware-based methods
     async fn min_tdp(&self) -> TDPResult<f64> {
         log::info!("Get TDP Min");
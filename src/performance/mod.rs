@@ -1,2 +1,11 @@
+pub mod apps;
 pub mod cpu;
+pub mod gamemode;
 pub mod gpu;
+pub mod monitor;
+pub mod overrides;
+pub mod power;
+pub mod power_profiles;
+pub mod profile;
+pub mod sleep;
+pub mod state;
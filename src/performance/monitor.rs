@@ -0,0 +1,122 @@
+use std::{collections::HashMap, time::Duration};
+
+use zbus::{
+    object_server::{Interface, SignalEmitter},
+    Connection,
+};
+
+use crate::performance::cpu::{core::CPUCore, cpu_features::Cpu};
+use crate::performance::gpu::dbus::{gpu::GPUBus, gpu::GPUDBusInterface, tdp::GPUTDPDBusIface};
+use crate::performance::power_profiles::{PowerProfiles, POWER_PROFILES_PATH};
+use crate::performance::profile::ProfileManager;
+use powerstation::constants::{CPU_PATH, GPU_PATH, PROFILES_PATH};
+
+/// How often to check for property changes made outside of PowerStation
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// DBus interfaces implementing this trait can be polled for property changes
+/// that happen outside of their setters, e.g. by firmware or by other tools
+/// writing to sysfs.
+pub trait PropertyMonitor {
+    type Snapshot: PartialEq + Send + Sync;
+
+    /// Returns the current values of all monitored properties
+    async fn snapshot(&self) -> Self::Snapshot;
+
+    /// Emit the "PropertiesChanged" signal for every property that differs
+    /// between the given snapshots.
+    async fn emit_changed(
+        &self,
+        previous: &Self::Snapshot,
+        current: &Self::Snapshot,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()>;
+}
+
+/// Last seen property values of every monitored object, keyed by object path
+#[derive(Default)]
+struct Snapshots {
+    cpu: HashMap<String, <Cpu as PropertyMonitor>::Snapshot>,
+    cores: HashMap<String, <CPUCore as PropertyMonitor>::Snapshot>,
+    cards: HashMap<String, <GPUDBusInterface as PropertyMonitor>::Snapshot>,
+    tdp: HashMap<String, <GPUTDPDBusIface as PropertyMonitor>::Snapshot>,
+    profiles: HashMap<String, <ProfileManager as PropertyMonitor>::Snapshot>,
+    power_profiles: HashMap<String, <PowerProfiles as PropertyMonitor>::Snapshot>,
+}
+
+/// Periodically poll the properties of all CPU, GPU and profile objects and
+/// emit "PropertiesChanged" for any value that changed out-of-band.
+pub fn watch_properties(connection: Connection) {
+    tokio::task::spawn(async move {
+        let mut snapshots = Snapshots::default();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            poll_all(&connection, &mut snapshots).await;
+        }
+    });
+}
+
+/// Poll every known CPU, GPU and profile object once
+async fn poll_all(connection: &Connection, snapshots: &mut Snapshots) {
+    let object_server = connection.object_server();
+
+    // Poll the CPU and all of its cores
+    poll::<Cpu>(connection, CPU_PATH, &mut snapshots.cpu).await;
+    let core_paths = match object_server.interface::<_, Cpu>(CPU_PATH).await {
+        Ok(cpu) => cpu.get().await.core_paths(),
+        Err(_) => vec![],
+    };
+    for path in core_paths {
+        poll::<CPUCore>(connection, &path, &mut snapshots.cores).await;
+    }
+
+    // Poll every GPU card and its TDP interface
+    let card_paths = match object_server.interface::<_, GPUBus>(GPU_PATH).await {
+        Ok(gpu_bus) => gpu_bus.get().await.card_paths(),
+        Err(_) => vec![],
+    };
+    for path in card_paths {
+        poll::<GPUDBusInterface>(connection, &path, &mut snapshots.cards).await;
+        poll::<GPUTDPDBusIface>(connection, &path, &mut snapshots.tdp).await;
+    }
+
+    // Poll the active profile, which changes along with any of its settings
+    poll::<ProfileManager>(connection, PROFILES_PATH, &mut snapshots.profiles).await;
+
+    // Poll whether the power-profiles-daemon performance profile is degraded
+    poll::<PowerProfiles>(
+        connection,
+        POWER_PROFILES_PATH,
+        &mut snapshots.power_profiles,
+    )
+    .await;
+}
+
+/// Take a snapshot of the interface at the given path and emit any changes
+/// since the last snapshot.
+async fn poll<I>(connection: &Connection, path: &str, last: &mut HashMap<String, I::Snapshot>)
+where
+    I: Interface + PropertyMonitor,
+{
+    let Ok(iface) = connection.object_server().interface::<_, I>(path).await else {
+        // The object may have been removed
+        last.remove(path);
+        return;
+    };
+    let current = iface.get().await.snapshot().await;
+    if let Some(previous) = last.get(path) {
+        if previous != &current {
+            log::debug!("Detected property change on {path}");
+            let result = iface
+                .get()
+                .await
+                .emit_changed(previous, &current, iface.signal_emitter())
+                .await;
+            if let Err(e) = result {
+                log::warn!("Failed to emit property changes for {path}: {e}");
+            }
+        }
+    }
+    last.insert(path.to_string(), current);
+}
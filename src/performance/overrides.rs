@@ -0,0 +1,248 @@
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use zbus::Connection;
+
+use crate::config::TdpLimit;
+use crate::performance::cpu::cpu_features::Cpu;
+use crate::performance::gpu::dbus::{gpu::GPUBus, gpu::GPUDBusInterface, tdp::GPUTDPDBusIface};
+use crate::performance::state::{CardState, CpuState};
+use powerstation::constants::{CPU_PATH, GPU_PATH};
+
+/// Settings that are applied temporarily, e.g. while a game is running. They
+/// are not saved, so the saved settings are restored after a restart.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Override {
+    pub cpu: CpuState,
+    /// Settings applied to every card
+    pub card: CardState,
+    /// TDP limit that is resolved for each card and replaces the TDP of the
+    /// card settings
+    pub tdp_limit: Option<TdpLimit>,
+    /// Upper limit for the TDP of every card. The TDP is only lowered if it is
+    /// higher.
+    pub max_tdp: Option<TdpLimit>,
+}
+
+/// Values replaced by an override
+#[derive(Debug, Clone, Default)]
+struct Replaced {
+    cpu: CpuState,
+    /// Card settings keyed by card path
+    cards: Vec<(String, CardState)>,
+}
+
+/// An override that is currently applied
+#[derive(Debug)]
+struct Applied {
+    name: String,
+    settings: Override,
+    replaced: Replaced,
+}
+
+/// Overrides that are currently applied, in the order they were applied. They
+/// are restored in reverse order, so that overlapping overrides, e.g. of
+/// GameMode and of an application, end with the values from before the first
+/// of them was applied.
+#[derive(Clone)]
+pub struct OverrideStack {
+    connection: Connection,
+    applied: Arc<Mutex<Vec<Applied>>>,
+}
+
+impl OverrideStack {
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            applied: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Apply the given override on top of all others under the given name,
+    /// replacing a previous override with the same name.
+    pub async fn push(&self, name: &str, settings: Override) {
+        let mut applied = self.applied.lock().await;
+        self.remove_locked(&mut applied, name).await;
+        let replaced = apply(&self.connection, &settings).await;
+        applied.push(Applied {
+            name: name.to_string(),
+            settings,
+            replaced,
+        });
+    }
+
+    /// Remove the override with the given name and restore the values it
+    /// replaced. Overrides applied after it stay in effect.
+    pub async fn remove(&self, name: &str) {
+        let mut applied = self.applied.lock().await;
+        self.remove_locked(&mut applied, name).await;
+    }
+
+    /// Apply all overrides again in their order, e.g. after the saved settings
+    /// were re-applied on resume. The values they replaced are kept.
+    pub async fn reapply(&self) {
+        let applied = self.applied.lock().await;
+        for override_ in applied.iter() {
+            log::info!("Re-applying {} settings", override_.name);
+            apply(&self.connection, &override_.settings).await;
+        }
+    }
+
+    // Remove the override with the given name. The overrides applied after it
+    // are restored first, most recent first, and applied again once the
+    // values replaced by the removed override are restored.
+    async fn remove_locked(&self, applied: &mut Vec<Applied>, name: &str) {
+        let Some(index) = applied.iter().position(|o| o.name == name) else {
+            return;
+        };
+        let unwound = applied.split_off(index);
+        for override_ in unwound.iter().rev() {
+            restore(&self.connection, &override_.replaced).await;
+        }
+        for override_ in unwound.into_iter().skip(1) {
+            let replaced = apply(&self.connection, &override_.settings).await;
+            applied.push(Applied {
+                replaced,
+                ..override_
+            });
+        }
+    }
+}
+
+/// Returns the DBus paths of all GPU cards
+async fn card_paths(connection: &Connection) -> Vec<String> {
+    match connection
+        .object_server()
+        .interface::<_, GPUBus>(GPU_PATH)
+        .await
+    {
+        Ok(gpu_bus) => gpu_bus.get().await.card_paths(),
+        Err(_) => vec![],
+    }
+}
+
+/// Apply the CPU settings and the card settings of the given override to
+/// every card without saving them, and return the values they replaced.
+async fn apply(connection: &Connection, settings: &Override) -> Replaced {
+    let object_server = connection.object_server();
+    let mut replaced = Replaced::default();
+    if settings.cpu != CpuState::default() {
+        if let Ok(cpu) = object_server.interface::<_, Cpu>(CPU_PATH).await {
+            replaced.cpu = cpu.get().await.current().await.only(&settings.cpu);
+            let result = cpu
+                .get_mut()
+                .await
+                .apply_temporarily(&settings.cpu, cpu.signal_emitter())
+                .await;
+            if let Err(e) = result {
+                log::error!("Failed to apply CPU settings: {e}");
+            }
+        }
+    }
+    if settings.card == CardState::default()
+        && settings.tdp_limit.is_none()
+        && settings.max_tdp.is_none()
+    {
+        return replaced;
+    }
+
+    for path in card_paths(connection).await {
+        let mut card_settings = settings.card.clone();
+        let mut previous = CardState::default();
+        if let Ok(tdp) = object_server
+            .interface::<_, GPUTDPDBusIface>(path.as_str())
+            .await
+        {
+            if let Some(limit) = settings.tdp_limit {
+                match tdp.get().await.resolve_tdp(limit).await {
+                    Ok(value) => card_settings.tdp = Some(value),
+                    Err(e) => log::warn!("Unable to determine TDP for {path}: {e}"),
+                }
+            }
+            let current = tdp.get().await.current().await;
+            if let Some(limit) = settings.max_tdp {
+                match tdp.get().await.resolve_tdp(limit).await {
+                    Ok(max_tdp) => {
+                        let value = card_settings.tdp.or(current.tdp);
+                        if value.is_some_and(|value| value > max_tdp) {
+                            log::info!("Limiting TDP of {path} to {max_tdp}");
+                            card_settings.tdp = Some(max_tdp);
+                        }
+                    }
+                    Err(e) => log::warn!("Unable to determine TDP limit for {path}: {e}"),
+                }
+            }
+            previous.merge(&current.only(&card_settings));
+            let result = tdp
+                .get_mut()
+                .await
+                .apply_temporarily(&card_settings, tdp.signal_emitter())
+                .await;
+            if let Err(e) = result {
+                log::error!("Failed to apply TDP settings to {path}: {e}");
+            }
+        }
+        if let Ok(card) = object_server
+            .interface::<_, GPUDBusInterface>(path.as_str())
+            .await
+        {
+            previous.merge(&card.get().await.current().await.only(&card_settings));
+            let result = card
+                .get_mut()
+                .await
+                .apply_temporarily(&card_settings, card.signal_emitter())
+                .await;
+            if let Err(e) = result {
+                log::error!("Failed to apply clock settings to {path}: {e}");
+            }
+        }
+        replaced.cards.push((path, previous));
+    }
+    replaced
+}
+
+/// Re-apply the values that were replaced by [apply] without saving them
+async fn restore(connection: &Connection, replaced: &Replaced) {
+    let object_server = connection.object_server();
+    if replaced.cpu != CpuState::default() {
+        if let Ok(cpu) = object_server.interface::<_, Cpu>(CPU_PATH).await {
+            let result = cpu
+                .get_mut()
+                .await
+                .apply_temporarily(&replaced.cpu, cpu.signal_emitter())
+                .await;
+            if let Err(e) = result {
+                log::error!("Failed to restore CPU settings: {e}");
+            }
+        }
+    }
+
+    for (path, previous) in replaced.cards.iter() {
+        if let Ok(tdp) = object_server
+            .interface::<_, GPUTDPDBusIface>(path.as_str())
+            .await
+        {
+            let result = tdp
+                .get_mut()
+                .await
+                .apply_temporarily(previous, tdp.signal_emitter())
+                .await;
+            if let Err(e) = result {
+                log::error!("Failed to restore TDP settings of {path}: {e}");
+            }
+        }
+        if let Ok(card) = object_server
+            .interface::<_, GPUDBusInterface>(path.as_str())
+            .await
+        {
+            let result = card
+                .get_mut()
+                .await
+                .reapply(previous, false, card.signal_emitter())
+                .await;
+            if let Err(e) = result {
+                log::error!("Failed to restore clock settings of {path}: {e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use futures_lite::StreamExt;
+use zbus::fdo::PropertiesProxy;
+use zbus::proxy::CacheProperties;
+use zbus::Connection;
+use zbus_macros::proxy;
+
+use crate::config::{ConfigStore, PowerConfig, TdpLimit};
+use crate::performance::overrides::{Override, OverrideStack};
+use crate::performance::profile::ProfileManager;
+use powerstation::constants::PROFILES_PATH;
+
+const UPOWER_BUS_NAME: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+const DISPLAY_DEVICE_PATH: &str = "/org/freedesktop/UPower/devices/DisplayDevice";
+
+/// Name of the TDP limit of the battery thresholds on the override stack
+const OVERRIDE_NAME: &str = "battery";
+
+/// Proxy for the UPower daemon, used to detect the power source
+#[proxy(
+    interface = "org.freedesktop.UPower",
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower"
+)]
+trait UPower {
+    /// Whether the system is running on battery power
+    #[zbus(property)]
+    fn on_battery(&self) -> zbus::Result<bool>;
+}
+
+/// Proxy for the UPower display device, which combines all batteries
+#[proxy(
+    interface = "org.freedesktop.UPower.Device",
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower/devices/DisplayDevice"
+)]
+trait UPowerDevice {
+    /// Battery charge in percent
+    #[zbus(property)]
+    fn percentage(&self) -> zbus::Result<f64>;
+}
+
+/// Profile and TDP limit to apply for a power source and battery level
+#[derive(Debug, Clone, Default, PartialEq)]
+struct PowerPolicy {
+    profile: Option<String>,
+    max_tdp: Option<TdpLimit>,
+}
+
+impl PowerPolicy {
+    /// Returns the policy from the given configuration that applies to the
+    /// given power source and battery level.
+    fn select(config: &PowerConfig, on_battery: bool, percentage: f64) -> PowerPolicy {
+        if !on_battery {
+            return PowerPolicy {
+                profile: config.ac_profile.clone(),
+                max_tdp: None,
+            };
+        }
+
+        let mut policy = PowerPolicy {
+            profile: config.battery_profile.clone(),
+            max_tdp: None,
+        };
+        let threshold = config
+            .battery_thresholds
+            .iter()
+            .filter(|t| percentage < t.below_percent)
+            .min_by(|a, b| a.below_percent.total_cmp(&b.below_percent));
+        if let Some(threshold) = threshold {
+            if threshold.profile.is_some() {
+                policy.profile.clone_from(&threshold.profile);
+            }
+            policy.max_tdp = threshold.max_tdp;
+        }
+        policy
+    }
+}
+
+/// Watch the power source and battery level reported by UPower and apply the
+/// configured profiles and TDP limits whenever they change. TDP limits are
+/// applied as overrides, so the previous TDP is restored once the battery is
+/// charged again or the system is plugged in.
+pub async fn watch_power_source(
+    connection: Connection,
+    store: ConfigStore,
+    overrides: OverrideStack,
+) -> zbus::Result<()> {
+    if store.get().await.power.is_empty() {
+        log::debug!("No power source profiles configured");
+        return Ok(());
+    }
+
+    let upower = UPowerProxy::builder(&connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    let device = UPowerDeviceProxy::builder(&connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    // Listen for property changes of both objects
+    let mut changes = properties_changed(&connection, UPOWER_PATH)
+        .await?
+        .or(properties_changed(&connection, DISPLAY_DEVICE_PATH).await?);
+
+    tokio::task::spawn(async move {
+        let mut last_policy: Option<PowerPolicy> = None;
+        loop {
+            match upower.on_battery().await {
+                Ok(on_battery) => {
+                    // Systems without a battery have no display device
+                    let percentage = device.percentage().await.unwrap_or(100.0);
+                    let config = store.get().await.power;
+                    let policy = PowerPolicy::select(&config, on_battery, percentage);
+                    if last_policy.as_ref() != Some(&policy) {
+                        log::info!(
+                            "Power source changed (on battery: {on_battery}, battery: {percentage}%). Applying {policy:?}"
+                        );
+                        apply_policy(&connection, &overrides, last_policy.as_ref(), &policy).await;
+                        last_policy = Some(policy);
+                    }
+                }
+                Err(e) => log::debug!("Unable to read power source from UPower: {e}"),
+            }
+
+            if changes.next().await.is_none() {
+                break;
+            }
+        }
+        log::warn!("Stopped watching for power source changes");
+    });
+
+    Ok(())
+}
+
+/// Returns a stream of "PropertiesChanged" signals of the given UPower object
+async fn properties_changed(
+    connection: &Connection,
+    path: &'static str,
+) -> zbus::Result<zbus::fdo::PropertiesChangedStream> {
+    let properties = PropertiesProxy::builder(connection)
+        .destination(UPOWER_BUS_NAME)?
+        .path(path)?
+        .build()
+        .await?;
+    properties.receive_properties_changed().await
+}
+
+/// Apply the given policy. The TDP limit of the last policy is removed first,
+/// then the profile is applied if it changed, and the TDP of every card is
+/// limited on top of it.
+async fn apply_policy(
+    connection: &Connection,
+    overrides: &OverrideStack,
+    last_policy: Option<&PowerPolicy>,
+    policy: &PowerPolicy,
+) {
+    overrides.remove(OVERRIDE_NAME).await;
+
+    let profile_changed = last_policy.is_none_or(|last| last.profile != policy.profile);
+    if let Some(name) = policy.profile.clone().filter(|_| profile_changed) {
+        match connection
+            .object_server()
+            .interface::<_, ProfileManager>(PROFILES_PATH)
+            .await
+        {
+            Ok(profiles) => {
+                let emitter = profiles.signal_emitter();
+                if let Err(e) = profiles.get().await.activate(name, None, emitter).await {
+                    log::error!("Failed to apply power source profile: {e}");
+                }
+            }
+            Err(e) => log::error!("Unable to find profile manager: {e}"),
+        }
+    }
+
+    if let Some(max_tdp) = policy.max_tdp {
+        let settings = Override {
+            max_tdp: Some(max_tdp),
+            ..Default::default()
+        };
+        overrides.push(OVERRIDE_NAME, settings).await;
+    }
+}
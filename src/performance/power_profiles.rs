@@ -0,0 +1,722 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use futures_lite::StreamExt;
+use tokio::sync::Mutex;
+use zbus::fdo::{self, DBusProxy, Properties};
+use zbus::message::Header;
+use zbus::names::InterfaceName;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::Value;
+use zbus::Connection;
+use zbus_macros::interface;
+
+use crate::config::{ConfigStore, HardwareLimit, PowerProfileSettings, TdpLimit};
+use crate::performance::gpu::dbus::{gpu::GPUBus, tdp::GPUTDPDBusIface};
+use crate::performance::gpu::platform::hardware::Hardware;
+use crate::performance::monitor::PropertyMonitor;
+use crate::performance::state::{CardState, StateStore};
+use crate::polkit::{self, Action};
+use powerstation::constants::GPU_PATH;
+
+/// Object path of the power-profiles-daemon interface
+pub const POWER_PROFILES_PATH: &str = "/org/freedesktop/UPower/PowerProfiles";
+/// Object path of the legacy power-profiles-daemon interface
+pub const LEGACY_POWER_PROFILES_PATH: &str = "/net/hadess/PowerProfiles";
+
+const POWER_PROFILES_NAME: &str = "org.freedesktop.UPower.PowerProfiles";
+const LEGACY_POWER_PROFILES_NAME: &str = "net.hadess.PowerProfiles";
+
+const POWER_SAVER: &str = "power-saver";
+const BALANCED: &str = "balanced";
+const PERFORMANCE: &str = "performance";
+const PROFILES: [&str; 3] = [POWER_SAVER, BALANCED, PERFORMANCE];
+
+/// Driver name reported for every profile
+const DRIVER: &str = "powerstation";
+/// Version of the power-profiles-daemon API that is implemented
+const VERSION: &str = "0.20";
+/// Reported as the reason for "PerformanceDegraded" if the performance
+/// profile is active but the TDP was lowered since, e.g. on low battery.
+const TDP_LIMITED: &str = "tdp-limited";
+
+/// A request of an application to use a profile until it is released
+#[derive(Debug, Clone)]
+struct Hold {
+    profile: String,
+    reason: String,
+    application_id: String,
+    /// Unique bus name of the client that requested the hold
+    owner: String,
+}
+
+/// The profile selected by the user along with all active holds
+#[derive(Debug, Clone)]
+struct Selection {
+    selected: String,
+    holds: BTreeMap<u32, Hold>,
+    next_cookie: u32,
+    degraded: String,
+}
+
+impl Selection {
+    /// Returns the profile in effect. Holds take precedence over the selected
+    /// profile, and "power-saver" holds over "performance" holds.
+    fn active(&self) -> &str {
+        if self.holds.values().any(|hold| hold.profile == POWER_SAVER) {
+            POWER_SAVER
+        } else if !self.holds.is_empty() {
+            PERFORMANCE
+        } else {
+            &self.selected
+        }
+    }
+
+    /// Returns the "ActiveProfileHolds" property value
+    fn holds_property(&self) -> Vec<HashMap<String, Value<'static>>> {
+        self.holds
+            .values()
+            .map(|hold| {
+                HashMap::from([
+                    (
+                        String::from("ApplicationId"),
+                        Value::from(hold.application_id.clone()),
+                    ),
+                    (String::from("Profile"), Value::from(hold.profile.clone())),
+                    (String::from("Reason"), Value::from(hold.reason.clone())),
+                ])
+            })
+            .collect()
+    }
+}
+
+/// Maps the power-profiles-daemon profiles to TDP and platform profile
+/// settings of every card with a TDP interface. The state is shared by the
+/// interfaces served at [POWER_PROFILES_PATH] and [LEGACY_POWER_PROFILES_PATH].
+#[derive(Clone)]
+pub struct PowerProfileService {
+    connection: Connection,
+    config: ConfigStore,
+    state: StateStore,
+    /// TDP of the "balanced" profile if it is not configured
+    balanced_tdp: Option<f64>,
+    selection: Arc<Mutex<Selection>>,
+}
+
+impl PowerProfileService {
+    /// Create the service with the last selected profile, which is applied
+    /// along with the other saved settings.
+    pub async fn new(connection: Connection, config: ConfigStore, state: StateStore) -> Self {
+        // Like the default "balanced" profile, use 60% of the TDP range
+        let balanced_tdp = Hardware::new().and_then(|hardware| {
+            let min_tdp = hardware.min_tdp();
+            let max_tdp = hardware.max_tdp();
+            if max_tdp <= 0.0 || min_tdp > max_tdp {
+                return None;
+            }
+            Some(
+                (min_tdp + (max_tdp - min_tdp) * 0.6)
+                    .round()
+                    .clamp(min_tdp, max_tdp),
+            )
+        });
+
+        let selected = state
+            .selected_power_profile()
+            .await
+            .filter(|profile| PROFILES.contains(&profile.as_str()))
+            .unwrap_or_else(|| String::from(BALANCED));
+
+        Self {
+            connection,
+            config,
+            state,
+            balanced_tdp,
+            selection: Arc::new(Mutex::new(Selection {
+                selected,
+                holds: BTreeMap::new(),
+                next_cookie: 0,
+                degraded: String::new(),
+            })),
+        }
+    }
+
+    /// Returns the settings for the given profile, falling back to defaults
+    /// similar to the platform profiles of power-profiles-daemon.
+    async fn settings(&self, profile: &str, tdp: &GPUTDPDBusIface) -> PowerProfileSettings {
+        let config = self.config.get().await.power_profiles;
+        let configured = match profile {
+            POWER_SAVER => config.power_saver,
+            BALANCED => config.balanced,
+            _ => config.performance,
+        };
+        if let Some(settings) = configured {
+            return settings;
+        }
+
+        let (limit, candidates): (_, &[&str]) = match profile {
+            POWER_SAVER => (
+                Some(TdpLimit::Hardware(HardwareLimit::Min)),
+                &["low-power", "quiet", "cool"],
+            ),
+            BALANCED => (self.balanced_tdp.map(TdpLimit::Watts), &[BALANCED]),
+            _ => (Some(TdpLimit::Hardware(HardwareLimit::Max)), &[PERFORMANCE]),
+        };
+        let available = tdp.power_profiles_available().await.unwrap_or_default();
+        PowerProfileSettings {
+            tdp: limit,
+            boost: None,
+            power_profile: candidates
+                .iter()
+                .find(|c| available.iter().any(|a| a == *c))
+                .map(|c| c.to_string()),
+        }
+    }
+
+    /// Returns the DBus paths of every card with a TDP interface
+    async fn tdp_paths(&self) -> Vec<String> {
+        let object_server = self.connection.object_server();
+        let card_paths = match object_server.interface::<_, GPUBus>(GPU_PATH).await {
+            Ok(gpu_bus) => gpu_bus.get().await.card_paths(),
+            Err(_) => vec![],
+        };
+
+        let mut paths = Vec::new();
+        for path in card_paths {
+            if object_server
+                .interface::<_, GPUTDPDBusIface>(path.as_str())
+                .await
+                .is_ok()
+            {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    /// Apply the settings of the given profile to every card with a TDP
+    /// interface, saving them if `save` is set. Profiles held by applications
+    /// are not saved, so the selected profile is restored after a restart.
+    /// Nothing is applied if any of the cards is leased by another client than
+    /// the sender of the message with the given header.
+    async fn apply(
+        &self,
+        profile: &str,
+        save: bool,
+        header: Option<&Header<'_>>,
+    ) -> fdo::Result<()> {
+        log::info!("Applying power profile {profile}");
+        let object_server = self.connection.object_server();
+        let paths = self.tdp_paths().await;
+        for path in paths.iter() {
+            let tdp = object_server
+                .interface::<_, GPUTDPDBusIface>(path.as_str())
+                .await?;
+            tdp.get().await.check_lease(header).await?;
+        }
+
+        for path in paths {
+            let tdp = object_server
+                .interface::<_, GPUTDPDBusIface>(path.as_str())
+                .await?;
+            let state = {
+                let tdp = tdp.get().await;
+                let settings = self.settings(profile, &tdp).await;
+                CardState {
+                    tdp: match settings.tdp {
+                        Some(limit) => Some(tdp.resolve_tdp(limit).await?),
+                        None => None,
+                    },
+                    boost: settings.boost,
+                    power_profile: settings.power_profile,
+                    ..Default::default()
+                }
+            };
+            let mut tdp_iface = tdp.get_mut().await;
+            let emitter = tdp.signal_emitter();
+            if save {
+                tdp_iface.apply(&state, emitter).await?;
+            } else {
+                tdp_iface.apply_temporarily(&state, emitter).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the reason the given active profile is degraded, or an empty
+    /// string if it is not.
+    async fn degraded(&self, profile: &str) -> String {
+        if profile != PERFORMANCE {
+            return String::new();
+        }
+        let object_server = self.connection.object_server();
+        for path in self.tdp_paths().await {
+            let Ok(tdp) = object_server
+                .interface::<_, GPUTDPDBusIface>(path.as_str())
+                .await
+            else {
+                continue;
+            };
+            let tdp = tdp.get().await;
+            let Some(limit) = self.settings(PERFORMANCE, &tdp).await.tdp else {
+                continue;
+            };
+            if let (Ok(target), Ok(current)) = (tdp.resolve_tdp(limit).await, tdp.tdp().await) {
+                if current < target {
+                    return String::from(TDP_LIMITED);
+                }
+            }
+        }
+        String::new()
+    }
+
+    /// Switch to the given selection on behalf of the sender of the message
+    /// with the given header, applying its active profile if it differs from
+    /// the current one. If applying fails, nothing is changed unless `force`
+    /// is set, e.g. since released holds cannot be kept.
+    async fn update(
+        &self,
+        current: &mut Selection,
+        mut next: Selection,
+        header: Option<&Header<'_>>,
+        force: bool,
+    ) -> fdo::Result<()> {
+        if next.active() != current.active() {
+            let save = next.holds.is_empty();
+            if let Err(e) = self.apply(next.active(), save, header).await {
+                if !force {
+                    return Err(e);
+                }
+                log::error!("Failed to apply power profile {}: {e}", next.active());
+            }
+        }
+        next.degraded = self.degraded(next.active()).await;
+
+        let mut changed = HashMap::new();
+        if next.active() != current.active() {
+            changed.insert("ActiveProfile", Value::from(next.active().to_string()));
+        }
+        if next.holds.keys().ne(current.holds.keys()) {
+            changed.insert("ActiveProfileHolds", Value::from(next.holds_property()));
+        }
+        if next.degraded != current.degraded {
+            changed.insert("PerformanceDegraded", Value::from(next.degraded.clone()));
+        }
+        let released: Vec<u32> = current
+            .holds
+            .keys()
+            .filter(|cookie| !next.holds.contains_key(cookie))
+            .copied()
+            .collect();
+
+        *current = next;
+        self.emit_changed(changed).await;
+        for cookie in released {
+            self.emit_released(cookie).await;
+        }
+        Ok(())
+    }
+
+    /// Select the given profile, releasing all holds
+    async fn set_active_profile(
+        &self,
+        profile: String,
+        header: Option<&Header<'_>>,
+    ) -> fdo::Result<()> {
+        if !PROFILES.contains(&profile.as_str()) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Invalid profile: {profile}"
+            )));
+        }
+        let mut selection = self.selection.lock().await;
+        let mut next = selection.clone();
+        next.selected = profile;
+        next.holds.clear();
+        self.update(&mut selection, next, header, false).await?;
+        self.state
+            .set_selected_power_profile(&selection.selected)
+            .await;
+        Ok(())
+    }
+
+    /// Hold the given profile for the sender of the message with the given
+    /// header until it is released, returning the cookie to release it with.
+    async fn hold_profile(
+        &self,
+        profile: String,
+        reason: String,
+        application_id: String,
+        header: &Header<'_>,
+    ) -> fdo::Result<u32> {
+        if profile != POWER_SAVER && profile != PERFORMANCE {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Only {POWER_SAVER} and {PERFORMANCE} can be held, not {profile}"
+            )));
+        }
+        log::info!("{application_id} holds power profile {profile}: {reason}");
+        let mut selection = self.selection.lock().await;
+        let mut next = selection.clone();
+        next.next_cookie += 1;
+        let cookie = next.next_cookie;
+        next.holds.insert(
+            cookie,
+            Hold {
+                profile,
+                reason,
+                application_id,
+                owner: sender(header),
+            },
+        );
+        self.update(&mut selection, next, Some(header), false)
+            .await?;
+        Ok(cookie)
+    }
+
+    /// Release the holds matching the given predicate and return to the
+    /// previously active profile. Returns the number of released holds.
+    async fn release<F>(&self, predicate: F) -> usize
+    where
+        F: Fn(u32, &Hold) -> bool,
+    {
+        let mut selection = self.selection.lock().await;
+        let mut next = selection.clone();
+        next.holds.retain(|cookie, hold| !predicate(*cookie, hold));
+        let count = selection.holds.len() - next.holds.len();
+        if count > 0 {
+            // Errors are logged, since the holds are released regardless
+            let _ = self.update(&mut selection, next, None, true).await;
+        }
+        count
+    }
+
+    /// Release the hold with the given cookie
+    async fn release_profile(&self, cookie: u32) -> fdo::Result<()> {
+        if self.release(|c, _| c == cookie).await == 0 {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "No hold with cookie {cookie}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Release all holds of the given client, e.g. after it disconnected
+    async fn release_owner(&self, owner: &str) {
+        let count = self.release(|_, hold| hold.owner == owner).await;
+        if count > 0 {
+            log::info!("Released {count} power profile holds of {owner}");
+        }
+    }
+
+    /// Emit "PropertiesChanged" with the given values on both object paths
+    async fn emit_changed(&self, changed: HashMap<&str, Value<'_>>) {
+        if changed.is_empty() {
+            return;
+        }
+        for (path, name) in [
+            (POWER_PROFILES_PATH, POWER_PROFILES_NAME),
+            (LEGACY_POWER_PROFILES_PATH, LEGACY_POWER_PROFILES_NAME),
+        ] {
+            let Ok(emitter) = SignalEmitter::new(&self.connection, path) else {
+                continue;
+            };
+            let name = InterfaceName::from_static_str_unchecked(name);
+            if let Err(e) =
+                Properties::properties_changed(&emitter, name, changed.clone(), Cow::Borrowed(&[]))
+                    .await
+            {
+                log::warn!("Failed to emit power profile changes: {e}");
+            }
+        }
+    }
+
+    /// Emit "ProfileReleased" for the given cookie on both object paths
+    async fn emit_released(&self, cookie: u32) {
+        if let Ok(emitter) = SignalEmitter::new(&self.connection, POWER_PROFILES_PATH) {
+            if let Err(e) = PowerProfiles::profile_released(&emitter, cookie).await {
+                log::warn!("Failed to emit released profile: {e}");
+            }
+        }
+        if let Ok(emitter) = SignalEmitter::new(&self.connection, LEGACY_POWER_PROFILES_PATH) {
+            if let Err(e) = LegacyPowerProfiles::profile_released(&emitter, cookie).await {
+                log::warn!("Failed to emit released profile: {e}");
+            }
+        }
+    }
+
+    /// Returns the "Profiles" property value
+    fn profiles(&self) -> Vec<HashMap<String, Value<'static>>> {
+        PROFILES
+            .iter()
+            .map(|profile| {
+                HashMap::from([
+                    (String::from("Profile"), Value::from(*profile)),
+                    (String::from("Driver"), Value::from(DRIVER)),
+                    (String::from("PlatformDriver"), Value::from(DRIVER)),
+                ])
+            })
+            .collect()
+    }
+}
+
+/// Returns the unique name of the sender of the given message
+fn sender(header: &Header<'_>) -> String {
+    header
+        .sender()
+        .map(|sender| sender.to_string())
+        .unwrap_or_default()
+}
+
+/// DBus interface compatible with power-profiles-daemon, used by desktop
+/// shells to switch between power profiles.
+pub struct PowerProfiles {
+    service: PowerProfileService,
+}
+
+impl PowerProfiles {
+    pub fn new(service: PowerProfileService) -> Self {
+        Self { service }
+    }
+}
+
+#[interface(name = "org.freedesktop.UPower.PowerProfiles")]
+impl PowerProfiles {
+    /// The profile in effect, either selected by the user or held by an
+    /// application
+    #[zbus(property)]
+    async fn active_profile(&self) -> String {
+        self.service.selection.lock().await.active().to_string()
+    }
+
+    /// Select the given profile, releasing all holds
+    #[zbus(property)]
+    async fn set_active_profile(
+        &mut self,
+        profile: String,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(&self.service.connection, header.as_ref(), Action::SetTdp).await?;
+        self.service
+            .set_active_profile(profile, header.as_ref())
+            .await
+    }
+
+    /// Deprecated in favor of "PerformanceDegraded"
+    #[zbus(property)]
+    async fn performance_inhibited(&self) -> String {
+        String::new()
+    }
+
+    /// Reason the performance profile is degraded, or an empty string
+    #[zbus(property)]
+    async fn performance_degraded(&self) -> String {
+        self.service.selection.lock().await.degraded.clone()
+    }
+
+    /// All available profiles
+    #[zbus(property)]
+    async fn profiles(&self) -> Vec<HashMap<String, Value<'static>>> {
+        self.service.profiles()
+    }
+
+    /// Additional actions performed when switching profiles
+    #[zbus(property)]
+    async fn actions(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Profiles held by applications
+    #[zbus(property)]
+    async fn active_profile_holds(&self) -> Vec<HashMap<String, Value<'static>>> {
+        self.service.selection.lock().await.holds_property()
+    }
+
+    /// Version of the implemented power-profiles-daemon API
+    #[zbus(property)]
+    async fn version(&self) -> String {
+        String::from(VERSION)
+    }
+
+    /// Hold the given profile until it is released or the caller disconnects
+    async fn hold_profile(
+        &self,
+        profile: String,
+        reason: String,
+        application_id: String,
+        #[zbus(header)] header: Header<'_>,
+    ) -> fdo::Result<u32> {
+        polkit::check(&self.service.connection, Some(&header), Action::SetTdp).await?;
+        self.service
+            .hold_profile(profile, reason, application_id, &header)
+            .await
+    }
+
+    /// Release the hold with the given cookie
+    async fn release_profile(&self, cookie: u32) -> fdo::Result<()> {
+        self.service.release_profile(cookie).await
+    }
+
+    /// Emitted when a hold is released, including when the user selects a
+    /// different profile
+    #[zbus(signal)]
+    async fn profile_released(emitter: &SignalEmitter<'_>, cookie: u32) -> zbus::Result<()>;
+}
+
+/// The power-profiles-daemon interface under its legacy name
+pub struct LegacyPowerProfiles {
+    service: PowerProfileService,
+}
+
+impl LegacyPowerProfiles {
+    pub fn new(service: PowerProfileService) -> Self {
+        Self { service }
+    }
+}
+
+#[interface(name = "net.hadess.PowerProfiles")]
+impl LegacyPowerProfiles {
+    #[zbus(property)]
+    async fn active_profile(&self) -> String {
+        self.service.selection.lock().await.active().to_string()
+    }
+
+    #[zbus(property)]
+    async fn set_active_profile(
+        &mut self,
+        profile: String,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        polkit::check(&self.service.connection, header.as_ref(), Action::SetTdp).await?;
+        self.service
+            .set_active_profile(profile, header.as_ref())
+            .await
+    }
+
+    #[zbus(property)]
+    async fn performance_inhibited(&self) -> String {
+        String::new()
+    }
+
+    #[zbus(property)]
+    async fn performance_degraded(&self) -> String {
+        self.service.selection.lock().await.degraded.clone()
+    }
+
+    #[zbus(property)]
+    async fn profiles(&self) -> Vec<HashMap<String, Value<'static>>> {
+        self.service.profiles()
+    }
+
+    #[zbus(property)]
+    async fn actions(&self) -> Vec<String> {
+        vec![]
+    }
+
+    #[zbus(property)]
+    async fn active_profile_holds(&self) -> Vec<HashMap<String, Value<'static>>> {
+        self.service.selection.lock().await.holds_property()
+    }
+
+    #[zbus(property)]
+    async fn version(&self) -> String {
+        String::from(VERSION)
+    }
+
+    async fn hold_profile(
+        &self,
+        profile: String,
+        reason: String,
+        application_id: String,
+        #[zbus(header)] header: Header<'_>,
+    ) -> fdo::Result<u32> {
+        polkit::check(&self.service.connection, Some(&header), Action::SetTdp).await?;
+        self.service
+            .hold_profile(profile, reason, application_id, &header)
+            .await
+    }
+
+    async fn release_profile(&self, cookie: u32) -> fdo::Result<()> {
+        self.service.release_profile(cookie).await
+    }
+
+    #[zbus(signal)]
+    async fn profile_released(emitter: &SignalEmitter<'_>, cookie: u32) -> zbus::Result<()>;
+}
+
+impl PropertyMonitor for PowerProfiles {
+    type Snapshot = String;
+
+    /// Re-check whether the performance profile is degraded, since the TDP
+    /// can be lowered at any time by other clients or the power policy.
+    async fn snapshot(&self) -> String {
+        let mut selection = self.service.selection.lock().await;
+        selection.degraded = self.service.degraded(selection.active()).await;
+        selection.degraded.clone()
+    }
+
+    async fn emit_changed(
+        &self,
+        previous: &String,
+        current: &String,
+        _emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()> {
+        // Emitted on the paths of both interfaces
+        if previous != current {
+            self.service
+                .emit_changed(HashMap::from([(
+                    "PerformanceDegraded",
+                    Value::from(current.clone()),
+                )]))
+                .await;
+        }
+        Ok(())
+    }
+}
+
+/// Serve the power-profiles-daemon compatible interfaces if they are enabled
+/// and claim their bus names. Holds are released when the client that
+/// requested them disconnects.
+pub async fn serve(
+    connection: &Connection,
+    config: ConfigStore,
+    state: StateStore,
+) -> zbus::Result<()> {
+    if !config.get().await.power_profiles.enabled {
+        log::debug!("Power profiles interface is disabled");
+        return Ok(());
+    }
+
+    let service = PowerProfileService::new(connection.clone(), config, state).await;
+    let object_server = connection.object_server();
+    object_server
+        .at(POWER_PROFILES_PATH, PowerProfiles::new(service.clone()))
+        .await?;
+    object_server
+        .at(
+            LEGACY_POWER_PROFILES_PATH,
+            LegacyPowerProfiles::new(service.clone()),
+        )
+        .await?;
+
+    let dbus = DBusProxy::new(connection).await?;
+    let mut owner_changes = dbus.receive_name_owner_changed().await?;
+    tokio::task::spawn(async move {
+        while let Some(signal) = owner_changes.next().await {
+            let Ok(args) = signal.args() else {
+                continue;
+            };
+            if args.new_owner().is_none() {
+                service.release_owner(args.name().as_str()).await;
+            }
+        }
+    });
+
+    for name in [POWER_PROFILES_NAME, LEGACY_POWER_PROFILES_NAME] {
+        if let Err(e) = connection.request_name(name).await {
+            log::warn!("Unable to claim {name}. Is power-profiles-daemon running? {e}");
+        }
+    }
+    Ok(())
+}
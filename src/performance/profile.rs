@@ -0,0 +1,644 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use zbus::fdo;
+use zbus::message::Header;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{self, OwnedValue, Value};
+use zbus::Connection;
+use zbus_macros::interface;
+
+use crate::performance::cpu::cpu_features::Cpu;
+use crate::performance::gpu::dbus::{gpu::GPUBus, gpu::GPUDBusInterface, tdp::GPUTDPDBusIface};
+use crate::performance::gpu::platform::hardware::Hardware;
+use crate::performance::monitor::PropertyMonitor;
+use crate::performance::state::{write_atomic, CardState, CpuState, ProfileState, StateStore};
+use crate::polkit::{self, Action};
+use powerstation::constants::{CPU_PATH, GPU_PATH};
+
+/// Path to the file where named profiles are stored
+pub const PROFILES_FILE: &str = "/var/lib/powerstation/profiles.toml";
+
+/// Settings replaced while applying a profile, to roll them back if another
+/// setting fails to apply
+#[derive(Debug)]
+enum Replaced {
+    Cpu(CpuState),
+    /// TDP settings of the card with the given DBus path
+    Tdp(String, CardState),
+    /// Clock settings of the card with the given DBus path
+    Clocks(String, CardState),
+}
+
+/// Settings that are applied together as a named profile. Unset values are
+/// left unchanged when the profile is applied.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    #[serde(default)]
+    pub cpu: CpuState,
+    /// TDP and clock settings, applied to every card with a TDP interface
+    #[serde(default)]
+    pub gpu: CardState,
+}
+
+impl Profile {
+    /// Returns a profile with the given settings, keyed by the name of the
+    /// DBus property they set (e.g. "TDP" or "SmtEnabled").
+    pub fn from_settings(settings: HashMap<String, OwnedValue>) -> fdo::Result<Profile> {
+        let mut profile = Profile::default();
+        for (key, value) in settings {
+            match key.as_str() {
+                "TDP" => profile.gpu.tdp = Some(setting(&key, value)?),
+                "Boost" => profile.gpu.boost = Some(setting(&key, value)?),
+                "ThermalThrottleLimitC" => {
+                    profile.gpu.thermal_throttle_limit_c = Some(setting(&key, value)?)
+                }
+                "PowerProfile" => profile.gpu.power_profile = Some(setting(&key, value)?),
+                "ManualClock" => profile.gpu.manual_clock = Some(setting(&key, value)?),
+                "ClockValueMhzMin" => profile.gpu.clock_value_mhz_min = Some(setting(&key, value)?),
+                "ClockValueMhzMax" => profile.gpu.clock_value_mhz_max = Some(setting(&key, value)?),
+                "PowerProfileMode" => profile.gpu.power_profile_mode = Some(setting(&key, value)?),
+                "BoostEnabled" => profile.cpu.boost_enabled = Some(setting(&key, value)?),
+                "SmtEnabled" => profile.cpu.smt_enabled = Some(setting(&key, value)?),
+                "CoresEnabled" => profile.cpu.cores_enabled = Some(setting(&key, value)?),
+                "CoresEnabledPolicy" => {
+                    profile.cpu.cores_enabled_policy = Some(setting(&key, value)?)
+                }
+                "ScalingGovernor" => profile.cpu.scaling_governor = Some(setting(&key, value)?),
+                "ScalingMinFreq" => profile.cpu.scaling_min_freq = Some(setting(&key, value)?),
+                "ScalingMaxFreq" => profile.cpu.scaling_max_freq = Some(setting(&key, value)?),
+                "PstateStatus" => profile.cpu.pstate_status = Some(setting(&key, value)?),
+                "EnergyPerformancePreference" => {
+                    profile.cpu.energy_performance_preference = Some(setting(&key, value)?)
+                }
+                "MinPerfPct" => profile.cpu.min_perf_pct = Some(setting(&key, value)?),
+                "MaxPerfPct" => profile.cpu.max_perf_pct = Some(setting(&key, value)?),
+                "NoTurbo" => profile.cpu.no_turbo = Some(setting(&key, value)?),
+                _ => {
+                    return Err(fdo::Error::InvalidArgs(format!(
+                        "Unknown profile setting: {key}"
+                    )))
+                }
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Returns the settings of this profile, keyed by the name of the DBus
+    /// property they set.
+    pub fn settings(&self) -> HashMap<String, Value<'static>> {
+        let mut settings = HashMap::new();
+        insert(&mut settings, "TDP", self.gpu.tdp);
+        insert(&mut settings, "Boost", self.gpu.boost);
+        insert(
+            &mut settings,
+            "ThermalThrottleLimitC",
+            self.gpu.thermal_throttle_limit_c,
+        );
+        insert(
+            &mut settings,
+            "PowerProfile",
+            self.gpu.power_profile.clone(),
+        );
+        insert(&mut settings, "ManualClock", self.gpu.manual_clock);
+        insert(
+            &mut settings,
+            "ClockValueMhzMin",
+            self.gpu.clock_value_mhz_min,
+        );
+        insert(
+            &mut settings,
+            "ClockValueMhzMax",
+            self.gpu.clock_value_mhz_max,
+        );
+        insert(
+            &mut settings,
+            "PowerProfileMode",
+            self.gpu.power_profile_mode.clone(),
+        );
+        insert(&mut settings, "BoostEnabled", self.cpu.boost_enabled);
+        insert(&mut settings, "SmtEnabled", self.cpu.smt_enabled);
+        insert(&mut settings, "CoresEnabled", self.cpu.cores_enabled);
+        insert(
+            &mut settings,
+            "CoresEnabledPolicy",
+            self.cpu.cores_enabled_policy.clone(),
+        );
+        insert(
+            &mut settings,
+            "ScalingGovernor",
+            self.cpu.scaling_governor.clone(),
+        );
+        insert(&mut settings, "ScalingMinFreq", self.cpu.scaling_min_freq);
+        insert(&mut settings, "ScalingMaxFreq", self.cpu.scaling_max_freq);
+        insert(
+            &mut settings,
+            "PstateStatus",
+            self.cpu.pstate_status.clone(),
+        );
+        insert(
+            &mut settings,
+            "EnergyPerformancePreference",
+            self.cpu.energy_performance_preference.clone(),
+        );
+        insert(&mut settings, "MinPerfPct", self.cpu.min_perf_pct);
+        insert(&mut settings, "MaxPerfPct", self.cpu.max_perf_pct);
+        insert(&mut settings, "NoTurbo", self.cpu.no_turbo);
+        settings
+    }
+
+    /// Returns the polkit actions needed to change the settings of this
+    /// profile, the same as for setting their DBus properties.
+    pub fn actions(&self) -> Vec<Action> {
+        let gpu = &self.gpu;
+        let cpu = &self.cpu;
+        let mut actions = Vec::new();
+        if gpu.tdp.is_some()
+            || gpu.boost.is_some()
+            || gpu.thermal_throttle_limit_c.is_some()
+            || gpu.power_profile.is_some()
+        {
+            actions.push(Action::SetTdp);
+        }
+        if gpu.manual_clock.is_some()
+            || gpu.clock_value_mhz_min.is_some()
+            || gpu.clock_value_mhz_max.is_some()
+            || gpu.power_profile_mode.is_some()
+        {
+            actions.push(Action::SetGpuClock);
+        }
+        if cpu.boost_enabled.is_some()
+            || cpu.smt_enabled.is_some()
+            || cpu.cores_enabled.is_some()
+            || cpu.cores_enabled_policy.is_some()
+        {
+            actions.push(Action::SetCpuTopology);
+        }
+        if cpu.scaling_governor.is_some()
+            || cpu.scaling_min_freq.is_some()
+            || cpu.scaling_max_freq.is_some()
+            || cpu.pstate_status.is_some()
+            || cpu.energy_performance_preference.is_some()
+            || cpu.min_perf_pct.is_some()
+            || cpu.max_perf_pct.is_some()
+            || cpu.no_turbo.is_some()
+        {
+            actions.push(Action::SetCpuFrequency);
+        }
+        actions
+    }
+
+    /// Check that the sender of the message with the given header is
+    /// authorized to change all settings of this profile
+    async fn authorize(
+        &self,
+        connection: &Connection,
+        header: Option<&Header<'_>>,
+    ) -> fdo::Result<()> {
+        for action in self.actions() {
+            polkit::check(connection, header, action).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Stores named profiles on disk and applies them to the CPU and all GPU
+/// cards with a TDP interface in a single call.
+pub struct ProfileManager {
+    connection: Connection,
+    state: StateStore,
+    path: PathBuf,
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl ProfileManager {
+    /// Load the profiles from the given path. If no profiles were saved yet,
+    /// default profiles for the detected hardware are used.
+    pub fn new<P: AsRef<Path>>(connection: Connection, state: StateStore, path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let profiles = match fs::read_to_string(&path) {
+            Ok(content) => match toml::from_str(&content) {
+                Ok(profiles) => {
+                    log::info!("Loaded profiles from {:?}", path);
+                    profiles
+                }
+                Err(e) => {
+                    log::warn!("Ignoring invalid profiles file {:?}: {e}", path);
+                    BTreeMap::new()
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                log::info!("No saved profiles found at {:?}. Using defaults.", path);
+                default_profiles()
+            }
+            Err(e) => {
+                log::warn!("Failed to read profiles file {:?}: {e}", path);
+                BTreeMap::new()
+            }
+        };
+
+        Self {
+            connection,
+            state,
+            path,
+            profiles,
+        }
+    }
+
+    /// Returns the profile with the given name
+    pub fn profile(&self, name: &str) -> Option<Profile> {
+        self.profiles.get(name).cloned()
+    }
+
+    /// Write the given profiles to disk
+    fn save(&self, profiles: &BTreeMap<String, Profile>) -> fdo::Result<()> {
+        let content = toml::to_string(profiles).map_err(|e| fdo::Error::Failed(e.to_string()))?;
+        write_atomic(&self.path, content).map_err(|e| fdo::Error::IOError(e.to_string()))
+    }
+
+    /// Returns the DBus path and name of every card with a TDP interface
+    async fn tdp_cards(&self) -> Vec<(String, String)> {
+        let object_server = self.connection.object_server();
+        let card_paths = match object_server.interface::<_, GPUBus>(GPU_PATH).await {
+            Ok(gpu_bus) => gpu_bus.get().await.card_paths(),
+            Err(_) => vec![],
+        };
+
+        let mut cards = Vec::new();
+        for path in card_paths {
+            if object_server
+                .interface::<_, GPUTDPDBusIface>(path.as_str())
+                .await
+                .is_err()
+            {
+                continue;
+            }
+            let Ok(card) = object_server
+                .interface::<_, GPUDBusInterface>(path.as_str())
+                .await
+            else {
+                continue;
+            };
+            let name = card.get().await.name().await;
+            cards.push((path, name));
+        }
+        cards
+    }
+
+    /// Apply the settings of the given profile to the CPU and every card with
+    /// a TDP interface. Nothing is applied if any of the cards is leased by
+    /// another client than the sender of the message with the given header,
+    /// or if any of the settings is invalid. If a setting fails to apply, the
+    /// settings that were already applied are rolled back. Returns the names
+    /// of the cards the GPU settings were applied to.
+    async fn apply(
+        &self,
+        profile: &Profile,
+        header: Option<&Header<'_>>,
+    ) -> fdo::Result<Vec<String>> {
+        let object_server = self.connection.object_server();
+        let cards = self.tdp_cards().await;
+        if profile.cpu != CpuState::default() {
+            let cpu = object_server.interface::<_, Cpu>(CPU_PATH).await?;
+            cpu.get().await.validate(&profile.cpu)?;
+        }
+        if profile.gpu != CardState::default() {
+            for (path, _) in cards.iter() {
+                let tdp = object_server
+                    .interface::<_, GPUTDPDBusIface>(path.as_str())
+                    .await?;
+                let tdp = tdp.get().await;
+                tdp.check_lease(header).await?;
+                tdp.validate(&profile.gpu).await?;
+            }
+        }
+
+        let mut replaced = Vec::new();
+        let result = self.apply_settings(profile, &cards, &mut replaced).await;
+        let Err(e) = result else {
+            return Ok(cards.into_iter().map(|(_, name)| name).collect());
+        };
+        log::warn!("Failed to apply profile: {e}");
+        match self.roll_back(replaced).await {
+            Ok(()) => Err(e),
+            Err(rollback) => {
+                log::error!("Failed to roll back profile: {rollback}");
+                Err(fdo::Error::IOError(format!(
+                    "{e}. Rolling back the previous settings failed as well: {rollback}"
+                )))
+            }
+        }
+    }
+
+    /// Apply the settings of the given profile to the CPU and the given cards,
+    /// recording the settings each of them replaces in `replaced` before they
+    /// are applied.
+    async fn apply_settings(
+        &self,
+        profile: &Profile,
+        cards: &[(String, String)],
+        replaced: &mut Vec<Replaced>,
+    ) -> fdo::Result<()> {
+        let object_server = self.connection.object_server();
+        if profile.cpu != CpuState::default() {
+            let cpu = object_server.interface::<_, Cpu>(CPU_PATH).await?;
+            let mut iface = cpu.get_mut().await;
+            replaced.push(Replaced::Cpu(iface.current().await.only(&profile.cpu)));
+            iface.apply(&profile.cpu, cpu.signal_emitter()).await?;
+        }
+        if profile.gpu == CardState::default() {
+            return Ok(());
+        }
+
+        for (path, _) in cards {
+            let tdp = object_server
+                .interface::<_, GPUTDPDBusIface>(path.as_str())
+                .await?;
+            let mut iface = tdp.get_mut().await;
+            let previous = iface.current().await.only(&profile.gpu);
+            replaced.push(Replaced::Tdp(path.clone(), previous));
+            iface.apply(&profile.gpu, tdp.signal_emitter()).await?;
+            drop(iface);
+
+            let card = object_server
+                .interface::<_, GPUDBusInterface>(path.as_str())
+                .await?;
+            let mut iface = card.get_mut().await;
+            let previous = iface.current().await.only(&profile.gpu);
+            replaced.push(Replaced::Clocks(path.clone(), previous));
+            iface.apply(&profile.gpu, card.signal_emitter()).await?;
+        }
+        Ok(())
+    }
+
+    /// Restore the given replaced settings in the reverse order they were
+    /// applied, and save them. All of them are restored even if one fails, and
+    /// the first error is returned.
+    async fn roll_back(&self, replaced: Vec<Replaced>) -> fdo::Result<()> {
+        let object_server = self.connection.object_server();
+        let mut result = Ok(());
+        for replaced in replaced.into_iter().rev() {
+            log::info!("Rolling back {replaced:?}");
+            let rollback = match &replaced {
+                Replaced::Cpu(previous) => {
+                    match object_server.interface::<_, Cpu>(CPU_PATH).await {
+                        Ok(cpu) => {
+                            cpu.get_mut()
+                                .await
+                                .apply(previous, cpu.signal_emitter())
+                                .await
+                        }
+                        Err(e) => Err(e.into()),
+                    }
+                }
+                Replaced::Tdp(path, previous) => {
+                    match object_server
+                        .interface::<_, GPUTDPDBusIface>(path.as_str())
+                        .await
+                    {
+                        Ok(tdp) => {
+                            tdp.get_mut()
+                                .await
+                                .roll_back(previous, tdp.signal_emitter())
+                                .await
+                        }
+                        Err(e) => Err(e.into()),
+                    }
+                }
+                Replaced::Clocks(path, previous) => {
+                    match object_server
+                        .interface::<_, GPUDBusInterface>(path.as_str())
+                        .await
+                    {
+                        Ok(card) => {
+                            card.get_mut()
+                                .await
+                                .reapply(previous, true, card.signal_emitter())
+                                .await
+                        }
+                        Err(e) => Err(e.into()),
+                    }
+                }
+            };
+            result = result.and(rollback);
+        }
+        result
+    }
+
+    /// Apply the given profile and make it the active profile. The sender of
+    /// the message with the given header needs to be authorized to change its
+    /// settings and cannot override the lease of another client. Calls
+    /// without a header are made by PowerStation itself, e.g. when switching
+    /// between AC and battery power.
+    pub async fn activate(
+        &self,
+        name: String,
+        header: Option<&Header<'_>>,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let profile = self.profiles.get(&name).ok_or_else(|| unknown(&name))?;
+        profile.authorize(&self.connection, header).await?;
+        log::info!("Applying profile {name}: {profile:?}");
+        let cards = self.apply(profile, header).await?;
+        self.state
+            .set_profile(Some(ProfileState { name, cards }))
+            .await;
+
+        self.active_profile_changed(emitter).await?;
+        Ok(())
+    }
+
+    /// Returns true if all settings of the given profile are still saved for
+    /// the CPU and the cards it was applied to. Only the saved state is read,
+    /// since the object server is locked while it emits "InterfacesAdded" for
+    /// this object.
+    async fn is_applied(&self, profile: &Profile, applied: &ProfileState) -> bool {
+        if !self.state.cpu().await.includes(&profile.cpu) {
+            return false;
+        }
+        for name in applied.cards.iter() {
+            if !self.state.card(name).await.includes(&profile.gpu) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[interface(name = "org.shadowblip.Profiles")]
+impl ProfileManager {
+    /// Names of all saved profiles
+    #[zbus(property)]
+    async fn profiles(&self) -> Vec<String> {
+        self.profiles.keys().cloned().collect()
+    }
+
+    /// Name of the last applied profile, or an empty string if no profile was
+    /// applied or any of its settings changed since.
+    #[zbus(property)]
+    async fn active_profile(&self) -> String {
+        let Some(applied) = self.state.profile().await else {
+            return String::new();
+        };
+        match self.profiles.get(&applied.name) {
+            Some(profile) if self.is_applied(profile, &applied).await => applied.name,
+            _ => String::new(),
+        }
+    }
+
+    /// Returns the settings of the given profile, keyed by the name of the
+    /// DBus property they set.
+    async fn get_profile(&self, name: String) -> fdo::Result<HashMap<String, Value<'static>>> {
+        let profile = self.profiles.get(&name).ok_or_else(|| unknown(&name))?;
+        Ok(profile.settings())
+    }
+
+    /// Create or replace the given profile. Settings are keyed by the name of
+    /// the DBus property they set, e.g. {"TDP": 15.0, "SmtEnabled": true}.
+    /// The caller needs to be authorized to change the settings of both the
+    /// new and the replaced profile.
+    async fn save_profile(
+        &mut self,
+        name: String,
+        settings: HashMap<String, OwnedValue>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(header)] header: Header<'_>,
+    ) -> fdo::Result<()> {
+        if name.is_empty() {
+            return Err(fdo::Error::InvalidArgs(String::from(
+                "Profile name cannot be empty",
+            )));
+        }
+        let profile = Profile::from_settings(settings)?;
+        profile.authorize(&self.connection, Some(&header)).await?;
+        if let Some(replaced) = self.profiles.get(&name) {
+            replaced.authorize(&self.connection, Some(&header)).await?;
+        }
+        let mut profiles = self.profiles.clone();
+        profiles.insert(name, profile);
+        self.save(&profiles)?;
+        self.profiles = profiles;
+
+        self.profiles_changed(&emitter).await?;
+        self.active_profile_changed(&emitter).await?;
+        Ok(())
+    }
+
+    /// Delete the given profile. The caller needs to be authorized to change
+    /// its settings.
+    async fn delete_profile(
+        &mut self,
+        name: String,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(header)] header: Header<'_>,
+    ) -> fdo::Result<()> {
+        let mut profiles = self.profiles.clone();
+        let Some(profile) = profiles.remove(&name) else {
+            return Err(unknown(&name));
+        };
+        profile.authorize(&self.connection, Some(&header)).await?;
+        self.save(&profiles)?;
+        self.profiles = profiles;
+        if self.state.profile().await.is_some_and(|p| p.name == name) {
+            self.state.set_profile(None).await;
+        }
+
+        self.profiles_changed(&emitter).await?;
+        self.active_profile_changed(&emitter).await?;
+        Ok(())
+    }
+
+    /// Apply all settings of the given profile to the CPU and every card with
+    /// a TDP interface. Stops at the first setting that fails.
+    async fn apply_profile(
+        &self,
+        name: String,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(header)] header: Header<'_>,
+    ) -> fdo::Result<()> {
+        self.activate(name, Some(&header), &emitter).await
+    }
+}
+
+impl PropertyMonitor for ProfileManager {
+    type Snapshot = String;
+
+    async fn snapshot(&self) -> String {
+        self.active_profile().await
+    }
+
+    async fn emit_changed(
+        &self,
+        previous: &String,
+        current: &String,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()> {
+        // Changing any setting of the active profile deactivates it
+        if previous != current {
+            self.active_profile_changed(emitter).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the default "silent", "balanced" and "turbo" profiles, scaled to
+/// the TDP limits of the detected hardware.
+fn default_profiles() -> BTreeMap<String, Profile> {
+    let Some(hardware) = Hardware::new() else {
+        return BTreeMap::new();
+    };
+    let min_tdp = hardware.min_tdp();
+    let max_tdp = hardware.max_tdp();
+    if max_tdp <= 0.0 || min_tdp > max_tdp {
+        log::info!("No TDP limits found for this hardware. Not adding default profiles.");
+        return BTreeMap::new();
+    }
+
+    // Each profile uses the given share of the TDP range and maximum boost
+    let profile = |tdp_share: f64, boost_share: f64| Profile {
+        gpu: CardState {
+            tdp: Some(
+                (min_tdp + (max_tdp - min_tdp) * tdp_share)
+                    .round()
+                    .clamp(min_tdp, max_tdp),
+            ),
+            boost: Some((hardware.max_boost() * boost_share).round()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    BTreeMap::from([
+        (String::from("silent"), profile(0.25, 0.0)),
+        (String::from("balanced"), profile(0.6, 0.5)),
+        (String::from("turbo"), profile(1.0, 1.0)),
+    ])
+}
+
+/// Returns the error for a profile that does not exist
+fn unknown(name: &str) -> fdo::Error {
+    fdo::Error::InvalidArgs(format!("Unknown profile: {name}"))
+}
+
+/// Convert the given profile setting to the type of its DBus property
+fn setting<T>(key: &str, value: OwnedValue) -> fdo::Result<T>
+where
+    T: TryFrom<OwnedValue, Error = zvariant::Error>,
+{
+    T::try_from(value).map_err(|e| fdo::Error::InvalidArgs(format!("Invalid value for {key}: {e}")))
+}
+
+/// Add the given profile setting if it is set
+fn insert<T>(settings: &mut HashMap<String, Value<'static>>, key: &str, value: Option<T>)
+where
+    T: Into<Value<'static>>,
+{
+    if let Some(value) = value {
+        settings.insert(key.to_string(), value.into());
+    }
+}
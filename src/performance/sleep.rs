@@ -0,0 +1,56 @@
+use futures_lite::StreamExt;
+use zbus::Connection;
+use zbus_macros::proxy;
+
+use crate::performance::overrides::OverrideStack;
+use crate::performance::state::{self, StateStore};
+
+/// Proxy for the logind manager, used to be notified about system sleep
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Login1Manager {
+    /// Emitted with `true` right before the system suspends and with `false`
+    /// after the system has resumed.
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// Watch for the system resuming from sleep and re-apply the last requested
+/// TDP and GPU clock settings, followed by the overrides that are currently
+/// applied. Many devices reset their power limits in firmware on resume.
+pub async fn watch_resume(
+    connection: Connection,
+    state: StateStore,
+    overrides: OverrideStack,
+) -> zbus::Result<()> {
+    let manager = Login1ManagerProxy::new(&connection).await?;
+    let mut signals = manager.receive_prepare_for_sleep().await?;
+
+    tokio::task::spawn(async move {
+        while let Some(signal) = signals.next().await {
+            let args = match signal.args() {
+                Ok(args) => args,
+                Err(e) => {
+                    log::warn!("Failed to parse PrepareForSleep signal: {e}");
+                    continue;
+                }
+            };
+            if args.start {
+                log::info!("System is going to sleep");
+                continue;
+            }
+
+            log::info!("System resumed from sleep. Re-applying settings.");
+            if let Err(e) = state::restore_cards(&connection, &state).await {
+                log::error!("Failed to re-apply settings after resume: {e}");
+            }
+            overrides.reapply().await;
+        }
+        log::warn!("Stopped watching for system sleep");
+    });
+
+    Ok(())
+}
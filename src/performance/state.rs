@@ -0,0 +1,437 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use zbus::Connection;
+
+use crate::performance::cpu::cpu_features::Cpu;
+use crate::performance::gpu::dbus::{gpu::GPUBus, gpu::GPUDBusInterface, tdp::GPUTDPDBusIface};
+use powerstation::constants::{CPU_PATH, GPU_PATH};
+
+/// Path to the file where the last applied settings are persisted
+pub const STATE_PATH: &str = "/var/lib/powerstation/state.toml";
+
+/// Last applied settings for a single GPU card and its TDP interface
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct CardState {
+    pub tdp: Option<f64>,
+    pub boost: Option<f64>,
+    pub thermal_throttle_limit_c: Option<f64>,
+    pub power_profile: Option<String>,
+    pub clock_value_mhz_min: Option<f64>,
+    pub clock_value_mhz_max: Option<f64>,
+    pub manual_clock: Option<bool>,
+    pub power_profile_mode: Option<String>,
+}
+
+impl CardState {
+    /// Overwrite these settings with every value that is set in `other`
+    pub fn merge(&mut self, other: &CardState) {
+        merge(&mut self.tdp, &other.tdp);
+        merge(&mut self.boost, &other.boost);
+        merge(
+            &mut self.thermal_throttle_limit_c,
+            &other.thermal_throttle_limit_c,
+        );
+        merge(&mut self.power_profile, &other.power_profile);
+        merge(&mut self.clock_value_mhz_min, &other.clock_value_mhz_min);
+        merge(&mut self.clock_value_mhz_max, &other.clock_value_mhz_max);
+        merge(&mut self.manual_clock, &other.manual_clock);
+        merge(&mut self.power_profile_mode, &other.power_profile_mode);
+    }
+
+    /// Returns true if every value that is set in `other` has the same value
+    /// in these settings.
+    pub fn includes(&self, other: &CardState) -> bool {
+        includes(&self.tdp, &other.tdp)
+            && includes(&self.boost, &other.boost)
+            && includes(
+                &self.thermal_throttle_limit_c,
+                &other.thermal_throttle_limit_c,
+            )
+            && includes(&self.power_profile, &other.power_profile)
+            && includes(&self.clock_value_mhz_min, &other.clock_value_mhz_min)
+            && includes(&self.clock_value_mhz_max, &other.clock_value_mhz_max)
+            && includes(&self.manual_clock, &other.manual_clock)
+            && includes(&self.power_profile_mode, &other.power_profile_mode)
+    }
+
+    /// Returns these settings limited to the values that are set in `other`,
+    /// e.g. to save the values that `other` is about to replace.
+    pub fn only(&self, other: &CardState) -> CardState {
+        CardState {
+            tdp: only(&self.tdp, &other.tdp),
+            boost: only(&self.boost, &other.boost),
+            thermal_throttle_limit_c: only(
+                &self.thermal_throttle_limit_c,
+                &other.thermal_throttle_limit_c,
+            ),
+            power_profile: only(&self.power_profile, &other.power_profile),
+            clock_value_mhz_min: only(&self.clock_value_mhz_min, &other.clock_value_mhz_min),
+            clock_value_mhz_max: only(&self.clock_value_mhz_max, &other.clock_value_mhz_max),
+            manual_clock: only(&self.manual_clock, &other.manual_clock),
+            power_profile_mode: only(&self.power_profile_mode, &other.power_profile_mode),
+        }
+    }
+
+    /// Returns the values of these settings that differ from `current`, e.g.
+    /// to restore only the values that were changed since.
+    pub fn changed(&self, current: &CardState) -> CardState {
+        CardState {
+            tdp: changed(&self.tdp, &current.tdp),
+            boost: changed(&self.boost, &current.boost),
+            thermal_throttle_limit_c: changed(
+                &self.thermal_throttle_limit_c,
+                &current.thermal_throttle_limit_c,
+            ),
+            power_profile: changed(&self.power_profile, &current.power_profile),
+            clock_value_mhz_min: changed(&self.clock_value_mhz_min, &current.clock_value_mhz_min),
+            clock_value_mhz_max: changed(&self.clock_value_mhz_max, &current.clock_value_mhz_max),
+            manual_clock: changed(&self.manual_clock, &current.manual_clock),
+            power_profile_mode: changed(&self.power_profile_mode, &current.power_profile_mode),
+        }
+    }
+}
+
+/// Last applied CPU settings
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct CpuState {
+    pub boost_enabled: Option<bool>,
+    pub smt_enabled: Option<bool>,
+    pub cores_enabled: Option<u32>,
+    pub cores_enabled_policy: Option<String>,
+    pub scaling_governor: Option<String>,
+    pub scaling_min_freq: Option<u32>,
+    pub scaling_max_freq: Option<u32>,
+    pub pstate_status: Option<String>,
+    pub energy_performance_preference: Option<String>,
+    pub min_perf_pct: Option<u32>,
+    pub max_perf_pct: Option<u32>,
+    pub no_turbo: Option<bool>,
+}
+
+impl CpuState {
+    /// Overwrite these settings with every value that is set in `other`
+    pub fn merge(&mut self, other: &CpuState) {
+        merge(&mut self.boost_enabled, &other.boost_enabled);
+        merge(&mut self.smt_enabled, &other.smt_enabled);
+        merge(&mut self.cores_enabled, &other.cores_enabled);
+        merge(&mut self.cores_enabled_policy, &other.cores_enabled_policy);
+        merge(&mut self.scaling_governor, &other.scaling_governor);
+        merge(&mut self.scaling_min_freq, &other.scaling_min_freq);
+        merge(&mut self.scaling_max_freq, &other.scaling_max_freq);
+        merge(&mut self.pstate_status, &other.pstate_status);
+        merge(
+            &mut self.energy_performance_preference,
+            &other.energy_performance_preference,
+        );
+        merge(&mut self.min_perf_pct, &other.min_perf_pct);
+        merge(&mut self.max_perf_pct, &other.max_perf_pct);
+        merge(&mut self.no_turbo, &other.no_turbo);
+    }
+
+    /// Returns true if every value that is set in `other` has the same value
+    /// in these settings.
+    pub fn includes(&self, other: &CpuState) -> bool {
+        includes(&self.boost_enabled, &other.boost_enabled)
+            && includes(&self.smt_enabled, &other.smt_enabled)
+            && includes(&self.cores_enabled, &other.cores_enabled)
+            && includes(&self.cores_enabled_policy, &other.cores_enabled_policy)
+            && includes(&self.scaling_governor, &other.scaling_governor)
+            && includes(&self.scaling_min_freq, &other.scaling_min_freq)
+            && includes(&self.scaling_max_freq, &other.scaling_max_freq)
+            && includes(&self.pstate_status, &other.pstate_status)
+            && includes(
+                &self.energy_performance_preference,
+                &other.energy_performance_preference,
+            )
+            && includes(&self.min_perf_pct, &other.min_perf_pct)
+            && includes(&self.max_perf_pct, &other.max_perf_pct)
+            && includes(&self.no_turbo, &other.no_turbo)
+    }
+
+    /// Returns these settings limited to the values that are set in `other`,
+    /// e.g. to save the values that `other` is about to replace.
+    pub fn only(&self, other: &CpuState) -> CpuState {
+        CpuState {
+            boost_enabled: only(&self.boost_enabled, &other.boost_enabled),
+            smt_enabled: only(&self.smt_enabled, &other.smt_enabled),
+            cores_enabled: only(&self.cores_enabled, &other.cores_enabled),
+            cores_enabled_policy: only(&self.cores_enabled_policy, &other.cores_enabled_policy),
+            scaling_governor: only(&self.scaling_governor, &other.scaling_governor),
+            scaling_min_freq: only(&self.scaling_min_freq, &other.scaling_min_freq),
+            scaling_max_freq: only(&self.scaling_max_freq, &other.scaling_max_freq),
+            pstate_status: only(&self.pstate_status, &other.pstate_status),
+            energy_performance_preference: only(
+                &self.energy_performance_preference,
+                &other.energy_performance_preference,
+            ),
+            min_perf_pct: only(&self.min_perf_pct, &other.min_perf_pct),
+            max_perf_pct: only(&self.max_perf_pct, &other.max_perf_pct),
+            no_turbo: only(&self.no_turbo, &other.no_turbo),
+        }
+    }
+}
+
+/// The last applied profile
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ProfileState {
+    pub name: String,
+    /// Names of the cards the GPU settings of the profile were applied to
+    #[serde(default)]
+    pub cards: Vec<String>,
+}
+
+/// All settings that should survive a daemon restart or reboot
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct State {
+    #[serde(default)]
+    pub cpu: CpuState,
+    /// Card settings keyed by card name (e.g. "card1")
+    #[serde(default)]
+    pub cards: HashMap<String, CardState>,
+    #[serde(default)]
+    pub profile: Option<ProfileState>,
+    /// Profile selected through the power-profiles-daemon interface
+    #[serde(default)]
+    pub selected_power_profile: Option<String>,
+}
+
+/// Shared handle to the persisted [State]. Every update is written to disk
+/// immediately so the last applied values can be restored on the next start.
+#[derive(Clone)]
+pub struct StateStore {
+    path: PathBuf,
+    state: Arc<Mutex<State>>,
+}
+
+impl StateStore {
+    /// Load the state from the given path. A missing or unreadable state file
+    /// results in an empty state.
+    pub fn load<P: AsRef<Path>>(path: P) -> StateStore {
+        let path = path.as_ref().to_path_buf();
+        let state = match fs::read_to_string(&path) {
+            Ok(content) => match toml::from_str::<State>(&content) {
+                Ok(state) => {
+                    log::info!("Loaded saved state from {:?}", path);
+                    state
+                }
+                Err(e) => {
+                    log::warn!("Ignoring invalid state file {:?}: {e}", path);
+                    State::default()
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                log::info!("No saved state found at {:?}", path);
+                State::default()
+            }
+            Err(e) => {
+                log::warn!("Failed to read state file {:?}: {e}", path);
+                State::default()
+            }
+        };
+
+        StateStore {
+            path,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Returns a copy of the saved CPU settings
+    pub async fn cpu(&self) -> CpuState {
+        self.state.lock().await.cpu.clone()
+    }
+
+    /// Returns a copy of the saved settings for the given card
+    pub async fn card(&self, name: &str) -> CardState {
+        let state = self.state.lock().await;
+        state.cards.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Returns the last applied profile
+    pub async fn profile(&self) -> Option<ProfileState> {
+        self.state.lock().await.profile.clone()
+    }
+
+    /// Update the last applied profile and write it to disk
+    pub async fn set_profile(&self, profile: Option<ProfileState>) {
+        let mut state = self.state.lock().await;
+        state.profile = profile;
+        self.save(&state);
+    }
+
+    /// Returns the profile last selected through the power-profiles-daemon
+    /// interface
+    pub async fn selected_power_profile(&self) -> Option<String> {
+        self.state.lock().await.selected_power_profile.clone()
+    }
+
+    /// Update the selected power-profiles-daemon profile and write it to disk
+    pub async fn set_selected_power_profile(&self, profile: &str) {
+        let mut state = self.state.lock().await;
+        state.selected_power_profile = Some(profile.to_string());
+        self.save(&state);
+    }
+
+    /// Update the saved CPU settings and write them to disk
+    pub async fn update_cpu<F>(&self, f: F)
+    where
+        F: FnOnce(&mut CpuState),
+    {
+        let mut state = self.state.lock().await;
+        f(&mut state.cpu);
+        self.save(&state);
+    }
+
+    /// Update the saved settings for the given card and write them to disk
+    pub async fn update_card<F>(&self, name: &str, f: F)
+    where
+        F: FnOnce(&mut CardState),
+    {
+        let mut state = self.state.lock().await;
+        f(state.cards.entry(name.to_string()).or_default());
+        self.save(&state);
+    }
+
+    /// Write the given state to disk
+    fn save(&self, state: &State) {
+        let content = match toml::to_string(state) {
+            Ok(content) => content,
+            Err(e) => {
+                log::error!("Failed to serialize state: {e}");
+                return;
+            }
+        };
+        if let Err(e) = write_atomic(&self.path, content) {
+            log::error!("Failed to save state file {:?}: {e}", self.path);
+        }
+    }
+}
+
+/// Write the given content to the given path, creating parent directories as
+/// needed. The file is written to a temporary location first and then renamed
+/// so a crash never leaves a partial file.
+pub fn write_atomic<P: AsRef<Path>>(path: P, content: String) -> io::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Re-apply the saved settings to every CPU and GPU object currently served
+/// on the given connection.
+pub async fn restore(connection: &Connection, store: &StateStore) -> zbus::Result<()> {
+    restore_cpu(connection, store).await?;
+    restore_cards(connection, store).await
+}
+
+/// Re-apply the saved CPU settings to the CPU object served on the given
+/// connection.
+pub async fn restore_cpu(connection: &Connection, store: &StateStore) -> zbus::Result<()> {
+    let cpu_state = store.cpu().await;
+    let cpu = connection
+        .object_server()
+        .interface::<_, Cpu>(CPU_PATH)
+        .await?;
+    cpu.get_mut()
+        .await
+        .restore(&cpu_state, cpu.signal_emitter())
+        .await;
+
+    Ok(())
+}
+
+/// Re-apply the saved TDP and clock settings to every GPU card served on the
+/// given connection.
+pub async fn restore_cards(connection: &Connection, store: &StateStore) -> zbus::Result<()> {
+    let gpu_bus = connection
+        .object_server()
+        .interface::<_, GPUBus>(GPU_PATH)
+        .await?;
+    let card_paths = gpu_bus.get().await.card_paths();
+    for path in card_paths {
+        restore_card(connection, store, path.as_str()).await?;
+    }
+
+    Ok(())
+}
+
+/// Re-apply the saved TDP and clock settings to the GPU card served at the
+/// given DBus path.
+pub async fn restore_card(
+    connection: &Connection,
+    store: &StateStore,
+    path: &str,
+) -> zbus::Result<()> {
+    let object_server = connection.object_server();
+    let Ok(card) = object_server.interface::<_, GPUDBusInterface>(path).await else {
+        return Ok(());
+    };
+    let name = card.get().await.name().await;
+    let card_state = store.card(name.as_str()).await;
+    if card_state == CardState::default() {
+        return Ok(());
+    }
+    log::info!("Restoring saved settings for {name}: {card_state:?}");
+
+    // Restore the TDP settings if the card has a TDP interface
+    if let Ok(tdp) = object_server.interface::<_, GPUTDPDBusIface>(path).await {
+        tdp.get_mut()
+            .await
+            .restore(&card_state, tdp.signal_emitter())
+            .await;
+    }
+
+    card.get_mut()
+        .await
+        .restore(&card_state, card.signal_emitter())
+        .await;
+
+    Ok(())
+}
+
+/// Returns true if the given value is within the given limits. Limits that
+/// could not be detected (zero or negative) are not enforced.
+pub fn within_limits(value: f64, min: Option<f64>, max: Option<f64>) -> bool {
+    if min.is_some_and(|min| min > 0.0 && value < min) {
+        return false;
+    }
+    if max.is_some_and(|max| max > 0.0 && value > max) {
+        return false;
+    }
+    true
+}
+
+/// Replace the given setting if the other setting is set
+fn merge<T: Clone>(setting: &mut Option<T>, other: &Option<T>) {
+    if other.is_some() {
+        setting.clone_from(other);
+    }
+}
+
+/// Returns the setting if the other setting is set as well
+fn only<T: Clone>(setting: &Option<T>, other: &Option<T>) -> Option<T> {
+    other.as_ref().and(setting.clone())
+}
+
+/// Returns the setting if it differs from the current setting
+fn changed<T: Clone + PartialEq>(setting: &Option<T>, current: &Option<T>) -> Option<T> {
+    setting
+        .clone()
+        .filter(|value| current.as_ref() != Some(value))
+}
+
+/// Returns true if the other setting is unset or has the same value
+fn includes<T: PartialEq>(setting: &Option<T>, other: &Option<T>) -> bool {
+    other.is_none() || setting == other
+}
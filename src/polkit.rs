@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use zbus::message::{Flags, Header};
+use zbus::zvariant::{Type, Value};
+use zbus::Connection;
+use zbus_macros::proxy;
+
+use powerstation::error::{self, Error};
+
+/// Polkit actions that callers need to be authorized for. The actions are
+/// defined in `org.shadowblip.PowerStation.policy`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Enumerate and query devices
+    Read,
+    /// Change the TDP, boost, thermal limit and power profile of a GPU
+    SetTdp,
+    /// Change the clock and power profile mode of a GPU
+    SetGpuClock,
+    /// Change the CPU boost, SMT and online cores
+    SetCpuTopology,
+    /// Change the CPU scaling governor and frequencies
+    SetCpuFrequency,
+}
+
+impl Action {
+    /// Returns the polkit action id
+    pub fn id(&self) -> &'static str {
+        match self {
+            Action::Read => "org.shadowblip.PowerStation.read",
+            Action::SetTdp => "org.shadowblip.PowerStation.set-tdp",
+            Action::SetGpuClock => "org.shadowblip.PowerStation.set-gpu-clock",
+            Action::SetCpuTopology => "org.shadowblip.PowerStation.set-cpu-topology",
+            Action::SetCpuFrequency => "org.shadowblip.PowerStation.set-cpu-frequency",
+        }
+    }
+}
+
+/// Flag to allow polkit to ask the user to authenticate
+const ALLOW_USER_INTERACTION: u32 = 1;
+
+/// Subject of an authorization check, identifying a client by its unique bus
+/// name.
+#[derive(Serialize, Type)]
+struct Subject<'a> {
+    kind: &'a str,
+    details: HashMap<&'a str, Value<'a>>,
+}
+
+/// Proxy for the polkit authority
+#[proxy(
+    interface = "org.freedesktop.PolicyKit1.Authority",
+    default_service = "org.freedesktop.PolicyKit1",
+    default_path = "/org/freedesktop/PolicyKit1/Authority"
+)]
+trait Authority {
+    /// Returns whether the subject is authorized, whether authentication
+    /// would be needed, and details about the result.
+    fn check_authorization(
+        &self,
+        subject: &Subject<'_>,
+        action_id: &str,
+        details: HashMap<&str, &str>,
+        flags: u32,
+        cancellation_id: &str,
+    ) -> zbus::Result<(bool, bool, HashMap<String, String>)>;
+}
+
+/// Check that the sender of the message with the given header is authorized
+/// for the given action. Calls without a header are made by PowerStation
+/// itself and are always allowed. Callers are denied if polkit cannot be
+/// reached.
+pub async fn check(
+    connection: &Connection,
+    header: Option<&Header<'_>>,
+    action: Action,
+) -> error::Result<()> {
+    let Some(header) = header else {
+        return Ok(());
+    };
+    let Some(sender) = header.sender() else {
+        return Err(Error::PermissionDenied(String::from(
+            "Unable to determine the caller",
+        )));
+    };
+
+    // Only prompt for a password if the caller is able to wait for it
+    let flags = if header
+        .primary()
+        .flags()
+        .contains(Flags::AllowInteractiveAuth)
+    {
+        ALLOW_USER_INTERACTION
+    } else {
+        0
+    };
+    let subject = Subject {
+        kind: "system-bus-name",
+        details: HashMap::from([("name", Value::from(sender.as_str()))]),
+    };
+
+    let authority = AuthorityProxy::new(connection).await?;
+    let result = authority
+        .check_authorization(&subject, action.id(), HashMap::new(), flags, "")
+        .await;
+    match result {
+        Ok((true, _, _)) => Ok(()),
+        Ok((false, _, _)) => {
+            log::info!("{sender} is not authorized for {}", action.id());
+            Err(Error::PermissionDenied(format!(
+                "Not authorized for {}",
+                action.id()
+            )))
+        }
+        Err(e) => {
+            log::warn!("Unable to check authorization of {sender}: {e}");
+            Err(Error::PermissionDenied(String::from(
+                "Unable to check authorization",
+            )))
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Environment variable used to run PowerStation against an alternate
+/// filesystem root, e.g. a fake tree of sysfs and procfs files for testing.
+pub const ROOT_ENV: &str = "POWERSTATION_ROOT";
+
+/// Filesystem root that all sysfs, procfs and data paths are resolved against
+static ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Set the filesystem root. This must be called before any path is resolved,
+/// otherwise the root from [ROOT_ENV] (or "/") remains in use.
+pub fn set_root<P: Into<PathBuf>>(root: P) {
+    let root = root.into();
+    if ROOT.set(root.clone()).is_err() {
+        log::warn!("Filesystem root is already set. Ignoring {:?}", root);
+    }
+}
+
+/// Returns the filesystem root. Defaults to the value of [ROOT_ENV], or "/"
+/// if it is not set.
+pub fn root() -> &'static Path {
+    ROOT.get_or_init(|| match std::env::var_os(ROOT_ENV) {
+        Some(root) if !root.is_empty() => PathBuf::from(root),
+        _ => PathBuf::from("/"),
+    })
+}
+
+/// Returns true if PowerStation is running against an alternate filesystem
+/// root instead of the real system.
+pub fn is_overridden() -> bool {
+    root() != Path::new("/")
+}
+
+/// Resolve the given absolute path against the filesystem root. E.g. with a
+/// root of "/tmp/fake", "/sys/class/drm" becomes "/tmp/fake/sys/class/drm".
+pub fn path(path: &str) -> String {
+    if !is_overridden() {
+        return path.to_string();
+    }
+    let root = root().to_string_lossy();
+    format!(
+        "{0}/{1}",
+        root.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
@@ -0,0 +1,299 @@
+//! Tests for the client library: the XML specifications in `bindings/dbus-xml`
+//! must match the interfaces served by the daemon, and every proxy member must
+//! exist on the served interfaces.
+
+mod common;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use common::*;
+use powerstation::client::{self, Error};
+use zbus::fdo::IntrospectableProxy;
+use zbus::zvariant::Value;
+use zbus_xml::Node;
+
+/// XML specification files and an object in the Steam Deck fixture that
+/// implements the interfaces they describe
+const BINDINGS: &[(&str, &str)] = &[
+    ("org-shadowblip-cpu.xml", CPU_PATH),
+    (
+        "org-shadowblip-cpu-core.xml",
+        "/org/shadowblip/Performance/CPU/Core0",
+    ),
+    (
+        "org-shadowblip-cpu-topology.xml",
+        "/org/shadowblip/Performance/CPU/Package0",
+    ),
+    ("org-shadowblip-gpu.xml", GPU_PATH),
+    ("org-shadowblip-gpu-card.xml", CARD0_PATH),
+    (
+        "org-shadowblip-gpu-card-connector.xml",
+        "/org/shadowblip/Performance/GPU/card0/eDP/1",
+    ),
+    ("org-shadowblip-profiles.xml", PROFILES_PATH),
+];
+
+/// Returns the members of all PowerStation interfaces in the given
+/// introspection XML, keyed by interface name.
+fn interfaces(xml: &str) -> BTreeMap<String, BTreeSet<String>> {
+    let node = Node::from_reader(xml.as_bytes()).expect("invalid introspection XML");
+    let mut interfaces = BTreeMap::new();
+    for iface in node.interfaces() {
+        let name = iface.name().to_string();
+        if !name.starts_with("org.shadowblip.") {
+            continue;
+        }
+        let mut members = BTreeSet::new();
+        for method in iface.methods() {
+            let args: Vec<String> = method
+                .args()
+                .iter()
+                .map(|arg| {
+                    let direction = format!("{:?}", arg.direction()).to_lowercase();
+                    let name = arg.name().unwrap_or_default();
+                    format!("{direction} {name}: {}", arg.ty().inner())
+                })
+                .collect();
+            members.insert(format!("method {}({})", method.name(), args.join(", ")));
+        }
+        for property in iface.properties() {
+            members.insert(format!(
+                "property {}: {} ({:?})",
+                property.name(),
+                property.ty().inner(),
+                property.access()
+            ));
+        }
+        for signal in iface.signals() {
+            let args: Vec<String> = signal
+                .args()
+                .iter()
+                .map(|arg| arg.ty().inner().to_string())
+                .collect();
+            members.insert(format!("signal {}({})", signal.name(), args.join(", ")));
+        }
+        interfaces.insert(name, members);
+    }
+    interfaces
+}
+
+/// Fails with the given property name if the proxy member does not exist on
+/// the served interface. Other errors (e.g. unsupported hardware) are fine.
+fn assert_served<T>(member: &str, result: zbus::Result<T>) {
+    if let Err(Error::NotFound(msg)) = result.map_err(Error::from) {
+        panic!("{member} is not served: {msg}");
+    }
+}
+
+#[tokio::test]
+async fn bindings_match_served_interfaces() {
+    let ps = Harness::start("steam_deck").await;
+
+    let bindings_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("bindings/dbus-xml");
+    for (file, path) in BINDINGS {
+        let expected = std::fs::read_to_string(bindings_dir.join(file)).unwrap();
+        let introspectable = IntrospectableProxy::builder(&ps.connection)
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(*path)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let served = introspectable.introspect().await.unwrap();
+        assert_eq!(
+            interfaces(&expected),
+            interfaces(&served),
+            "{file} does not match the interfaces served at {path}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn proxies_match_served_interfaces() {
+    let ps = Harness::start("steam_deck").await;
+    let conn = &ps.connection;
+
+    let cpu = client::cpu(conn).await.unwrap();
+    assert_served(
+        "CPU.AvailableEnergyPerformancePreferences",
+        cpu.available_energy_performance_preferences().await,
+    );
+    assert_served("CPU.AvailableGovernors", cpu.available_governors().await);
+    assert_served("CPU.BoostEnabled", cpu.boost_enabled().await);
+    assert_served("CPU.CoresCount", cpu.cores_count().await);
+    assert_served("CPU.CoresEnabled", cpu.cores_enabled().await);
+    assert_served("CPU.CoresEnabledPolicy", cpu.cores_enabled_policy().await);
+    assert_served("CPU.CpuinfoMaxFreq", cpu.cpuinfo_max_freq().await);
+    assert_served("CPU.CpuinfoMinFreq", cpu.cpuinfo_min_freq().await);
+    assert_served("CPU.CurrentFreq", cpu.current_freq().await);
+    assert_served(
+        "CPU.EnergyPerformancePreference",
+        cpu.energy_performance_preference().await,
+    );
+    assert_served("CPU.Features", cpu.features().await);
+    assert_served("CPU.MaxPerfPct", cpu.max_perf_pct().await);
+    assert_served("CPU.MinPerfPct", cpu.min_perf_pct().await);
+    assert_served("CPU.NoTurbo", cpu.no_turbo().await);
+    assert_served("CPU.PstateDriver", cpu.pstate_driver().await);
+    assert_served("CPU.PstateStatus", cpu.pstate_status().await);
+    assert_served("CPU.ScalingGovernor", cpu.scaling_governor().await);
+    assert_served("CPU.ScalingMaxFreq", cpu.scaling_max_freq().await);
+    assert_served("CPU.ScalingMinFreq", cpu.scaling_min_freq().await);
+    assert_served("CPU.SmtEnabled", cpu.smt_enabled().await);
+    assert_served("CPU.TelemetryInterval", cpu.telemetry_interval().await);
+    assert_served("CPU.Temperature", cpu.temperature().await);
+    assert_served("CPU.Utilization", cpu.utilization().await);
+    assert_served("CPU.HasFeature", cpu.has_feature("cpb").await);
+    assert_served("CPU.EnumerateL3Caches", cpu.enumerate_l3_caches().await);
+    assert_served("CPU.EnumeratePackages", cpu.enumerate_packages().await);
+
+    let cores = client::cores(conn).await.unwrap();
+    assert_eq!(cores.len(), 8);
+    let core = &cores[1];
+    assert_served("Core.AvailableGovernors", core.available_governors().await);
+    assert_served("Core.Capacity", core.capacity().await);
+    assert_served("Core.CoreId", core.core_id().await);
+    assert_served("Core.CoreType", core.core_type().await);
+    assert_served("Core.CpuinfoMaxFreq", core.cpuinfo_max_freq().await);
+    assert_served("Core.CpuinfoMinFreq", core.cpuinfo_min_freq().await);
+    assert_served("Core.CurrentFreq", core.current_freq().await);
+    assert_served("Core.Number", core.number().await);
+    assert_served("Core.Online", core.online().await);
+    assert_served("Core.ScalingGovernor", core.scaling_governor().await);
+    assert_served("Core.ScalingMaxFreq", core.scaling_max_freq().await);
+    assert_served("Core.ScalingMinFreq", core.scaling_min_freq().await);
+    assert_served("Core.Temperature", core.temperature().await);
+    assert_served("Core.Utilization", core.utilization().await);
+
+    let package = client::TopologyProxy::builder(conn)
+        .path("/org/shadowblip/Performance/CPU/Package0")
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert_served("Topology.Children", package.children().await);
+    assert_served("Topology.Enabled", package.enabled().await);
+    assert_served("Topology.Id", package.id().await);
+    assert_served("Topology.Level", package.level().await);
+    assert_served("Topology.Threads", package.threads().await);
+
+    let card = client::integrated_gpu(conn).await.unwrap();
+    assert_served("Card.Class", card.class().await);
+    assert_served("Card.ClassId", card.class_id().await);
+    assert_served("Card.ClockLimitMhzMax", card.clock_limit_mhz_max().await);
+    assert_served("Card.ClockLimitMhzMin", card.clock_limit_mhz_min().await);
+    assert_served("Card.ClockValueMhzMax", card.clock_value_mhz_max().await);
+    assert_served("Card.ClockValueMhzMin", card.clock_value_mhz_min().await);
+    assert_served("Card.Device", card.device().await);
+    assert_served("Card.DeviceId", card.device_id().await);
+    assert_served("Card.GpuBusyPercent", card.gpu_busy_percent().await);
+    assert_served("Card.LeaseOwner", card.lease_owner().await);
+    assert_served("Card.ManualClock", card.manual_clock().await);
+    assert_served("Card.Name", card.name().await);
+    assert_served("Card.Path", card.path().await);
+    assert_served("Card.PowerProfileMode", card.power_profile_mode().await);
+    assert_served(
+        "Card.PowerProfileModesAvailable",
+        card.power_profile_modes_available().await,
+    );
+    assert_served("Card.RevisionId", card.revision_id().await);
+    assert_served("Card.Subdevice", card.subdevice().await);
+    assert_served("Card.SubdeviceId", card.subdevice_id().await);
+    assert_served("Card.SubvendorId", card.subvendor_id().await);
+    assert_served("Card.Vendor", card.vendor().await);
+    assert_served("Card.VendorId", card.vendor_id().await);
+
+    let tdp = client::integrated_gpu_tdp(conn).await.unwrap();
+    assert_served("TDP.TDP", tdp.tdp().await);
+    assert_served("TDP.MinTdp", tdp.min_tdp().await);
+    assert_served("TDP.MaxTdp", tdp.max_tdp().await);
+    assert_served("TDP.Boost", tdp.boost().await);
+    assert_served("TDP.MaxBoost", tdp.max_boost().await);
+    assert_served(
+        "TDP.ThermalThrottleLimitC",
+        tdp.thermal_throttle_limit_c().await,
+    );
+    assert_served("TDP.PowerProfile", tdp.power_profile().await);
+    assert_served(
+        "TDP.PowerProfilesAvailable",
+        tdp.power_profiles_available().await,
+    );
+
+    let connectors = client::connectors(conn, &card).await.unwrap();
+    assert_eq!(connectors.len(), 2);
+    let connector = &connectors[0];
+    assert_served("Connector.DPMS", connector.dpms().await);
+    assert_served("Connector.Enabled", connector.enabled().await);
+    assert_served("Connector.Id", connector.id().await);
+    assert_served("Connector.Modes", connector.modes().await);
+    assert_served("Connector.Name", connector.name().await);
+    assert_served("Connector.Path", connector.path().await);
+    assert_served("Connector.Status", connector.status().await);
+
+    let profiles = client::profiles(conn).await.unwrap();
+    assert_served("Profiles.ActiveProfile", profiles.active_profile().await);
+    assert_served("Profiles.Profiles", profiles.profiles().await);
+    assert_served("Profiles.GetProfile", profiles.get_profile("turbo").await);
+    assert_served(
+        "Profiles.SaveProfile",
+        profiles.save_profile("custom", HashMap::new()).await,
+    );
+    assert_served(
+        "Profiles.ApplyProfile",
+        profiles.apply_profile("custom").await,
+    );
+    assert_served(
+        "Profiles.DeleteProfile",
+        profiles.delete_profile("custom").await,
+    );
+}
+
+#[tokio::test]
+async fn finds_the_integrated_gpu_tdp() {
+    let ps = Harness::start("steam_deck").await;
+
+    let tdp = client::integrated_gpu_tdp(&ps.connection).await.unwrap();
+    assert_eq!(tdp.inner().path().as_str(), CARD0_PATH);
+    tdp.set_tdp(12.0).await.unwrap();
+    assert_eq!(tdp.tdp().await.unwrap(), 12.0);
+}
+
+#[tokio::test]
+async fn returns_typed_errors() {
+    let ps = Harness::start("intel_laptop").await;
+    let conn = &ps.connection;
+
+    // The Intel fixture has no ACPI platform profiles
+    let tdp = client::tdp(conn, "card0").await.unwrap();
+    let err = tdp.power_profile().await.map_err(Error::from).unwrap_err();
+    assert!(matches!(err, Error::NotSupported(_)), "{err:?}");
+
+    // Methods return PowerStation errors, which are mapped the same way
+    let limits = HashMap::from([("PowerProfile", Value::from("performance"))]);
+    let err = tdp
+        .set_limits(limits)
+        .await
+        .map_err(Error::from)
+        .unwrap_err();
+    assert!(matches!(err, Error::NotSupported(_)), "{err:?}");
+    let limits = HashMap::from([("Turbo", Value::from(true))]);
+    let err = tdp
+        .set_limits(limits)
+        .await
+        .map_err(Error::from)
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidArgs(_)), "{err:?}");
+
+    let card = client::card(conn, "card9").await.unwrap();
+    let err = card.name().await.map_err(Error::from).unwrap_err();
+    assert!(matches!(err, Error::NotFound(_)), "{err:?}");
+
+    let cpu = client::cpu(conn).await.unwrap();
+    let err = cpu
+        .set_cores_enabled(0)
+        .await
+        .map_err(Error::from)
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidArgs(_)), "{err:?}");
+}
@@ -0,0 +1,91 @@
+//! Mocked Feral GameMode daemon, served on a private session bus so tests can
+//! simulate games starting and exiting.
+
+use zbus::{interface, object_server::SignalEmitter, zvariant::ObjectPath, Connection};
+
+const GAMEMODE_BUS_NAME: &str = "com.feralinteractive.GameMode";
+const GAMEMODE_PATH: &str = "/com/feralinteractive/GameMode";
+
+struct GameMode {
+    games: Vec<i32>,
+}
+
+#[interface(name = "com.feralinteractive.GameMode")]
+impl GameMode {
+    #[zbus(property)]
+    fn client_count(&self) -> i32 {
+        self.games.len() as i32
+    }
+
+    #[zbus(signal)]
+    async fn game_registered(
+        emitter: &SignalEmitter<'_>,
+        pid: i32,
+        object_path: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn game_unregistered(
+        emitter: &SignalEmitter<'_>,
+        pid: i32,
+        object_path: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+}
+
+/// Handle to the mocked GameMode object
+pub struct MockGameMode {
+    connection: Connection,
+}
+
+impl MockGameMode {
+    /// Serve the GameMode object on the given connection and claim the
+    /// GameMode bus name.
+    pub async fn serve(connection: &Connection) -> MockGameMode {
+        connection
+            .object_server()
+            .at(GAMEMODE_PATH, GameMode { games: vec![] })
+            .await
+            .expect("failed to serve GameMode");
+        connection
+            .request_name(GAMEMODE_BUS_NAME)
+            .await
+            .expect("failed to claim GameMode bus name");
+
+        MockGameMode {
+            connection: connection.clone(),
+        }
+    }
+
+    /// Register a game with the given process id
+    pub async fn register_game(&self, pid: i32) {
+        let iface = self.iface().await;
+        iface.get_mut().await.games.push(pid);
+        let path = game_path(pid);
+        GameMode::game_registered(iface.signal_emitter(), pid, path)
+            .await
+            .unwrap();
+    }
+
+    /// Unregister the game with the given process id
+    pub async fn unregister_game(&self, pid: i32) {
+        let iface = self.iface().await;
+        iface.get_mut().await.games.retain(|game| *game != pid);
+        let path = game_path(pid);
+        GameMode::game_unregistered(iface.signal_emitter(), pid, path)
+            .await
+            .unwrap();
+    }
+
+    async fn iface(&self) -> zbus::object_server::InterfaceRef<GameMode> {
+        self.connection
+            .object_server()
+            .interface::<_, GameMode>(GAMEMODE_PATH)
+            .await
+            .unwrap()
+    }
+}
+
+/// Returns the object path GameMode uses for the game with the given pid
+fn game_path(pid: i32) -> ObjectPath<'static> {
+    ObjectPath::try_from(format!("{GAMEMODE_PATH}/Games/{pid}")).unwrap()
+}
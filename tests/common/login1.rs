@@ -0,0 +1,47 @@
+//! Mocked logind manager, served on the private test bus so tests can
+//! simulate the system going to sleep and resuming.
+
+use zbus::{interface, object_server::SignalEmitter, Connection};
+
+const LOGIN1_BUS_NAME: &str = "org.freedesktop.login1";
+const LOGIN1_PATH: &str = "/org/freedesktop/login1";
+
+struct Manager;
+
+#[interface(name = "org.freedesktop.login1.Manager")]
+impl Manager {
+    #[zbus(signal)]
+    async fn prepare_for_sleep(emitter: &SignalEmitter<'_>, start: bool) -> zbus::Result<()>;
+}
+
+/// Handle to the mocked logind manager
+pub struct MockLogin1 {
+    connection: Connection,
+}
+
+impl MockLogin1 {
+    /// Serve the logind manager on the given connection and claim the logind
+    /// bus name.
+    pub async fn serve(connection: &Connection) -> MockLogin1 {
+        connection
+            .object_server()
+            .at(LOGIN1_PATH, Manager)
+            .await
+            .expect("failed to serve logind manager");
+        connection
+            .request_name(LOGIN1_BUS_NAME)
+            .await
+            .expect("failed to claim logind bus name");
+
+        MockLogin1 {
+            connection: connection.clone(),
+        }
+    }
+
+    /// Emit "PrepareForSleep" right before suspending (`true`) or after
+    /// resuming (`false`)
+    pub async fn prepare_for_sleep(&self, start: bool) {
+        let emitter = SignalEmitter::new(&self.connection, LOGIN1_PATH).unwrap();
+        Manager::prepare_for_sleep(&emitter, start).await.unwrap();
+    }
+}
@@ -0,0 +1,429 @@
+//! Test harness that runs PowerStation against a fixture filesystem root on a
+//! private D-Bus bus. Each [Harness] starts its own dbus-daemon and daemon
+//! instance, so tests can run in parallel without touching the real system.
+#![allow(dead_code)]
+
+pub mod gamemode;
+pub mod login1;
+pub mod polkit;
+pub mod upower;
+
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+    time::{Duration, Instant},
+};
+
+use polkit::MockAuthority;
+use tempfile::TempDir;
+use zbus::{
+    fdo::DBusProxy,
+    names::BusName,
+    proxy::{Builder, CacheProperties},
+    zvariant::{OwnedValue, Value},
+    Connection, Proxy,
+};
+
+pub const BUS_NAME: &str = "org.shadowblip.PowerStation";
+pub const CPU_PATH: &str = "/org/shadowblip/Performance/CPU";
+pub const GPU_PATH: &str = "/org/shadowblip/Performance/GPU";
+pub const CARD0_PATH: &str = "/org/shadowblip/Performance/GPU/card0";
+pub const PROFILES_PATH: &str = "/org/shadowblip/Performance/Profiles";
+pub const POWER_PROFILES_PATH: &str = "/org/freedesktop/UPower/PowerProfiles";
+pub const LEGACY_POWER_PROFILES_PATH: &str = "/net/hadess/PowerProfiles";
+
+pub const CPU_IFACE: &str = "org.shadowblip.CPU";
+pub const CORE_IFACE: &str = "org.shadowblip.CPU.Core";
+pub const TOPOLOGY_IFACE: &str = "org.shadowblip.CPU.Topology";
+pub const GPU_IFACE: &str = "org.shadowblip.GPU";
+pub const CARD_IFACE: &str = "org.shadowblip.GPU.Card";
+pub const TDP_IFACE: &str = "org.shadowblip.GPU.Card.TDP";
+pub const CONNECTOR_IFACE: &str = "org.shadowblip.GPU.Card.Connector";
+pub const PROFILES_IFACE: &str = "org.shadowblip.Profiles";
+pub const POWER_PROFILES_IFACE: &str = "org.freedesktop.UPower.PowerProfiles";
+pub const LEGACY_POWER_PROFILES_IFACE: &str = "net.hadess.PowerProfiles";
+
+/// How long to wait for the daemon to claim its bus name
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How long to wait for the daemon to react to an external change
+const CHANGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Configuration for the private dbus-daemon. Everything is allowed since the
+/// bus only exists for the duration of a single test.
+const BUS_CONFIG: &str = r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <listen>unix:path=@SOCKET@</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow user="*"/>
+    <allow own="*"/>
+    <allow send_destination="*"/>
+    <allow receive_sender="*"/>
+  </policy>
+</busconfig>
+"#;
+
+/// A running PowerStation instance serving a copy of a fixture
+pub struct Harness {
+    dir: TempDir,
+    bus: Child,
+    daemon: Option<Child>,
+    address: String,
+    // Session buses started with [Harness::start_session_bus]
+    session_buses: Vec<Child>,
+    pub connection: Connection,
+    /// Polkit authority that authorizes all actions unless they are denied
+    pub authority: MockAuthority,
+}
+
+impl Harness {
+    /// Start PowerStation against a copy of the given fixture in
+    /// `tests/fixtures`.
+    pub async fn start(fixture: &str) -> Harness {
+        Self::start_with(fixture, |_| {}).await
+    }
+
+    /// Start PowerStation against a copy of the given fixture, calling `setup`
+    /// with the fixture root before the daemon is started.
+    pub async fn start_with<F>(fixture: &str, setup: F) -> Harness
+    where
+        F: FnOnce(&Path),
+    {
+        let mut harness = Self::prepare(fixture, setup).await;
+        harness.start_daemon().await;
+        harness
+    }
+
+    /// Start the private bus with a copy of the given fixture without starting
+    /// the daemon, e.g. to serve mocked system services first. Call
+    /// [Harness::start_daemon] to start PowerStation.
+    pub async fn prepare<F>(fixture: &str, setup: F) -> Harness
+    where
+        F: FnOnce(&Path),
+    {
+        let dir = tempfile::tempdir().expect("failed to create temporary directory");
+        let root = dir.path().join("root");
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        copy_dir(&manifest_dir.join("tests/fixtures").join(fixture), &root);
+        copy_dir(
+            &manifest_dir.join("rootfs/usr/share/powerstation/platform"),
+            &root.join("usr/share/powerstation/platform"),
+        );
+        setup(&root);
+
+        let (bus, address) = start_bus(dir.path());
+        let connection = zbus::connection::Builder::address(address.as_str())
+            .expect("invalid bus address")
+            .build()
+            .await
+            .expect("failed to connect to the test bus");
+        let authority = MockAuthority::serve(&connection).await;
+
+        Harness {
+            dir,
+            bus,
+            daemon: None,
+            address,
+            session_buses: vec![],
+            connection,
+            authority,
+        }
+    }
+
+    /// Start another private bus as the session bus of the given user, with
+    /// its socket in `/run/user/<uid>/bus` of the fixture root, and return a
+    /// connection to it
+    pub async fn start_session_bus(&mut self, uid: u32) -> Connection {
+        let dir = self.path(&format!("/run/user/{uid}"));
+        fs::create_dir_all(&dir).unwrap();
+        let (bus, address) = start_bus(&dir);
+        self.session_buses.push(bus);
+        zbus::connection::Builder::address(address.as_str())
+            .expect("invalid bus address")
+            .build()
+            .await
+            .expect("failed to connect to the session bus")
+    }
+
+    /// Stop the daemon and start it again with the same fixture root
+    pub async fn restart(&mut self) {
+        self.stop_daemon();
+        self.start_daemon().await;
+    }
+
+    /// Returns the path of the given absolute path inside the fixture root
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.dir
+            .path()
+            .join("root")
+            .join(path.trim_start_matches('/'))
+    }
+
+    /// Returns the trimmed contents of the given file in the fixture root
+    pub fn read(&self, path: &str) -> String {
+        let path = self.path(path);
+        fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("failed to read {path:?}: {e}"))
+            .trim()
+            .to_string()
+    }
+
+    /// Returns the untrimmed contents of the given file in the fixture root,
+    /// e.g. to check that a shorter value replaced the whole file.
+    pub fn read_raw(&self, path: &str) -> String {
+        let path = self.path(path);
+        fs::read_to_string(&path).unwrap_or_else(|e| panic!("failed to read {path:?}: {e}"))
+    }
+
+    /// Write the given value to a file in the fixture root, e.g. to simulate
+    /// firmware changing a value.
+    pub fn write(&self, path: &str, value: &str) {
+        let path = self.path(path);
+        fs::write(&path, value).unwrap_or_else(|e| panic!("failed to write {path:?}: {e}"));
+    }
+
+    /// Copy the given directory of the fixture root to another path in the
+    /// fixture root. The copy appears at once, like a device added to sysfs.
+    pub fn copy_dir(&self, from: &str, to: &str) {
+        let staging = self.dir.path().join("staging");
+        copy_dir(&self.path(from), &staging);
+        fs::rename(&staging, self.path(to)).unwrap();
+    }
+
+    /// Wait until the given file in the fixture root contains the expected
+    /// value, e.g. after the daemon reacted to a change of a system service.
+    pub async fn wait_for(&self, path: &str, expected: &str) {
+        let start = Instant::now();
+        loop {
+            let value = self.read(path);
+            if value == expected {
+                return;
+            }
+            if start.elapsed() > CHANGE_TIMEOUT {
+                panic!(
+                    "{path} is {value:?} instead of {expected:?}:\n{}",
+                    self.log()
+                );
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Send SIGHUP to the daemon and wait until it reloaded its configuration
+    pub async fn reload(&self) {
+        let reloads = self.log().matches("Reloaded configuration").count();
+        let pid = self
+            .daemon
+            .as_ref()
+            .expect("powerstation is not running")
+            .id();
+        let status = Command::new("kill")
+            .args(["-HUP", &pid.to_string()])
+            .status()
+            .expect("failed to run kill");
+        assert!(status.success(), "failed to send SIGHUP to powerstation");
+
+        let start = Instant::now();
+        while self.log().matches("Reloaded configuration").count() == reloads {
+            if start.elapsed() > CHANGE_TIMEOUT {
+                panic!("powerstation did not reload in time:\n{}", self.log());
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Returns the log output of all daemon runs so far
+    pub fn log(&self) -> String {
+        fs::read_to_string(self.dir.path().join("powerstation.log")).unwrap_or_default()
+    }
+
+    /// Run powerstationctl with the given arguments against the test bus. This
+    /// does not block the test, since the mocked services need to keep
+    /// answering the daemon.
+    pub async fn ctl(&self, args: &[&str]) -> Output {
+        tokio::process::Command::new(env!("CARGO_BIN_EXE_powerstationctl"))
+            .args(args)
+            .env("DBUS_SYSTEM_BUS_ADDRESS", &self.address)
+            .output()
+            .await
+            .expect("failed to run powerstationctl")
+    }
+
+    /// Open another connection to the test bus, e.g. to test clients that
+    /// disconnect.
+    pub async fn connect(&self) -> Connection {
+        zbus::connection::Builder::address(self.address.as_str())
+            .expect("invalid bus address")
+            .build()
+            .await
+            .expect("failed to connect to the test bus")
+    }
+
+    /// Returns an uncached proxy for the given object and interface
+    pub async fn proxy(&self, path: &str, interface: &str) -> Proxy<'static> {
+        Self::service_proxy(&self.connection, BUS_NAME, path, interface).await
+    }
+
+    /// Returns an uncached proxy for the given object and interface of the
+    /// given service, e.g. one of the power-profiles-daemon bus names.
+    pub async fn service_proxy(
+        connection: &Connection,
+        destination: &str,
+        path: &str,
+        interface: &str,
+    ) -> Proxy<'static> {
+        Builder::new(connection)
+            .destination(destination.to_string())
+            .unwrap()
+            .path(path.to_string())
+            .unwrap()
+            .interface(interface.to_string())
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .expect("failed to create proxy")
+    }
+
+    /// Read the given property
+    pub async fn get<T>(&self, path: &str, interface: &str, property: &str) -> T
+    where
+        T: TryFrom<OwnedValue>,
+        T::Error: Into<zbus::Error>,
+    {
+        self.try_get(path, interface, property)
+            .await
+            .unwrap_or_else(|e| panic!("failed to get {interface}.{property}: {e}"))
+    }
+
+    /// Read the given property, returning any error from the daemon
+    pub async fn try_get<T>(&self, path: &str, interface: &str, property: &str) -> zbus::Result<T>
+    where
+        T: TryFrom<OwnedValue>,
+        T::Error: Into<zbus::Error>,
+    {
+        let proxy = self.proxy(path, interface).await;
+        proxy.get_property(property).await
+    }
+
+    /// Set the given property
+    pub async fn set<'a, T>(&self, path: &str, interface: &str, property: &str, value: T)
+    where
+        T: 'a + Into<Value<'a>>,
+    {
+        self.try_set(path, interface, property, value)
+            .await
+            .unwrap_or_else(|e| panic!("failed to set {interface}.{property}: {e}"));
+    }
+
+    /// Set the given property, returning any error from the daemon
+    pub async fn try_set<'a, T>(
+        &self,
+        path: &str,
+        interface: &str,
+        property: &str,
+        value: T,
+    ) -> zbus::fdo::Result<()>
+    where
+        T: 'a + Into<Value<'a>>,
+    {
+        let proxy = self.proxy(path, interface).await;
+        proxy.set_property(property, value).await
+    }
+
+    /// Start the daemon and wait for it to claim its bus name
+    pub async fn start_daemon(&mut self) {
+        let log = File::options()
+            .create(true)
+            .append(true)
+            .open(self.dir.path().join("powerstation.log"))
+            .expect("failed to create log file");
+        let daemon = Command::new(env!("CARGO_BIN_EXE_powerstation"))
+            .env("DBUS_SYSTEM_BUS_ADDRESS", &self.address)
+            .env("POWERSTATION_ROOT", self.path("/"))
+            .env("XDG_DATA_DIRS", "/usr/share")
+            .stdout(log.try_clone().unwrap())
+            .stderr(log)
+            .spawn()
+            .expect("failed to start powerstation");
+        self.daemon = Some(daemon);
+
+        let dbus = DBusProxy::new(&self.connection).await.unwrap();
+        let name = BusName::try_from(BUS_NAME).unwrap();
+        let start = Instant::now();
+        loop {
+            if dbus.name_has_owner(name.clone()).await.unwrap_or(false) {
+                return;
+            }
+            if let Some(status) = self.daemon.as_mut().unwrap().try_wait().unwrap() {
+                panic!("powerstation exited with {status}:\n{}", self.log());
+            }
+            if start.elapsed() > STARTUP_TIMEOUT {
+                panic!("powerstation did not start in time:\n{}", self.log());
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Stop the daemon if it is running
+    fn stop_daemon(&mut self) {
+        if let Some(mut daemon) = self.daemon.take() {
+            let _ = daemon.kill();
+            let _ = daemon.wait();
+        }
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.stop_daemon();
+        for bus in self.session_buses.iter_mut().chain([&mut self.bus]) {
+            let _ = bus.kill();
+            let _ = bus.wait();
+        }
+    }
+}
+
+/// Start a private dbus-daemon in the given directory and return it along
+/// with its address.
+fn start_bus(dir: &Path) -> (Child, String) {
+    let socket = dir.join("bus");
+    let config = BUS_CONFIG.replace("@SOCKET@", socket.to_str().unwrap());
+    let config_path = dir.join("bus.conf");
+    fs::write(&config_path, config).unwrap();
+
+    let mut bus = Command::new("dbus-daemon")
+        .arg(format!("--config-file={}", config_path.display()))
+        .arg("--nofork")
+        .arg("--print-address")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start dbus-daemon; is it installed?");
+
+    // The daemon prints its address once it is ready to accept connections
+    let stdout = bus.stdout.take().unwrap();
+    let mut address = String::new();
+    BufReader::new(stdout)
+        .read_line(&mut address)
+        .expect("failed to read dbus-daemon address");
+
+    (bus, address.trim().to_string())
+}
+
+/// Recursively copy the given directory
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap_or_else(|e| panic!("failed to read {from:?}: {e}")) {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Mocked polkit authority, served on the private test bus so tests can deny
+//! actions and check which authorizations were requested.
+
+use std::collections::{HashMap, HashSet};
+
+use zbus::{interface, zvariant::OwnedValue, Connection};
+
+const POLKIT_BUS_NAME: &str = "org.freedesktop.PolicyKit1";
+const AUTHORITY_PATH: &str = "/org/freedesktop/PolicyKit1/Authority";
+
+struct Authority {
+    denied: HashSet<String>,
+    checks: Vec<(String, String)>,
+}
+
+#[interface(name = "org.freedesktop.PolicyKit1.Authority")]
+impl Authority {
+    fn check_authorization(
+        &mut self,
+        subject: (String, HashMap<String, OwnedValue>),
+        action_id: String,
+        _details: HashMap<String, String>,
+        _flags: u32,
+        _cancellation_id: String,
+    ) -> (bool, bool, HashMap<String, String>) {
+        let (kind, details) = subject;
+        let name = details
+            .get("name")
+            .and_then(|name| String::try_from(name.clone()).ok())
+            .unwrap_or_default();
+        self.checks
+            .push((format!("{kind}:{name}"), action_id.clone()));
+        (!self.denied.contains(&action_id), false, HashMap::new())
+    }
+}
+
+/// Handle to the mocked polkit authority
+pub struct MockAuthority {
+    connection: Connection,
+}
+
+impl MockAuthority {
+    /// Serve the authority on the given connection and claim the polkit bus
+    /// name. All actions are authorized until they are denied.
+    pub async fn serve(connection: &Connection) -> MockAuthority {
+        let authority = Authority {
+            denied: HashSet::new(),
+            checks: vec![],
+        };
+        connection
+            .object_server()
+            .at(AUTHORITY_PATH, authority)
+            .await
+            .expect("failed to serve polkit authority");
+        connection
+            .request_name(POLKIT_BUS_NAME)
+            .await
+            .expect("failed to claim polkit bus name");
+
+        MockAuthority {
+            connection: connection.clone(),
+        }
+    }
+
+    /// Deny the given action to all callers
+    pub async fn deny(&self, action_id: &str) {
+        let iface = self.iface().await;
+        iface.get_mut().await.denied.insert(action_id.to_string());
+    }
+
+    /// Returns the subject and action of every authorization check so far.
+    /// Subjects are formatted as "system-bus-name:<unique name>".
+    pub async fn checks(&self) -> Vec<(String, String)> {
+        self.iface().await.get().await.checks.clone()
+    }
+
+    async fn iface(&self) -> zbus::object_server::InterfaceRef<Authority> {
+        self.connection
+            .object_server()
+            .interface::<_, Authority>(AUTHORITY_PATH)
+            .await
+            .unwrap()
+    }
+}
@@ -0,0 +1,93 @@
+//! Mocked UPower daemon, served on the private test bus so tests can simulate
+//! switching between AC and battery power.
+
+use zbus::{interface, Connection};
+
+const UPOWER_BUS_NAME: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+const DISPLAY_DEVICE_PATH: &str = "/org/freedesktop/UPower/devices/DisplayDevice";
+
+struct UPower {
+    on_battery: bool,
+}
+
+#[interface(name = "org.freedesktop.UPower")]
+impl UPower {
+    #[zbus(property)]
+    fn on_battery(&self) -> bool {
+        self.on_battery
+    }
+}
+
+struct DisplayDevice {
+    percentage: f64,
+}
+
+#[interface(name = "org.freedesktop.UPower.Device")]
+impl DisplayDevice {
+    #[zbus(property)]
+    fn percentage(&self) -> f64 {
+        self.percentage
+    }
+}
+
+/// Handle to the mocked UPower objects
+pub struct MockUPower {
+    connection: Connection,
+}
+
+impl MockUPower {
+    /// Serve the UPower objects on the given connection and claim the UPower
+    /// bus name.
+    pub async fn serve(connection: &Connection, on_battery: bool, percentage: f64) -> MockUPower {
+        let object_server = connection.object_server();
+        object_server
+            .at(UPOWER_PATH, UPower { on_battery })
+            .await
+            .expect("failed to serve UPower");
+        object_server
+            .at(DISPLAY_DEVICE_PATH, DisplayDevice { percentage })
+            .await
+            .expect("failed to serve UPower display device");
+        connection
+            .request_name(UPOWER_BUS_NAME)
+            .await
+            .expect("failed to claim UPower bus name");
+
+        MockUPower {
+            connection: connection.clone(),
+        }
+    }
+
+    /// Switch between AC and battery power
+    pub async fn set_on_battery(&self, on_battery: bool) {
+        let iface = self
+            .connection
+            .object_server()
+            .interface::<_, UPower>(UPOWER_PATH)
+            .await
+            .unwrap();
+        let mut upower = iface.get_mut().await;
+        upower.on_battery = on_battery;
+        upower
+            .on_battery_changed(iface.signal_emitter())
+            .await
+            .unwrap();
+    }
+
+    /// Change the battery charge level
+    pub async fn set_percentage(&self, percentage: f64) {
+        let iface = self
+            .connection
+            .object_server()
+            .interface::<_, DisplayDevice>(DISPLAY_DEVICE_PATH)
+            .await
+            .unwrap();
+        let mut device = iface.get_mut().await;
+        device.percentage = percentage;
+        device
+            .percentage_changed(iface.signal_emitter())
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+//! Integration tests against an Intel laptop fixture: a Tiger Lake CPU with
+//! RAPL power limits and Iris Xe graphics.
+
+mod common;
+
+use common::*;
+use futures_lite::StreamExt;
+
+const LONG_TDP: &str = "/sys/class/powercap/intel-rapl/intel-rapl:0/constraint_0_power_limit_uw";
+const SHORT_TDP: &str = "/sys/class/powercap/intel-rapl/intel-rapl:0/constraint_1_power_limit_uw";
+const INTEL_PSTATE: &str = "/sys/devices/system/cpu/intel_pstate";
+
+#[tokio::test]
+async fn enumerates_the_gpu() {
+    let ps = Harness::start("intel_laptop").await;
+
+    let vendor: String = ps.get(CARD0_PATH, CARD_IFACE, "Vendor").await;
+    let device: String = ps.get(CARD0_PATH, CARD_IFACE, "Device").await;
+    let subdevice: String = ps.get(CARD0_PATH, CARD_IFACE, "Subdevice").await;
+    assert_eq!(vendor, "Intel");
+    assert_eq!(device, "TigerLake-LP GT2 [Iris Xe Graphics]");
+    assert_eq!(subdevice, "TigerLake-LP GT2 [Iris Xe Graphics]");
+}
+
+#[tokio::test]
+async fn reads_tdp_from_rapl() {
+    let ps = Harness::start("intel_laptop").await;
+
+    let tdp: f64 = ps.get(CARD0_PATH, TDP_IFACE, "TDP").await;
+    let boost: f64 = ps.get(CARD0_PATH, TDP_IFACE, "Boost").await;
+    let min: f64 = ps.get(CARD0_PATH, TDP_IFACE, "MinTdp").await;
+    let max: f64 = ps.get(CARD0_PATH, TDP_IFACE, "MaxTdp").await;
+    assert_eq!(tdp, 28.0);
+    assert_eq!(boost, 12.0);
+    assert_eq!(min, 12.0);
+    assert_eq!(max, 28.0);
+}
+
+#[tokio::test]
+async fn writes_tdp_to_rapl_and_keeps_boost() {
+    let ps = Harness::start("intel_laptop").await;
+
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 15.0).await;
+    assert_eq!(ps.read(LONG_TDP), "15000000");
+    assert_eq!(ps.read(SHORT_TDP), "27000000");
+}
+
+#[tokio::test]
+async fn writes_boost_to_rapl() {
+    let ps = Harness::start("intel_laptop").await;
+
+    ps.set(CARD0_PATH, TDP_IFACE, "Boost", 2.0).await;
+    assert_eq!(ps.read(LONG_TDP), "28000000");
+    assert_eq!(ps.read(SHORT_TDP), "30000000");
+
+    ps.set(CARD0_PATH, TDP_IFACE, "Boost", 0.0).await;
+    assert_eq!(ps.read(SHORT_TDP), "28000000");
+}
+
+#[tokio::test]
+async fn replaces_the_previous_values() {
+    let ps = Harness::start("intel_laptop").await;
+
+    // The fixture files end with a newline that must not be left behind
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 15.0).await;
+    assert_eq!(ps.read_raw(LONG_TDP), "15000000");
+    assert_eq!(ps.read_raw(SHORT_TDP), "27000000");
+
+    ps.set(CARD0_PATH, CARD_IFACE, "ClockValueMhzMin", 300.0)
+        .await;
+    ps.set(CARD0_PATH, CARD_IFACE, "ClockValueMhzMax", 950.0)
+        .await;
+    assert_eq!(ps.read_raw("/sys/class/drm/card0/gt_min_freq_mhz"), "300");
+    assert_eq!(ps.read_raw("/sys/class/drm/card0/gt_max_freq_mhz"), "950");
+}
+
+#[tokio::test]
+async fn writes_gpu_clocks() {
+    let ps = Harness::start("intel_laptop").await;
+
+    let min: f64 = ps.get(CARD0_PATH, CARD_IFACE, "ClockLimitMhzMin").await;
+    let max: f64 = ps.get(CARD0_PATH, CARD_IFACE, "ClockLimitMhzMax").await;
+    assert_eq!(min, 100.0);
+    assert_eq!(max, 1300.0);
+
+    ps.set(CARD0_PATH, CARD_IFACE, "ClockValueMhzMin", 300.0)
+        .await;
+    ps.set(CARD0_PATH, CARD_IFACE, "ClockValueMhzMax", 1100.0)
+        .await;
+    assert_eq!(ps.read("/sys/class/drm/card0/gt_min_freq_mhz"), "300");
+    assert_eq!(ps.read("/sys/class/drm/card0/gt_max_freq_mhz"), "1100");
+
+    let result = ps
+        .try_set(CARD0_PATH, CARD_IFACE, "ClockValueMhzMax", 0.0)
+        .await;
+    assert!(result.is_err());
+    assert_eq!(ps.read("/sys/class/drm/card0/gt_max_freq_mhz"), "1100");
+}
+
+#[tokio::test]
+async fn has_no_power_profiles() {
+    let ps = Harness::start("intel_laptop").await;
+
+    let result = ps
+        .try_get::<String>(CARD0_PATH, TDP_IFACE, "PowerProfile")
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn serves_connectors() {
+    let ps = Harness::start("intel_laptop").await;
+
+    let edp_path = format!("{CARD0_PATH}/eDP/1");
+    let hdmi_path = format!("{CARD0_PATH}/HDMI/A/1");
+    let modes: Vec<String> = ps.get(&edp_path, CONNECTOR_IFACE, "Modes").await;
+    let id: u32 = ps.get(&hdmi_path, CONNECTOR_IFACE, "Id").await;
+    let status: String = ps.get(&hdmi_path, CONNECTOR_IFACE, "Status").await;
+    assert_eq!(modes, vec!["1920x1080", "1680x1050", "1280x1024"]);
+    assert_eq!(id, 104);
+    assert_eq!(status, "disconnected");
+}
+
+#[tokio::test]
+async fn reports_no_cpu_boost_without_cpb() {
+    let ps = Harness::start("intel_laptop").await;
+
+    let boost: bool = ps.get(CPU_PATH, CPU_IFACE, "BoostEnabled").await;
+    let smt: bool = ps.get(CPU_PATH, CPU_IFACE, "SmtEnabled").await;
+    assert!(!boost);
+    assert!(smt);
+}
+
+#[tokio::test]
+async fn writes_cores_enabled_without_smt() {
+    let ps = Harness::start("intel_laptop").await;
+
+    ps.set(CPU_PATH, CPU_IFACE, "SmtEnabled", false).await;
+    assert_eq!(ps.read("/sys/devices/system/cpu/smt/control"), "off");
+
+    // Only physical cores can be enabled while SMT is disabled
+    ps.set(CPU_PATH, CPU_IFACE, "CoresEnabled", 2u32).await;
+    for (cpu, online) in [(1, "1"), (2, "0"), (3, "0")] {
+        let path = format!("/sys/bus/cpu/devices/cpu{cpu}/online");
+        assert_eq!(ps.read(&path), online, "cpu{cpu}");
+    }
+}
+
+#[tokio::test]
+async fn writes_intel_pstate_settings() {
+    let ps = Harness::start("intel_laptop").await;
+
+    let driver: String = ps.get(CPU_PATH, CPU_IFACE, "PstateDriver").await;
+    let status: String = ps.get(CPU_PATH, CPU_IFACE, "PstateStatus").await;
+    let preferences: Vec<String> = ps
+        .get(CPU_PATH, CPU_IFACE, "AvailableEnergyPerformancePreferences")
+        .await;
+    assert_eq!(driver, "intel_pstate");
+    assert_eq!(status, "active");
+    assert!(
+        preferences.contains(&"power".to_string()),
+        "{preferences:?}"
+    );
+
+    ps.set(CPU_PATH, CPU_IFACE, "EnergyPerformancePreference", "power")
+        .await;
+    for cpu in 0..8 {
+        let path = format!("/sys/bus/cpu/devices/cpu{cpu}/cpufreq/energy_performance_preference");
+        assert_eq!(ps.read(&path), "power", "cpu{cpu}");
+    }
+    let result = ps
+        .try_set(CPU_PATH, CPU_IFACE, "EnergyPerformancePreference", "fast")
+        .await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::InvalidArgs(_))),
+        "{result:?}"
+    );
+
+    ps.set(CPU_PATH, CPU_IFACE, "MaxPerfPct", 60u32).await;
+    ps.set(CPU_PATH, CPU_IFACE, "NoTurbo", true).await;
+    assert_eq!(ps.read(&format!("{INTEL_PSTATE}/max_perf_pct")), "60");
+    assert_eq!(ps.read(&format!("{INTEL_PSTATE}/no_turbo")), "1");
+    let result = ps.try_set(CPU_PATH, CPU_IFACE, "MinPerfPct", 80u32).await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::InvalidArgs(_))),
+        "{result:?}"
+    );
+    let result = ps.try_set(CPU_PATH, CPU_IFACE, "MaxPerfPct", 120u32).await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::LimitsExceeded(_))),
+        "{result:?}"
+    );
+
+    ps.set(CPU_PATH, CPU_IFACE, "PstateStatus", "passive").await;
+    assert_eq!(ps.read(&format!("{INTEL_PSTATE}/status")), "passive");
+    let result = ps
+        .try_set(CPU_PATH, CPU_IFACE, "PstateStatus", "guided")
+        .await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::InvalidArgs(_))),
+        "{result:?}"
+    );
+}
+
+#[tokio::test]
+async fn reads_hybrid_core_types() {
+    let ps = Harness::start_with("intel_laptop", |root| {
+        for (name, cpus) in [("cpu_core", "0-1,4-5"), ("cpu_atom", "2-3,6-7")] {
+            let dir = root.join("sys/devices").join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("cpus"), cpus).unwrap();
+        }
+    })
+    .await;
+
+    for (core, expected) in [(0, "performance"), (3, "efficiency"), (5, "performance")] {
+        let path = format!("{CPU_PATH}/Core{core}");
+        let core_type: String = ps.get(&path, CORE_IFACE, "CoreType").await;
+        assert_eq!(core_type, expected, "Core{core}");
+    }
+}
+
+/// Write the given CPU times of all CPUs to the fixture's /proc/stat, with
+/// each core reporting a tenth of them
+fn write_proc_stat(root: &std::path::Path, busy: u64, idle: u64) {
+    let mut stat = format!("cpu  {busy} 0 0 {idle} 0 0 0 0 0 0\n");
+    for cpu in 0..8 {
+        stat += &format!("cpu{cpu} {} 0 0 {} 0 0 0 0 0 0\n", busy / 10, idle / 10);
+    }
+    stat += "intr 0\nctxt 0\n";
+    std::fs::write(root.join("proc/stat"), stat).unwrap();
+}
+
+#[tokio::test]
+async fn reads_cpu_telemetry() {
+    let ps = Harness::start_with("intel_laptop", |root| {
+        write_proc_stat(root, 4000, 6000);
+        for cpu in [1, 5] {
+            let path = format!("sys/bus/cpu/devices/cpu{cpu}/cpufreq/scaling_cur_freq");
+            std::fs::write(root.join(path), "2600000").unwrap();
+        }
+        let hwmon = root.join("sys/class/hwmon/hwmon4");
+        std::fs::create_dir_all(&hwmon).unwrap();
+        for (file, value) in [
+            ("name", "coretemp"),
+            ("temp1_label", "Package id 0"),
+            ("temp1_input", "55000"),
+            ("temp2_label", "Core 0"),
+            ("temp2_input", "52000"),
+            ("temp3_label", "Core 1"),
+            ("temp3_input", "61500"),
+        ] {
+            std::fs::write(hwmon.join(file), value).unwrap();
+        }
+    })
+    .await;
+
+    let freq: u32 = ps.get(CPU_PATH, CPU_IFACE, "CurrentFreq").await;
+    assert_eq!(freq, 2000000);
+    let temperature: f64 = ps.get(CPU_PATH, CPU_IFACE, "Temperature").await;
+    assert_eq!(temperature, 55.0);
+    // Cores 1 and 5 are SMT siblings
+    let temperature: f64 = ps
+        .get(&format!("{CPU_PATH}/Core5"), CORE_IFACE, "Temperature")
+        .await;
+    assert_eq!(temperature, 61.5);
+
+    // Utilization is averaged since boot at first, and then computed since the
+    // previous read. Reads shortly after another return the same sample.
+    let utilization: f64 = ps.get(CPU_PATH, CPU_IFACE, "Utilization").await;
+    assert_eq!(utilization, 40.0);
+    write_proc_stat(&ps.path("/"), 5000, 15000);
+    let utilization: f64 = ps.get(CPU_PATH, CPU_IFACE, "Utilization").await;
+    assert_eq!(utilization, 40.0);
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    let utilization: f64 = ps.get(CPU_PATH, CPU_IFACE, "Utilization").await;
+    assert_eq!(utilization, 10.0);
+    let utilization: f64 = ps
+        .get(&format!("{CPU_PATH}/Core3"), CORE_IFACE, "Utilization")
+        .await;
+    assert_eq!(utilization, 10.0);
+
+    // The telemetry signal is only emitted once an interval is set
+    let result = ps
+        .try_set(CPU_PATH, CPU_IFACE, "TelemetryInterval", 10u32)
+        .await;
+    assert!(result.unwrap_err().to_string().contains("InvalidArgs"));
+    let proxy = ps.proxy(CPU_PATH, CPU_IFACE).await;
+    let mut signals = proxy.receive_signal("Telemetry").await.unwrap();
+    ps.set(CPU_PATH, CPU_IFACE, "TelemetryInterval", 100u32)
+        .await;
+    let signal = tokio::time::timeout(std::time::Duration::from_secs(5), signals.next())
+        .await
+        .expect("no telemetry signal was emitted")
+        .unwrap();
+    let (freq, _, temperature, cores_freq, cores_utilization): (u32, f64, f64, Vec<u32>, Vec<f64>) =
+        signal.body().deserialize().unwrap();
+    assert_eq!(freq, 2000000);
+    assert_eq!(temperature, 55.0);
+    assert_eq!(cores_freq[1], 2600000);
+    assert_eq!(cores_utilization.len(), 8);
+
+    // The signal is sent to every client, so setting the interval needs
+    // authorization
+    ps.authority
+        .deny("org.shadowblip.PowerStation.set-cpu-frequency")
+        .await;
+    let result = ps
+        .try_set(CPU_PATH, CPU_IFACE, "TelemetryInterval", 0u32)
+        .await;
+    assert!(result.is_err());
+}
@@ -0,0 +1,100 @@
+//! Integration tests for the powerstationctl command line client, run against
+//! the Steam Deck fixture.
+
+mod common;
+
+use std::process::Output;
+
+use common::*;
+use serde_json::{json, Value};
+
+const SLOW_PPT: &str = "/sys/class/drm/card0/device/hwmon/hwmon5/power1_cap";
+
+/// Returns the JSON printed by a successful command
+fn json_output(output: Output) -> Value {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "command failed: {stderr}");
+    serde_json::from_slice(&output.stdout).expect("invalid JSON output")
+}
+
+#[tokio::test]
+async fn lists_cards() {
+    let ps = Harness::start("steam_deck").await;
+
+    let cards = json_output(ps.ctl(&["cards", "--json"]).await);
+    assert_eq!(cards[0]["name"], "card0");
+    assert_eq!(cards[0]["class"], "integrated");
+    assert_eq!(cards[0]["path"], CARD0_PATH);
+
+    let output = ps.ctl(&["cards"]).await;
+    let table = String::from_utf8_lossy(&output.stdout);
+    assert!(table.contains("VanGogh [AMD Custom GPU 0405]"), "{table}");
+}
+
+#[tokio::test]
+async fn lists_cores_and_connectors() {
+    let ps = Harness::start("steam_deck").await;
+
+    let cores = json_output(ps.ctl(&["cores", "--json"]).await);
+    assert_eq!(cores.as_array().unwrap().len(), 8);
+    assert_eq!(cores[5], json!({"number": 5, "core_id": 1, "online": true}));
+
+    let connectors = json_output(ps.ctl(&["connectors", "--json"]).await);
+    assert_eq!(connectors[0]["name"], "eDP-1");
+    assert_eq!(connectors[0]["modes"], json!(["800x1280"]));
+    assert_eq!(connectors[1]["name"], "DP-1");
+    assert_eq!(connectors[1]["status"], "disconnected");
+}
+
+#[tokio::test]
+async fn gets_and_sets_tdp() {
+    let ps = Harness::start("steam_deck").await;
+
+    let tdp = json_output(ps.ctl(&["tdp", "--json"]).await);
+    assert_eq!(tdp, json!({"tdp": 15.0, "min_tdp": 3.0, "max_tdp": 15.0}));
+
+    let tdp = json_output(ps.ctl(&["--card", "card0", "tdp", "10", "--json"]).await);
+    assert_eq!(tdp["tdp"], 10.0);
+    assert_eq!(ps.read(SLOW_PPT), "10000000");
+}
+
+#[tokio::test]
+async fn reports_errors_from_the_daemon() {
+    let ps = Harness::start("steam_deck").await;
+
+    let output = ps.ctl(&["tdp", "0.5"]).await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.starts_with("Error:"), "{stderr}");
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+}
+
+#[tokio::test]
+async fn sets_cpu_settings() {
+    let ps = Harness::start("steam_deck").await;
+
+    let smt = json_output(ps.ctl(&["smt", "off", "--json"]).await);
+    assert_eq!(smt, json!({"smt_enabled": false}));
+    assert_eq!(ps.read("/sys/devices/system/cpu/smt/control"), "off");
+
+    let smt = json_output(ps.ctl(&["smt", "on", "--json"]).await);
+    assert_eq!(smt, json!({"smt_enabled": true}));
+
+    let cores = json_output(ps.ctl(&["cores-enabled", "4", "--json"]).await);
+    assert_eq!(cores, json!({"cores_enabled": 4, "cores_count": 8}));
+    assert_eq!(ps.read("/sys/bus/cpu/devices/cpu1/online"), "1");
+    assert_eq!(ps.read("/sys/bus/cpu/devices/cpu2/online"), "0");
+}
+
+#[tokio::test]
+async fn lists_and_applies_profiles() {
+    let ps = Harness::start("steam_deck").await;
+
+    let profile = json_output(ps.ctl(&["profile", "silent", "--json"]).await);
+    assert_eq!(profile, json!({"active_profile": "silent"}));
+    assert_eq!(ps.read(SLOW_PPT), "6000000");
+
+    let profiles = json_output(ps.ctl(&["profiles", "--json"]).await);
+    assert_eq!(profiles[1], json!({"name": "silent", "active": true}));
+    assert_eq!(profiles[2], json!({"name": "turbo", "active": false}));
+}
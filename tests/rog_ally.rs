@@ -0,0 +1,430 @@
+//! Integration tests against an ASUS ROG Ally fixture: a Z1 Extreme with
+//! asus-armoury firmware attributes, ACPI platform profiles and an amdgpu
+//! hwmon interface without PPT labels, so the TDP is controlled through the
+//! asus-wmi backend.
+
+mod common;
+
+use common::*;
+use zbus::zvariant::OwnedObjectPath;
+
+const ATTRIBUTES: &str = "/sys/class/firmware-attributes/asus-armoury/attributes";
+const STATE: &str = "/var/lib/powerstation/state.toml";
+
+#[tokio::test]
+async fn reads_tdp_limits_from_dmi_overrides() {
+    let ps = Harness::start("rog_ally").await;
+
+    let min: f64 = ps.get(CARD0_PATH, TDP_IFACE, "MinTdp").await;
+    let max: f64 = ps.get(CARD0_PATH, TDP_IFACE, "MaxTdp").await;
+    let max_boost: f64 = ps.get(CARD0_PATH, TDP_IFACE, "MaxBoost").await;
+    assert_eq!(min, 5.0);
+    assert_eq!(max, 30.0);
+    assert_eq!(max_boost, 10.0);
+}
+
+#[tokio::test]
+async fn reads_tdp_from_firmware_attributes() {
+    let ps = Harness::start("rog_ally").await;
+
+    let tdp: f64 = ps.get(CARD0_PATH, TDP_IFACE, "TDP").await;
+    let boost: f64 = ps.get(CARD0_PATH, TDP_IFACE, "Boost").await;
+    assert_eq!(tdp, 15.0);
+    assert_eq!(boost, 10.0);
+    let backend: String = ps.get(CARD0_PATH, TDP_IFACE, "ActiveTdpBackend").await;
+    assert_eq!(backend, "asus_wmi");
+}
+
+#[tokio::test]
+async fn writes_tdp_to_firmware_attributes() {
+    let ps = Harness::start("rog_ally").await;
+
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 20.0).await;
+    assert_eq!(
+        ps.read(&format!("{ATTRIBUTES}/ppt_pl1_spl/current_value")),
+        "20"
+    );
+    // The boost is kept on top of the new TDP
+    assert_eq!(
+        ps.read(&format!("{ATTRIBUTES}/ppt_pl2_sppt/current_value")),
+        "30"
+    );
+
+    ps.set(CARD0_PATH, TDP_IFACE, "Boost", 5.0).await;
+    assert_eq!(
+        ps.read(&format!("{ATTRIBUTES}/ppt_pl1_spl/current_value")),
+        "20"
+    );
+    assert_eq!(
+        ps.read(&format!("{ATTRIBUTES}/ppt_pl2_sppt/current_value")),
+        "25"
+    );
+    let boost: f64 = ps.get(CARD0_PATH, TDP_IFACE, "Boost").await;
+    assert_eq!(boost, 5.0);
+
+    // Values outside of the limits are refused
+    let result = ps.try_set(CARD0_PATH, TDP_IFACE, "Boost", 30.0).await;
+    assert!(result.is_err());
+    assert_eq!(
+        ps.read(&format!("{ATTRIBUTES}/ppt_pl2_sppt/current_value")),
+        "25"
+    );
+}
+
+#[tokio::test]
+async fn writes_power_profile_to_acpi() {
+    let ps = Harness::start("rog_ally").await;
+
+    let profile: String = ps.get(CARD0_PATH, TDP_IFACE, "PowerProfile").await;
+    assert_eq!(profile, "balanced");
+
+    ps.set(CARD0_PATH, TDP_IFACE, "PowerProfile", "quiet").await;
+    assert_eq!(ps.read("/sys/firmware/acpi/platform_profile"), "quiet");
+
+    ps.set(CARD0_PATH, TDP_IFACE, "PowerProfile", "max-performance")
+        .await;
+    assert_eq!(
+        ps.read("/sys/firmware/acpi/platform_profile"),
+        "performance"
+    );
+
+    let result = ps
+        .try_set(CARD0_PATH, TDP_IFACE, "PowerProfile", "turbo")
+        .await;
+    assert!(result.is_err());
+    assert_eq!(
+        ps.read("/sys/firmware/acpi/platform_profile"),
+        "performance"
+    );
+}
+
+#[tokio::test]
+async fn serves_connectors() {
+    let ps = Harness::start("rog_ally").await;
+
+    let path = format!("{CARD0_PATH}/eDP/1");
+    let id: u32 = ps.get(&path, CONNECTOR_IFACE, "Id").await;
+    let modes: Vec<String> = ps.get(&path, CONNECTOR_IFACE, "Modes").await;
+    assert_eq!(id, 98);
+    assert_eq!(modes, vec!["1920x1080", "1280x720"]);
+}
+
+#[tokio::test]
+async fn writes_cores_enabled() {
+    let ps = Harness::start("rog_ally").await;
+
+    let count: u32 = ps.get(CPU_PATH, CPU_IFACE, "CoresCount").await;
+    assert_eq!(count, 16);
+
+    ps.set(CPU_PATH, CPU_IFACE, "CoresEnabled", 6u32).await;
+    let online: Vec<String> = (1..16)
+        .map(|cpu| ps.read(&format!("/sys/bus/cpu/devices/cpu{cpu}/online")))
+        .collect();
+    // Cores 0-2 and their siblings 8-10 are online
+    let expected: Vec<&str> = (1..16)
+        .map(|cpu| if cpu % 8 < 3 { "1" } else { "0" })
+        .collect();
+    assert_eq!(online, expected);
+}
+
+#[tokio::test]
+async fn writes_amd_pstate_mode_and_epp() {
+    let ps = Harness::start("rog_ally").await;
+
+    let driver: String = ps.get(CPU_PATH, CPU_IFACE, "PstateDriver").await;
+    assert_eq!(driver, "amd-pstate");
+
+    ps.set(CPU_PATH, CPU_IFACE, "PstateStatus", "guided").await;
+    assert_eq!(
+        ps.read("/sys/devices/system/cpu/amd_pstate/status"),
+        "guided"
+    );
+    ps.set(
+        CPU_PATH,
+        CPU_IFACE,
+        "EnergyPerformancePreference",
+        "balance_power",
+    )
+    .await;
+    for cpu in 0..16 {
+        let path = format!("/sys/bus/cpu/devices/cpu{cpu}/cpufreq/energy_performance_preference");
+        assert_eq!(ps.read(&path), "balance_power", "cpu{cpu}");
+    }
+
+    // The performance limits are specific to intel_pstate
+    let result = ps.try_get::<u32>(CPU_PATH, CPU_IFACE, "MaxPerfPct").await;
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("NotSupported"), "{error}");
+}
+
+#[tokio::test]
+async fn ignores_a_switched_off_pstate_driver() {
+    let ps = Harness::start_with("rog_ally", |root| {
+        let status = root.join("sys/devices/system/cpu/amd_pstate/status");
+        std::fs::write(status, "off\n").unwrap();
+        write_cpufreq(root, "scaling_driver", |_| "acpi-cpufreq".to_string());
+    })
+    .await;
+
+    let driver: String = ps.get(CPU_PATH, CPU_IFACE, "PstateDriver").await;
+    assert_eq!(driver, "");
+    let status: String = ps.get(CPU_PATH, CPU_IFACE, "PstateStatus").await;
+    assert_eq!(status, "off");
+
+    // The driver can be switched on again
+    ps.set(CPU_PATH, CPU_IFACE, "PstateStatus", "active").await;
+    let driver: String = ps.get(CPU_PATH, CPU_IFACE, "PstateDriver").await;
+    assert_eq!(driver, "amd-pstate");
+}
+
+/// Returns the numbers of the online CPUs, except for cpu0
+fn online_cores(ps: &Harness) -> Vec<u32> {
+    (1..16)
+        .filter(|cpu| ps.read(&format!("/sys/bus/cpu/devices/cpu{cpu}/online")) == "1")
+        .collect()
+}
+
+/// Write the given cpufreq attribute of every CPU, keyed by physical core
+fn write_cpufreq(root: &std::path::Path, name: &str, value: impl Fn(u32) -> String) {
+    for cpu in 0..16 {
+        let path = root.join(format!("sys/bus/cpu/devices/cpu{cpu}/cpufreq/{name}"));
+        std::fs::write(path, value(cpu % 8)).unwrap();
+    }
+}
+
+/// Write the CPUs sharing an L3 cache with every CPU, keyed by physical core
+fn write_l3_domains(root: &std::path::Path, domain: impl Fn(u32) -> &'static str) {
+    for cpu in 0..16 {
+        let dir = root.join(format!("sys/bus/cpu/devices/cpu{cpu}/cache/index3"));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("shared_cpu_list"), domain(cpu % 8)).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn detects_efficiency_cores() {
+    // Simulate a Z2 Extreme with three Zen 5 and five Zen 5c cores
+    let ps = Harness::start_with("rog_ally", |root| {
+        write_cpufreq(root, "amd_pstate_highest_perf", |core| {
+            if core < 3 { "255" } else { "166" }.to_string()
+        });
+    })
+    .await;
+
+    let core_type: String = ps
+        .get(&format!("{CPU_PATH}/Core9"), CORE_IFACE, "CoreType")
+        .await;
+    assert_eq!(core_type, "performance");
+    let core_type: String = ps
+        .get(&format!("{CPU_PATH}/Core3"), CORE_IFACE, "CoreType")
+        .await;
+    let capacity: u32 = ps
+        .get(&format!("{CPU_PATH}/Core3"), CORE_IFACE, "Capacity")
+        .await;
+    assert_eq!(core_type, "efficiency");
+    assert_eq!(capacity, 666);
+
+    // Performance cores are kept online by default
+    ps.set(CPU_PATH, CPU_IFACE, "CoresEnabled", 6u32).await;
+    assert_eq!(online_cores(&ps), vec![1, 2, 8, 9, 10]);
+
+    // Changing the policy rearranges the enabled cores
+    ps.set(CPU_PATH, CPU_IFACE, "CoresEnabledPolicy", "efficiency")
+        .await;
+    assert_eq!(online_cores(&ps), vec![3, 4, 8, 11, 12]);
+    let state = ps.read("/var/lib/powerstation/state.toml");
+    assert!(
+        state.contains("cores_enabled_policy = \"efficiency\""),
+        "{state}"
+    );
+
+    let result = ps
+        .try_set(CPU_PATH, CPU_IFACE, "CoresEnabledPolicy", "fastest")
+        .await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::InvalidArgs(_))),
+        "{result:?}"
+    );
+}
+
+#[tokio::test]
+async fn keeps_cores_of_one_ccd_online() {
+    // Simulate two CCDs, with the highest ranked cores on the second one
+    let ps = Harness::start_with("rog_ally", |root| {
+        write_cpufreq(root, "amd_pstate_highest_perf", |_| "166".to_string());
+        write_cpufreq(root, "amd_pstate_prefcore_ranking", |core| {
+            if core == 4 || core == 5 { "236" } else { "196" }.to_string()
+        });
+        write_l3_domains(root, |core| if core < 4 { "0-3,8-11" } else { "4-7,12-15" });
+    })
+    .await;
+
+    // Cores of the same kind have no type
+    let core_type: String = ps
+        .get(&format!("{CPU_PATH}/Core4"), CORE_IFACE, "CoreType")
+        .await;
+    assert_eq!(core_type, "");
+
+    ps.set(CPU_PATH, CPU_IFACE, "CoresEnabled", 6u32).await;
+    assert_eq!(online_cores(&ps), vec![4, 5, 8, 12, 13]);
+
+    ps.set(CPU_PATH, CPU_IFACE, "CoresEnabledPolicy", "ccd")
+        .await;
+    assert_eq!(online_cores(&ps), vec![1, 2, 8, 9, 10]);
+}
+
+#[tokio::test]
+async fn serves_topology_of_two_dies() {
+    // Simulate two dies with an L3 cache each, core IDs that restart on every
+    // die and a gap in the CPU numbering
+    let mut ps = Harness::start_with("rog_ally", |root| {
+        for cpu in 0..16 {
+            let dir = root.join(format!("sys/bus/cpu/devices/cpu{cpu}/topology"));
+            std::fs::write(dir.join("die_id"), ((cpu % 8) / 4).to_string()).unwrap();
+            std::fs::write(dir.join("core_id"), (cpu % 4).to_string()).unwrap();
+        }
+        write_l3_domains(root, |core| if core < 4 { "0-3,8-11" } else { "4-7,12-15" });
+        std::fs::remove_dir_all(root.join("sys/bus/cpu/devices/cpu6")).unwrap();
+    })
+    .await;
+    let paths = |paths: &[&str]| -> Vec<OwnedObjectPath> {
+        paths
+            .iter()
+            .map(|path| OwnedObjectPath::try_from(format!("{CPU_PATH}/{path}")).unwrap())
+            .collect()
+    };
+    let online_cores = |ps: &Harness| -> Vec<u32> {
+        (1..16)
+            .filter(|cpu| *cpu != 6)
+            .filter(|cpu| ps.read(&format!("/sys/bus/cpu/devices/cpu{cpu}/online")) == "1")
+            .collect()
+    };
+
+    // Cores are numbered after their sysfs directory
+    let count: u32 = ps.get(CPU_PATH, CPU_IFACE, "CoresCount").await;
+    assert_eq!(count, 15);
+    let proxy = ps.proxy(CPU_PATH, CPU_IFACE).await;
+    let cores: Vec<OwnedObjectPath> = proxy.call("EnumerateCores", &()).await.unwrap();
+    assert_eq!(cores[5..7], paths(&["Core5", "Core7"]));
+    let number: u32 = ps
+        .get(&format!("{CPU_PATH}/Core15"), CORE_IFACE, "Number")
+        .await;
+    assert_eq!(number, 15);
+
+    // Cores with the same ID on different dies are not siblings
+    let packages: Vec<OwnedObjectPath> = proxy.call("EnumeratePackages", &()).await.unwrap();
+    assert_eq!(packages, paths(&["Package0"]));
+    let dies: Vec<OwnedObjectPath> = ps
+        .get(&format!("{CPU_PATH}/Package0"), TOPOLOGY_IFACE, "Children")
+        .await;
+    assert_eq!(dies, paths(&["Package0/Die0", "Package0/Die1"]));
+    let core = format!("{CPU_PATH}/Package0/Die1/Cluster0/Core0");
+    let level: String = ps.get(&core, TOPOLOGY_IFACE, "Level").await;
+    assert_eq!(level, "core");
+    let threads: Vec<OwnedObjectPath> = ps.get(&core, TOPOLOGY_IFACE, "Threads").await;
+    assert_eq!(threads, paths(&["Core4", "Core12"]));
+
+    ps.set(CPU_PATH, CPU_IFACE, "CoresEnabled", 4u32).await;
+    assert_eq!(online_cores(&ps), vec![1, 8, 9]);
+
+    // Whole L3 caches can be brought online or offline
+    let caches: Vec<OwnedObjectPath> = proxy.call("EnumerateL3Caches", &()).await.unwrap();
+    assert_eq!(caches, paths(&["L3Cache0", "L3Cache4"]));
+    let cache = format!("{CPU_PATH}/L3Cache4");
+    let children: Vec<OwnedObjectPath> = ps.get(&cache, TOPOLOGY_IFACE, "Children").await;
+    assert_eq!(children.len(), 4);
+    ps.set(&cache, TOPOLOGY_IFACE, "Enabled", true).await;
+    assert_eq!(online_cores(&ps), vec![1, 4, 5, 7, 8, 9, 12, 13, 14, 15]);
+    ps.set(&cache, TOPOLOGY_IFACE, "Enabled", false).await;
+    assert_eq!(online_cores(&ps), vec![1, 8, 9]);
+    let enabled: bool = ps
+        .get(
+            &format!("{CPU_PATH}/Package0/Die1"),
+            TOPOLOGY_IFACE,
+            "Enabled",
+        )
+        .await;
+    assert!(!enabled);
+
+    // The L3 cache of core 0 cannot be disabled
+    let result = ps
+        .try_set(
+            &format!("{CPU_PATH}/L3Cache0"),
+            TOPOLOGY_IFACE,
+            "Enabled",
+            false,
+        )
+        .await;
+    assert!(result.unwrap_err().to_string().contains("InvalidArgs"));
+
+    // The enabled cores are restored with the CCD policy
+    let state = ps.read(STATE);
+    assert!(state.contains("cores_enabled = 4"), "{state}");
+    assert!(state.contains("cores_enabled_policy = \"ccd\""), "{state}");
+    ps.set(&cache, TOPOLOGY_IFACE, "Enabled", true).await;
+    ps.set(CPU_PATH, CPU_IFACE, "CoresEnabledPolicy", "performance")
+        .await;
+    ps.set(&cache, TOPOLOGY_IFACE, "Enabled", false).await;
+    for cpu in 1..16 {
+        if cpu != 6 {
+            ps.write(&format!("/sys/bus/cpu/devices/cpu{cpu}/online"), "1");
+        }
+    }
+    ps.restart().await;
+    assert_eq!(online_cores(&ps), vec![1, 2, 3, 8, 9, 10, 11]);
+    let policy: String = ps.get(CPU_PATH, CPU_IFACE, "CoresEnabledPolicy").await;
+    assert_eq!(policy, "ccd");
+}
+
+#[tokio::test]
+async fn pairs_offline_cores_with_their_siblings() {
+    // Simulate a Z2 Extreme booted with core 11 offline. The kernel removes the
+    // topology, cache and cpufreq attributes of offline cores, and the online
+    // cores still list it as their sibling.
+    let ps = Harness::start_with("rog_ally", |root| {
+        write_cpufreq(root, "amd_pstate_highest_perf", |core| {
+            if core < 3 { "255" } else { "166" }.to_string()
+        });
+        write_l3_domains(root, |_| "0-15");
+        for cpu in 0..16 {
+            let dir = root.join(format!("sys/bus/cpu/devices/cpu{cpu}/topology"));
+            let core = cpu % 8;
+            let siblings = format!("{core},{}", core + 8);
+            std::fs::write(dir.join("thread_siblings_list"), siblings).unwrap();
+        }
+        let cpu11 = root.join("sys/bus/cpu/devices/cpu11");
+        for dir in ["topology", "cache", "cpufreq"] {
+            std::fs::remove_dir_all(cpu11.join(dir)).unwrap();
+        }
+        std::fs::write(cpu11.join("online"), "0").unwrap();
+    })
+    .await;
+    let paths = |numbers: &[u32]| -> Vec<OwnedObjectPath> {
+        numbers
+            .iter()
+            .map(|n| OwnedObjectPath::try_from(format!("{CPU_PATH}/Core{n}")).unwrap())
+            .collect()
+    };
+
+    let core = format!("{CPU_PATH}/Core11");
+    let core_type: String = ps.get(&core, CORE_IFACE, "CoreType").await;
+    let capacity: u32 = ps.get(&core, CORE_IFACE, "Capacity").await;
+    assert_eq!(core_type, "efficiency");
+    assert_eq!(capacity, 666);
+    let threads: Vec<OwnedObjectPath> = ps
+        .get(
+            &format!("{CPU_PATH}/Package0/Die0/Cluster0/Core3"),
+            TOPOLOGY_IFACE,
+            "Threads",
+        )
+        .await;
+    assert_eq!(threads, paths(&[3, 11]));
+    let threads: Vec<OwnedObjectPath> = ps
+        .get(&format!("{CPU_PATH}/L3Cache0"), TOPOLOGY_IFACE, "Threads")
+        .await;
+    assert_eq!(threads, paths(&(0..16).collect::<Vec<u32>>()));
+
+    // The core is enabled along with its sibling
+    ps.set(CPU_PATH, CPU_IFACE, "CoresEnabled", 8u32).await;
+    assert_eq!(online_cores(&ps), vec![1, 2, 3, 8, 9, 10, 11]);
+}
@@ -0,0 +1,1393 @@
+//! Integration tests against a Steam Deck fixture: a Van Gogh APU with hwmon
+//! slowPPT/fastPPT labels, ACPI platform profiles and 4 cores with SMT.
+
+mod common;
+
+use std::{collections::HashMap, fs};
+
+use common::{gamemode::MockGameMode, login1::MockLogin1, upower::MockUPower, *};
+use futures_lite::StreamExt;
+use zbus::fdo::{
+    InterfacesAdded, InterfacesRemoved, ObjectManagerProxy, PropertiesChangedStream,
+    PropertiesProxy,
+};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+const HWMON: &str = "/sys/class/drm/card0/device/hwmon/hwmon5";
+const SLOW_PPT: &str = "/sys/class/drm/card0/device/hwmon/hwmon5/power1_cap";
+const FAST_PPT: &str = "/sys/class/drm/card0/device/hwmon/hwmon5/power2_cap";
+const STATE: &str = "/var/lib/powerstation/state.toml";
+const PROFILES: &str = "/var/lib/powerstation/profiles.toml";
+const CONFIG: &str = "/etc/powerstation/config.toml";
+const PLATFORM_PROFILE: &str = "/sys/firmware/acpi/platform_profile";
+const PERFORMANCE_LEVEL: &str = "/sys/class/drm/card0/device/power_dpm_force_performance_level";
+const POWER_PROFILE_MODE: &str = "/sys/class/drm/card0/device/pp_power_profile_mode";
+const POWER_PROFILES_NAME: &str = "org.freedesktop.UPower.PowerProfiles";
+
+/// Start PowerStation with the given configuration file content
+async fn start_with_config(config: &str) -> Harness {
+    Harness::start_with("steam_deck", |root| {
+        let path = root.join(CONFIG.trim_start_matches('/'));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, config).unwrap();
+    })
+    .await
+}
+
+#[tokio::test]
+async fn enumerates_the_gpu() {
+    let ps = Harness::start("steam_deck").await;
+
+    let proxy = ps.proxy(GPU_PATH, GPU_IFACE).await;
+    let cards: Vec<OwnedObjectPath> = proxy.call("EnumerateCards", &()).await.unwrap();
+    assert_eq!(cards, vec![OwnedObjectPath::try_from(CARD0_PATH).unwrap()]);
+
+    let vendor: String = ps.get(CARD0_PATH, CARD_IFACE, "Vendor").await;
+    let device: String = ps.get(CARD0_PATH, CARD_IFACE, "Device").await;
+    let class: String = ps.get(CARD0_PATH, CARD_IFACE, "Class").await;
+    assert_eq!(vendor, "AMD");
+    assert_eq!(device, "VanGogh [AMD Custom GPU 0405]");
+    assert_eq!(class, "integrated");
+}
+
+#[tokio::test]
+async fn reads_tdp_from_hwmon() {
+    let ps = Harness::start("steam_deck").await;
+
+    let tdp: f64 = ps.get(CARD0_PATH, TDP_IFACE, "TDP").await;
+    let boost: f64 = ps.get(CARD0_PATH, TDP_IFACE, "Boost").await;
+    assert_eq!(tdp, 15.0);
+    assert_eq!(boost, 5.0);
+}
+
+#[tokio::test]
+async fn reads_tdp_limits_from_platform_database() {
+    let ps = Harness::start("steam_deck").await;
+
+    let min: f64 = ps.get(CARD0_PATH, TDP_IFACE, "MinTdp").await;
+    let max: f64 = ps.get(CARD0_PATH, TDP_IFACE, "MaxTdp").await;
+    let max_boost: f64 = ps.get(CARD0_PATH, TDP_IFACE, "MaxBoost").await;
+    assert_eq!(min, 3.0);
+    assert_eq!(max, 15.0);
+    assert_eq!(max_boost, 5.0);
+}
+
+#[tokio::test]
+async fn writes_tdp_to_hwmon_and_keeps_boost() {
+    let ps = Harness::start("steam_deck").await;
+
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 10.0).await;
+    assert_eq!(ps.read(SLOW_PPT), "10000000");
+    assert_eq!(ps.read(FAST_PPT), "15000000");
+
+    let tdp: f64 = ps.get(CARD0_PATH, TDP_IFACE, "TDP").await;
+    assert_eq!(tdp, 10.0);
+}
+
+#[tokio::test]
+async fn writes_boost_to_fast_ppt() {
+    let ps = Harness::start("steam_deck").await;
+
+    ps.set(CARD0_PATH, TDP_IFACE, "Boost", 2.0).await;
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+    assert_eq!(ps.read(FAST_PPT), "17000000");
+}
+
+/// Subscribe to the property changes of the first card on the given
+/// connection
+async fn receive_card_changes(connection: &zbus::Connection) -> PropertiesChangedStream {
+    PropertiesProxy::builder(connection)
+        .destination(BUS_NAME)
+        .unwrap()
+        .path(CARD0_PATH)
+        .unwrap()
+        .build()
+        .await
+        .unwrap()
+        .receive_properties_changed()
+        .await
+        .unwrap()
+}
+
+/// Wait until "PropertiesChanged" announces the given TDP
+async fn wait_for_tdp_change(changes: &mut PropertiesChangedStream, tdp: f64, timeout: u64) {
+    let wait = async {
+        while let Some(signal) = changes.next().await {
+            let args = signal.args().unwrap();
+            if args.interface_name().as_str() != TDP_IFACE {
+                continue;
+            }
+            if let Some(value) = args.changed_properties().get("TDP") {
+                if f64::try_from(value).unwrap() == tdp {
+                    return;
+                }
+            }
+        }
+    };
+    tokio::time::timeout(std::time::Duration::from_secs(timeout), wait)
+        .await
+        .unwrap_or_else(|_| panic!("no change of the TDP to {tdp} was announced"));
+}
+
+#[tokio::test]
+async fn announces_changes_to_other_clients() {
+    let ps = Harness::start("steam_deck").await;
+    let client = ps.connect().await;
+    let mut changes = receive_card_changes(&client).await;
+
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 10.0).await;
+    wait_for_tdp_change(&mut changes, 10.0, 5).await;
+}
+
+#[tokio::test]
+async fn announces_changes_made_outside_of_powerstation() {
+    let ps = Harness::start("steam_deck").await;
+    let mut changes = receive_card_changes(&ps.connection).await;
+
+    // The properties are polled every 5 seconds
+    ps.write(SLOW_PPT, "8000000");
+    wait_for_tdp_change(&mut changes, 8.0, 12).await;
+}
+
+#[tokio::test]
+async fn rejects_invalid_tdp() {
+    let ps = Harness::start("steam_deck").await;
+
+    let result = ps.try_set(CARD0_PATH, TDP_IFACE, "TDP", 0.5).await;
+    assert!(result.is_err());
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+    assert_eq!(ps.read(&format!("{HWMON}/power2_cap")), "20000000");
+}
+
+#[tokio::test]
+async fn writes_power_profile_to_acpi() {
+    let ps = Harness::start("steam_deck").await;
+
+    let profiles: Vec<String> = ps
+        .get(CARD0_PATH, TDP_IFACE, "PowerProfilesAvailable")
+        .await;
+    assert_eq!(profiles, vec!["low-power", "balanced", "performance"]);
+
+    ps.set(CARD0_PATH, TDP_IFACE, "PowerProfile", "performance")
+        .await;
+    assert_eq!(
+        ps.read("/sys/firmware/acpi/platform_profile"),
+        "performance"
+    );
+
+    // Legacy RyzenAdj profile names are translated
+    ps.set(CARD0_PATH, TDP_IFACE, "PowerProfile", "power-saving")
+        .await;
+    assert_eq!(ps.read("/sys/firmware/acpi/platform_profile"), "balanced");
+}
+
+#[tokio::test]
+async fn writes_manual_clock() {
+    let ps = Harness::start("steam_deck").await;
+
+    let min: f64 = ps.get(CARD0_PATH, CARD_IFACE, "ClockLimitMhzMin").await;
+    let max: f64 = ps.get(CARD0_PATH, CARD_IFACE, "ClockLimitMhzMax").await;
+    assert_eq!(min, 200.0);
+    assert_eq!(max, 1600.0);
+
+    ps.set(CARD0_PATH, CARD_IFACE, "ManualClock", true).await;
+    assert_eq!(
+        ps.read("/sys/class/drm/card0/device/power_dpm_force_performance_level"),
+        "manual"
+    );
+    ps.set(CARD0_PATH, CARD_IFACE, "ManualClock", false).await;
+    assert_eq!(
+        ps.read("/sys/class/drm/card0/device/power_dpm_force_performance_level"),
+        "auto"
+    );
+}
+
+#[tokio::test]
+async fn writes_power_profile_mode() {
+    let ps = Harness::start("steam_deck").await;
+
+    let modes: Vec<String> = ps
+        .get(CARD0_PATH, CARD_IFACE, "PowerProfileModesAvailable")
+        .await;
+    assert_eq!(
+        modes,
+        vec![
+            "BOOTUP_DEFAULT",
+            "3D_FULL_SCREEN",
+            "POWER_SAVING",
+            "VIDEO",
+            "VR",
+            "COMPUTE",
+            "CUSTOM"
+        ]
+    );
+    let mode: String = ps.get(CARD0_PATH, CARD_IFACE, "PowerProfileMode").await;
+    assert_eq!(mode, "BOOTUP_DEFAULT");
+
+    // The kernel selects modes by index. Unlike sysfs, the fixture file is
+    // replaced by the written index, so the changed mode cannot be read back.
+    let _ = ps
+        .try_set(CARD0_PATH, CARD_IFACE, "PowerProfileMode", "VIDEO")
+        .await;
+    assert_eq!(ps.read(POWER_PROFILE_MODE), "3");
+    ps.write(POWER_PROFILE_MODE, " 0 BOOTUP_DEFAULT :\n 3 VIDEO*:\n");
+    let mode: String = ps.get(CARD0_PATH, CARD_IFACE, "PowerProfileMode").await;
+    assert_eq!(mode, "VIDEO");
+
+    let result = ps
+        .try_set(CARD0_PATH, CARD_IFACE, "PowerProfileMode", "TURBO")
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn enforces_hardware_tdp_limits() {
+    let ps = Harness::start("steam_deck").await;
+    let result = ps.try_set(CARD0_PATH, TDP_IFACE, "TDP", 20.0).await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::LimitsExceeded(_))),
+        "{result:?}"
+    );
+    let result = ps.try_set(CARD0_PATH, TDP_IFACE, "Boost", 8.0).await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::LimitsExceeded(_))),
+        "{result:?}"
+    );
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+    assert_eq!(ps.read(FAST_PPT), "20000000");
+
+    let ps = start_with_config("[tdp]\nlimit_policy = \"clamp\"\n").await;
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 2.0).await;
+    assert_eq!(ps.read(SLOW_PPT), "3000000");
+
+    // The limits of the hwmon interface are enforced by the kernel as well
+    let ps = start_with_config("[tdp]\nlimit_policy = \"unsafe\"\n").await;
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 12.0).await;
+    ps.set(CARD0_PATH, TDP_IFACE, "Boost", 7.0).await;
+    assert_eq!(ps.read(FAST_PPT), "19000000");
+    let result = ps.try_set(CARD0_PATH, TDP_IFACE, "TDP", 16.0).await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::LimitsExceeded(_))),
+        "{result:?}"
+    );
+    assert_eq!(ps.read(SLOW_PPT), "12000000");
+}
+
+#[tokio::test]
+async fn reports_and_orders_tdp_backends() {
+    let ps = Harness::start("steam_deck").await;
+    let backends: Vec<String> = ps.get(CARD0_PATH, TDP_IFACE, "TdpBackends").await;
+    assert_eq!(backends, vec!["hwmon"]);
+    let _: f64 = ps.get(CARD0_PATH, TDP_IFACE, "TDP").await;
+    let active: String = ps.get(CARD0_PATH, TDP_IFACE, "ActiveTdpBackend").await;
+    assert_eq!(active, "hwmon");
+
+    // Backends that are not listed in the platform database are not used
+    let dmi_override = r#"
+[[models]]
+model_name = "Jupiter"
+min_tdp = 3.0
+max_tdp = 15.0
+max_boost = 5.0
+tdp_backends = ["ryzenadj"]
+"#;
+    let with_override = |root: &std::path::Path| {
+        let path = root.join("usr/share/powerstation/platform/dmi_overrides_apu_database.toml");
+        let mut database = fs::read_to_string(&path).unwrap();
+        database.push_str(dmi_override);
+        fs::write(path, database).unwrap();
+    };
+    let ps = Harness::start_with("steam_deck", with_override).await;
+    let backends: Vec<String> = ps.get(CARD0_PATH, TDP_IFACE, "TdpBackends").await;
+    assert!(backends.is_empty(), "{backends:?}");
+    assert!(ps
+        .try_set(CARD0_PATH, TDP_IFACE, "TDP", 10.0)
+        .await
+        .is_err());
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+    let active: String = ps.get(CARD0_PATH, TDP_IFACE, "ActiveTdpBackend").await;
+    assert_eq!(active, "");
+
+    // The configured order takes precedence over the platform database
+    let ps = Harness::start_with("steam_deck", |root| {
+        with_override(root);
+        let path = root.join(CONFIG.trim_start_matches('/'));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "[tdp]\nbackends = [\"hwmon\"]\n").unwrap();
+    })
+    .await;
+    let backends: Vec<String> = ps.get(CARD0_PATH, TDP_IFACE, "TdpBackends").await;
+    assert_eq!(backends, vec!["hwmon"]);
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 10.0).await;
+    assert_eq!(ps.read(SLOW_PPT), "10000000");
+}
+
+#[tokio::test]
+async fn loads_config_drop_ins_and_reloads_on_sighup() {
+    const DROP_IN: &str = "/etc/powerstation/config.d/50-policy.toml";
+    let ps = Harness::start_with("steam_deck", |root| {
+        let dir = root.join("etc/powerstation/config.d");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            root.join(CONFIG.trim_start_matches('/')),
+            "[tdp]\nlimit_policy = \"unsafe\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("50-policy.toml"),
+            "[tdp]\nlimit_policy = \"clamp\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("90-typo.toml"),
+            "[tdp]\nlimit_polcy = \"unsafe\"\n",
+        )
+        .unwrap();
+    })
+    .await;
+
+    // Drop-ins replace the values of the main file, and invalid files are
+    // ignored with an error naming the unknown key
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 2.0).await;
+    assert_eq!(ps.read(SLOW_PPT), "3000000");
+    let log = ps.log();
+    assert!(log.contains("90-typo.toml"), "{log}");
+    assert!(log.contains("unknown field `limit_polcy`"), "{log}");
+
+    ps.write(DROP_IN, "[tdp]\nlimit_policy = \"reject\"\n");
+    ps.reload().await;
+    let result = ps.try_set(CARD0_PATH, TDP_IFACE, "TDP", 2.0).await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::LimitsExceeded(_))),
+        "{result:?}"
+    );
+    assert_eq!(ps.read(SLOW_PPT), "3000000");
+}
+
+#[tokio::test]
+async fn sets_tdp_limits_in_one_call() {
+    let ps = Harness::start("steam_deck").await;
+    let proxy = ps.proxy(CARD0_PATH, TDP_IFACE).await;
+
+    let limits = HashMap::from([
+        ("TDP", Value::from(12.0)),
+        ("Boost", Value::from(3.0)),
+        ("PowerProfile", Value::from("performance")),
+    ]);
+    let () = proxy.call("SetLimits", &(limits,)).await.unwrap();
+    assert_eq!(ps.read(SLOW_PPT), "12000000");
+    assert_eq!(ps.read(FAST_PPT), "15000000");
+    assert_eq!(ps.read(PLATFORM_PROFILE), "performance");
+
+    // Nothing is applied if one of the values is invalid
+    let limits = HashMap::from([("TDP", Value::from(8.0)), ("Boost", Value::from(50.0))]);
+    let result: zbus::Result<()> = proxy.call("SetLimits", &(limits,)).await;
+    let Err(zbus::Error::MethodError(name, _, _)) = result else {
+        panic!("Invalid boost was accepted: {result:?}");
+    };
+    assert_eq!(
+        name.as_str(),
+        "org.shadowblip.PowerStation.Error.OutOfRange"
+    );
+    let limits = HashMap::from([
+        ("TDP", Value::from(8.0)),
+        ("ThermalThrottleLimitC", Value::from(80.0)),
+    ]);
+    let result: zbus::Result<()> = proxy.call("SetLimits", &(limits,)).await;
+    assert!(result.is_err());
+    assert_eq!(ps.read(SLOW_PPT), "12000000");
+
+    // Applied values are rolled back if a later one fails
+    fs::remove_file(ps.path(PLATFORM_PROFILE)).unwrap();
+    fs::create_dir(ps.path(PLATFORM_PROFILE)).unwrap();
+    let limits = HashMap::from([
+        ("TDP", Value::from(8.0)),
+        ("PowerProfile", Value::from("low-power")),
+    ]);
+    let result: zbus::Result<()> = proxy.call("SetLimits", &(limits.clone(),)).await;
+    assert!(result.is_err());
+    assert_eq!(ps.read(SLOW_PPT), "12000000");
+    assert_eq!(ps.read(FAST_PPT), "15000000");
+
+    // The previous values are rolled back even if they are outside of the
+    // limits, e.g. when set by firmware
+    ps.write(&format!("{HWMON}/power1_cap_min"), "1000000");
+    ps.write(SLOW_PPT, "2000000");
+    ps.write(FAST_PPT, "5000000");
+    let result: zbus::Result<()> = proxy.call("SetLimits", &(limits.clone(),)).await;
+    assert!(result.is_err());
+    assert_eq!(ps.read(SLOW_PPT), "2000000");
+    assert_eq!(ps.read(FAST_PPT), "5000000");
+
+    // A failed rollback is reported
+    ps.write(&format!("{HWMON}/power1_cap_min"), "3000000");
+    let result: zbus::Result<()> = proxy.call("SetLimits", &(limits,)).await;
+    let Err(zbus::Error::MethodError(_, Some(message), _)) = result else {
+        panic!("Limits were set: {result:?}");
+    };
+    assert!(message.contains("Rolling back"), "{message}");
+}
+
+/// Wait until the given stream of "InterfacesAdded" or "InterfacesRemoved"
+/// signals announces the object at the given path
+async fn wait_for_object<S, T, F>(changes: &mut S, path: &str, object_path: F)
+where
+    S: futures_lite::Stream<Item = T> + Unpin,
+    F: Fn(&T) -> String,
+{
+    let wait = async {
+        while let Some(signal) = changes.next().await {
+            if object_path(&signal) == path {
+                return;
+            }
+        }
+    };
+    tokio::time::timeout(std::time::Duration::from_secs(5), wait)
+        .await
+        .unwrap_or_else(|_| panic!("{path} was not announced"));
+}
+
+/// Returns the paths of all cards
+async fn enumerate_cards(ps: &Harness) -> Vec<OwnedObjectPath> {
+    let proxy = ps.proxy(GPU_PATH, GPU_IFACE).await;
+    proxy.call("EnumerateCards", &()).await.unwrap()
+}
+
+#[tokio::test]
+async fn serves_hotplugged_cards_and_connectors() {
+    let ps = Harness::start("steam_deck").await;
+    let manager = ObjectManagerProxy::builder(&ps.connection)
+        .destination(BUS_NAME)
+        .unwrap()
+        .path("/org/shadowblip/Performance")
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let mut added = manager.receive_interfaces_added().await.unwrap();
+    let mut removed = manager.receive_interfaces_removed().await.unwrap();
+    let added_path = |signal: &InterfacesAdded| signal.args().unwrap().object_path().to_string();
+    let removed_path =
+        |signal: &InterfacesRemoved| signal.args().unwrap().object_path().to_string();
+
+    let connector_path = format!("{CARD0_PATH}/DP/2");
+    ps.copy_dir("/sys/class/drm/card0-DP-1", "/sys/class/drm/card0-DP-2");
+    wait_for_object(&mut added, &connector_path, added_path).await;
+    let name: String = ps.get(&connector_path, CONNECTOR_IFACE, "Name").await;
+    assert_eq!(name, "DP-2");
+
+    std::fs::remove_dir_all(ps.path("/sys/class/drm/card0-DP-2")).unwrap();
+    wait_for_object(&mut removed, &connector_path, removed_path).await;
+
+    let card_path = format!("{GPU_PATH}/card1");
+    ps.copy_dir("/sys/class/drm/card0", "/sys/class/drm/card1");
+    wait_for_object(&mut added, &card_path, added_path).await;
+    let cards = enumerate_cards(&ps).await;
+    assert_eq!(cards.len(), 2);
+    assert!(cards.iter().any(|card| card.as_str() == card_path));
+    let tdp: f64 = ps.get(&card_path, TDP_IFACE, "TDP").await;
+    assert_eq!(tdp, 15.0);
+
+    std::fs::remove_dir_all(ps.path("/sys/class/drm/card1")).unwrap();
+    wait_for_object(&mut removed, &card_path, removed_path).await;
+    let cards = enumerate_cards(&ps).await;
+    assert_eq!(cards, vec![OwnedObjectPath::try_from(CARD0_PATH).unwrap()]);
+}
+
+#[tokio::test]
+async fn leases_cards_to_a_single_client() {
+    let ps = Harness::start("steam_deck").await;
+    let slow_ppt = ps.read(SLOW_PPT);
+    let owner = ps.connect().await;
+    let owner_name = owner.unique_name().unwrap().to_string();
+    let card = Harness::service_proxy(&owner, BUS_NAME, CARD0_PATH, CARD_IFACE).await;
+    let tdp = Harness::service_proxy(&owner, BUS_NAME, CARD0_PATH, TDP_IFACE).await;
+
+    let () = card.call("AcquireLease", &()).await.unwrap();
+    let lease_owner: String = ps.get(CARD0_PATH, CARD_IFACE, "LeaseOwner").await;
+    assert_eq!(lease_owner, owner_name);
+    tdp.set_property("TDP", 12.0).await.unwrap();
+    assert_eq!(ps.read(SLOW_PPT), "12000000");
+
+    // Other clients can neither change settings nor release the lease
+    let result = ps.try_set(CARD0_PATH, TDP_IFACE, "TDP", 8.0).await;
+    let Err(zbus::fdo::Error::AccessDenied(message)) = result else {
+        panic!("TDP was changed by another client: {result:?}");
+    };
+    assert!(message.contains(&owner_name), "{message}");
+    let result = ps
+        .try_set(CARD0_PATH, CARD_IFACE, "ManualClock", true)
+        .await;
+    assert!(result.is_err());
+    let proxy = ps.proxy(CARD0_PATH, CARD_IFACE).await;
+    let result: zbus::Result<()> = proxy.call("ReleaseLease", &()).await;
+    assert!(result.is_err());
+    let profiles = ps.proxy(PROFILES_PATH, PROFILES_IFACE).await;
+    let result: zbus::Result<()> = profiles.call("ApplyProfile", &("turbo",)).await;
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains(&owner_name), "{error}");
+    assert_eq!(ps.read(SLOW_PPT), "12000000");
+
+    // Releasing the lease restores the previous settings
+    let () = card.call("ReleaseLease", &()).await.unwrap();
+    assert_eq!(ps.read(SLOW_PPT), slow_ppt);
+    let lease_owner: String = ps.get(CARD0_PATH, CARD_IFACE, "LeaseOwner").await;
+    assert_eq!(lease_owner, "");
+
+    // So does disconnecting from the bus
+    let () = card.call("AcquireLease", &()).await.unwrap();
+    tdp.set_property("TDP", 12.0).await.unwrap();
+    drop((card, tdp));
+    owner.close().await.unwrap();
+    ps.wait_for(SLOW_PPT, &slow_ppt).await;
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 8.0).await;
+}
+
+#[tokio::test]
+async fn serves_connectors() {
+    let ps = Harness::start("steam_deck").await;
+
+    let proxy = ps.proxy(CARD0_PATH, CARD_IFACE).await;
+    let connectors: Vec<OwnedObjectPath> = proxy.call("EnumerateConnectors", &()).await.unwrap();
+    let mut connectors: Vec<String> = connectors.iter().map(|p| p.to_string()).collect();
+    connectors.sort();
+    let edp_path = format!("{CARD0_PATH}/eDP/1");
+    let dp_path = format!("{CARD0_PATH}/DP/1");
+    assert_eq!(connectors, vec![dp_path.clone(), edp_path.clone()]);
+
+    let name: String = ps.get(&edp_path, CONNECTOR_IFACE, "Name").await;
+    let id: u32 = ps.get(&edp_path, CONNECTOR_IFACE, "Id").await;
+    let status: String = ps.get(&edp_path, CONNECTOR_IFACE, "Status").await;
+    let enabled: bool = ps.get(&edp_path, CONNECTOR_IFACE, "Enabled").await;
+    let modes: Vec<String> = ps.get(&edp_path, CONNECTOR_IFACE, "Modes").await;
+    let dpms: bool = ps.get(&edp_path, CONNECTOR_IFACE, "DPMS").await;
+    assert_eq!(name, "eDP-1");
+    assert_eq!(id, 79);
+    assert_eq!(status, "connected");
+    assert!(enabled);
+    assert_eq!(modes, vec!["800x1280"]);
+    assert!(dpms);
+
+    let status: String = ps.get(&dp_path, CONNECTOR_IFACE, "Status").await;
+    let enabled: bool = ps.get(&dp_path, CONNECTOR_IFACE, "Enabled").await;
+    let modes: Vec<String> = ps.get(&dp_path, CONNECTOR_IFACE, "Modes").await;
+    assert_eq!(status, "disconnected");
+    assert!(!enabled);
+    assert!(modes.is_empty());
+}
+
+#[tokio::test]
+async fn reads_cpu_topology() {
+    let ps = Harness::start("steam_deck").await;
+
+    let count: u32 = ps.get(CPU_PATH, CPU_IFACE, "CoresCount").await;
+    let enabled: u32 = ps.get(CPU_PATH, CPU_IFACE, "CoresEnabled").await;
+    let features: Vec<String> = ps.get(CPU_PATH, CPU_IFACE, "Features").await;
+    assert_eq!(count, 8);
+    assert_eq!(enabled, 8);
+    assert!(features.contains(&"cpb".to_string()));
+
+    let core_id: u32 = ps
+        .get(&format!("{CPU_PATH}/Core5"), CORE_IFACE, "CoreId")
+        .await;
+    assert_eq!(core_id, 1);
+}
+
+#[tokio::test]
+async fn writes_cores_enabled_keeping_siblings_together() {
+    let ps = Harness::start("steam_deck").await;
+
+    ps.set(CPU_PATH, CPU_IFACE, "CoresEnabled", 4u32).await;
+    for (cpu, online) in [
+        (1, "1"),
+        (2, "0"),
+        (3, "0"),
+        (4, "1"),
+        (5, "1"),
+        (6, "0"),
+        (7, "0"),
+    ] {
+        let path = format!("/sys/bus/cpu/devices/cpu{cpu}/online");
+        assert_eq!(ps.read(&path), online, "cpu{cpu}");
+    }
+
+    let enabled: u32 = ps.get(CPU_PATH, CPU_IFACE, "CoresEnabled").await;
+    assert_eq!(enabled, 4);
+}
+
+#[tokio::test]
+async fn writes_core_online() {
+    let ps = Harness::start("steam_deck").await;
+
+    let path = format!("{CPU_PATH}/Core3");
+    ps.set(&path, CORE_IFACE, "Online", false).await;
+    assert_eq!(ps.read("/sys/bus/cpu/devices/cpu3/online"), "0");
+
+    let online: bool = ps.get(&path, CORE_IFACE, "Online").await;
+    assert!(!online);
+}
+
+#[tokio::test]
+async fn writes_smt_and_boost() {
+    let ps = Harness::start("steam_deck").await;
+
+    ps.set(CPU_PATH, CPU_IFACE, "SmtEnabled", false).await;
+    assert_eq!(ps.read("/sys/devices/system/cpu/smt/control"), "off");
+    ps.set(CPU_PATH, CPU_IFACE, "SmtEnabled", true).await;
+    assert_eq!(ps.read("/sys/devices/system/cpu/smt/control"), "on");
+
+    ps.set(CPU_PATH, CPU_IFACE, "BoostEnabled", false).await;
+    assert_eq!(ps.read("/sys/devices/system/cpu/cpufreq/boost"), "0");
+    let boost: bool = ps.get(CPU_PATH, CPU_IFACE, "BoostEnabled").await;
+    assert!(!boost);
+}
+
+#[tokio::test]
+async fn replaces_the_previous_values() {
+    let ps = Harness::start("steam_deck").await;
+
+    // The fixture files end with a newline that must not be left behind
+    ps.set(CPU_PATH, CPU_IFACE, "SmtEnabled", false).await;
+    ps.set(CPU_PATH, CPU_IFACE, "SmtEnabled", true).await;
+    assert_eq!(ps.read_raw("/sys/devices/system/cpu/smt/control"), "on");
+    ps.set(CPU_PATH, CPU_IFACE, "BoostEnabled", false).await;
+    assert_eq!(ps.read_raw("/sys/devices/system/cpu/cpufreq/boost"), "0");
+    ps.set(&format!("{CPU_PATH}/Core3"), CORE_IFACE, "Online", false)
+        .await;
+    assert_eq!(ps.read_raw("/sys/bus/cpu/devices/cpu3/online"), "0");
+}
+
+#[tokio::test]
+async fn writes_cpu_governor_and_frequencies() {
+    let ps = Harness::start("steam_deck").await;
+
+    let governors: Vec<String> = ps.get(CPU_PATH, CPU_IFACE, "AvailableGovernors").await;
+    let governor: String = ps.get(CPU_PATH, CPU_IFACE, "ScalingGovernor").await;
+    let min: u32 = ps.get(CPU_PATH, CPU_IFACE, "CpuinfoMinFreq").await;
+    let max: u32 = ps.get(CPU_PATH, CPU_IFACE, "CpuinfoMaxFreq").await;
+    assert!(
+        governors.contains(&"powersave".to_string()),
+        "{governors:?}"
+    );
+    assert_eq!(governor, "schedutil");
+    assert_eq!((min, max), (1400000, 3500000));
+
+    ps.set(CPU_PATH, CPU_IFACE, "ScalingGovernor", "powersave")
+        .await;
+    ps.set(CPU_PATH, CPU_IFACE, "ScalingMaxFreq", 2000000u32)
+        .await;
+    for cpu in 0..8 {
+        let path = format!("/sys/bus/cpu/devices/cpu{cpu}/cpufreq");
+        assert_eq!(ps.read(&format!("{path}/scaling_governor")), "powersave");
+        assert_eq!(ps.read(&format!("{path}/scaling_max_freq")), "2000000");
+    }
+    let state = ps.read(STATE);
+    assert!(
+        state.contains("scaling_governor = \"powersave\""),
+        "{state}"
+    );
+    assert!(state.contains("scaling_max_freq = 2000000"), "{state}");
+
+    let result = ps
+        .try_set(CPU_PATH, CPU_IFACE, "ScalingGovernor", "turbo")
+        .await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::InvalidArgs(_))),
+        "{result:?}"
+    );
+    let result = ps
+        .try_set(CPU_PATH, CPU_IFACE, "ScalingMaxFreq", 4000000u32)
+        .await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::LimitsExceeded(_))),
+        "{result:?}"
+    );
+    let result = ps
+        .try_set(CPU_PATH, CPU_IFACE, "ScalingMinFreq", 2500000u32)
+        .await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::InvalidArgs(_))),
+        "{result:?}"
+    );
+
+    // Single cores can be set on their own
+    let path = format!("{CPU_PATH}/Core3");
+    ps.set(&path, CORE_IFACE, "ScalingMinFreq", 1600000u32)
+        .await;
+    assert_eq!(
+        ps.read("/sys/bus/cpu/devices/cpu3/cpufreq/scaling_min_freq"),
+        "1600000"
+    );
+    assert_eq!(
+        ps.read("/sys/bus/cpu/devices/cpu2/cpufreq/scaling_min_freq"),
+        "1400000"
+    );
+    let current: u32 = ps.get(&path, CORE_IFACE, "CurrentFreq").await;
+    assert_eq!(current, 2800000);
+}
+
+#[tokio::test]
+async fn checks_polkit_authorization() {
+    let ps = Harness::start("steam_deck").await;
+    let sender = format!("system-bus-name:{}", ps.connection.unique_name().unwrap());
+    let slow_ppt = ps.read(SLOW_PPT);
+
+    ps.authority
+        .deny("org.shadowblip.PowerStation.set-tdp")
+        .await;
+    let result = ps.try_set(CARD0_PATH, TDP_IFACE, "TDP", 12.0).await;
+    assert!(
+        matches!(result, Err(zbus::fdo::Error::AccessDenied(_))),
+        "{result:?}"
+    );
+    assert_eq!(ps.read(SLOW_PPT), slow_ppt);
+
+    // Other actions are authorized separately
+    ps.set(CPU_PATH, CPU_IFACE, "SmtEnabled", false).await;
+    assert_eq!(ps.read("/sys/devices/system/cpu/smt/control"), "off");
+    let checks = ps.authority.checks().await;
+    for action in [
+        "org.shadowblip.PowerStation.set-tdp",
+        "org.shadowblip.PowerStation.set-cpu-topology",
+    ] {
+        assert!(
+            checks.contains(&(sender.clone(), action.to_string())),
+            "{checks:?}"
+        );
+    }
+
+    ps.authority.deny("org.shadowblip.PowerStation.read").await;
+    let proxy = ps.proxy(CPU_PATH, CPU_IFACE).await;
+    let result: zbus::Result<Vec<OwnedObjectPath>> = proxy.call("EnumerateCores", &()).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn saves_and_restores_settings() {
+    let mut ps = Harness::start("steam_deck").await;
+
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 12.0).await;
+    ps.set(CPU_PATH, CPU_IFACE, "BoostEnabled", false).await;
+    let state = ps.read(STATE);
+    assert!(state.contains("tdp = 12.0"), "{state}");
+    assert!(state.contains("boost_enabled = false"), "{state}");
+
+    // Simulate the firmware resetting the values on reboot
+    ps.write(SLOW_PPT, "15000000\n");
+    ps.write(FAST_PPT, "20000000\n");
+    ps.write("/sys/devices/system/cpu/cpufreq/boost", "1\n");
+    ps.restart().await;
+
+    assert_eq!(ps.read(SLOW_PPT), "12000000");
+    assert_eq!(ps.read(FAST_PPT), "17000000");
+    assert_eq!(ps.read("/sys/devices/system/cpu/cpufreq/boost"), "0");
+}
+
+#[tokio::test]
+async fn reapplies_settings_on_resume() {
+    let mut ps = Harness::prepare("steam_deck", |_| {}).await;
+    let login1 = MockLogin1::serve(&ps.connection).await;
+    ps.start_daemon().await;
+
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 10.0).await;
+    assert_eq!(ps.read(SLOW_PPT), "10000000");
+
+    // Firmware resets the power limits during sleep
+    login1.prepare_for_sleep(true).await;
+    ps.write(SLOW_PPT, "15000000");
+    login1.prepare_for_sleep(false).await;
+    ps.wait_for(SLOW_PPT, "10000000").await;
+}
+
+#[tokio::test]
+async fn restores_enabled_cores_without_their_topology() {
+    let mut ps = Harness::start("steam_deck").await;
+    ps.set(CPU_PATH, CPU_IFACE, "CoresEnabled", 4u32).await;
+
+    // The kernel removes the topology of offline cores
+    for cpu in [2, 3, 6, 7] {
+        let path = format!("/sys/bus/cpu/devices/cpu{cpu}");
+        assert_eq!(ps.read(&format!("{path}/online")), "0");
+        fs::remove_dir_all(ps.path(&format!("{path}/topology"))).unwrap();
+    }
+    ps.restart().await;
+
+    let count: u32 = ps.get(CPU_PATH, CPU_IFACE, "CoresEnabled").await;
+    assert_eq!(count, 4);
+    for cpu in 1..8 {
+        let online = ps.read(&format!("/sys/bus/cpu/devices/cpu{cpu}/online"));
+        let expected = if cpu % 4 < 2 { "1" } else { "0" };
+        assert_eq!(online, expected, "cpu{cpu}");
+    }
+}
+
+#[tokio::test]
+async fn skips_saved_tdp_outside_of_limits() {
+    let ps = Harness::start_with("steam_deck", |root| {
+        let state = root.join(STATE.trim_start_matches('/'));
+        std::fs::create_dir_all(state.parent().unwrap()).unwrap();
+        std::fs::write(state, "[cards.card0]\ntdp = 25.0\n").unwrap();
+    })
+    .await;
+
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+}
+
+#[tokio::test]
+async fn derives_default_profiles_from_platform_database() {
+    let ps = Harness::start("steam_deck").await;
+
+    let names: Vec<String> = ps.get(PROFILES_PATH, PROFILES_IFACE, "Profiles").await;
+    assert_eq!(names, vec!["balanced", "silent", "turbo"]);
+
+    let proxy = ps.proxy(PROFILES_PATH, PROFILES_IFACE).await;
+    let balanced: HashMap<String, OwnedValue> =
+        proxy.call("GetProfile", &("balanced",)).await.unwrap();
+    assert_eq!(balanced.len(), 2);
+    assert_eq!(f64::try_from(&balanced["TDP"]).unwrap(), 10.0);
+    assert_eq!(f64::try_from(&balanced["Boost"]).unwrap(), 3.0);
+}
+
+#[tokio::test]
+async fn applies_and_saves_profiles() {
+    let mut ps = Harness::start("steam_deck").await;
+    let proxy = ps.proxy(PROFILES_PATH, PROFILES_IFACE).await;
+
+    let settings = HashMap::from([
+        ("TDP", Value::from(8.0)),
+        ("Boost", Value::from(0.0)),
+        ("CoresEnabled", Value::from(4u32)),
+    ]);
+    let () = proxy
+        .call("SaveProfile", &("quiet", settings))
+        .await
+        .unwrap();
+    let () = proxy.call("ApplyProfile", &("quiet",)).await.unwrap();
+
+    assert_eq!(ps.read(SLOW_PPT), "8000000");
+    assert_eq!(ps.read(FAST_PPT), "8000000");
+    assert_eq!(ps.read("/sys/bus/cpu/devices/cpu2/online"), "0");
+    let active: String = ps.get(PROFILES_PATH, PROFILES_IFACE, "ActiveProfile").await;
+    assert_eq!(active, "quiet");
+
+    // Profiles and the active profile survive a restart
+    let profiles = ps.read(PROFILES);
+    assert!(profiles.contains("[quiet.gpu]"), "{profiles}");
+    ps.restart().await;
+    let active: String = ps.get(PROFILES_PATH, PROFILES_IFACE, "ActiveProfile").await;
+    assert_eq!(active, "quiet");
+
+    // Changing any setting of the profile deactivates it
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 9.0).await;
+    let active: String = ps.get(PROFILES_PATH, PROFILES_IFACE, "ActiveProfile").await;
+    assert_eq!(active, "");
+}
+
+#[tokio::test]
+async fn rejects_invalid_profiles() {
+    let ps = Harness::start("steam_deck").await;
+    let proxy = ps.proxy(PROFILES_PATH, PROFILES_IFACE).await;
+
+    let result: zbus::Result<()> = proxy.call("ApplyProfile", &("missing",)).await;
+    assert!(result.is_err());
+
+    let settings = HashMap::from([("Turbo", Value::from(true))]);
+    let result: zbus::Result<()> = proxy.call("SaveProfile", &("broken", settings)).await;
+    assert!(result.is_err());
+    let settings = HashMap::from([("TDP", Value::from("high"))]);
+    let result: zbus::Result<()> = proxy.call("SaveProfile", &("broken", settings)).await;
+    assert!(result.is_err());
+
+    let names: Vec<String> = ps.get(PROFILES_PATH, PROFILES_IFACE, "Profiles").await;
+    assert!(!names.contains(&"broken".to_string()));
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+}
+
+#[tokio::test]
+async fn rolls_back_profiles_that_fail_to_apply() {
+    let ps = Harness::start("steam_deck").await;
+    let proxy = ps.proxy(PROFILES_PATH, PROFILES_IFACE).await;
+
+    // Nothing is applied if any of the settings is invalid
+    let settings = HashMap::from([
+        ("TDP", Value::from(8.0)),
+        ("CoresEnabled", Value::from(4u32)),
+        ("PowerProfile", Value::from("turbo")),
+    ]);
+    let () = proxy
+        .call("SaveProfile", &("invalid", settings))
+        .await
+        .unwrap();
+    let result: zbus::Result<()> = proxy.call("ApplyProfile", &("invalid",)).await;
+    assert!(result.is_err());
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+    assert_eq!(ps.read("/sys/bus/cpu/devices/cpu2/online"), "1");
+
+    // Settings that were applied before one failed are rolled back
+    let settings = HashMap::from([
+        ("TDP", Value::from(8.0)),
+        ("CoresEnabled", Value::from(4u32)),
+        ("PowerProfileMode", Value::from("TURBO")),
+    ]);
+    let () = proxy
+        .call("SaveProfile", &("failing", settings))
+        .await
+        .unwrap();
+    let result: zbus::Result<()> = proxy.call("ApplyProfile", &("failing",)).await;
+    assert!(result.is_err());
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+    assert_eq!(ps.read("/sys/bus/cpu/devices/cpu2/online"), "1");
+    let active: String = ps.get(PROFILES_PATH, PROFILES_IFACE, "ActiveProfile").await;
+    assert_eq!(active, "");
+    let state = ps.read(STATE);
+    assert!(state.contains("tdp = 15.0"), "{state}");
+    assert!(state.contains("cores_enabled = 8"), "{state}");
+}
+
+#[tokio::test]
+async fn requires_authorization_for_profiles() {
+    let ps = Harness::start("steam_deck").await;
+    let proxy = ps.proxy(PROFILES_PATH, PROFILES_IFACE).await;
+    ps.authority
+        .deny("org.shadowblip.PowerStation.set-tdp")
+        .await;
+
+    let result: zbus::Result<()> = proxy.call("ApplyProfile", &("turbo",)).await;
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("AccessDenied"), "{error}");
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+    let active: String = ps.get(PROFILES_PATH, PROFILES_IFACE, "ActiveProfile").await;
+    assert_eq!(active, "");
+
+    let settings = HashMap::from([("TDP", Value::from(8.0))]);
+    let result: zbus::Result<()> = proxy.call("SaveProfile", &("quiet", settings)).await;
+    assert!(result.is_err());
+    let result: zbus::Result<()> = proxy.call("DeleteProfile", &("turbo",)).await;
+    assert!(result.is_err());
+    let names: Vec<String> = ps.get(PROFILES_PATH, PROFILES_IFACE, "Profiles").await;
+    assert_eq!(names, vec!["balanced", "silent", "turbo"]);
+
+    // Profiles are authorized by the settings they contain
+    let settings = HashMap::from([("SmtEnabled", Value::from(false))]);
+    let () = proxy
+        .call("SaveProfile", &("no-smt", settings))
+        .await
+        .unwrap();
+    let () = proxy.call("ApplyProfile", &("no-smt",)).await.unwrap();
+    assert_eq!(ps.read("/sys/devices/system/cpu/smt/control"), "off");
+}
+
+#[tokio::test]
+async fn switches_profiles_with_the_power_source() {
+    let mut ps = Harness::prepare("steam_deck", |root| {
+        let config = root.join(CONFIG.trim_start_matches('/'));
+        std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+        std::fs::write(
+            config,
+            r#"[power]
+ac_profile = "turbo"
+battery_profile = "silent"
+
+[[power.battery_thresholds]]
+below_percent = 20
+max_tdp = "min"
+"#,
+        )
+        .unwrap();
+    })
+    .await;
+    let upower = MockUPower::serve(&ps.connection, true, 80.0).await;
+    ps.start_daemon().await;
+
+    // The battery profile is applied on startup
+    ps.wait_for(SLOW_PPT, "6000000").await;
+    assert_eq!(ps.read(FAST_PPT), "6000000");
+
+    upower.set_on_battery(false).await;
+    ps.wait_for(SLOW_PPT, "15000000").await;
+    let active: String = ps.get(PROFILES_PATH, PROFILES_IFACE, "ActiveProfile").await;
+    assert_eq!(active, "turbo");
+
+    // Low battery caps the TDP at the hardware minimum
+    upower.set_on_battery(true).await;
+    ps.wait_for(SLOW_PPT, "6000000").await;
+    upower.set_percentage(10.0).await;
+    ps.wait_for(SLOW_PPT, "3000000").await;
+
+    // The previous TDP is restored once the battery is above the threshold
+    upower.set_percentage(30.0).await;
+    ps.wait_for(SLOW_PPT, "6000000").await;
+    let state = ps.read(STATE);
+    assert!(state.contains("tdp = 6.0"), "{state}");
+}
+
+#[tokio::test]
+async fn maps_power_profiles_to_tdp_and_platform_profile() {
+    let ps = start_with_config("[power_profiles]\nenabled = true\n").await;
+    let proxy = Harness::service_proxy(
+        &ps.connection,
+        POWER_PROFILES_NAME,
+        POWER_PROFILES_PATH,
+        POWER_PROFILES_IFACE,
+    )
+    .await;
+
+    let active: String = proxy.get_property("ActiveProfile").await.unwrap();
+    assert_eq!(active, "balanced");
+    let profiles: Vec<HashMap<String, OwnedValue>> = proxy.get_property("Profiles").await.unwrap();
+    assert_eq!(profiles.len(), 3);
+
+    proxy
+        .set_property("ActiveProfile", "power-saver")
+        .await
+        .unwrap();
+    assert_eq!(ps.read(SLOW_PPT), "3000000");
+    assert_eq!(ps.read(PLATFORM_PROFILE), "low-power");
+
+    proxy
+        .set_property("ActiveProfile", "performance")
+        .await
+        .unwrap();
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+    assert_eq!(ps.read(PLATFORM_PROFILE), "performance");
+
+    // The legacy interface shares the same state
+    let legacy = Harness::service_proxy(
+        &ps.connection,
+        "net.hadess.PowerProfiles",
+        LEGACY_POWER_PROFILES_PATH,
+        LEGACY_POWER_PROFILES_IFACE,
+    )
+    .await;
+    legacy
+        .set_property("ActiveProfile", "balanced")
+        .await
+        .unwrap();
+    assert_eq!(ps.read(SLOW_PPT), "10000000");
+    assert_eq!(ps.read(PLATFORM_PROFILE), "balanced");
+    let active: String = proxy.get_property("ActiveProfile").await.unwrap();
+    assert_eq!(active, "balanced");
+
+    // Switching profiles needs the same authorization as setting the TDP
+    ps.authority
+        .deny("org.shadowblip.PowerStation.set-tdp")
+        .await;
+    let result = proxy.set_property("ActiveProfile", "performance").await;
+    assert!(result.is_err());
+    let result: zbus::Result<u32> = proxy
+        .call(
+            "HoldProfile",
+            &("performance", "Gaming", "org.example.Test"),
+        )
+        .await;
+    assert!(result.is_err());
+    assert_eq!(ps.read(SLOW_PPT), "10000000");
+
+    let result = proxy.set_property("ActiveProfile", "turbo").await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn applies_configured_power_profiles() {
+    let ps = start_with_config(
+        r#"[power_profiles]
+enabled = true
+
+[power_profiles.power-saver]
+tdp = 5
+boost = 0
+"#,
+    )
+    .await;
+    let proxy = Harness::service_proxy(
+        &ps.connection,
+        POWER_PROFILES_NAME,
+        POWER_PROFILES_PATH,
+        POWER_PROFILES_IFACE,
+    )
+    .await;
+
+    proxy
+        .set_property("ActiveProfile", "power-saver")
+        .await
+        .unwrap();
+    assert_eq!(ps.read(SLOW_PPT), "5000000");
+    assert_eq!(ps.read(FAST_PPT), "5000000");
+    assert_eq!(ps.read(PLATFORM_PROFILE), "balanced");
+}
+
+#[tokio::test]
+async fn holds_power_profiles_until_released() {
+    let ps = start_with_config("[power_profiles]\nenabled = true\n").await;
+    let proxy = Harness::service_proxy(
+        &ps.connection,
+        POWER_PROFILES_NAME,
+        POWER_PROFILES_PATH,
+        POWER_PROFILES_IFACE,
+    )
+    .await;
+
+    let cookie: u32 = proxy
+        .call(
+            "HoldProfile",
+            &("performance", "Compiling", "org.example.Test"),
+        )
+        .await
+        .unwrap();
+    let active: String = proxy.get_property("ActiveProfile").await.unwrap();
+    assert_eq!(active, "performance");
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+    // Holds are never saved
+    let state = std::fs::read_to_string(ps.path(STATE)).unwrap_or_default();
+    assert!(!state.contains("tdp = 15.0"), "{state}");
+    let holds: Vec<HashMap<String, OwnedValue>> =
+        proxy.get_property("ActiveProfileHolds").await.unwrap();
+    assert_eq!(holds.len(), 1);
+    assert_eq!(
+        String::try_from(holds[0]["ApplicationId"].clone()).unwrap(),
+        "org.example.Test"
+    );
+
+    // Power saver holds take precedence
+    let saver: u32 = proxy
+        .call(
+            "HoldProfile",
+            &("power-saver", "Low battery", "org.example.Test"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(ps.read(SLOW_PPT), "3000000");
+    let () = proxy.call("ReleaseProfile", &(saver,)).await.unwrap();
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+
+    // Releasing the last hold returns to the selected profile
+    let () = proxy.call("ReleaseProfile", &(cookie,)).await.unwrap();
+    let active: String = proxy.get_property("ActiveProfile").await.unwrap();
+    assert_eq!(active, "balanced");
+    assert_eq!(ps.read(SLOW_PPT), "10000000");
+    let result: zbus::Result<()> = proxy.call("ReleaseProfile", &(cookie,)).await;
+    assert!(result.is_err());
+    let result: zbus::Result<u32> = proxy
+        .call("HoldProfile", &("balanced", "", "org.example.Test"))
+        .await;
+    assert!(result.is_err());
+
+    // Holds are released when the client disconnects
+    let client = ps.connect().await;
+    let client_proxy = Harness::service_proxy(
+        &client,
+        POWER_PROFILES_NAME,
+        POWER_PROFILES_PATH,
+        POWER_PROFILES_IFACE,
+    )
+    .await;
+    let _: u32 = client_proxy
+        .call(
+            "HoldProfile",
+            &("power-saver", "Gaming", "org.example.Game"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(ps.read(SLOW_PPT), "3000000");
+    drop(client_proxy);
+    drop(client);
+    ps.wait_for(SLOW_PPT, "10000000").await;
+    let holds: Vec<HashMap<String, OwnedValue>> =
+        proxy.get_property("ActiveProfileHolds").await.unwrap();
+    assert!(holds.is_empty());
+}
+
+#[tokio::test]
+async fn restores_the_selected_power_profile() {
+    let mut ps = start_with_config("[power_profiles]\nenabled = true\n").await;
+    let proxy = Harness::service_proxy(
+        &ps.connection,
+        POWER_PROFILES_NAME,
+        POWER_PROFILES_PATH,
+        POWER_PROFILES_IFACE,
+    )
+    .await;
+    proxy
+        .set_property("ActiveProfile", "power-saver")
+        .await
+        .unwrap();
+    let _: u32 = proxy
+        .call(
+            "HoldProfile",
+            &("performance", "Compiling", "org.example.Test"),
+        )
+        .await
+        .unwrap();
+    drop(proxy);
+
+    // Holds end with the daemon, while the selected profile is restored
+    ps.restart().await;
+    let proxy = Harness::service_proxy(
+        &ps.connection,
+        POWER_PROFILES_NAME,
+        POWER_PROFILES_PATH,
+        POWER_PROFILES_IFACE,
+    )
+    .await;
+    let active: String = proxy.get_property("ActiveProfile").await.unwrap();
+    assert_eq!(active, "power-saver");
+    assert_eq!(ps.read(SLOW_PPT), "3000000");
+    assert_eq!(ps.read(PLATFORM_PROFILE), "low-power");
+}
+
+#[tokio::test]
+async fn applies_gamemode_settings_while_games_run() {
+    let mut ps = Harness::prepare("steam_deck", |root| {
+        let config = root.join(CONFIG.trim_start_matches('/'));
+        std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+        std::fs::write(
+            config,
+            r#"[gamemode]
+enabled = true
+tdp = "max"
+boost = 5
+power_profile_mode = "3D_FULL_SCREEN"
+gpu_clock_mhz = 1600
+"#,
+        )
+        .unwrap();
+    })
+    .await;
+    // GameMode runs on the session bus of the user, not on the system bus
+    let session_bus = ps.start_session_bus(1000).await;
+    let gamemode = MockGameMode::serve(&session_bus).await;
+    ps.start_daemon().await;
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 8.0).await;
+    let fast_ppt = ps.read(FAST_PPT);
+
+    gamemode.register_game(1000).await;
+    ps.wait_for(SLOW_PPT, "15000000").await;
+    assert_eq!(ps.read(FAST_PPT), "20000000");
+    assert_eq!(ps.read(PERFORMANCE_LEVEL), "manual");
+    assert_eq!(ps.read(POWER_PROFILE_MODE), "1");
+
+    // Settings are kept until the last game exits
+    gamemode.register_game(1001).await;
+    gamemode.unregister_game(1000).await;
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert_eq!(ps.read(SLOW_PPT), "15000000");
+
+    gamemode.unregister_game(1001).await;
+    ps.wait_for(SLOW_PPT, "8000000").await;
+    assert_eq!(ps.read(FAST_PPT), fast_ppt);
+    assert_eq!(ps.read(PERFORMANCE_LEVEL), "auto");
+}
+
+#[tokio::test]
+async fn applies_app_profiles_while_processes_run() {
+    let ps = start_with_config(
+        r#"[[apps]]
+process = "eldenring.exe"
+profile = "gaming"
+
+[[apps]]
+process = "steam"
+profile = "turbo"
+"#,
+    )
+    .await;
+    let proxy = ps.proxy(PROFILES_PATH, PROFILES_IFACE).await;
+    let settings = HashMap::from([
+        ("TDP", Value::from(12.0)),
+        ("CoresEnabled", Value::from(4u32)),
+    ]);
+    let () = proxy
+        .call("SaveProfile", &("gaming", settings))
+        .await
+        .unwrap();
+    let slow_ppt = ps.read(SLOW_PPT);
+
+    // Start a process by adding it to the fixture procfs
+    let start = |pid: u32, comm: &str, cmdline: &str| {
+        std::fs::create_dir_all(ps.path(&format!("/proc/{pid}"))).unwrap();
+        ps.write(&format!("/proc/{pid}/comm"), &format!("{comm}\n"));
+        ps.write(&format!("/proc/{pid}/cmdline"), cmdline);
+    };
+    start(
+        100,
+        "steam",
+        "/home/deck/.steam/steam/ubuntu12_32/steam\0-gamepadui\0",
+    );
+    ps.wait_for(SLOW_PPT, "15000000").await;
+
+    // Wine reports the Windows executable only in the command line
+    start(
+        4242,
+        "wine64-preload",
+        "Z:\\home\\deck\\Games\\ELDEN RING\\eldenring.exe\0",
+    );
+    ps.wait_for(SLOW_PPT, "12000000").await;
+    assert_eq!(ps.read("/sys/bus/cpu/devices/cpu2/online"), "0");
+
+    // The settings replaced by the game are restored once it exits
+    std::fs::remove_dir_all(ps.path("/proc/4242")).unwrap();
+    ps.wait_for(SLOW_PPT, "15000000").await;
+    assert_eq!(ps.read("/sys/bus/cpu/devices/cpu2/online"), "1");
+
+    std::fs::remove_dir_all(ps.path("/proc/100")).unwrap();
+    ps.wait_for(SLOW_PPT, &slow_ppt).await;
+}
+
+#[tokio::test]
+async fn restores_overlapping_overrides_in_reverse_order() {
+    let mut ps = Harness::prepare("steam_deck", |root| {
+        let config = root.join(CONFIG.trim_start_matches('/'));
+        std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+        std::fs::write(
+            config,
+            r#"[gamemode]
+enabled = true
+tdp = "max"
+
+[[apps]]
+process = "eldenring.exe"
+profile = "gaming"
+"#,
+        )
+        .unwrap();
+    })
+    .await;
+    // GameMode runs on the session bus of the user, not on the system bus
+    let session_bus = ps.start_session_bus(1000).await;
+    let gamemode = MockGameMode::serve(&session_bus).await;
+    ps.start_daemon().await;
+    let proxy = ps.proxy(PROFILES_PATH, PROFILES_IFACE).await;
+    let settings = HashMap::from([
+        ("TDP", Value::from(12.0)),
+        ("CoresEnabled", Value::from(4u32)),
+    ]);
+    let () = proxy
+        .call("SaveProfile", &("gaming", settings))
+        .await
+        .unwrap();
+    ps.set(CARD0_PATH, TDP_IFACE, "TDP", 8.0).await;
+
+    gamemode.register_game(1000).await;
+    ps.wait_for(SLOW_PPT, "15000000").await;
+    std::fs::create_dir_all(ps.path("/proc/4242")).unwrap();
+    ps.write("/proc/4242/comm", "eldenring.exe\n");
+    ps.wait_for(SLOW_PPT, "12000000").await;
+    assert_eq!(ps.read("/sys/bus/cpu/devices/cpu2/online"), "0");
+
+    // The application settings stay in effect when GameMode ends first
+    gamemode.unregister_game(1000).await;
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(ps.read(SLOW_PPT), "12000000");
+    assert_eq!(ps.read("/sys/bus/cpu/devices/cpu2/online"), "0");
+
+    // Overrides are never saved
+    let state = ps.read(STATE);
+    assert!(state.contains("tdp = 8.0"), "{state}");
+    assert!(!state.contains("cores_enabled"), "{state}");
+
+    std::fs::remove_dir_all(ps.path("/proc/4242")).unwrap();
+    ps.wait_for(SLOW_PPT, "8000000").await;
+    assert_eq!(ps.read("/sys/bus/cpu/devices/cpu2/online"), "1");
+    let state = ps.read(STATE);
+    assert!(state.contains("tdp = 8.0"), "{state}");
+}